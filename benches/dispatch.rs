@@ -0,0 +1,82 @@
+//! Throughput of the fetch-decode-execute loop, for `cargo bench`.
+//!
+//! criterion isn't a dependency, so this is a plain timing harness: each
+//! program runs for a fixed number of instructions a few times over and
+//! the best run is reported, in millions of instructions per second.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cpu_emulator_chip_8::asm::assemble;
+use cpu_emulator_chip_8::cpu::{EmulatorMode, CPU};
+
+const INSTRUCTIONS: usize = 5_000_000;
+const RUNS: usize = 10;
+
+/// Register arithmetic and skips, the bulk of most games.
+const ALU: &str = "
+    loop:
+        ADD V0, 1
+        LD V1, V0
+        XOR V1, V2
+        SHR V1
+        ADD V2, V1
+        SE V2, 0
+        SUB V3, V2
+        JP loop
+";
+
+/// Sprites, BCD and memory loads.
+const GRAPHICS: &str = "
+        LD I, 0x300
+    loop:
+        ADD V0, 3
+        ADD V1, 1
+        LD F, V0
+        DRW V0, V1, 5
+        LD I, 0x300
+        LD B, V0
+        LD V2, [I]
+        JP loop
+";
+
+/// Subroutine calls and timers.
+const CALLS: &str = "
+    loop:
+        CALL sub
+        LD V0, DT
+        SE V0, 0
+        JP loop
+        LD DT, V1
+        JP loop
+    sub:
+        ADD V1, 1
+        RET
+";
+
+fn bench(name: &str, source: &str, mode: EmulatorMode) {
+    let rom = assemble(source).expect("benchmark programs assemble");
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut cpu = CPU::new_with_mode(mode);
+        cpu.load_rom(&rom).unwrap();
+        let start = Instant::now();
+        black_box(cpu.run_for(INSTRUCTIONS)).unwrap();
+        best = best.min(start.elapsed());
+        black_box(&cpu.registers);
+    }
+    let per_second = INSTRUCTIONS as f64 / best.as_secs_f64();
+    println!(
+        "{:<10} {:>8.1} M instructions/s  ({:.1} ns each)",
+        name,
+        per_second / 1e6,
+        best.as_nanos() as f64 / INSTRUCTIONS as f64
+    );
+}
+
+fn main() {
+    bench("alu", ALU, EmulatorMode::Chip8);
+    bench("graphics", GRAPHICS, EmulatorMode::Chip8);
+    bench("calls", CALLS, EmulatorMode::Chip8);
+    bench("xo-alu", ALU, EmulatorMode::XoChip);
+}
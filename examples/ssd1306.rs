@@ -0,0 +1,140 @@
+//! Driving a 128x64 SSD1306 OLED, the display most CHIP-8 handhelds use.
+//!
+//! The SSD1306 keeps its pixels in eight pages of 128 bytes, each byte a
+//! column of eight pixels with the top one in bit 0. A driver keeps a copy
+//! of that memory, sets pixels in it and sends the pages that changed over
+//! I2C or SPI. Here the bus is stood in for by printing the pages that
+//! would be sent, and the screen itself at the end. It talks to no real
+//! display, and it doesn't go through `embedded-graphics`, which this
+//! crate has no adapter for yet:
+//!
+//! ```text
+//! cargo run --example ssd1306 [rom.ch8]
+//! ```
+
+use std::{env, fs, process};
+
+use cpu_emulator_chip_8::asm::assemble;
+use cpu_emulator_chip_8::cpu::{EmulatorMode, CPU};
+use cpu_emulator_chip_8::display::{Panel, PanelRenderer};
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+const PAGES: usize = HEIGHT / 8;
+
+/// Draws the digits 0 to F across the screen when no ROM is given.
+const DEMO: &str = "
+        LD V0, 0
+        LD V1, 0
+        LD V2, 1
+    loop:
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V0, 1
+        ADD V1, 4
+        SE V0, 16
+        JP loop
+    end:
+        JP end
+";
+
+struct Ssd1306 {
+    pages: [[u8; WIDTH]; PAGES],
+    /// Pages changed since the last flush, one bit each.
+    dirty: u8,
+}
+
+impl Ssd1306 {
+    fn new() -> Self {
+        Ssd1306 {
+            pages: [[0; WIDTH]; PAGES],
+            dirty: 0,
+        }
+    }
+
+    /// Where a real driver would write each dirty page to the bus.
+    fn flush(&mut self) {
+        for page in 0..PAGES {
+            if self.dirty & 1 << page != 0 {
+                println!("page {}: {:02x?}", page, &self.pages[page][..16]);
+            }
+        }
+        self.dirty = 0;
+    }
+
+    fn lit(&self, x: usize, y: usize) -> bool {
+        self.pages[y / 8][x] & 1 << (y % 8) != 0
+    }
+}
+
+impl Panel for Ssd1306 {
+    type Error = ();
+
+    fn size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn draw_pixels<I>(&mut self, pixels: I) -> Result<(), ()>
+    where
+        I: IntoIterator<Item = (usize, usize, u8)>,
+    {
+        for (x, y, color) in pixels {
+            let column = &mut self.pages[y / 8][x];
+            if color != 0 {
+                *column |= 1 << (y % 8);
+            } else {
+                *column &= !(1 << (y % 8));
+            }
+            self.dirty |= 1 << (y / 8);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ()> {
+        self.pages = [[0; WIDTH]; PAGES];
+        self.dirty = u8::MAX;
+        Ok(())
+    }
+}
+
+fn main() {
+    let rom = match env::args().nth(1) {
+        Some(path) => fs::read(&path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }),
+        None => assemble(DEMO).expect("the demo assembles"),
+    };
+    let mut cpu = CPU::new_with_mode(EmulatorMode::Chip8);
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+
+    let mut panel = Ssd1306::new();
+    let mut renderer = PanelRenderer::new();
+    for frame in 0..60 {
+        if let Err(err) = cpu.run_frame(11) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        cpu.tick_timers();
+        let _ = renderer.render(&mut cpu.display, &mut panel);
+        if panel.dirty != 0 {
+            println!("frame {}:", frame);
+            panel.flush();
+        }
+    }
+
+    for y in (0..HEIGHT).step_by(2) {
+        let row: String = (0..WIDTH)
+            .map(|x| match (panel.lit(x, y), panel.lit(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            })
+            .collect();
+        println!("{}", row.trim_end());
+    }
+}
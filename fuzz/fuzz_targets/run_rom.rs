@@ -0,0 +1,36 @@
+//! Runs arbitrary bytes as a ROM: `cargo fuzz run run_rom`. The first byte
+//! picks the variant and quirks, the rest is the program.
+
+#![no_main]
+
+use cpu_emulator_chip_8::cpu::{EmulatorMode, Quirks, XorShift, CPU};
+use libfuzzer_sys::fuzz_target;
+
+const FRAMES: usize = 60;
+const INSTRUCTIONS_PER_FRAME: usize = 100;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&setup, rom)) = data.split_first() else {
+        return;
+    };
+    let (mode, quirks) = match setup % 3 {
+        0 => (EmulatorMode::Chip8, Quirks::cosmac_vip()),
+        1 => (EmulatorMode::SuperChip, Quirks::super_chip()),
+        _ => (EmulatorMode::XoChip, Quirks::xo_chip()),
+    };
+    let mut cpu = CPU::new_with_mode(mode);
+    cpu.quirks = quirks;
+    cpu.quirks.wrap_memory = setup & 0x80 != 0;
+    cpu.set_rng(Box::new(XorShift::new(setup as u32)));
+    if cpu.load_rom(rom).is_err() {
+        return;
+    }
+    for frame in 0..FRAMES {
+        if cpu.run_bounded(INSTRUCTIONS_PER_FRAME).is_err() {
+            return;
+        }
+        cpu.tick_timers();
+        // keep FX0A from blocking the whole run
+        cpu.set_key((frame % 16) as u8, frame % 2 == 0);
+    }
+});
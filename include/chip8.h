@@ -0,0 +1,77 @@
+/*
+ * C interface to the cpu-emulator-chip-8 library, see src/ffi/mod.rs.
+ * Build the shared library with `cargo build --release --features ffi`.
+ *
+ *     chip8_t *chip8 = chip8_new(CHIP8_MODE_CHIP8);
+ *     chip8_load_rom(chip8, rom, rom_len);
+ *     while (chip8_step(chip8) == CHIP8_OK) {
+ *         size_t width, height;
+ *         const uint8_t *pixels = chip8_framebuffer(chip8, &width, &height);
+ *         ...draw pixels, feed chip8_key_event, wait for the next frame...
+ *     }
+ *     chip8_free(chip8);
+ */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CHIP8_OK 0
+/* The program ran 00FD and won't run any further. */
+#define CHIP8_HALTED 1
+#define CHIP8_ERROR_NULL -1
+/* The ROM is too large for memory. */
+#define CHIP8_ERROR_ROM -2
+/* The program faulted, see chip8_error. */
+#define CHIP8_ERROR_FAULT -3
+#define CHIP8_ERROR_KEY -4
+
+#define CHIP8_MODE_CHIP8 0
+#define CHIP8_MODE_SCHIP 1
+#define CHIP8_MODE_XO 2
+
+typedef struct Chip8 chip8_t;
+
+/* A new machine with no program loaded, or NULL for an unknown mode. */
+chip8_t *chip8_new(int mode);
+
+/* Frees a machine from chip8_new. NULL is ignored. */
+void chip8_free(chip8_t *chip8);
+
+/* Loads a program and restarts from power-on, keeping the mode. */
+int chip8_load_rom(chip8_t *chip8, const uint8_t *rom, size_t len);
+
+/* Instructions per 60 Hz frame, 11 until set. */
+int chip8_set_speed(chip8_t *chip8, size_t instructions_per_frame);
+
+/* Runs one 60 Hz frame and ticks the timers. */
+int chip8_step(chip8_t *chip8);
+
+/*
+ * The screen, one byte per pixel row by row: 0 off, 1 to 3 lit, by
+ * XO-CHIP plane. width and height may be NULL. The buffer is valid until
+ * the next call on this machine.
+ */
+const uint8_t *chip8_framebuffer(chip8_t *chip8, size_t *width, size_t *height);
+
+/* Presses or releases keypad key 0x0 to 0xF. */
+int chip8_key_event(chip8_t *chip8, uint8_t key, bool pressed);
+
+/* Whether the buzzer should sound. */
+bool chip8_is_beeping(const chip8_t *chip8);
+
+/* Why the program stopped, owned by the machine, or NULL. */
+const char *chip8_error(const chip8_t *chip8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CHIP8_H */
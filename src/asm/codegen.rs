@@ -0,0 +1,146 @@
+use crate::disasm::Instruction;
+
+impl Instruction {
+    /// The opcode [`crate::cpu::decode`] turns back into this instruction.
+    /// Operands are masked to the bits their field has, so `Jp(0x1234)`
+    /// jumps to 0x234. For [`Instruction::LdILong`] this is only the F000
+    /// half, and for [`Instruction::LdIHuge`] the half with the top byte; see
+    /// [`Instruction::to_bytes`].
+    pub fn opcode(&self) -> u16 {
+        use Instruction::*;
+
+        let x = |x: u8| (x as u16 & 0xF) << 8;
+        let xy = |base: u16, x_reg: u8, y: u8| base | x(x_reg) | (y as u16 & 0xF) << 4;
+        let nnn = |nnn: u16| nnn & 0xFFF;
+        match *self {
+            Sys(address) => nnn(address),
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jp(address) => 0x1000 | nnn(address),
+            Call(address) => 0x2000 | nnn(address),
+            SeByte { x: vx, nn } => 0x3000 | x(vx) | nn as u16,
+            SneByte { x: vx, nn } => 0x4000 | x(vx) | nn as u16,
+            SeReg { x, y } => xy(0x5000, x, y),
+            SaveRange { x, y } => xy(0x5002, x, y),
+            LoadRange { x, y } => xy(0x5003, x, y),
+            LdByte { x: vx, nn } => 0x6000 | x(vx) | nn as u16,
+            AddByte { x: vx, nn } => 0x7000 | x(vx) | nn as u16,
+            LdReg { x, y } => xy(0x8000, x, y),
+            Or { x, y } => xy(0x8001, x, y),
+            And { x, y } => xy(0x8002, x, y),
+            Xor { x, y } => xy(0x8003, x, y),
+            AddReg { x, y } => xy(0x8004, x, y),
+            Sub { x, y } => xy(0x8005, x, y),
+            Shr { x, y } => xy(0x8006, x, y),
+            Subn { x, y } => xy(0x8007, x, y),
+            Shl { x, y } => xy(0x800E, x, y),
+            SneReg { x, y } => xy(0x9000, x, y),
+            LdI(address) => 0xA000 | nnn(address),
+            // the register BXNN reads is the top nibble of the address
+            JpOffset { nnn: address, .. } => 0xB000 | nnn(address),
+            Rnd { x: vx, nn } => 0xC000 | x(vx) | nn as u16,
+            Drw { x, y, n } => xy(0xD000, x, y) | (n as u16 & 0xF),
+            Skp(vx) => 0xE09E | x(vx),
+            Sknp(vx) => 0xE0A1 | x(vx),
+            LdILong(_) => 0xF000,
+            Plane(n) => 0xF001 | x(n),
+            Audio => 0xF002,
+            LdVxDt(vx) => 0xF007 | x(vx),
+            LdKey(vx) => 0xF00A | x(vx),
+            LdDt(vx) => 0xF015 | x(vx),
+            LdSt(vx) => 0xF018 | x(vx),
+            AddI(vx) => 0xF01E | x(vx),
+            LdFont(vx) => 0xF029 | x(vx),
+            LdBigFont(vx) => 0xF030 | x(vx),
+            Bcd(vx) => 0xF033 | x(vx),
+            Pitch(vx) => 0xF03A | x(vx),
+            Store(vx) => 0xF055 | x(vx),
+            Load(vx) => 0xF065 | x(vx),
+            StoreRpl(vx) => 0xF075 | x(vx),
+            LoadRpl(vx) => 0xF085 | x(vx),
+            MegaOff => 0x0010,
+            MegaOn => 0x0011,
+            LdIHuge(address) => 0x0100 | (address >> 16) as u16 & 0xFF,
+            LdPalette(nn) => 0x0200 | nn as u16,
+            SpriteWidth(nn) => 0x0300 | nn as u16,
+            SpriteHeight(nn) => 0x0400 | nn as u16,
+            Alpha(nn) => 0x0500 | nn as u16,
+            DigiSound(n) => 0x0600 | (n as u16 & 0xF),
+            StopSound => 0x0700,
+            BlendMode(n) => 0x0800 | (n as u16 & 0xF),
+            CollisionColor(nn) => 0x0900 | nn as u16,
+            Unknown(opcode) => opcode,
+        }
+    }
+
+    /// The instruction as it sits in memory, big-endian, four bytes for
+    /// [`Instruction::LdILong`] and [`Instruction::LdIHuge`] and two for
+    /// everything else.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.opcode().to_be_bytes().to_vec();
+        match *self {
+            Instruction::LdILong(address) => bytes.extend_from_slice(&address.to_be_bytes()),
+            Instruction::LdIHuge(address) => {
+                bytes.extend_from_slice(&(address as u16).to_be_bytes())
+            }
+            _ => {}
+        }
+        bytes
+    }
+}
+
+/// Encodes `program` into a ROM image, the typed counterpart of
+/// [`super::assemble`] for building programs in code:
+///
+/// ```
+/// use cpu_emulator_chip_8::asm::encode;
+/// use cpu_emulator_chip_8::disasm::Instruction::*;
+///
+/// let rom = encode(&[LdByte { x: 0, nn: 5 }, AddByte { x: 0, nn: 1 }, Exit]);
+/// assert_eq!(rom, [0x60, 0x05, 0x70, 0x01, 0x00, 0xFD]);
+/// ```
+pub fn encode(program: &[Instruction]) -> Vec<u8> {
+    program.iter().flat_map(Instruction::to_bytes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::disasm::{disassemble, disassemble_rom};
+
+    #[test]
+    fn every_opcode_round_trips_through_decoding() {
+        for opcode in 0..=u16::MAX {
+            let instruction = disassemble(opcode);
+            assert_eq!(instruction.opcode(), opcode, "{}", instruction);
+        }
+    }
+
+    #[test]
+    fn programs_encode_like_the_assembler() {
+        use Instruction::*;
+
+        let program = [
+            LdI(0x20A),
+            LdILong(0x1234),
+            Drw { x: 1, y: 2, n: 5 },
+            JpOffset { x: 3, nnn: 0x321 },
+        ];
+        let rom = encode(&program);
+        let source = "LD I, 0x20A\nLD I, LONG 0x1234\nDRW V1, V2, 5\nJP V0, 0x321";
+        assert_eq!(rom, assemble(source).unwrap());
+        let decoded: Vec<Instruction> = disassemble_rom(&rom)
+            .into_iter()
+            .map(|(_, instruction, _)| instruction)
+            .collect();
+        assert_eq!(decoded, program);
+    }
+}
@@ -0,0 +1,496 @@
+//! A small assembler for the mnemonics [`crate::disasm`] prints.
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! SPEED = 3               ; constants
+//! start:                  ; labels, alone or in front of an instruction
+//!     LD V0, SPEED
+//!     LD I, sprite
+//! loop: ADD V0, 0xFF
+//!     SE V0, 0
+//!     JP loop
+//!     DRW V1, V2, 2
+//!     JP start
+//! sprite:
+//!     db 0b1100_0000, 0x60
+//! ```
+//!
+//! Mnemonics, register names and directives are case-insensitive; labels
+//! and constants are not. Numbers are decimal, `0x` hex or `0b` binary,
+//! and operands may add or subtract several of them, e.g. `sprite + 5`.
+//! `LD I, NNNN` becomes the four-byte XO-CHIP form when the address does
+//! not fit in 12 bits. Labels further on aren't known yet when that is
+//! decided, so write `LD I, LONG label` for those above 0xFFF.
+//! Mega-Chip's 24-bit load is `LDHI NNNNNN`.
+//! `db` emits bytes and `dw` big-endian words. The program is assembled
+//! for [`PROGRAM_START`].
+//!
+//! Programs built in code rather than written out, as in tests, can be
+//! encoded from [`Instruction`](crate::disasm::Instruction)s with
+//! [`encode`] instead.
+//!
+//! Source written for Octo, usually `.8o` files, goes through
+//! [`assemble_octo`] instead, which understands the common part of its
+//! syntax.
+
+mod codegen;
+mod octo;
+
+use std::collections::{BTreeMap, HashMap};
+use std::{error, fmt};
+
+use crate::cpu::PROGRAM_START;
+
+pub use codegen::encode;
+pub use octo::assemble_octo;
+
+/// Extensions of Octo source files, for [`assemble_octo`].
+pub const OCTO_EXTENSIONS: &[&str] = &["8o", "o8"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based source line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// One statement with its label and comment stripped.
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+    /// An `LD I` that takes four bytes, decided in the first pass so that
+    /// the second emits the size every later label was placed with.
+    long: bool,
+}
+
+/// An assembled ROM image and where its labels ended up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub rom: Vec<u8>,
+    /// Label name to address; constants aren't included.
+    pub labels: BTreeMap<String, usize>,
+}
+
+/// Assembles `source` into a ROM image.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_program(source).map(|program| program.rom)
+}
+
+/// Like [`assemble`], keeping the labels too, e.g. to find a breakpoint's
+/// new address after the source was edited.
+pub fn assemble_program(source: &str) -> Result<Program, AsmError> {
+    let mut symbols: HashMap<&str, u32> = HashMap::new();
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut address = PROGRAM_START as u32;
+
+    // First pass: collect labels and constants, and work out where every
+    // statement lands.
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| AsmError { line, message };
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+
+        if let Some((name, value)) = text.split_once('=') {
+            let name = name.trim();
+            check_name(name).map_err(error)?;
+            let value = evaluate(value.trim(), &symbols).map_err(error)?;
+            if symbols.insert(name, value).is_some() {
+                return Err(error(format!("{} is defined twice", name)));
+            }
+            continue;
+        }
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            check_name(label).map_err(error)?;
+            if symbols.insert(label, address).is_some() {
+                return Err(error(format!("{} is defined twice", label)));
+            }
+            labels.insert(label.to_string(), address as usize);
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<&str> = if rest.trim().is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+        let mut statement = Statement {
+            line,
+            mnemonic: mnemonic.to_ascii_uppercase(),
+            operands,
+            long: false,
+        };
+        statement.long = statement.mnemonic == "LD" && is_long_load(&statement, &symbols);
+        address += size(&statement) as u32;
+        statements.push(statement);
+    }
+
+    // Second pass: encode, now that every label is known.
+    let mut rom = Vec::new();
+    for statement in &statements {
+        encode_statement(statement, &symbols, &mut rom).map_err(|message| AsmError {
+            line: statement.line,
+            message,
+        })?;
+    }
+    Ok(Program { rom, labels })
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if !valid || register(name).is_some() {
+        return Err(format!("invalid name {:?}", name));
+    }
+    Ok(())
+}
+
+/// Bytes a statement assembles to. Errors are left for [`encode_statement`].
+fn size(statement: &Statement) -> usize {
+    match statement.mnemonic.as_str() {
+        "DB" => statement.operands.len(),
+        "DW" => 2 * statement.operands.len(),
+        "LD" if statement.long => 4,
+        "LDHI" => 4,
+        _ => 2,
+    }
+}
+
+/// Whether `LD I` needs the long form, going by the symbols defined so
+/// far: labels further on can't make it long.
+fn is_long_load(statement: &Statement, symbols: &HashMap<&str, u32>) -> bool {
+    match statement.operands.as_slice() {
+        [dest, value] if dest.eq_ignore_ascii_case("I") => {
+            long_operand(value).is_some()
+                || evaluate(value, symbols).is_ok_and(|value| value > 0xFFF)
+        }
+        _ => false,
+    }
+}
+
+/// The address part of `LONG addr`.
+fn long_operand(operand: &str) -> Option<&str> {
+    let (keyword, rest) = operand.split_once(char::is_whitespace)?;
+    keyword.eq_ignore_ascii_case("LONG").then(|| rest.trim())
+}
+
+fn register(operand: &str) -> Option<u16> {
+    let digit = operand.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u16::from_str_radix(digit, 16).ok()
+}
+
+fn number(text: &str) -> Option<u32> {
+    let text = text.replace('_', "");
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Sums numbers and symbols joined by `+` and `-`.
+fn evaluate(expression: &str, symbols: &HashMap<&str, u32>) -> Result<u32, String> {
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    let mut rest = expression.trim();
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = rest[..end].trim();
+        if term.is_empty() {
+            return Err(format!("malformed expression {:?}", expression));
+        }
+        let value = number(term)
+            .or_else(|| symbols.get(term).copied())
+            .ok_or_else(|| format!("unknown symbol {:?}", term))?;
+        total += sign * value as i64;
+        if end == rest.len() {
+            break;
+        }
+        sign = if rest[end..].starts_with('+') { 1 } else { -1 };
+        rest = &rest[end + 1..];
+    }
+    u32::try_from(total).map_err(|_| format!("{:?} is negative", expression))
+}
+
+fn encode_statement(
+    statement: &Statement,
+    symbols: &HashMap<&str, u32>,
+    rom: &mut Vec<u8>,
+) -> Result<(), String> {
+    let ops = &statement.operands;
+    let value = |operand: &str, max: u32| -> Result<u16, String> {
+        let value = evaluate(operand, symbols)?;
+        if value > max {
+            return Err(format!("{} does not fit in {:#X}", operand, max));
+        }
+        Ok(value as u16)
+    };
+    let reg = |operand: &str| {
+        register(operand).ok_or_else(|| format!("expected a register, found {:?}", operand))
+    };
+    let addr = |operand: &str| value(operand, 0xFFF);
+    let byte = |operand: &str| value(operand, 0xFF);
+    let nibble = |operand: &str| value(operand, 0xF);
+    let xy = |base: u16, x: &str, y: &str| -> Result<u16, String> {
+        Ok(base | reg(x)? << 8 | reg(y)? << 4)
+    };
+
+    let opcode = match (statement.mnemonic.as_str(), ops.as_slice()) {
+        ("DB", _) => {
+            for operand in ops {
+                rom.push(byte(operand)? as u8);
+            }
+            return Ok(());
+        }
+        ("DW", _) => {
+            for operand in ops {
+                rom.extend_from_slice(&value(operand, 0xFFFF)?.to_be_bytes());
+            }
+            return Ok(());
+        }
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [n]) => 0x00C0 | nibble(n)?,
+        ("SCU", [n]) => 0x00D0 | nibble(n)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [a]) => addr(a)?,
+        ("JP", [v0, a]) if v0.eq_ignore_ascii_case("V0") => 0xB000 | addr(a)?,
+        ("JP", [a]) => 0x1000 | addr(a)?,
+        ("CALL", [a]) => 0x2000 | addr(a)?,
+        ("SE", [x, y]) if register(y).is_some() => xy(0x5000, x, y)?,
+        ("SE", [x, nn]) => 0x3000 | reg(x)? << 8 | byte(nn)?,
+        ("SNE", [x, y]) if register(y).is_some() => xy(0x9000, x, y)?,
+        ("SNE", [x, nn]) => 0x4000 | reg(x)? << 8 | byte(nn)?,
+        ("SAVE", [range]) => range_op(0x5002, range, &reg)?,
+        ("LOAD", [range]) => range_op(0x5003, range, &reg)?,
+        ("LD", [i, a]) if i.eq_ignore_ascii_case("I") => {
+            if statement.long {
+                let target = long_operand(a).unwrap_or(a);
+                rom.extend_from_slice(&[0xF0, 0x00]);
+                rom.extend_from_slice(&value(target, 0xFFFF)?.to_be_bytes());
+                return Ok(());
+            }
+            match evaluate(a, symbols)? {
+                // a label further on, so the first pass left two bytes
+                far if far > 0xFFF => {
+                    return Err(format!(
+                        "{} is above 0xFFF, which needs LD I, LONG {}",
+                        a, a
+                    ))
+                }
+                near => 0xA000 | near as u16,
+            }
+        }
+        ("LD", [dest, x]) if register(x).is_some() && register(dest).is_none() => {
+            let low = match dest.to_ascii_uppercase().as_str() {
+                "DT" => 0x15,
+                "ST" => 0x18,
+                "F" => 0x29,
+                "HF" => 0x30,
+                "B" => 0x33,
+                "[I]" => 0x55,
+                "R" => 0x75,
+                _ => return Err(format!("cannot load into {:?}", dest)),
+            };
+            0xF000 | reg(x)? << 8 | low
+        }
+        ("LD", [x, src]) => {
+            let x = reg(x)? << 8;
+            match src.to_ascii_uppercase().as_str() {
+                _ if register(src).is_some() => 0x8000 | x | reg(src)? << 4,
+                "DT" => 0xF007 | x,
+                "K" => 0xF00A | x,
+                "[I]" => 0xF065 | x,
+                "R" => 0xF085 | x,
+                _ => 0x6000 | x | byte(src)?,
+            }
+        }
+        ("ADD", [i, x]) if i.eq_ignore_ascii_case("I") => 0xF01E | reg(x)? << 8,
+        ("ADD", [x, y]) if register(y).is_some() => xy(0x8004, x, y)?,
+        ("ADD", [x, nn]) => 0x7000 | reg(x)? << 8 | byte(nn)?,
+        ("OR", [x, y]) => xy(0x8001, x, y)?,
+        ("AND", [x, y]) => xy(0x8002, x, y)?,
+        ("XOR", [x, y]) => xy(0x8003, x, y)?,
+        ("SUB", [x, y]) => xy(0x8005, x, y)?,
+        ("SHR", [x]) => xy(0x8006, x, x)?,
+        ("SHR", [x, y]) => xy(0x8006, x, y)?,
+        ("SUBN", [x, y]) => xy(0x8007, x, y)?,
+        ("SHL", [x]) => xy(0x800E, x, x)?,
+        ("SHL", [x, y]) => xy(0x800E, x, y)?,
+        ("RND", [x, nn]) => 0xC000 | reg(x)? << 8 | byte(nn)?,
+        ("DRW", [x, y, n]) => xy(0xD000, x, y)? | nibble(n)?,
+        ("SKP", [x]) => 0xE09E | reg(x)? << 8,
+        ("SKNP", [x]) => 0xE0A1 | reg(x)? << 8,
+        ("PLANE", [n]) => 0xF001 | nibble(n)? << 8,
+        ("AUDIO", []) => 0xF002,
+        ("PITCH", [x]) => 0xF03A | reg(x)? << 8,
+        ("MEGAOFF", []) => 0x0010,
+        ("MEGAON", []) => 0x0011,
+        ("LDHI", [a]) => {
+            let address = evaluate(a, symbols)?;
+            if address > 0xFF_FFFF {
+                return Err(format!("{} does not fit in 0xFFFFFF", a));
+            }
+            rom.extend_from_slice(&(0x0100 | address >> 16).to_be_bytes()[2..]);
+            rom.extend_from_slice(&(address as u16).to_be_bytes());
+            return Ok(());
+        }
+        ("LDPAL", [nn]) => 0x0200 | byte(nn)?,
+        ("SPRW", [nn]) => 0x0300 | byte(nn)?,
+        ("SPRH", [nn]) => 0x0400 | byte(nn)?,
+        ("ALPHA", [nn]) => 0x0500 | byte(nn)?,
+        ("DIGISND", [n]) => 0x0600 | nibble(n)?,
+        ("STOPSND", []) => 0x0700,
+        ("BMODE", [n]) => 0x0800 | nibble(n)?,
+        ("CCOL", [nn]) => 0x0900 | byte(nn)?,
+        (mnemonic, ops) => {
+            return Err(format!(
+                "unknown instruction {} with {} operand(s)",
+                mnemonic,
+                ops.len()
+            ))
+        }
+    };
+    rom.extend_from_slice(&opcode.to_be_bytes());
+    Ok(())
+}
+
+/// Encodes XO-CHIP's `SAVE VX - VY` / `LOAD VX - VY`.
+fn range_op(
+    base: u16,
+    range: &str,
+    reg: &dyn Fn(&str) -> Result<u16, String>,
+) -> Result<u16, String> {
+    let (x, y) = range
+        .split_once('-')
+        .ok_or_else(|| format!("expected VX - VY, found {:?}", range))?;
+    Ok(base | reg(x.trim())? << 8 | reg(y.trim())? << 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble_rom;
+    use crate::reference::OPCODES;
+
+    #[test]
+    fn assembles_the_module_example() {
+        let source = "
+            ; comments run to the end of the line
+            SPEED = 3
+            start:
+                LD V0, SPEED
+                LD I, sprite
+            loop: ADD V0, 0xFF
+                SE V0, 0
+                JP loop
+                DRW V1, V2, 2
+                JP start
+            sprite:
+                db 0b1100_0000, 0x60
+        ";
+
+        assert_eq!(
+            assemble(source).unwrap(),
+            [
+                0x60, 0x03, 0xA2, 0x0E, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x04, 0xD1, 0x22, 0x12, 0x00,
+                0xC0, 0x60,
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_the_disassembler() {
+        let mut rom = Vec::new();
+        for info in OPCODES {
+            // fill the operand fields so every register and value differs
+            let opcode = info.pattern | (0x1234 & !info.mask);
+            rom.extend_from_slice(&opcode.to_be_bytes());
+            if opcode == 0xF000 || opcode & 0xFF00 == 0x0100 {
+                rom.extend_from_slice(&[0xBE, 0xEF]);
+            }
+        }
+        let source: String = disassemble_rom(&rom)
+            .into_iter()
+            .map(|(_, _, text)| text + "\n")
+            .collect();
+
+        assert_eq!(assemble(&source).unwrap(), rom, "{}", source);
+    }
+
+    #[test]
+    fn long_loads() {
+        assert_eq!(assemble("LD I, 0x1234").unwrap(), [0xF0, 0x00, 0x12, 0x34]);
+        assert_eq!(
+            assemble("LD I, LONG data\ndata: db 1").unwrap(),
+            [0xF0, 0x00, 0x02, 0x04, 0x01]
+        );
+
+        // the first pass left two bytes for this, so it can't grow to four
+        let zeros = " 0,".repeat(0xE00);
+        let source = format!(
+            "LD I, far
+after: CLS
+db{} 0
+far: db 1",
+            zeros
+        );
+        let err = assemble(&source).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(
+            err.message,
+            "far is above 0xFFF, which needs LD I, LONG far"
+        );
+        let long = source.replace("LD I, far", "LD I, LONG far");
+        let program = assemble_program(&long).unwrap();
+        assert_eq!(program.labels["after"], 0x204);
+        assert_eq!(program.rom[4..6], [0x00, 0xE0]);
+        assert_eq!(program.rom[program.labels["far"] - PROGRAM_START], 1);
+    }
+
+    #[test]
+    fn accepts_the_sprite_importer_output() {
+        let source = "; 8x2 at (0, 0)\nsprite_0_0:\n    db 0b10000001\n    db 0b01111110\n";
+
+        assert_eq!(assemble(source).unwrap(), [0x81, 0x7E]);
+    }
+
+    #[test]
+    fn errors_carry_the_line() {
+        let err = assemble("CLS\nJP nowhere").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.to_string(), "line 2: unknown symbol \"nowhere\"");
+
+        assert_eq!(assemble("LD V0, 256").unwrap_err().line, 1);
+        assert_eq!(assemble("a:\na:").unwrap_err().line, 2);
+        assert!(assemble("FROB V0").is_err());
+    }
+}
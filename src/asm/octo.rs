@@ -0,0 +1,852 @@
+//! Octo's syntax, see [`assemble_octo`].
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::{number, AsmError, Program};
+use crate::cpu::PROGRAM_START;
+
+/// Macro expansions allowed in one program, to stop runaway recursion.
+const MAX_EXPANSIONS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A test on registers, as written after `if` and `while`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Equal(u16, Operand),
+    NotEqual(u16, Operand),
+    Less(u16, Operand),
+    Greater(u16, Operand),
+    LessOrEqual(u16, Operand),
+    GreaterOrEqual(u16, Operand),
+    Key(u16),
+    NotKey(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(u16),
+    Byte(u16),
+}
+
+impl Condition {
+    fn negated(self) -> Condition {
+        use Condition::*;
+
+        match self {
+            Equal(x, y) => NotEqual(x, y),
+            NotEqual(x, y) => Equal(x, y),
+            Less(x, y) => GreaterOrEqual(x, y),
+            GreaterOrEqual(x, y) => Less(x, y),
+            Greater(x, y) => LessOrEqual(x, y),
+            LessOrEqual(x, y) => Greater(x, y),
+            Key(x) => NotKey(x),
+            NotKey(x) => Key(x),
+        }
+    }
+
+    /// Opcodes that skip whatever follows them unless the condition holds.
+    fn opcodes(self) -> Vec<u16> {
+        use Condition::*;
+
+        let x = |x: u16| x << 8;
+        // VF := y - vx, VF = no borrow, i.e. vx <= y
+        let sub_from = |vx: u16, y: Operand| match y {
+            Operand::Register(vy) => [0x8F00 | vy << 4, 0x8F05 | vx << 4],
+            Operand::Byte(nn) => [0x6F00 | nn, 0x8F05 | vx << 4],
+        };
+        // VF := vx - y, VF = no borrow, i.e. vx >= y
+        let sub = |vx: u16, y: Operand| match y {
+            Operand::Register(vy) => [0x8F00 | vy << 4, 0x8F07 | vx << 4],
+            Operand::Byte(nn) => [0x6F00 | nn, 0x8F07 | vx << 4],
+        };
+        let flag_is = |pair: [u16; 2], flag: u16| vec![pair[0], pair[1], 0x4F00 | flag];
+        match self {
+            Equal(vx, Operand::Byte(nn)) => vec![0x4000 | x(vx) | nn],
+            NotEqual(vx, Operand::Byte(nn)) => vec![0x3000 | x(vx) | nn],
+            Equal(vx, Operand::Register(vy)) => vec![0x9000 | x(vx) | vy << 4],
+            NotEqual(vx, Operand::Register(vy)) => vec![0x5000 | x(vx) | vy << 4],
+            Less(vx, y) => flag_is(sub(vx, y), 0),
+            GreaterOrEqual(vx, y) => flag_is(sub(vx, y), 1),
+            Greater(vx, y) => flag_is(sub_from(vx, y), 0),
+            LessOrEqual(vx, y) => flag_is(sub_from(vx, y), 1),
+            Key(vx) => vec![0xE0A1 | x(vx)],
+            NotKey(vx) => vec![0xE09E | x(vx)],
+        }
+    }
+}
+
+/// An open `begin` or `loop`, with the jumps waiting for its end.
+enum Block {
+    If { jump: usize },
+    Else { jump: usize },
+    Loop { start: usize, breaks: Vec<usize> },
+}
+
+/// A jump or load whose label wasn't defined yet.
+struct Fixup {
+    offset: usize,
+    name: String,
+    line: usize,
+    long: bool,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    rom: Vec<u8>,
+    labels: BTreeMap<String, usize>,
+    constants: HashMap<String, u32>,
+    aliases: HashMap<String, u16>,
+    macros: HashMap<String, Macro>,
+    blocks: Vec<Block>,
+    fixups: Vec<Fixup>,
+    expansions: usize,
+    line: usize,
+}
+
+/// Assembles source in the common subset of Octo's syntax, so programs
+/// written for Octo can be built here:
+///
+/// ```text
+/// # comments start with a hash
+/// :const SPEED 3
+/// :alias x v1
+/// :macro bump reg { reg += SPEED }
+///
+/// : main
+///     i := ball
+///     loop
+///         sprite x x 2
+///         bump x
+///         if x == 60 then x := 0
+///         sprite x x 2
+///     again
+///
+/// : ball 0b11000000 0xC0
+/// ```
+///
+/// Labels, `:const`, `:alias`, `:macro`, `:org`, `:byte` and `:call` are
+/// understood, as are every instruction, `if ... then`, `if ... begin ...
+/// else ... end`, `loop ... while ... again` and the `<`, `>`, `<=` and `>=`
+/// comparisons, which use VF like Octo's. `:breakpoint` and `:monitor` are
+/// skipped. `:calc`, `:unpack`, `:next`, `:pointer`, `:stringmode` and
+/// `:assert` are not supported. Like Octo, the program starts with a jump
+/// to `main`.
+pub fn assemble_octo(source: &str) -> Result<Program, AsmError> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        rom: Vec::new(),
+        labels: BTreeMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        blocks: Vec::new(),
+        fixups: Vec::new(),
+        expansions: 0,
+        line: 1,
+    };
+    assembler.jump_to("main", 0x1000)?;
+    while let Some(token) = assembler.tokens.pop_front() {
+        assembler.line = token.line;
+        assembler.statement(&token.text)?;
+    }
+    assembler.finish()
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (index, raw) in source.lines().enumerate() {
+        let text = raw.split('#').next().unwrap_or("");
+        tokens.extend(text.split_whitespace().map(|word| Token {
+            text: word.to_string(),
+            line: index + 1,
+        }));
+    }
+    tokens
+}
+
+impl Assembler {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn here(&self) -> usize {
+        PROGRAM_START + self.rom.len()
+    }
+
+    fn next(&mut self) -> Result<String, AsmError> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| self.error("unexpected end of program"))?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), AsmError> {
+        match self.next()? {
+            found if found == word => Ok(()),
+            found => Err(self.error(format!("expected {}, found {:?}", word, found))),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    /// Writes `address` into the low 12 bits of the opcode at `offset`.
+    fn patch(&mut self, offset: usize, address: usize) -> Result<(), AsmError> {
+        if address > 0xFFF {
+            return Err(self.error(format!("{:#X} does not fit in 12 bits", address)));
+        }
+        self.rom[offset] = (self.rom[offset] & 0xF0) | (address >> 8) as u8;
+        self.rom[offset + 1] = address as u8;
+        Ok(())
+    }
+
+    fn statement(&mut self, word: &str) -> Result<(), AsmError> {
+        if let Some(registered) = self.macros.get(word).cloned() {
+            return self.expand(registered);
+        }
+        if let Some(x) = self.register(word) {
+            return self.assignment(x);
+        }
+        match word {
+            ":" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                if self.labels.insert(name.clone(), self.here()).is_some() {
+                    return Err(self.error(format!("{} is defined twice", name)));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let value = self.next()?;
+                let value = self.value(&value)?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let register = self.next()?;
+                let register = self.expect_register(&register)?;
+                self.aliases.insert(name, register);
+            }
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let address = self.next()?;
+                let address = self.value(&address)? as usize;
+                if address < self.here() {
+                    return Err(self.error(format!("cannot go back to {:#X}", address)));
+                }
+                self.rom.resize(address - PROGRAM_START, 0);
+            }
+            ":byte" => {
+                let value = self.next()?;
+                let value = self.byte(&value)?;
+                self.rom.push(value as u8);
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.jump_to(&target, 0x2000)?;
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ";" | "return" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "scroll-down" => self.nibble_op(0x00C0)?,
+            "scroll-up" => self.nibble_op(0x00D0)?,
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "native" => {
+                let target = self.next()?;
+                self.jump_to(&target, 0x0000)?;
+            }
+            "jump" => {
+                let target = self.next()?;
+                self.jump_to(&target, 0x1000)?;
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.jump_to(&target, 0xB000)?;
+            }
+            "sprite" => {
+                let (x, y) = (self.next()?, self.next()?);
+                let (x, y) = (self.expect_register(&x)?, self.expect_register(&y)?);
+                let n = self.next()?;
+                let n = self.nibble(&n)?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "bcd" => self.register_op(0xF033)?,
+            "saveflags" => self.register_op(0xF075)?,
+            "loadflags" => self.register_op(0xF085)?,
+            "save" => self.range_op(0xF055, 0x5002)?,
+            "load" => self.range_op(0xF065, 0x5003)?,
+            "plane" => {
+                let n = self.next()?;
+                let n = self.nibble(&n)?;
+                self.emit(0xF001 | n << 8);
+            }
+            "audio" => self.emit(0xF002),
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.next()?;
+                let x = self.expect_register(&x)?;
+                let low = match word {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(0xF000 | x << 8 | low);
+            }
+            "i" => self.index_assignment()?,
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => condition.opcodes().into_iter().for_each(|op| self.emit(op)),
+                    "begin" => {
+                        let jump = self.skip_unless(condition);
+                        self.blocks.push(Block::If { jump });
+                    }
+                    found => {
+                        return Err(self.error(format!("expected then or begin, found {:?}", found)))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump }) => {
+                    let end = self.rom.len();
+                    self.emit(0x1000);
+                    self.patch(jump, self.here())?;
+                    self.blocks.push(Block::Else { jump: end });
+                }
+                _ => return Err(self.error("else without if ... begin")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump } | Block::Else { jump }) => self.patch(jump, self.here())?,
+                _ => return Err(self.error("end without if ... begin")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here(),
+                breaks: Vec::new(),
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                let jump = self.skip_unless(condition);
+                match self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    Some(Block::Loop { breaks, .. }) => breaks.push(jump),
+                    _ => return Err(self.error("while outside a loop")),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks }) => {
+                    let jump = self.rom.len();
+                    self.emit(0x1000);
+                    self.patch(jump, start)?;
+                    for jump in breaks {
+                        self.patch(jump, self.here())?;
+                    }
+                }
+                _ => return Err(self.error("again without loop")),
+            },
+            ":calc" | ":unpack" | ":next" | ":pointer" | ":stringmode" | ":assert" => {
+                return Err(self.error(format!("{} is not supported", word)))
+            }
+            _ if word.starts_with(':') => {
+                return Err(self.error(format!("unknown directive {}", word)))
+            }
+            _ => match self.number(word) {
+                Some(_) => {
+                    let value = self.byte(word)?;
+                    self.rom.push(value as u8);
+                }
+                // anything else names a subroutine, defined later or not
+                None => self.jump_to(word, 0x2000)?,
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits the test for `condition` and a jump around what follows, for
+    /// the jump to be taken when the condition fails. Returns where the
+    /// jump is, to patch once the target is known.
+    fn skip_unless(&mut self, condition: Condition) -> usize {
+        for opcode in condition.negated().opcodes() {
+            self.emit(opcode);
+        }
+        let jump = self.rom.len();
+        self.emit(0x1000);
+        jump
+    }
+
+    fn assignment(&mut self, x: u16) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let source = self.next()?;
+        let vx = x << 8;
+        let register = self.register(&source);
+        let opcode = match (op.as_str(), register) {
+            (":=", Some(y)) => 0x8000 | vx | y << 4,
+            (":=", None) => match source.as_str() {
+                "random" => {
+                    let mask = self.next()?;
+                    0xC000 | vx | self.byte(&mask)?
+                }
+                "delay" => 0xF007 | vx,
+                "key" => 0xF00A | vx,
+                _ => 0x6000 | vx | self.byte(&source)?,
+            },
+            ("+=", Some(y)) => 0x8004 | vx | y << 4,
+            ("+=", None) => 0x7000 | vx | self.byte(&source)?,
+            ("-=", Some(y)) => 0x8005 | vx | y << 4,
+            ("-=", None) => 0x7000 | vx | (self.byte(&source)?.wrapping_neg() & 0xFF),
+            ("=-", Some(y)) => 0x8007 | vx | y << 4,
+            ("|=", Some(y)) => 0x8001 | vx | y << 4,
+            ("&=", Some(y)) => 0x8002 | vx | y << 4,
+            ("^=", Some(y)) => 0x8003 | vx | y << 4,
+            (">>=", Some(y)) => 0x8006 | vx | y << 4,
+            ("<<=", Some(y)) => 0x800E | vx | y << 4,
+            _ => return Err(self.error(format!("cannot do {} {} here", op, source))),
+        };
+        self.emit(opcode);
+        Ok(())
+    }
+
+    fn index_assignment(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let source = self.next()?;
+        match (op.as_str(), source.as_str()) {
+            ("+=", _) => {
+                let x = self.expect_register(&source)?;
+                self.emit(0xF01E | x << 8);
+            }
+            (":=", "hex" | "bighex") => {
+                let x = self.next()?;
+                let x = self.expect_register(&x)?;
+                self.emit(if source == "hex" { 0xF029 } else { 0xF030 } | x << 8);
+            }
+            (":=", "long") => {
+                let target = self.next()?;
+                self.emit(0xF000);
+                match self.address(&target) {
+                    Some(address) if address > 0xFFFF => {
+                        return Err(self.error(format!("{} does not fit in 0xFFFF", target)))
+                    }
+                    Some(address) => self.emit(address as u16),
+                    None => self.forward(&target, 0x0000, true),
+                }
+            }
+            (":=", _) => self.jump_to(&source, 0xA000)?,
+            _ => return Err(self.error(format!("cannot do i {} {}", op, source))),
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.next()?;
+        let x = self.expect_register(&x)?;
+        let op = self.next()?;
+        if op == "key" || op == "-key" {
+            return Ok(if op == "key" {
+                Condition::Key(x)
+            } else {
+                Condition::NotKey(x)
+            });
+        }
+        let y = self.next()?;
+        let y = match self.register(&y) {
+            Some(register) => Operand::Register(register),
+            None => Operand::Byte(self.byte(&y)?),
+        };
+        Ok(match op.as_str() {
+            "==" => Condition::Equal(x, y),
+            "!=" => Condition::NotEqual(x, y),
+            "<" => Condition::Less(x, y),
+            ">" => Condition::Greater(x, y),
+            "<=" => Condition::LessOrEqual(x, y),
+            ">=" => Condition::GreaterOrEqual(x, y),
+            _ => return Err(self.error(format!("unknown comparison {:?}", op))),
+        })
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name = self.next()?;
+        self.check_name(&name)?;
+        let mut params = Vec::new();
+        loop {
+            match self.next()?.as_str() {
+                "{" => break,
+                param => params.push(param.to_string()),
+            }
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        while let Some(token) = self.tokens.pop_front() {
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                self.macros.insert(name, Macro { params, body });
+                return Ok(());
+            }
+            body.push(token);
+        }
+        Err(self.error(format!("macro {} has no closing }}", name)))
+    }
+
+    /// Queues the body of `registered` with its parameters replaced by the
+    /// tokens that follow the invocation.
+    fn expand(&mut self, registered: Macro) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error("too many macro expansions, is one recursive?"));
+        }
+        let mut arguments = HashMap::new();
+        for param in &registered.params {
+            arguments.insert(param.as_str(), self.next()?);
+        }
+        let line = self.line;
+        for token in registered.body.iter().rev() {
+            let text = arguments.get(token.text.as_str()).unwrap_or(&token.text);
+            self.tokens.push_front(Token {
+                text: text.clone(),
+                line,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), AsmError> {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid || self.register(name).is_some() {
+            return Err(self.error(format!("invalid name {:?}", name)));
+        }
+        Ok(())
+    }
+
+    fn register(&self, word: &str) -> Option<u16> {
+        if let Some(&register) = self.aliases.get(word) {
+            return Some(register);
+        }
+        let digit = word.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&self, word: &str) -> Result<u16, AsmError> {
+        self.register(word)
+            .ok_or_else(|| self.error(format!("expected a register, found {:?}", word)))
+    }
+
+    /// A number, with Octo's negative decimals, or a constant.
+    fn number(&self, word: &str) -> Option<i64> {
+        match word.strip_prefix('-') {
+            Some(rest) => number(rest).map(|value| -(value as i64)),
+            None => number(word).map(i64::from),
+        }
+        .or_else(|| self.constants.get(word).map(|&value| value as i64))
+    }
+
+    /// A number, constant or label defined so far.
+    fn value(&self, word: &str) -> Result<u32, AsmError> {
+        if let Some(address) = self.labels.get(word) {
+            return Ok(*address as u32);
+        }
+        match self.number(word) {
+            Some(value) if value >= 0 => Ok(value as u32),
+            _ => Err(self.error(format!("{:?} is not a value", word))),
+        }
+    }
+
+    fn byte(&self, word: &str) -> Result<u16, AsmError> {
+        match self.number(word) {
+            Some(value @ -128..=255) => Ok(value as u16 & 0xFF),
+            Some(_) => Err(self.error(format!("{} does not fit in a byte", word))),
+            None => Err(self.error(format!("{:?} is not a byte", word))),
+        }
+    }
+
+    fn nibble(&self, word: &str) -> Result<u16, AsmError> {
+        match self.number(word) {
+            Some(value @ 0..=15) => Ok(value as u16),
+            _ => Err(self.error(format!("{:?} is not a number from 0 to 15", word))),
+        }
+    }
+
+    fn nibble_op(&mut self, base: u16) -> Result<(), AsmError> {
+        let n = self.next()?;
+        let n = self.nibble(&n)?;
+        self.emit(base | n);
+        Ok(())
+    }
+
+    fn register_op(&mut self, base: u16) -> Result<(), AsmError> {
+        let x = self.next()?;
+        let x = self.expect_register(&x)?;
+        self.emit(base | x << 8);
+        Ok(())
+    }
+
+    /// `save vx` or XO-CHIP's `save vx - vy`, likewise for `load`.
+    fn range_op(&mut self, single: u16, range: u16) -> Result<(), AsmError> {
+        let x = self.next()?;
+        let x = self.expect_register(&x)?;
+        if self.peek() != Some("-") {
+            self.emit(single | x << 8);
+            return Ok(());
+        }
+        self.next()?;
+        let y = self.next()?;
+        let y = self.expect_register(&y)?;
+        self.emit(range | x << 8 | y << 4);
+        Ok(())
+    }
+
+    /// The address `word` stands for, if it is known yet.
+    fn address(&self, word: &str) -> Option<usize> {
+        self.labels
+            .get(word)
+            .copied()
+            .or_else(|| self.number(word).map(|value| value as usize))
+    }
+
+    /// Emits `base` with the 12-bit address of `target`, patched later if
+    /// it is a label still to come.
+    fn jump_to(&mut self, target: &str, base: u16) -> Result<(), AsmError> {
+        match self.address(target) {
+            Some(address) if address > 0xFFF => {
+                Err(self.error(format!("{} does not fit in 12 bits", target)))
+            }
+            Some(address) => {
+                self.emit(base | address as u16);
+                Ok(())
+            }
+            None => {
+                self.check_name(target)?;
+                self.forward(target, base, false);
+                Ok(())
+            }
+        }
+    }
+
+    fn forward(&mut self, target: &str, base: u16, long: bool) {
+        self.fixups.push(Fixup {
+            offset: self.rom.len(),
+            name: target.to_string(),
+            line: self.line,
+            long,
+        });
+        self.emit(base);
+    }
+
+    fn finish(mut self) -> Result<Program, AsmError> {
+        if !self.blocks.is_empty() {
+            return Err(self.error("a begin or loop is never closed"));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let error = |message: String| AsmError {
+                line: fixup.line,
+                message,
+            };
+            let address = *self
+                .labels
+                .get(&fixup.name)
+                .ok_or_else(|| error(format!("undefined name {}", fixup.name)))?;
+            if fixup.long && address > 0xFFFF {
+                return Err(error(format!("{} does not fit in 0xFFFF", fixup.name)));
+            } else if fixup.long {
+                self.rom[fixup.offset..fixup.offset + 2]
+                    .copy_from_slice(&(address as u16).to_be_bytes());
+            } else if address > 0xFFF {
+                return Err(error(format!("{} does not fit in 12 bits", fixup.name)));
+            } else {
+                self.patch(fixup.offset, address)?;
+            }
+        }
+        Ok(Program {
+            rom: self.rom,
+            labels: self.labels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::disasm::disassemble_rom;
+
+    /// A ball bouncing off the walls, written for this test in the style of
+    /// Octo's examples. It is not a published program: round trips against
+    /// published Octo sources are still to be added as fixtures.
+    const BOUNCE: &str = "
+        :alias x v0
+        :alias y v1
+        :alias dx v2
+        :alias dy v3
+        :const WIDTH 63
+
+        : ball 0x80 0x00
+
+        : main
+            dx := 1
+            dy := 1
+            i := ball
+            loop
+                sprite x y 1
+                x += dx
+                y += dy
+                if x == 0 then dx := 1
+                if x == WIDTH then dx := -1
+                if y == 0 then dy := 1
+                if y == 31 then dy := -1
+                sprite x y 1
+                delay := v4
+                loop
+                    v5 := delay
+                    while v5 != 0
+                again
+            again
+    ";
+
+    /// [`BOUNCE`] by hand in this crate's own syntax.
+    const BOUNCE_EXPECTED: &str = "
+            JP main
+        ball:
+            db 0x80
+            db 0x00
+        main:
+            LD V2, 1
+            LD V3, 1
+            LD I, 0x202
+        outer:
+            DRW V0, V1, 1
+            ADD V0, V2
+            ADD V1, V3
+            SNE V0, 0
+            LD V2, 1
+            SNE V0, 63
+            LD V2, 0xFF
+            SNE V1, 0
+            LD V3, 1
+            SNE V1, 31
+            LD V3, 0xFF
+            DRW V0, V1, 1
+            LD DT, V4
+        inner:
+            LD V5, DT
+            SNE V5, 0
+            JP done
+            JP inner
+        done:
+            JP outer
+    ";
+
+    #[test]
+    fn octo_programs_match_their_plain_listing() {
+        let program = assemble_octo(BOUNCE).unwrap();
+        assert_eq!(program.rom, assemble(BOUNCE_EXPECTED).unwrap());
+        assert_eq!(program.labels["main"], 0x204);
+
+        // and back again through the disassembler
+        let listing: String = disassemble_rom(&program.rom)
+            .into_iter()
+            .map(|(_, _, text)| text + "\n")
+            .collect();
+        assert_eq!(assemble(&listing).unwrap(), program.rom);
+    }
+
+    #[test]
+    fn blocks_macros_and_comparisons() {
+        let source = "
+            :macro twice op reg { reg op 1 reg op 1 }
+            : main
+                twice += v1           # v1 += 1 v1 += 1
+                if v1 > 5 begin
+                    clear
+                else
+                    v2 := key
+                end
+                if v0 -key then return
+                i := long data
+            : data 1 2
+        ";
+        let rom = assemble_octo(source).unwrap().rom;
+        let expected = [
+            0x12, 0x02, // jump main
+            0x71, 0x01, 0x71, 0x01, //
+            // vf := 5; vf -= v1; jump to the else unless vf == 0
+            0x6F, 0x05, 0x8F, 0x15, 0x4F, 0x01, 0x12, 0x12, //
+            0x00, 0xE0, 0x12, 0x14, // clear, jump over the else
+            0xF2, 0x0A, //
+            0xE0, 0x9E, 0x00, 0xEE, //
+            0xF0, 0x00, 0x02, 0x1C, //
+            0x01, 0x02,
+        ];
+        assert_eq!(rom, expected);
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        let error = assemble_octo(": main\n  jump nowhere").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: undefined name nowhere");
+        let error = assemble_octo(": main\n loop\n clear").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: a begin or loop is never closed");
+        let error = assemble_octo("clear").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: undefined name main");
+        let error = assemble_octo(": main v0 := 256").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: 256 does not fit in a byte");
+        let error = assemble_octo(": main i := long 0x12345").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: 0x12345 does not fit in 0xFFFF");
+        let far = ": main i := long far\n :org 0x10200\n : far 0";
+        let error = assemble_octo(far).unwrap_err();
+        assert_eq!(error.to_string(), "line 1: far does not fit in 0xFFFF");
+        // loops and ifs jump as far as jump does
+        let high = ": main\n :org 0x1000\n loop v0 += 1\n again";
+        let error = assemble_octo(high).unwrap_err();
+        assert_eq!(error.to_string(), "line 4: 0x1000 does not fit in 12 bits");
+        let high = ": main\n :org 0xFFC\n if v0 == 1 begin v1 := 2 end";
+        let error = assemble_octo(high).unwrap_err();
+        assert_eq!(error.to_string(), "line 3: 0x1002 does not fit in 12 bits");
+    }
+}
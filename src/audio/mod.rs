@@ -0,0 +1,192 @@
+//! Sound output for frontends: turns the CPU's buzzer into samples and
+//! hands them to an [`AudioSink`], which is where a sound device plugs in.
+//! Headless and test builds use [`NullSink`] or collect into a `Vec`, and
+//! [`Resampled`] adapts a sink whose rate the samples weren't made for.
+//! [`sample_ring`] carries samples to a device callback on another thread.
+//! Frontends with an audio stream of their own can skip all that and pull
+//! from an [`AudioSource`] directly.
+
+mod resample;
+mod ring;
+mod source;
+
+pub use resample::{Resampled, Resampler};
+pub use ring::{sample_ring, SampleConsumer, SampleProducer};
+pub use source::{AudioPattern, AudioSource, PATTERN_BITS};
+
+/// Somewhere to send mono `f32` samples in `-1.0..=1.0`.
+pub trait AudioSink {
+    /// Rate the sink plays samples at, in Hz.
+    fn sample_rate(&self) -> u32;
+
+    fn write(&mut self, samples: &[f32]);
+}
+
+/// Discards everything, for builds without sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NullSink {
+    pub sample_rate: u32,
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+/// Collects samples at 48 kHz, handy in tests.
+impl AudioSink for Vec<f32> {
+    fn sample_rate(&self) -> u32 {
+        48_000
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        self.extend_from_slice(samples);
+    }
+}
+
+/// Square-wave beep while the sound timer runs.
+///
+/// With no `frequency` the tone is whatever the [`AudioSource`] plays,
+/// which for a [`CPU`](crate::cpu::CPU) is the XO-CHIP pattern buffer and a
+/// 250 Hz square wave for everything else. A fixed frequency replaces the
+/// waveform but still sounds for exactly as long as the timer says.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beeper {
+    pub frequency: Option<f32>,
+    /// Output amplitude, `0.0..=1.0`.
+    pub volume: f32,
+    /// Position within the square wave's period, `0.0..1.0`.
+    phase: f32,
+    /// Fractional samples not yet produced, kept so frames don't drift.
+    carry: f64,
+    buffer: Vec<f32>,
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Beeper {
+            frequency: None,
+            volume: 0.25,
+            phase: 0.0,
+            carry: 0.0,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Beeper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_frequency(frequency: f32) -> Self {
+        Beeper {
+            frequency: Some(frequency),
+            ..Self::default()
+        }
+    }
+
+    /// Produces one 60 Hz frame of audio into `sink`. Call once per frame,
+    /// before [`CPU::tick_timers`](crate::cpu::CPU::tick_timers).
+    pub fn play_frame<S: AudioSource + ?Sized>(
+        &mut self,
+        source: &mut S,
+        sink: &mut dyn AudioSink,
+    ) {
+        let sample_rate = sink.sample_rate();
+        self.carry += sample_rate as f64 / 60.0;
+        let count = self.carry as usize;
+        self.carry -= count as f64;
+
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        buffer.resize(count, 0.0);
+        self.render(source, &mut buffer, sample_rate);
+        sink.write(&buffer);
+        self.buffer = buffer;
+    }
+
+    /// Fills `out` from `source`, with this beeper's frequency and volume
+    /// applied.
+    pub fn render<S: AudioSource + ?Sized>(
+        &mut self,
+        source: &mut S,
+        out: &mut [f32],
+        sample_rate: u32,
+    ) {
+        source.fill(out, sample_rate);
+        let step = self.frequency.map(|f| f / sample_rate as f32);
+        for sample in out {
+            if *sample == 0.0 {
+                continue;
+            }
+            if let Some(step) = step {
+                *sample = if self.phase < 0.5 { 1.0 } else { -1.0 };
+                self.phase = (self.phase + step) % 1.0;
+            }
+            *sample *= self.volume;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    #[test]
+    fn silent_while_the_timer_is_zero() {
+        let mut cpu = CPU::new();
+        let mut samples = Vec::new();
+
+        Beeper::new().play_frame(&mut cpu, &mut samples);
+
+        assert_eq!(samples.len(), 800);
+        assert!(samples.iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn fixed_frequency_square_wave_at_volume() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 1;
+        let mut beeper = Beeper::with_frequency(750.0);
+        beeper.volume = 0.5;
+        let mut samples = Vec::new();
+
+        beeper.play_frame(&mut cpu, &mut samples);
+
+        // 64 samples per period at 48 kHz
+        assert!(samples[..32].iter().all(|s| *s == 0.5));
+        assert!(samples[32..64].iter().all(|s| *s == -0.5));
+        assert!(samples[64..96].iter().all(|s| *s == 0.5));
+    }
+
+    struct Counter(usize);
+
+    impl AudioSink for Counter {
+        fn sample_rate(&self) -> u32 {
+            22_050
+        }
+
+        fn write(&mut self, samples: &[f32]) {
+            self.0 += samples.len();
+        }
+    }
+
+    #[test]
+    fn frames_at_odd_sample_rates_do_not_drift() {
+        let mut cpu = CPU::new();
+        let mut sink = Counter(0);
+        let mut beeper = Beeper::new();
+
+        // 367.5 samples per frame
+        for _ in 0..60 {
+            beeper.play_frame(&mut cpu, &mut sink);
+        }
+
+        assert_eq!(sink.0, 22_050);
+    }
+}
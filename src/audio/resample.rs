@@ -0,0 +1,129 @@
+use super::AudioSink;
+
+/// Streaming linear-interpolation resampler for mono audio.
+///
+/// Good enough for a buzzer: square waves pick up a little smoothing at the
+/// edges but keep their pitch exactly, which is what matters when a device
+/// runs at a rate the samples weren't produced for. Output lags the input
+/// by one source sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resampler {
+    from: u32,
+    to: u32,
+    /// Source samples advanced per output sample.
+    step: f64,
+    /// Where the next output falls between `previous` and the next input.
+    position: f64,
+    previous: f32,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        Resampler {
+            from,
+            to,
+            step: from as f64 / to as f64,
+            position: 1.0,
+            previous: 0.0,
+        }
+    }
+
+    pub fn from_rate(&self) -> u32 {
+        self.from
+    }
+
+    pub fn to_rate(&self) -> u32 {
+        self.to
+    }
+
+    /// Converts `input` and appends the result to `out`. Calls can split the
+    /// stream anywhere; the fractional position carries over.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        for &sample in input {
+            while self.position < 1.0 {
+                let t = self.position as f32;
+                out.push(self.previous + (sample - self.previous) * t);
+                self.position += self.step;
+            }
+            self.position -= 1.0;
+            self.previous = sample;
+        }
+    }
+}
+
+/// Wraps a sink so that samples can be written at `from` Hz whatever rate
+/// the inner sink runs at.
+pub struct Resampled<S: AudioSink> {
+    pub inner: S,
+    resampler: Resampler,
+    buffer: Vec<f32>,
+}
+
+impl<S: AudioSink> Resampled<S> {
+    pub fn new(inner: S, from: u32) -> Self {
+        let resampler = Resampler::new(from, inner.sample_rate());
+        Resampled {
+            inner,
+            resampler,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<S: AudioSink> AudioSink for Resampled<S> {
+    fn sample_rate(&self) -> u32 {
+        self.resampler.from_rate()
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        self.buffer.clear();
+        self.resampler.process(samples, &mut self.buffer);
+        self.inner.write(&self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_rates_pass_samples_through() {
+        let mut resampler = Resampler::new(48_000, 48_000);
+        let mut out = Vec::new();
+
+        resampler.process(&[0.5, -0.5, 1.0, 0.25], &mut out);
+
+        assert_eq!(out, [0.5, -0.5, 1.0]);
+    }
+
+    #[test]
+    fn output_length_follows_the_rate_ratio() {
+        for to in [44_100, 22_050, 96_000] {
+            let mut resampler = Resampler::new(48_000, to);
+            let mut out = Vec::new();
+            // in uneven chunks, to check the position carries over; the
+            // one sample lag costs up to two outputs
+            for chunk in vec![1.0; 48_000].chunks(777) {
+                resampler.process(chunk, &mut out);
+            }
+
+            assert!(
+                (out.len() as i64 - to as i64).abs() <= 2,
+                "{} -> {}",
+                to,
+                out.len()
+            );
+            assert!(out[1..].iter().all(|s| (*s - 1.0).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn sink_adapter_reports_the_source_rate() {
+        let mut sink = Resampled::new(Vec::new(), 96_000);
+        assert_eq!(sink.sample_rate(), 96_000);
+
+        sink.write(&[1.0; 960]);
+
+        assert!((479..=481).contains(&sink.inner.len()));
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::AudioSink;
+
+/// Creates a lock-free single-producer, single-consumer queue of samples
+/// holding up to `capacity` of them, for handing audio from the emulation
+/// thread to a device callback. Neither side ever blocks, so a slow frame
+/// on one thread can't make the other one stall.
+pub fn sample_ring(capacity: usize, sample_rate: u32) -> (SampleProducer, SampleConsumer) {
+    let shared = Arc::new(Shared {
+        // one slot stays empty to tell a full ring from an empty one
+        slots: (0..capacity + 1).map(|_| AtomicU32::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    (
+        SampleProducer {
+            shared: Arc::clone(&shared),
+            sample_rate,
+            dropped: 0,
+        },
+        SampleConsumer { shared, missed: 0 },
+    )
+}
+
+struct Shared {
+    /// Samples as `f32` bits.
+    slots: Box<[AtomicU32]>,
+    /// Next slot to read, only advanced by the consumer.
+    read: AtomicUsize,
+    /// Next slot to write, only advanced by the producer.
+    write: AtomicUsize,
+}
+
+impl Shared {
+    fn next(&self, index: usize) -> usize {
+        (index + 1) % self.slots.len()
+    }
+}
+
+/// The emulation side of a [`sample_ring`], an [`AudioSink`] for a
+/// [`super::Beeper`].
+pub struct SampleProducer {
+    shared: Arc<Shared>,
+    sample_rate: u32,
+    dropped: u64,
+}
+
+impl SampleProducer {
+    /// Samples that can be queued right now without dropping any.
+    pub fn free(&self) -> usize {
+        let write = self.shared.write.load(Ordering::Relaxed);
+        let read = self.shared.read.load(Ordering::Acquire);
+        (read + self.shared.slots.len() - write - 1) % self.shared.slots.len()
+    }
+
+    /// Samples thrown away because the ring was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl AudioSink for SampleProducer {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Queues as many samples as fit and drops the rest.
+    fn write(&mut self, samples: &[f32]) {
+        let shared = &self.shared;
+        let mut write = shared.write.load(Ordering::Relaxed);
+        let read = shared.read.load(Ordering::Acquire);
+        for (written, sample) in samples.iter().enumerate() {
+            let next = shared.next(write);
+            if next == read {
+                self.dropped += (samples.len() - written) as u64;
+                break;
+            }
+            shared.slots[write].store(sample.to_bits(), Ordering::Relaxed);
+            write = next;
+        }
+        shared.write.store(write, Ordering::Release);
+    }
+}
+
+/// The device side of a [`sample_ring`].
+pub struct SampleConsumer {
+    shared: Arc<Shared>,
+    missed: u64,
+}
+
+impl SampleConsumer {
+    /// Samples queued right now.
+    pub fn len(&self) -> usize {
+        let write = self.shared.write.load(Ordering::Acquire);
+        let read = self.shared.read.load(Ordering::Relaxed);
+        (write + self.shared.slots.len() - read) % self.shared.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Samples the device asked for that weren't there yet.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Fills `out` from the queue, padding with silence when it runs dry,
+    /// as an audio callback needs. Returns how many samples were queued.
+    pub fn fill(&mut self, out: &mut [f32]) -> usize {
+        let shared = &self.shared;
+        let write = shared.write.load(Ordering::Acquire);
+        let mut read = shared.read.load(Ordering::Relaxed);
+        let mut filled = 0;
+        for sample in out.iter_mut() {
+            if read == write {
+                *sample = 0.0;
+                continue;
+            }
+            *sample = f32::from_bits(shared.slots[read].load(Ordering::Relaxed));
+            read = shared.next(read);
+            filled += 1;
+        }
+        shared.read.store(read, Ordering::Release);
+        self.missed += (out.len() - filled) as u64;
+        filled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn ring_drops_when_full_and_pads_when_empty() {
+        let (mut producer, mut consumer) = sample_ring(4, 48_000);
+        assert_eq!(producer.free(), 4);
+
+        producer.write(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        assert_eq!(producer.dropped(), 2);
+        assert_eq!(consumer.len(), 4);
+
+        let mut out = [1.0; 6];
+        assert_eq!(consumer.fill(&mut out), 4);
+        assert_eq!(out, [0.1, 0.2, 0.3, 0.4, 0.0, 0.0]);
+        assert_eq!(consumer.missed(), 2);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn samples_arrive_in_order_across_threads() {
+        let (mut producer, mut consumer) = sample_ring(64, 48_000);
+        let samples: Vec<f32> = (0..10_000).map(|n| n as f32).collect();
+
+        let feeder = thread::spawn(move || {
+            let mut fed = 0;
+            while fed < samples.len() {
+                let room = producer.free().min(samples.len() - fed);
+                producer.write(&samples[fed..fed + room]);
+                fed += room;
+                thread::yield_now();
+            }
+            producer.dropped()
+        });
+        let mut received = Vec::new();
+        let mut out = [0.0; 16];
+        while received.len() < 10_000 {
+            let filled = consumer.fill(&mut out);
+            received.extend_from_slice(&out[..filled]);
+            thread::yield_now();
+        }
+        assert_eq!(feeder.join().unwrap(), 0);
+
+        assert!(received.iter().enumerate().all(|(n, s)| *s == n as f32));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::cpu::CPU;
+
+/// Bits in XO-CHIP's audio pattern.
+pub const PATTERN_BITS: usize = 128;
+
+/// Where a frontend pulls samples from to mix into its own stream, at
+/// whatever rate the stream runs. [`CPU`] is one; [`super::Beeper`] plays
+/// any of them into an [`super::AudioSink`].
+pub trait AudioSource {
+    /// Fills `out` with samples in `-1.0..=1.0` at `sample_rate`, moving
+    /// playback on by as much.
+    fn fill(&mut self, out: &mut [f32], sample_rate: u32);
+
+    /// What is playing, for a debug view to draw.
+    fn pattern(&self) -> AudioPattern;
+}
+
+impl AudioSource for CPU {
+    fn fill(&mut self, out: &mut [f32], sample_rate: u32) {
+        self.render_audio(out, sample_rate);
+    }
+
+    fn pattern(&self) -> AudioPattern {
+        AudioPattern {
+            bits: self.audio_pattern,
+            playback_rate: self.audio_playback_rate(),
+            position: self.audio.pattern_position,
+            playing: self.sound_timer > 0,
+        }
+    }
+}
+
+/// A snapshot of the 1-bit pattern buffer and how it is being played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioPattern {
+    /// Most significant bit of the first byte first.
+    pub bits: [u8; 16],
+    /// Bits played per second.
+    pub playback_rate: f64,
+    /// Bit being played, fractional.
+    pub position: f64,
+    /// Whether the sound timer is running.
+    pub playing: bool,
+}
+
+impl AudioPattern {
+    pub fn bit(&self, index: usize) -> bool {
+        let index = index % PATTERN_BITS;
+        self.bits[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    /// Frequency the whole pattern repeats at, in Hz.
+    pub fn repeat_rate(&self) -> f64 {
+        self.playback_rate / PATTERN_BITS as f64
+    }
+
+    /// The pattern as a square wave `columns` characters wide, squeezed
+    /// or stretched to fit: a line of `_` over the high bits, one of `_`
+    /// under the low ones and a `^` below the bit being played.
+    ///
+    /// ```text
+    ///     ____    ____
+    /// ____    ____
+    ///       ^
+    /// ```
+    pub fn waveform(&self, columns: usize) -> String {
+        let columns = columns.max(1);
+        let bit_at = |column: usize| self.bit(column * PATTERN_BITS / columns);
+        let row = |high: bool| -> String {
+            (0..columns)
+                .map(|column| if bit_at(column) == high { '_' } else { ' ' })
+                .collect()
+        };
+        let cursor = self.position as usize * columns / PATTERN_BITS;
+        let mut marker = " ".repeat(cursor.min(columns - 1));
+        marker.push('^');
+        format!("{}\n{}\n{}\n", row(true), row(false), marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::DEFAULT_PATTERN;
+
+    #[test]
+    fn the_cpu_reports_its_pattern_and_rate() {
+        let mut cpu = CPU::new();
+        cpu.audio_pattern[0] = 0b1000_0001;
+        cpu.pitch = 112;
+
+        let pattern = cpu.pattern();
+        assert!(pattern.bit(0) && pattern.bit(7) && !pattern.bit(1));
+        assert_eq!(pattern.playback_rate, 8000.0);
+        assert_eq!(pattern.repeat_rate(), 62.5);
+        assert!(!pattern.playing);
+
+        cpu.sound_timer = 2;
+        let mut out = [0.0; 12];
+        cpu.fill(&mut out, 48_000);
+        assert_eq!(out[0], 1.0);
+        assert!(cpu.pattern().playing);
+        assert!((cpu.pattern().position - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn waveforms_squeeze_the_pattern_into_columns() {
+        let pattern = AudioPattern {
+            bits: DEFAULT_PATTERN,
+            playback_rate: 4000.0,
+            position: 24.0,
+            playing: true,
+        };
+
+        // a column per byte, alternately all low and all high
+        let expected = format!("{}\n{}\n   ^\n", " _".repeat(8), "_ ".repeat(8));
+        assert_eq!(pattern.waveform(16), expected);
+        // too few columns and every one lands on a low byte
+        assert_eq!(pattern.waveform(4), "    \n____\n^\n");
+    }
+}
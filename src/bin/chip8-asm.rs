@@ -0,0 +1,57 @@
+use std::{env, fs, path::Path, process};
+
+use cpu_emulator_chip_8::asm::{assemble, assemble_octo, OCTO_EXTENSIONS};
+
+fn main() {
+    let mut input = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => match args.next() {
+                Some(path) => output = Some(path),
+                None => {
+                    eprintln!("-o needs a path");
+                    process::exit(2);
+                }
+            },
+            _ if input.is_none() => input = Some(arg),
+            _ => {
+                eprintln!("unexpected argument {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("usage: chip8-asm <program.s|program.8o> [-o program.ch8]");
+        process::exit(2);
+    };
+    let output = output.unwrap_or_else(|| {
+        Path::new(&input)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    let source = fs::read_to_string(&input).unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    let is_octo = Path::new(&input)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| OCTO_EXTENSIONS.contains(&extension));
+    let assembled = if is_octo {
+        assemble_octo(&source).map(|program| program.rom)
+    } else {
+        assemble(&source)
+    };
+    let rom = assembled.unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    if let Err(err) = fs::write(&output, &rom) {
+        eprintln!("{}: {}", output, err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,51 @@
+use std::{env, io, process};
+
+use cpu_emulator_chip_8::cpu::{EmulatorMode, CPU};
+use cpu_emulator_chip_8::debugger::{gdb, Debugger};
+
+fn main() {
+    let mut mode = EmulatorMode::Chip8;
+    let mut rom = None;
+    let mut gdb_port = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = EmulatorMode::SuperChip,
+            "--xo" => mode = EmulatorMode::XoChip,
+            "--gdb" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => gdb_port = Some(port),
+                _ => {
+                    eprintln!("--gdb needs a port");
+                    process::exit(2);
+                }
+            },
+            _ if rom.is_none() => rom = Some(arg),
+            _ => {
+                eprintln!("unexpected argument {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("usage: chip8-debug <rom.ch8> [--schip | --xo] [--gdb PORT]");
+        process::exit(2);
+    };
+
+    let mut cpu = CPU::new_with_mode(mode);
+    if let Err(err) = cpu.load_rom_from_path(&rom) {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    }
+    let mut debugger = Debugger::new(cpu);
+    let served = match gdb_port {
+        Some(port) => {
+            eprintln!("waiting for gdb on 127.0.0.1:{}", port);
+            gdb::listen(&mut debugger, ("127.0.0.1", port))
+        }
+        None => debugger.repl(io::stdin().lock(), io::stdout()),
+    };
+    if let Err(err) = served {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
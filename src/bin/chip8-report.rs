@@ -0,0 +1,69 @@
+use std::{env, fs, path::Path, process};
+
+use cpu_emulator_chip_8::cpu::{EmulatorMode, CPU};
+use cpu_emulator_chip_8::disasm::{collect_coverage, html_report};
+
+const INSTRUCTIONS_PER_FRAME: usize = 11;
+
+fn main() {
+    let mut mode = EmulatorMode::Chip8;
+    let mut frames = 600;
+    let mut rom = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = EmulatorMode::SuperChip,
+            "--xo" => mode = EmulatorMode::XoChip,
+            "--frames" => match args.next().map(|value| value.parse()) {
+                Some(Ok(value)) => frames = value,
+                _ => {
+                    eprintln!("--frames needs a number");
+                    process::exit(2);
+                }
+            },
+            "-o" => match args.next() {
+                Some(path) => output = Some(path),
+                None => {
+                    eprintln!("-o needs a path");
+                    process::exit(2);
+                }
+            },
+            _ if rom.is_none() => rom = Some(arg),
+            _ => {
+                eprintln!("unexpected argument {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("usage: chip8-report <rom.ch8> [--frames N] [--schip | --xo] [-o report.html]");
+        process::exit(2);
+    };
+    let output = output.unwrap_or_else(|| {
+        Path::new(&rom)
+            .with_extension("html")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    let program = fs::read(&rom).unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    });
+    let mut cpu = CPU::new_with_mode(mode);
+    if let Err(err) = cpu.load_rom(&program) {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    }
+    let executed = collect_coverage(&mut cpu, frames, INSTRUCTIONS_PER_FRAME);
+
+    let title = Path::new(&rom)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| rom.clone());
+    if let Err(err) = fs::write(&output, html_report(&title, &program, &executed)) {
+        eprintln!("{}: {}", output, err);
+        process::exit(1);
+    }
+}
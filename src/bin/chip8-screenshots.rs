@@ -0,0 +1,140 @@
+use std::{
+    env, fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process,
+};
+
+use cpu_emulator_chip_8::capture::Recorder;
+use cpu_emulator_chip_8::cpu::{Status, CPU};
+use cpu_emulator_chip_8::display::{FrameBuffer, Palette, HIRES_WIDTH};
+use cpu_emulator_chip_8::library;
+use cpu_emulator_chip_8::metadata;
+
+const INSTRUCTIONS_PER_FRAME: u32 = 11;
+
+/// Runs one ROM headlessly and returns its final screen together with the
+/// palette from its descriptor, and a clip of the whole run when `gif` is
+/// set. A fault ends the run early but still produces a screenshot.
+fn capture(
+    rom: &Path,
+    frames: usize,
+    gif: bool,
+) -> Result<(FrameBuffer, Palette, Option<Recorder>), String> {
+    let meta = metadata::load_sidecar(rom).map_err(|err| err.to_string())?;
+    let mut cpu = CPU::new_with_mode(meta.as_ref().and_then(|m| m.mode()).unwrap_or_default());
+    cpu.load_rom_from_path(rom).map_err(|err| err.to_string())?;
+
+    // same colors as the desktop frontend
+    let mut palette = Palette::default();
+    let mut instructions_per_frame = INSTRUCTIONS_PER_FRAME;
+    if let Some(meta) = &meta {
+        meta.options.apply_quirks(&mut cpu.quirks);
+        instructions_per_frame = meta.options.tickrate.unwrap_or(instructions_per_frame);
+        meta.options.apply_palette(&mut palette);
+    }
+
+    let mut recorder = gif.then(|| Recorder::new(palette, 1));
+    for _ in 0..frames {
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(&cpu.display);
+        }
+        match cpu.run_frame(instructions_per_frame as usize) {
+            Ok(Status::Halted) => break,
+            Ok(_) => cpu.tick_timers(),
+            Err(fault) => {
+                eprintln!("{}: {}", rom.display(), fault);
+                break;
+            }
+        }
+    }
+    Ok((cpu.display, palette, recorder))
+}
+
+/// Writes a hires-sized RGB PNG, doubling low resolution pixels.
+fn save_png(path: &Path, fb: &FrameBuffer, palette: &Palette) -> Result<(), String> {
+    let file = fs::File::create(path).map_err(|err| err.to_string())?;
+    fb.to_image(palette)
+        .scaled(HIRES_WIDTH / fb.width())
+        .write_png(BufWriter::new(file))
+        .map_err(|err| err.to_string())
+}
+
+fn main() {
+    let mut frames = 300;
+    let mut gif = false;
+    let mut input = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => match args.next().map(|value| value.parse()) {
+                Some(Ok(value)) => frames = value,
+                _ => {
+                    eprintln!("--frames needs a number");
+                    process::exit(2);
+                }
+            },
+            "--gif" => gif = true,
+            "-o" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("-o needs a directory");
+                    process::exit(2);
+                }
+            },
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("unexpected argument {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("usage: chip8-screenshots <rom-dir> [--frames N] [--gif] [-o out-dir]");
+        process::exit(2);
+    };
+    let output = output.unwrap_or_else(|| input.clone());
+
+    let entries = fs::read_dir(&input).unwrap_or_else(|err| {
+        eprintln!("{}: {}", input.display(), err);
+        process::exit(1);
+    });
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| library::is_rom(path))
+        .collect();
+    roms.sort();
+    if let Err(err) = fs::create_dir_all(&output) {
+        eprintln!("{}: {}", output.display(), err);
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for rom in &roms {
+        let png = output.join(rom.with_extension("png").file_name().unwrap_or_default());
+        let clip = png.with_extension("gif");
+        let result = capture(rom, frames, gif).and_then(|(fb, palette, recorder)| {
+            save_png(&png, &fb, &palette)?;
+            match recorder {
+                Some(recorder) => recorder.save(&clip).map_err(|err| err.to_string()),
+                None => Ok(()),
+            }
+        });
+        match result {
+            Ok(()) => {
+                println!("{}", png.display());
+                if gif {
+                    println!("{}", clip.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: {}", rom.display(), err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,38 @@
+use std::{env, fs, path::Path, process};
+
+use cpu_emulator_chip_8::tools::sprite_import::{bitmap_from_png, split_into_sprites, to_assembly};
+
+fn main() {
+    let mut invert = false;
+    let mut input = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--invert" => invert = true,
+            _ if input.is_none() => input = Some(arg),
+            _ => {
+                eprintln!("unexpected argument {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("usage: chip8-sprite <image.png> [--invert]");
+        process::exit(2);
+    };
+
+    let bytes = fs::read(&input).unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    let bitmap = bitmap_from_png(&bytes, invert).unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+
+    let name = Path::new(&input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+        .unwrap_or_else(|| "sprite".to_string());
+    print!("{}", to_assembly(&split_into_sprites(&bitmap, &name)));
+}
@@ -0,0 +1,537 @@
+//! Text-mode frontend: draws the screen with half-block characters, two
+//! CHIP-8 rows per terminal line, next to a register panel. Runs anywhere
+//! with a Unix terminal, including over SSH. Given a directory instead of
+//! a ROM, it first lists the ROMs there to pick one from. With `--watch`
+//! the ROM, or the assembly source it was given instead, is reloaded
+//! whenever it changes.
+
+use std::{
+    env,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    mem,
+    path::Path,
+    process, thread,
+    time::Duration,
+};
+
+use cpu_emulator_chip_8::audio::AudioSource;
+use cpu_emulator_chip_8::clock::SystemClock;
+use cpu_emulator_chip_8::cpu::{EmulatorMode, CPU};
+use cpu_emulator_chip_8::database::RomDatabase;
+use cpu_emulator_chip_8::debugger::{LiveSlot, LiveWatch};
+use cpu_emulator_chip_8::display::FrameBuffer;
+use cpu_emulator_chip_8::emulator::{
+    read_program, Emulator, Input, Pace, ReloadError, RomWatcher, Screen,
+};
+use cpu_emulator_chip_8::i18n::{self, text};
+use cpu_emulator_chip_8::keypad::{KeypadState, KEY_COUNT};
+use cpu_emulator_chip_8::library::{self, LibraryEntry};
+use cpu_emulator_chip_8::metadata;
+use cpu_emulator_chip_8::speedrun::SplitTimer;
+
+/// Terminals report key presses but not releases, so a key counts as held
+/// for this many frames after it was last seen. Auto-repeat keeps it down.
+const HOLD_FRAMES: u32 = 8;
+
+/// How often `--watch` looks at the ROM file, twice a second.
+const WATCH_INTERVAL_FRAMES: u64 = 30;
+
+/// Frames of history kept for Backspace, five seconds.
+const REWIND_FRAMES: usize = 300;
+
+/// Same layout as the desktop frontend's keymap.
+const KEYMAP: [(u8, u8); 16] = [
+    (b'1', 0x1),
+    (b'2', 0x2),
+    (b'3', 0x3),
+    (b'4', 0xC),
+    (b'q', 0x4),
+    (b'w', 0x5),
+    (b'e', 0x6),
+    (b'r', 0xD),
+    (b'a', 0x7),
+    (b's', 0x8),
+    (b'd', 0x9),
+    (b'f', 0xE),
+    (b'z', 0xA),
+    (b'x', 0x0),
+    (b'c', 0xB),
+    (b'v', 0xF),
+];
+
+/// Puts the terminal in raw, non-blocking mode on the alternate screen and
+/// restores it when dropped.
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        // SAFETY: termios is plain data and tcgetattr fills it in.
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid termios derived from the current one.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+        Ok(RawTerminal { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: restores the settings read in `enter`.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+/// Keys seen on stdin, each with the frames it stays held for.
+#[derive(Default)]
+struct TerminalInput {
+    held: [u32; KEY_COUNT],
+    /// Backspace was read this frame.
+    rewinding: bool,
+    /// Tab was read this frame.
+    toggle_heatmap: bool,
+    /// P was read this frame.
+    toggle_pause: bool,
+    /// Set by `+`, `-` and `=`: double, half and normal speed.
+    pace: Option<Pace>,
+    quit: bool,
+}
+
+impl TerminalInput {
+    fn poll(&mut self) {
+        for frames in &mut self.held {
+            *frames = frames.saturating_sub(1);
+        }
+        self.rewinding = false;
+        let mut bytes = [0; 64];
+        let Ok(n) = io::stdin().read(&mut bytes) else {
+            return;
+        };
+        self.rewinding = bytes[..n].contains(&0x7F);
+        self.toggle_heatmap = bytes[..n].contains(&b'\t');
+        self.toggle_pause = bytes[..n].contains(&b'p');
+        self.pace = bytes[..n].iter().rev().find_map(|byte| match byte {
+            b'+' => Some(Pace::Percent(200)),
+            b'-' => Some(Pace::Percent(50)),
+            b'=' => Some(Pace::NORMAL),
+            _ => None,
+        });
+        // a lone ESC, not the start of an arrow key sequence
+        if &bytes[..n] == b"\x1b" || bytes[..n].contains(&0x03) {
+            self.quit = true;
+        }
+        for byte in &bytes[..n] {
+            let byte = byte.to_ascii_lowercase();
+            if let Some((_, key)) = KEYMAP.iter().find(|(host, _)| *host == byte) {
+                self.held[*key as usize] = HOLD_FRAMES;
+            }
+        }
+    }
+}
+
+impl Input for TerminalInput {
+    fn keys(&self) -> KeypadState {
+        self.held.map(|frames| frames > 0)
+    }
+}
+
+/// Renders into a string that the main loop prints together with the
+/// register panel, so each frame is a single write.
+#[derive(Default)]
+struct TerminalScreen {
+    lines: Vec<String>,
+}
+
+impl Screen for TerminalScreen {
+    fn draw(&mut self, fb: &FrameBuffer) {
+        self.lines = half_blocks(fb, 1);
+    }
+}
+
+/// Every `step`th pixel of `fb` as half-block characters, two rows per
+/// line.
+fn half_blocks(fb: &FrameBuffer, step: usize) -> Vec<String> {
+    let lit = |x, y| y < fb.height() && fb.color(x, y) != 0;
+    (0..fb.height())
+        .step_by(2 * step)
+        .map(|y| {
+            (0..fb.width())
+                .step_by(step)
+                .map(|x| match (lit(x, y), lit(x, y + step)) {
+                    (false, false) => ' ',
+                    (true, false) => '\u{2580}',
+                    (false, true) => '\u{2584}',
+                    (true, true) => '\u{2588}',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Lets the player pick a ROM from `dir`, showing each one's thumbnail
+/// next to the list. `None` if they quit instead.
+fn choose_rom(dir: &Path) -> Option<LibraryEntry> {
+    let mut entries = library::scan(dir, &RomDatabase::builtin()).unwrap_or_else(|err| {
+        eprintln!("{}: {}", dir.display(), err);
+        process::exit(1);
+    });
+    if entries.is_empty() {
+        eprintln!(
+            "{}",
+            i18n::format("tui-library-empty", &[("dir", &dir.display())])
+        );
+        process::exit(1);
+    }
+
+    let _terminal = enter_terminal();
+    let mut selected = 0;
+    let mut redraw = true;
+    loop {
+        if redraw {
+            let titles: Vec<String> = entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    if i == selected {
+                        format!("\x1b[7m> {}\x1b[0m", entry.title)
+                    } else {
+                        format!("  {}", entry.title)
+                    }
+                })
+                .collect();
+            // hires thumbnails at half size, so every one is as big
+            let thumbnail = entries[selected].thumbnail();
+            let step = thumbnail.width() / FrameBuffer::new().width();
+            let mut preview = half_blocks(thumbnail, step);
+            preview.push(String::new());
+            preview.push(text("tui-library-help").to_string());
+
+            let mut out = String::from("\x1b[H\x1b[2J");
+            for row in 0..titles.len().max(preview.len()) {
+                let picture = preview.get(row).map_or("", String::as_str);
+                let title = titles.get(row).map_or("", String::as_str);
+                let _ = write!(out, "{:<64}  \u{2502} {}\r\n", picture, title);
+            }
+            print!("{}", out);
+            let _ = io::stdout().flush();
+            redraw = false;
+        }
+
+        thread::sleep(Duration::from_millis(16));
+        let mut bytes = [0; 64];
+        let n = io::stdin().read(&mut bytes).unwrap_or(0);
+        match &bytes[..n] {
+            [] => {}
+            b"\x1b[A" | b"k" => selected = selected.saturating_sub(1),
+            b"\x1b[B" | b"j" => selected = (selected + 1).min(entries.len() - 1),
+            b"\r" | b"\n" => return Some(entries.swap_remove(selected)),
+            b"\x1b" | b"q" | [0x03] => return None,
+            _ => continue,
+        }
+        redraw = n > 0;
+    }
+}
+
+fn enter_terminal() -> RawTerminal {
+    RawTerminal::enter().unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            i18n::format("tui-terminal-failed", &[("error", &err)])
+        );
+        process::exit(1);
+    })
+}
+
+/// Shades from no accesses up, one step per power of four.
+const SHADES: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+
+/// Memory access counts laid out like the screen, each character covering
+/// an equal slice of memory from top left to bottom right. Slices that were
+/// written to are drawn in red.
+fn heatmap(cpu: &CPU, columns: usize, lines: usize) -> Vec<String> {
+    let Some(stats) = cpu.memory.access_stats() else {
+        return Vec::new();
+    };
+    let buckets = stats.buckets(columns * lines);
+    buckets
+        .chunks(columns)
+        .map(|row| {
+            let mut line = String::new();
+            for (reads, writes) in row {
+                let total = reads + writes;
+                let level = if total == 0 {
+                    0
+                } else {
+                    (1 + total.ilog2() as usize / 2).min(SHADES.len() - 1)
+                };
+                if *writes > 0 {
+                    let _ = write!(line, "\x1b[31m{}\x1b[0m", SHADES[level]);
+                } else {
+                    line.push(SHADES[level]);
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Frames a changed timer, I or byte at I stays highlighted for.
+const HIGHLIGHT_FRAMES: u64 = 30;
+
+/// Width of the audio pattern in the panel, four bits a column.
+const WAVEFORM_COLUMNS: usize = 32;
+
+/// `value` in reverse video if `slot` changed recently.
+fn live_value(live: &LiveWatch, slot: LiveSlot, value: String) -> String {
+    if live.changed_within(slot, HIGHLIGHT_FRAMES) {
+        format!("\x1b[7m{}\x1b[0m", value)
+    } else {
+        value
+    }
+}
+
+fn panel(cpu: &CPU, live: &LiveWatch, timer: Option<&SplitTimer>) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "PC {:04X}  I {}",
+            cpu.memory_position,
+            live_value(live, LiveSlot::I, format!("{:04X}", live.i))
+        ),
+        format!(
+            "DT {}    ST {}",
+            live_value(
+                live,
+                LiveSlot::DelayTimer,
+                format!("{:02X}", live.delay_timer)
+            ),
+            live_value(
+                live,
+                LiveSlot::SoundTimer,
+                format!("{:02X}", live.sound_timer)
+            )
+        ),
+    ];
+    for (row, bytes) in live.at_i.chunks(8).enumerate() {
+        let cells: Vec<String> = bytes
+            .iter()
+            .enumerate()
+            .map(|(column, byte)| {
+                live_value(
+                    live,
+                    LiveSlot::AtI(row * 8 + column),
+                    format!("{:02X}", byte),
+                )
+            })
+            .collect();
+        let label = if row == 0 { "[I]" } else { "   " };
+        lines.push(format!("{} {}", label, cells.join(" ")));
+    }
+    lines.push(String::new());
+    for (row, values) in cpu.registers.chunks(2).enumerate() {
+        lines.push(format!(
+            "V{:X} {:02X}    V{:X} {:02X}",
+            row * 2,
+            values[0],
+            row * 2 + 1,
+            values[1]
+        ));
+    }
+    if cpu.mode.has_xo_chip() {
+        // the pattern is what XO-CHIP music is made of, worth seeing
+        let pattern = cpu.pattern();
+        lines.push(String::new());
+        lines.push(format!("SND {:.0} Hz", pattern.playback_rate));
+        lines.extend(
+            pattern
+                .waveform(WAVEFORM_COLUMNS)
+                .lines()
+                .map(str::to_string),
+        );
+    }
+    lines.push(String::new());
+    for id in [
+        "tui-help-rewind",
+        "tui-help-memory",
+        "tui-help-pace",
+        "tui-help-quit",
+    ] {
+        lines.push(text(id).to_string());
+    }
+    if let Some(timer) = timer {
+        lines.push(String::new());
+        lines.extend(timer.to_string().lines().map(str::to_string));
+    }
+    lines
+}
+
+fn main() {
+    if let Some(err) = i18n::init_from_env() {
+        eprintln!("CHIP8_MESSAGES: {}", err);
+    }
+    let mut mode = EmulatorMode::Chip8;
+    let mut rom = None;
+    let mut max_frame_skip = 0;
+    let mut watch = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = EmulatorMode::SuperChip,
+            "--xo" => mode = EmulatorMode::XoChip,
+            "--mega" => mode = EmulatorMode::MegaChip,
+            "--watch" => watch = true,
+            "--frame-skip" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => max_frame_skip = n,
+                None => {
+                    eprintln!("{}", text("tui-frame-skip-needs-number"));
+                    process::exit(2);
+                }
+            },
+            _ if rom.is_none() => rom = Some(arg),
+            _ => {
+                eprintln!(
+                    "{}",
+                    i18n::format("unexpected-argument", &[("argument", &arg)])
+                );
+                process::exit(2);
+            }
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("{}", text("tui-usage"));
+        process::exit(2);
+    };
+
+    // a ROM picked from a library comes with its own settings
+    let (cpu, rom, instructions_per_frame) = if Path::new(&rom).is_dir() {
+        let Some(entry) = choose_rom(Path::new(&rom)) else {
+            return;
+        };
+        let cpu = entry.cpu().map_err(ReloadError::from);
+        (
+            cpu,
+            entry.path.display().to_string(),
+            Some(entry.instructions_per_frame),
+        )
+    } else {
+        let mut cpu = CPU::new_with_mode(mode);
+        let loaded = read_program(Path::new(&rom))
+            .and_then(|program| Ok(cpu.load_rom(&program.rom)?))
+            .map(|()| cpu);
+        (loaded, rom, None)
+    };
+    let cpu = cpu.unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    });
+    // splits from the descriptor get a speedrun timer in the panel
+    let splits = metadata::load_sidecar(Path::new(&rom))
+        .ok()
+        .flatten()
+        .map(|meta| meta.splits)
+        .unwrap_or_default();
+    let mut timer = match SplitTimer::new(&splits) {
+        Ok(timer) => (!splits.is_empty()).then_some(timer),
+        Err(err) => {
+            eprintln!(
+                "{}",
+                i18n::format("tui-splits-ignored", &[("rom", &rom), ("error", &err)])
+            );
+            None
+        }
+    };
+    let mut emulator = Emulator::new(cpu, TerminalScreen::default(), TerminalInput::default(), ());
+    if let Some(instructions_per_frame) = instructions_per_frame {
+        emulator.instructions_per_frame = instructions_per_frame;
+    }
+    emulator.enable_rewind(REWIND_FRAMES);
+    // slow terminals can drop frames instead of slowing the game down
+    emulator.max_frame_skip = max_frame_skip;
+    emulator.cpu.memory.set_tracking_access(true);
+    let mut show_heatmap = false;
+    let mut live = LiveWatch::new(&emulator.cpu);
+    let mut watcher = watch.then(|| RomWatcher::new(&rom));
+    let mut reloaded = None;
+    let mut polls = 0u64;
+
+    let terminal = enter_terminal();
+    let result = emulator.run(&SystemClock::new(), |emulator| {
+        // a frame only ran if the pace wasn't paused going into it
+        if let (Some(timer), false) = (&mut timer, emulator.pace() == Pace::Paused) {
+            timer.update(&emulator.cpu);
+        }
+        emulator.input.poll();
+        show_heatmap ^= emulator.input.toggle_heatmap;
+        if emulator.input.toggle_pause {
+            emulator.toggle_pause();
+        }
+        if let Some(pace) = emulator.input.pace {
+            emulator.set_pace(pace);
+        }
+        // key repeat is slower than 60 Hz, so rewind faster than real time
+        if emulator.input.rewinding {
+            emulator.rewind(4);
+        }
+        // counted here, the CPU's frames stop while paused
+        polls += 1;
+        if let Some(watcher) = watcher
+            .as_mut()
+            .filter(|_| polls.is_multiple_of(WATCH_INTERVAL_FRAMES))
+        {
+            if let Some(program) = watcher.poll() {
+                reloaded = Some(
+                    match program.and_then(|program| Ok(emulator.load_program(program)?)) {
+                        Ok(()) => i18n::format("reloaded", &[("rom", &rom)]),
+                        Err(err) => i18n::format("not-reloaded", &[("rom", &rom), ("error", &err)]),
+                    },
+                );
+            }
+        }
+        if emulator.skipped_frame() {
+            return !emulator.input.quit;
+        }
+
+        let mut out = String::from("\x1b[H");
+        live.update(&emulator.cpu);
+        let mut panel = panel(&emulator.cpu, &live, timer.as_ref());
+        if let Some(reloaded) = &reloaded {
+            panel.extend([String::new(), reloaded.clone()]);
+        }
+        let width = emulator.cpu.display.width();
+        let heat;
+        let lines = if show_heatmap {
+            heat = heatmap(&emulator.cpu, width, emulator.screen.lines.len());
+            &heat
+        } else {
+            &emulator.screen.lines
+        };
+        let rows = lines.len().max(panel.len());
+        for row in 0..rows {
+            let screen = lines.get(row).map_or("", String::as_str);
+            let side = panel.get(row).map_or("", String::as_str);
+            let _ = write!(out, "\x1b[2K{:<width$}  \u{2502} {}\r\n", screen, side);
+        }
+        print!("{}", out);
+        let _ = io::stdout().flush();
+        !emulator.input.quit
+    });
+    drop(terminal);
+
+    if let Err(fault) = result {
+        eprintln!("{}: {}", rom, fault);
+        process::exit(1);
+    }
+}
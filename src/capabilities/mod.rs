@@ -0,0 +1,180 @@
+//! What this build of the crate can do, for hosts that adapt their UI to
+//! it at runtime instead of repeating the crate's `cfg(feature)`s, e.g. to
+//! hide audio settings when nothing here can play sound.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::cpu::EmulatorMode;
+
+/// A way of showing games to a player, each behind its own feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frontend {
+    /// The `chip8` window, feature `desktop`.
+    Desktop,
+    /// `chip8-tui`, feature `tui`.
+    Terminal,
+    /// `WasmChip8` for web pages, feature `wasm`.
+    Web,
+}
+
+impl Frontend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Frontend::Desktop => "desktop",
+            Frontend::Terminal => "terminal",
+            Frontend::Web => "web",
+        }
+    }
+}
+
+/// Parts of the library that are left out unless their feature is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subsystem {
+    /// Screenshots and GIF clips, see `crate::capture`.
+    Capture,
+    /// The C interface of `include/chip8.h`, see `crate::ffi`.
+    Ffi,
+    /// Reading and writing PNG images.
+    Png,
+}
+
+impl Subsystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Capture => "capture",
+            Subsystem::Ffi => "ffi",
+            Subsystem::Png => "png",
+        }
+    }
+}
+
+/// See [`capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// The crate's version.
+    pub version: &'static str,
+    /// Every member of the CHIP-8 family the CPU can emulate.
+    pub variants: &'static [EmulatorMode],
+    pub frontends: &'static [Frontend],
+    /// Sound devices the crate plays through by itself. None so far: hosts
+    /// plug their own into [`crate::audio::AudioSink`].
+    pub audio_backends: &'static [&'static str],
+    pub subsystems: &'static [Subsystem],
+}
+
+impl Capabilities {
+    pub fn has_frontend(&self, frontend: Frontend) -> bool {
+        self.frontends.contains(&frontend)
+    }
+
+    pub fn has_subsystem(&self, subsystem: Subsystem) -> bool {
+        self.subsystems.contains(&subsystem)
+    }
+
+    /// Whether the crate can make sound itself, without the host's help.
+    pub fn has_audio_output(&self) -> bool {
+        !self.audio_backends.is_empty()
+    }
+}
+
+/// One line per kind, e.g. `frontends: desktop, terminal`.
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T>(items: &[T], name: impl Fn(&T) -> &'static str) -> String {
+            match items {
+                [] => "none".to_string(),
+                _ => items.iter().map(name).collect::<Vec<_>>().join(", "),
+            }
+        }
+
+        writeln!(f, "version: {}", self.version)?;
+        let variants = list(self.variants, |mode| match mode {
+            EmulatorMode::Chip8 => "chip8",
+            EmulatorMode::SuperChip => "schip",
+            EmulatorMode::XoChip => "xo",
+            EmulatorMode::MegaChip => "mega",
+        });
+        writeln!(f, "variants: {}", variants)?;
+        writeln!(
+            f,
+            "frontends: {}",
+            list(self.frontends, |frontend| frontend.name())
+        )?;
+        writeln!(f, "audio: {}", list(self.audio_backends, |backend| backend))?;
+        write!(
+            f,
+            "subsystems: {}",
+            list(self.subsystems, |subsystem| subsystem.name())
+        )
+    }
+}
+
+/// What was compiled into this build.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        variants: &[
+            EmulatorMode::Chip8,
+            EmulatorMode::SuperChip,
+            EmulatorMode::XoChip,
+            EmulatorMode::MegaChip,
+        ],
+        frontends: &[
+            #[cfg(feature = "desktop")]
+            Frontend::Desktop,
+            #[cfg(feature = "tui")]
+            Frontend::Terminal,
+            #[cfg(feature = "wasm")]
+            Frontend::Web,
+        ],
+        audio_backends: &[],
+        subsystems: &[
+            #[cfg(feature = "capture")]
+            Subsystem::Capture,
+            #[cfg(feature = "ffi")]
+            Subsystem::Ffi,
+            #[cfg(feature = "png")]
+            Subsystem::Png,
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_follow_the_features() {
+        let found = capabilities();
+        assert_eq!(found.variants.len(), 4);
+        assert_eq!(
+            found.has_frontend(Frontend::Desktop),
+            cfg!(feature = "desktop")
+        );
+        assert_eq!(found.has_frontend(Frontend::Web), cfg!(feature = "wasm"));
+        assert_eq!(found.has_subsystem(Subsystem::Png), cfg!(feature = "png"));
+        assert!(!found.has_audio_output());
+    }
+
+    #[test]
+    fn capabilities_print_one_kind_per_line() {
+        let found = Capabilities {
+            version: "1.2.3",
+            variants: &[EmulatorMode::Chip8, EmulatorMode::XoChip],
+            frontends: &[Frontend::Terminal],
+            audio_backends: &[],
+            subsystems: &[Subsystem::Capture, Subsystem::Png],
+        };
+        assert_eq!(
+            found.to_string(),
+            "version: 1.2.3\nvariants: chip8, xo\nfrontends: terminal\naudio: none\n\
+             subsystems: capture, png"
+        );
+        let json = serde_json::to_string(&found).unwrap();
+        assert!(json.contains(r#""frontends":["terminal"]"#), "{}", json);
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::display::{FrameBuffer, Palette, HIRES_HEIGHT, HIRES_WIDTH};
+
+/// Emulator frames per second, which [`Recorder::capture`] is called at.
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// Browsers show GIF frames shorter than 2/100 s for 1/10 s, so no frame
+/// is kept for less than this many emulator frames.
+const MIN_FRAMES: u32 = 2;
+
+/// Codes in the LZW dictionary before it has to start over.
+const MAX_CODES: u16 = 4096;
+
+/// Collects the screen while a ROM runs and writes it out as a looping
+/// animated GIF. Every frame is drawn at high resolution size, low
+/// resolution ones doubled, so clips that switch modes keep one size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recorder {
+    palette: Palette,
+    scale: usize,
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    /// Palette index of every pixel at high resolution, unscaled.
+    pixels: Vec<u8>,
+    /// Emulator frames it stays up for.
+    shown: u32,
+}
+
+impl Recorder {
+    /// A recorder coloring pixels from `palette` and blowing them up
+    /// `scale` times beyond high resolution.
+    pub fn new(palette: Palette, scale: usize) -> Self {
+        Recorder {
+            palette,
+            scale: scale.max(1),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Adds the screen as it is now. Call once per 60 Hz frame; screens
+    /// that don't change only lengthen the frame before them.
+    pub fn capture(&mut self, fb: &FrameBuffer) {
+        let (width, height) = (fb.width(), fb.height());
+        let pixels: Vec<u8> = (0..HIRES_WIDTH * HIRES_HEIGHT)
+            .map(|index| {
+                fb.color(
+                    index % HIRES_WIDTH * width / HIRES_WIDTH,
+                    index / HIRES_WIDTH * height / HIRES_HEIGHT,
+                )
+            })
+            .collect();
+        if let Some(last) = self.frames.last_mut() {
+            if last.pixels == pixels {
+                last.shown += 1;
+                return;
+            }
+            if last.shown < MIN_FRAMES {
+                // too short to show, so this screen takes its place
+                last.pixels = pixels;
+                last.shown += 1;
+                return;
+            }
+        }
+        self.frames.push(Frame { pixels, shown: 1 });
+    }
+
+    /// Frames of the GIF so far, after merging.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn write_gif(&self, mut out: impl Write) -> io::Result<()> {
+        let width = (HIRES_WIDTH * self.scale) as u16;
+        let height = (HIRES_HEIGHT * self.scale) as u16;
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // global color table of 4 entries, background color 0
+        out.write_all(&[0x91, 0, 0])?;
+        for color in self.palette.colors() {
+            out.write_all(&color.to_be_bytes()[1..])?;
+        }
+        // loop forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        // delays in hundredths of a second, rounded so they never drift
+        let mut elapsed = 0;
+        for frame in &self.frames {
+            let start = elapsed * 100 / FRAMES_PER_SECOND;
+            elapsed += frame.shown;
+            let delay = (elapsed * 100 / FRAMES_PER_SECOND - start) as u16;
+            out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+            out.write_all(&delay.to_le_bytes())?;
+            out.write_all(&[0x00, 0x00])?;
+
+            out.write_all(&[0x2C, 0, 0, 0, 0])?;
+            out.write_all(&width.to_le_bytes())?;
+            out.write_all(&height.to_le_bytes())?;
+            out.write_all(&[0x00, 2])?;
+            let data = lzw(&self.scaled(&frame.pixels));
+            for block in data.chunks(255) {
+                out.write_all(&[block.len() as u8])?;
+                out.write_all(block)?;
+            }
+            out.write_all(&[0x00])?;
+        }
+        out.write_all(&[0x3B])
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        self.write_gif(&mut out)?;
+        out.flush()
+    }
+
+    fn scaled(&self, pixels: &[u8]) -> Vec<u8> {
+        let width = HIRES_WIDTH * self.scale;
+        (0..width * HIRES_HEIGHT * self.scale)
+            .map(|index| {
+                let (x, y) = (index % width / self.scale, index / width / self.scale);
+                pixels[y * HIRES_WIDTH + x]
+            })
+            .collect()
+    }
+}
+
+/// Packs codes into bytes least significant bit first, as GIF wants them.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF's variable-width LZW for 2-bit color indices.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 4;
+    const END: u16 = 5;
+    let mut out = BitWriter::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = END + 1;
+    let mut size = 3;
+    out.write(CLEAR, size);
+
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let Some(code) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&longer) = codes.get(&(code, index)) {
+            prefix = Some(longer);
+            continue;
+        }
+        out.write(code, size);
+        if next < MAX_CODES {
+            if next == 1 << size {
+                size += 1;
+            }
+            codes.insert((code, index), next);
+            next += 1;
+        } else {
+            out.write(CLEAR, size);
+            codes.clear();
+            next = END + 1;
+            size = 3;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(code) = prefix {
+        out.write(code, size);
+    }
+    out.write(END, size);
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plain GIF LZW decoder to check the encoder against.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..4).map(|index| vec![index]).collect();
+            table.extend([Vec::new(), Vec::new()]);
+        };
+        reset(&mut table);
+        let (mut size, mut bits, mut count) = (3, 0u32, 0);
+        let mut bytes = data.iter();
+        let mut previous: Option<usize> = None;
+        let mut out = Vec::new();
+        loop {
+            while count < size {
+                bits |= (*bytes.next().expect("no end code") as u32) << count;
+                count += 8;
+            }
+            let code = (bits & ((1 << size) - 1)) as usize;
+            bits >>= size;
+            count -= size;
+            match code {
+                4 => {
+                    reset(&mut table);
+                    size = 3;
+                    previous = None;
+                    continue;
+                }
+                5 => return out,
+                _ => {}
+            }
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let entry = match table.get(code) {
+                        Some(entry) => entry.clone(),
+                        // the code being defined by this very step
+                        None => {
+                            let mut entry = table[previous].clone();
+                            entry.push(entry[0]);
+                            entry
+                        }
+                    };
+                    if table.len() < 4096 {
+                        let mut added = table[previous].clone();
+                        added.push(entry[0]);
+                        table.push(added);
+                        if table.len() == 1 << size && size < 12 {
+                            size += 1;
+                        }
+                    }
+                    entry
+                }
+            };
+            out.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips_through_a_full_dictionary() {
+        let flat = vec![0; 5000];
+        assert_eq!(unlzw(&lzw(&flat)), flat);
+        // a noisy screen fills the dictionary several times over
+        let mut seed = 1u32;
+        let noisy: Vec<u8> = (0..HIRES_WIDTH * HIRES_HEIGHT * 4)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8 & 3
+            })
+            .collect();
+        assert_eq!(unlzw(&lzw(&noisy)), noisy);
+    }
+
+    #[test]
+    fn recorder_merges_frames_and_writes_a_gif() {
+        let mut fb = FrameBuffer::new();
+        let mut recorder = Recorder::new(Palette::new([0x000000, 0xFFFFFF, 0, 0]), 1);
+        for _ in 0..30 {
+            recorder.capture(&fb);
+        }
+        // a change one frame after another is folded into a single frame
+        fb.draw_sprite(0, 0, &[0x80]);
+        recorder.capture(&fb);
+        fb.draw_sprite(4, 0, &[0x80]);
+        recorder.capture(&fb);
+        fb.set_hires(true);
+        fb.draw_sprite(0, 0, &[0x80]);
+        for _ in 0..10 {
+            recorder.capture(&fb);
+        }
+        assert_eq!(recorder.len(), 3);
+        assert_eq!(recorder.frames[1].pixels[0], 1);
+        assert_eq!(recorder.frames[1].pixels[8], 1);
+        assert_eq!(recorder.frames[2].pixels[1], 0);
+
+        let mut gif = Vec::new();
+        recorder.write_gif(&mut gif).unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], &[128, 0, 64, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+        // 30, 2 and 10 frames at 60 Hz
+        let delays: Vec<u16> = gif
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| window[..3] == [0x21, 0xF9, 0x04])
+            .map(|(at, _)| u16::from_le_bytes([gif[at + 4], gif[at + 5]]))
+            .collect();
+        assert_eq!(delays, [50, 3, 17]);
+    }
+}
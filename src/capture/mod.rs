@@ -0,0 +1,125 @@
+//! Screenshots and clips without a window: [`FrameBuffer::to_image`] for
+//! stills, written as PPM or, with the `png` feature, PNG, and a
+//! [`Recorder`] that turns a run into an animated GIF. Meant for
+//! generating screenshots and demo clips from CI and scripts.
+
+mod gif;
+
+use std::io::{self, Write};
+
+use crate::display::{FrameBuffer, Palette};
+
+pub use gif::Recorder;
+
+/// An RGB picture, `0x00RRGGBB` per pixel, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl FrameBuffer {
+    /// The screen at its current resolution, each pixel colored by its
+    /// [`FrameBuffer::color`] index into `palette`. The Mega-Chip screen
+    /// comes out in the program's own colors instead.
+    pub fn to_image(&self, palette: &Palette) -> Image {
+        let palette = palette.colors();
+        let (width, height) = (self.width(), self.height());
+        let pixels = (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width, index / width);
+                match self.mega() {
+                    Some(mega) => mega.rgb(x, y),
+                    None => palette[self.color(x, y) as usize],
+                }
+            })
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+impl Image {
+    /// Blown up `factor` times in each direction, e.g. 2 to bring a low
+    /// resolution screen to high resolution size.
+    pub fn scaled(&self, factor: usize) -> Image {
+        let factor = factor.max(1);
+        let width = self.width * factor;
+        let height = self.height * factor;
+        let pixels = (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width / factor, index / width / factor);
+                self.pixels[y * self.width + x]
+            })
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn rgb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| [(color >> 16) as u8, (color >> 8) as u8, *color as u8])
+            .collect()
+    }
+
+    /// Binary PPM (P6), which needs no encoder and every image tool reads.
+    pub fn write_ppm(&self, mut out: impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.rgb())
+    }
+
+    #[cfg(feature = "png")]
+    pub fn write_png(&self, out: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.rgb())
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = Palette::new([0x000000, 0xFFFFFF, 0xFF0000, 0x0000FF]);
+
+    #[test]
+    fn images_follow_the_palette_and_scale() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(1, 0, &[0x80]);
+
+        let image = fb.to_image(&PALETTE);
+        assert_eq!((image.width, image.height), (64, 32));
+        assert_eq!(&image.pixels[..3], &[0, 0xFFFFFF, 0]);
+
+        let big = image.scaled(2);
+        assert_eq!((big.width, big.height), (128, 64));
+        assert_eq!(&big.pixels[..4], &[0, 0, 0xFFFFFF, 0xFFFFFF]);
+        assert_eq!(big.pixels[128 + 2], 0xFFFFFF);
+    }
+
+    #[test]
+    fn ppm_has_a_header_and_rgb_bytes() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0x80]);
+        let mut out = Vec::new();
+
+        fb.to_image(&PALETTE).write_ppm(&mut out).unwrap();
+
+        let header = b"P6\n64 32\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out.len(), header.len() + 64 * 32 * 3);
+        assert_eq!(&out[header.len()..][..6], &[255, 255, 255, 0, 0, 0]);
+    }
+}
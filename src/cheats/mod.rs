@@ -0,0 +1,259 @@
+//! Cheats kept in a file next to the ROM, in the [`crate::config`] subset
+//! of TOML. Each one is a named set of `address = byte` lines:
+//!
+//! ```toml
+//! # written over the program once it's loaded
+//! [patch."Skip the title screen"]
+//! 0x200 = 0x12
+//! 0x201 = 0x40
+//!
+//! # written back after every instruction, whatever the program stores
+//! [freeze."Infinite lives"]
+//! enabled = false   # on by default
+//! 0x3F0 = 3
+//! ```
+//!
+//! [`crate::emulator::Emulator::set_cheats`] puts them to work and turns
+//! them on and off while the game runs.
+
+use std::fs;
+use std::path::Path;
+
+use crate::config::{self, ConfigError, Entry, Value};
+use crate::cpu::{BIG_FONT_ADDRESS, BIG_FONT_SET, CPU, FONT_ADDRESS, FONT_SET, PROGRAM_START};
+
+/// Highest address a cheat can name, the end of XO-CHIP memory.
+pub const MAX_ADDRESS: usize = 0xFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    /// Written once, when the ROM is loaded or the cheat turned on.
+    Patch,
+    /// Written after every instruction.
+    Freeze,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub kind: CheatKind,
+    pub enabled: bool,
+    /// `(address, value)` in file order.
+    pub bytes: Vec<(usize, u8)>,
+}
+
+impl Cheat {
+    /// Writes the cheat's bytes into memory. Addresses past the end of
+    /// memory in the CPU's mode are left alone.
+    pub fn write(&self, cpu: &mut CPU) {
+        for &(address, value) in &self.bytes {
+            if let Some(byte) = cpu.memory.get_mut(address) {
+                *byte = value;
+            }
+        }
+    }
+
+    /// Puts back what a freshly loaded machine had at the cheat's
+    /// addresses, undoing a patch: the ROM, the fonts below it, and zero
+    /// everywhere else.
+    pub fn restore(&self, cpu: &mut CPU) {
+        for &(address, _) in &self.bytes {
+            let original = match address.checked_sub(PROGRAM_START) {
+                Some(offset) => cpu.rom().get(offset),
+                None => font_byte(address),
+            };
+            let original = original.copied().unwrap_or(0);
+            if let Some(byte) = cpu.memory.get_mut(address) {
+                *byte = original;
+            }
+        }
+    }
+}
+
+/// The byte of either font at `address`, if it holds one.
+fn font_byte(address: usize) -> Option<&'static u8> {
+    let glyph = |start: usize, font: &'static [u8]| font.get(address.checked_sub(start)?);
+    glyph(FONT_ADDRESS, &FONT_SET).or_else(|| glyph(BIG_FONT_ADDRESS, &BIG_FONT_SET))
+}
+
+/// Every cheat from a file, in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError {
+            line: 0,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut cheats = Vec::new();
+        for section in config::parse(text)? {
+            let kind = match section.path.as_slice() {
+                [kind, _] if kind == "patch" => CheatKind::Patch,
+                [kind, _] if kind == "freeze" => CheatKind::Freeze,
+                _ => {
+                    let header = section.path.join(".");
+                    return Err(section.error(format!("unknown section [{}]", header)));
+                }
+            };
+            let mut cheat = Cheat {
+                name: section.path[1].clone(),
+                kind,
+                enabled: true,
+                bytes: Vec::new(),
+            };
+            for entry in &section.entries {
+                match (entry.key.as_str(), &entry.value) {
+                    ("enabled", Value::Bool(enabled)) => cheat.enabled = *enabled,
+                    ("enabled", _) => return Err(entry.error("enabled must be true or false")),
+                    _ => cheat.bytes.push((address_of(entry)?, byte_of(entry)?)),
+                }
+            }
+            cheats.push(cheat);
+        }
+        Ok(Cheats { cheats })
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cheat> {
+        self.cheats.iter().find(|cheat| cheat.name == name)
+    }
+
+    /// Turns the cheat called `name` on or off, returning it, or `None`
+    /// if there is no such cheat. Memory isn't touched.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Option<&Cheat> {
+        let cheat = self.cheats.iter_mut().find(|cheat| cheat.name == name)?;
+        cheat.enabled = enabled;
+        Some(cheat)
+    }
+
+    /// Writes every enabled patch, for a freshly loaded or reset program.
+    pub fn apply_patches(&self, cpu: &mut CPU) {
+        self.enabled(CheatKind::Patch)
+            .for_each(|cheat| cheat.write(cpu));
+    }
+
+    /// Writes every enabled freeze, to be called after each instruction.
+    pub fn enforce_freezes(&self, cpu: &mut CPU) {
+        self.enabled(CheatKind::Freeze)
+            .for_each(|cheat| cheat.write(cpu));
+    }
+
+    /// Whether any freeze is on, so execution has to stop after every
+    /// instruction to enforce it.
+    pub fn has_freezes(&self) -> bool {
+        self.enabled(CheatKind::Freeze).next().is_some()
+    }
+
+    fn enabled(&self, kind: CheatKind) -> impl Iterator<Item = &Cheat> {
+        self.cheats
+            .iter()
+            .filter(move |cheat| cheat.enabled && cheat.kind == kind)
+    }
+}
+
+/// The key of an `address = byte` line, decimal or `0x` hex.
+fn address_of(entry: &Entry) -> Result<usize, ConfigError> {
+    let parsed = match entry.key.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => entry.key.parse(),
+    };
+    match parsed {
+        Ok(address) if address <= MAX_ADDRESS => Ok(address),
+        _ => Err(entry.error(format!("not an address: {}", entry.key))),
+    }
+}
+
+fn byte_of(entry: &Entry) -> Result<u8, ConfigError> {
+    let value = entry.integer()?;
+    u8::try_from(value).map_err(|_| entry.error(format!("not a byte: {}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEATS: &str = r#"
+        [patch."Skip the title screen"]
+        0x200 = 0x12
+        513 = 0x40
+
+        [freeze."Infinite lives"]
+        enabled = false
+        0x3F0 = 3
+    "#;
+
+    #[test]
+    fn cheats_are_read_from_the_file() {
+        let cheats = Cheats::parse(CHEATS).unwrap();
+
+        assert_eq!(
+            cheats.cheats(),
+            [
+                Cheat {
+                    name: "Skip the title screen".to_string(),
+                    kind: CheatKind::Patch,
+                    enabled: true,
+                    bytes: vec![(0x200, 0x12), (0x201, 0x40)],
+                },
+                Cheat {
+                    name: "Infinite lives".to_string(),
+                    kind: CheatKind::Freeze,
+                    enabled: false,
+                    bytes: vec![(0x3F0, 3)],
+                },
+            ]
+        );
+        assert!(!cheats.has_freezes());
+
+        let error = Cheats::parse("[freeze.lives]\n0x3F0 = 256").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: not a byte: 256");
+        let error = Cheats::parse("[poke.lives]").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: unknown section [poke.lives]");
+    }
+
+    #[test]
+    fn patches_are_written_and_restored() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xE0, 0x12, 0x00]).unwrap();
+        let mut cheats = Cheats::parse(CHEATS).unwrap();
+
+        cheats.apply_patches(&mut cpu);
+        cheats.enforce_freezes(&mut cpu);
+        assert_eq!(cpu.memory[0x200..0x202], [0x12, 0x40]);
+        assert_eq!(cpu.memory[0x3F0], 0);
+
+        cheats.set_enabled("Infinite lives", true).unwrap();
+        cheats.enforce_freezes(&mut cpu);
+        assert_eq!(cpu.memory[0x3F0], 3);
+
+        cheats
+            .get("Skip the title screen")
+            .unwrap()
+            .restore(&mut cpu);
+        assert_eq!(cpu.memory[0x200..0x202], [0x00, 0xE0]);
+        assert!(cheats.set_enabled("Walk through walls", true).is_none());
+
+        // patches on the fonts put the glyphs back
+        let font = Cheats::parse("[patch.font]\n0x050 = 0\n0x0A0 = 0\n0x010 = 9").unwrap();
+        let cheat = font.get("font").unwrap();
+        cheat.write(&mut cpu);
+        cheat.restore(&mut cpu);
+        assert_eq!(cpu.memory[FONT_ADDRESS], FONT_SET[0]);
+        assert_eq!(cpu.memory[BIG_FONT_ADDRESS], BIG_FONT_SET[0]);
+        assert_eq!(cpu.memory[0x010], 0);
+    }
+}
@@ -0,0 +1,215 @@
+//! Where the emulator gets the time from. Everything time-dependent, such
+//! as frame pacing and latency measurement, asks a [`Clock`], so that tests
+//! can drive time by hand with [`ManualClock`] and builds without
+//! `std::time`, like wasm or embedded ones, can supply their own.
+
+use alloc::rc::Rc;
+use core::cell::Cell;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock {
+    /// Monotonic time since some fixed origin, e.g. when the clock was
+    /// created.
+    fn now(&self) -> Duration;
+
+    /// Wall-clock time since the Unix epoch, for timestamps shown to
+    /// people. `None` if the clock doesn't know it.
+    fn unix_time(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Blocks for `duration`, on the host thread by default. Without
+    /// `std` it spins on [`Clock::now`].
+    fn sleep(&self, duration: Duration) {
+        #[cfg(feature = "std")]
+        std::thread::sleep(duration);
+        #[cfg(not(feature = "std"))]
+        {
+            let until = self.now() + duration;
+            while self.now() < until {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Rc<C> {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn unix_time(&self) -> Option<Duration> {
+        (**self).unix_time()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// The host's clocks, through `std::time`. There is no such clock on
+/// `wasm32-unknown-unknown`; web builds should implement [`Clock`] on top
+/// of `performance.now()` instead.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn unix_time(&self) -> Option<Duration> {
+        SystemTime::now().duration_since(UNIX_EPOCH).ok()
+    }
+}
+
+/// A clock that only moves when told to. Share it through an `Rc` to keep
+/// a handle after passing it on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManualClock {
+    now: Cell<Duration>,
+    unix_time: Cell<Option<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+        if let Some(unix_time) = self.unix_time.get() {
+            self.unix_time.set(Some(unix_time + by));
+        }
+    }
+
+    /// Sets the wall-clock time, which then advances along with
+    /// [`Clock::now`].
+    pub fn set_unix_time(&self, since_epoch: Duration) {
+        self.unix_time.set(Some(since_epoch));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    fn unix_time(&self) -> Option<Duration> {
+        self.unix_time.get()
+    }
+
+    /// Returns at once, having moved the clock on by `duration`.
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Spaces frames evenly on a [`Clock`]. The frontend sleeps for whatever
+/// [`FramePacer::wait`] returns; the pacer itself never blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacer {
+    period: Duration,
+    next: Option<Duration>,
+}
+
+/// Falling further behind than this many frames drops them instead of
+/// running them all at once to catch up.
+const MAX_LAG_FRAMES: u32 = 4;
+
+impl FramePacer {
+    pub fn new(frames_per_second: u32) -> Self {
+        FramePacer {
+            period: Duration::from_secs(1) / frames_per_second.max(1),
+            next: None,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Call once per frame, after producing it. Returns how long to wait
+    /// before starting the next one.
+    pub fn wait(&mut self, clock: &dyn Clock) -> Duration {
+        let now = clock.now();
+        let mut next = self.next.unwrap_or(now) + self.period;
+        if now > next + self.period * MAX_LAG_FRAMES {
+            next = now;
+        }
+        self.next = Some(next);
+        next.saturating_sub(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn manual_clock_moves_only_when_told() {
+        let clock = Rc::new(ManualClock::new());
+        let shared: Rc<dyn Clock> = clock.clone();
+        assert_eq!(shared.unix_time(), None);
+
+        clock.set_unix_time(Duration::from_secs(1_700_000_000));
+        clock.advance(5 * MS);
+
+        assert_eq!(shared.now(), 5 * MS);
+        assert_eq!(
+            shared.unix_time(),
+            Some(Duration::from_secs(1_700_000_000) + 5 * MS)
+        );
+    }
+
+    #[test]
+    fn pacer_keeps_an_even_rhythm() {
+        let clock = ManualClock::new();
+        let mut pacer = FramePacer::new(50);
+
+        assert_eq!(pacer.wait(&clock), 20 * MS);
+        // a frame that took 15 ms only waits for the rest of its slot
+        clock.advance(20 * MS + 15 * MS);
+        assert_eq!(pacer.wait(&clock), 5 * MS);
+        // a late frame starts the next one right away, then catches up
+        clock.advance(5 * MS + 30 * MS);
+        assert_eq!(pacer.wait(&clock), Duration::ZERO);
+        clock.advance(5 * MS);
+        assert_eq!(pacer.wait(&clock), 5 * MS);
+    }
+
+    #[test]
+    fn pacer_gives_up_on_catching_up_after_a_stall() {
+        let clock = ManualClock::new();
+        let mut pacer = FramePacer::new(50);
+        pacer.wait(&clock);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(pacer.wait(&clock), Duration::ZERO);
+        assert_eq!(pacer.wait(&clock), 20 * MS);
+    }
+}
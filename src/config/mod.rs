@@ -0,0 +1,281 @@
+//! The small subset of TOML that user configuration files such as the
+//! keymap and the ROM database overlay are written in: `[section]`
+//! headers with dotted and quoted parts, and `key = value` lines whose
+//! values are integers, strings, booleans or arrays of those.
+//!
+//! Only what those files need is supported. There are no inline tables,
+//! multi-line strings or dotted keys outside headers.
+
+use std::{error, fmt};
+
+/// A line of a text file that could not be understood. Every line-based
+/// format in the crate reports its errors with this type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    /// 1-based line, 0 for problems with the file as a whole.
+    pub line: usize,
+    pub message: String,
+}
+
+/// A line of a configuration file that could not be understood.
+pub type ConfigError = LineError;
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl error::Error for LineError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// One `key = value` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub line: usize,
+    pub key: String,
+    pub value: Value,
+}
+
+impl Entry {
+    /// An error about this entry.
+    pub fn error(&self, message: impl Into<String>) -> ConfigError {
+        ConfigError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    pub fn integer(&self) -> Result<i64, ConfigError> {
+        match self.value {
+            Value::Integer(n) => Ok(n),
+            _ => Err(self.error(format!("{} must be a number", self.key))),
+        }
+    }
+
+    pub fn string(&self) -> Result<&str, ConfigError> {
+        match &self.value {
+            Value::String(text) => Ok(text),
+            _ => Err(self.error(format!("{} must be a string", self.key))),
+        }
+    }
+}
+
+/// The entries under one header, or before the first one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Section {
+    pub line: usize,
+    /// The header split at its dots, e.g. `["game", "Space Invaders"]`
+    /// for `[game."Space Invaders"]`. Empty before the first header.
+    pub path: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+impl Section {
+    pub fn error(&self, message: impl Into<String>) -> ConfigError {
+        ConfigError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Every section in file order, starting with the unnamed one before the
+/// first header, which is left out if empty.
+pub fn parse(text: &str) -> Result<Vec<Section>, ConfigError> {
+    let mut sections = vec![Section::default()];
+    for (index, raw) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| ConfigError {
+            line: number,
+            message,
+        };
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| error("section header has no ]".to_string()))?;
+            sections.push(Section {
+                line: number,
+                path: split_path(header).map_err(error)?,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(format!("expected key = value, not {:?}", line)))?;
+        let entry = Entry {
+            line: number,
+            key: unquote(key.trim()).map_err(error)?,
+            value: parse_value(value.trim()).map_err(error)?,
+        };
+        sections
+            .last_mut()
+            .expect("there is always a section")
+            .entries
+            .push(entry);
+    }
+    if sections[0].entries.is_empty() {
+        sections.remove(0);
+    }
+    Ok(sections)
+}
+
+/// Drops a `#` comment, unless the `#` is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (at, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..at],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits at dots outside quotes.
+fn split_path(header: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (at, c) in header.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => {
+                parts.push(unquote(header[start..at].trim())?);
+                start = at + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(unquote(header[start..].trim())?);
+    Ok(parts)
+}
+
+/// A bare or double-quoted key.
+fn unquote(name: &str) -> Result<String, String> {
+    if let Some(quoted) = name.strip_prefix('"') {
+        return match quoted.strip_suffix('"') {
+            Some(inner) if !inner.contains('"') => Ok(inner.to_string()),
+            _ => Err(format!("badly quoted name {}", name)),
+        };
+    }
+    let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(bare) {
+        return Err(format!(
+            "names other than letters, digits, _ and - need quotes: {}",
+            name
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(items) = text.strip_prefix('[') {
+        let items = items.strip_suffix(']').ok_or("array has no ]")?;
+        let mut values = Vec::new();
+        for item in split_items(items) {
+            if !item.trim().is_empty() {
+                values.push(parse_value(item.trim())?);
+            }
+        }
+        return Ok(Value::Array(values));
+    }
+    if let Some(quoted) = text.strip_prefix('"') {
+        return match quoted.strip_suffix('"') {
+            Some(inner) if !inner.contains('"') => Ok(Value::String(inner.to_string())),
+            _ => Err(format!("badly quoted string {}", text)),
+        };
+    }
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    let digits = text.replace('_', "");
+    let parsed = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    parsed
+        .map(Value::Integer)
+        .map_err(|_| format!("not a value: {}", text))
+}
+
+/// Splits array items at commas outside strings.
+fn split_items(items: &str) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    items.split(move |c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ',' && !quoted
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_and_values() {
+        let sections = parse(
+            r##"
+            top = 1
+            [rom.ab12."some.thing"]   # comment
+            title = "A # not a comment"
+            tickrate = 0x1_0
+            debug = false
+            palette = ["#000000", "#FFFFFF",]
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].path, Vec::<String>::new());
+        assert_eq!(sections[1].path, ["rom", "ab12", "some.thing"]);
+        let values: Vec<&Value> = sections[1].entries.iter().map(|e| &e.value).collect();
+        assert_eq!(
+            values,
+            [
+                &Value::String("A # not a comment".to_string()),
+                &Value::Integer(16),
+                &Value::Bool(false),
+                &Value::Array(vec![
+                    Value::String("#000000".to_string()),
+                    Value::String("#FFFFFF".to_string())
+                ]),
+            ]
+        );
+        assert_eq!(sections[1].entries[1].line, 5);
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let bad = |text: &str| parse(text).unwrap_err();
+
+        assert_eq!(bad("a = 1\nb = two").line, 2);
+        assert_eq!(bad("[a.b").message, "section header has no ]");
+        assert_eq!(bad("\n\nc d = 4").line, 3);
+        assert_eq!(bad("x = [1, 2").line, 1);
+    }
+}
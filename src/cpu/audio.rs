@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use super::CPU;
+
+/// Pattern the buzzer plays until an XO-CHIP program loads its own: a
+/// square wave, 250 Hz at the default pitch.
+#[rustfmt::skip]
+pub const DEFAULT_PATTERN: [u8; 16] = [
+    0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+    0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+];
+
+const PATTERN_BITS: f64 = 128.0;
+
+/// How far sample generation has got, so that a restored save state
+/// continues the tone mid-waveform and for exactly the time it had left.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AudioPlayback {
+    /// Bit of [`CPU::audio_pattern`] being played, fractional.
+    pub pattern_position: f64,
+    /// Part of the current sound timer period already rendered: 0 right
+    /// after [`CPU::tick_timers`], up to 1 once a whole frame of samples
+    /// has been produced.
+    pub timer_phase: f64,
+}
+
+impl CPU {
+    /// Fills `out` with samples in `-1.0..=1.0` for a device running at
+    /// `sample_rate`, playing the pattern buffer while the sound timer runs.
+    ///
+    /// The tone stops after exactly `sound_timer` frames of audio even if
+    /// the frontend asks for samples faster than it ticks the timers, and
+    /// silence leaves the waveform position alone so the next beep picks up
+    /// where the last one ended.
+    pub fn render_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        let bits_per_sample = self.audio_playback_rate() / sample_rate as f64;
+        let frames_per_sample = 60.0 / sample_rate as f64;
+        let playback = &mut self.audio;
+
+        for sample in out {
+            let remaining = self.sound_timer as f64 - playback.timer_phase;
+            // less than half a sample left rounds to done
+            if remaining < frames_per_sample / 2.0 {
+                *sample = 0.0;
+                continue;
+            }
+            let bit = playback.pattern_position as usize;
+            let high = self.audio_pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if high { 1.0 } else { -1.0 };
+            playback.pattern_position =
+                (playback.pattern_position + bits_per_sample) % PATTERN_BITS;
+            playback.timer_phase =
+                (playback.timer_phase + frames_per_sample).min(self.sound_timer as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silent_without_sound_timer() {
+        let mut cpu = CPU::new();
+        let mut out = [1.0; 64];
+
+        cpu.render_audio(&mut out, 48_000);
+
+        assert!(out.iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn plays_the_pattern_at_the_playback_rate() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 1;
+        let mut out = [0.0; 16];
+
+        // one sample per bit
+        cpu.render_audio(&mut out, 4000);
+
+        assert_eq!(&out[..8], &[-1.0; 8]);
+        assert_eq!(&out[8..], &[1.0; 8]);
+        assert_eq!(cpu.audio.pattern_position, 16.0);
+    }
+
+    #[test]
+    fn tone_lasts_exactly_the_timer_frames() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 2;
+        // two frames are 1600 samples at 48 kHz, ask for more without ticking
+        let mut out = [0.0; 2000];
+
+        cpu.render_audio(&mut out, 48_000);
+
+        assert!(out[..1600].iter().all(|s| *s != 0.0));
+        assert!(out[1600..].iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn ticking_starts_a_new_timer_period() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 2;
+        let mut out = [0.0; 400];
+        cpu.render_audio(&mut out, 48_000);
+        assert!((cpu.audio.timer_phase - 0.5).abs() < 1e-9);
+
+        cpu.tick_timers();
+
+        assert_eq!(cpu.audio.timer_phase, 0.0);
+        assert_eq!(cpu.sound_timer, 1);
+    }
+}
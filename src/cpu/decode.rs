@@ -0,0 +1,209 @@
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use crate::disasm::Instruction;
+
+/// An opcode that is not an instruction of any CHIP-8 variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpcode(pub u16);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:04x}", self.0)
+    }
+}
+
+impl error::Error for UnknownOpcode {}
+
+/// Decodes a single opcode into the instruction it encodes.
+///
+/// Decoding does not depend on the emulated variant: SUPER-CHIP and XO-CHIP
+/// instructions decode in every mode and it is up to the interpreter to
+/// reject them. Never returns [`Instruction::Unknown`]. The long load comes
+/// back as `LdILong(0)`, its address is the word after the opcode, and
+/// Mega-Chip's as `LdIHuge` with only the top byte. Mega-Chip opcodes
+/// overlap 0NNN; the interpreter runs them as SYS outside that mode.
+pub fn decode(opcode: u16) -> Result<Instruction, UnknownOpcode> {
+    use Instruction::*;
+
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+    let unknown = Err(UnknownOpcode(opcode));
+
+    let instruction = match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Cls,
+            0x00EE => Ret,
+            0x00C0..=0x00CF => ScrollDown(n),
+            0x00D0..=0x00DF => ScrollUp(n),
+            0x00FB => ScrollRight,
+            0x00FC => ScrollLeft,
+            0x00FD => Exit,
+            0x00FE => Lores,
+            0x00FF => Hires,
+            0x0010 => MegaOff,
+            0x0011 => MegaOn,
+            0x0100..=0x01FF => LdIHuge((nn as u32) << 16),
+            0x0200..=0x02FF => LdPalette(nn),
+            0x0300..=0x03FF => SpriteWidth(nn),
+            0x0400..=0x04FF => SpriteHeight(nn),
+            0x0500..=0x05FF => Alpha(nn),
+            0x0600..=0x060F => DigiSound(n),
+            0x0700 => StopSound,
+            0x0800..=0x080F => BlendMode(n),
+            0x0900..=0x09FF => CollisionColor(nn),
+            _ => Sys(nnn),
+        },
+        0x1 => Jp(nnn),
+        0x2 => Call(nnn),
+        0x3 => SeByte { x, nn },
+        0x4 => SneByte { x, nn },
+        0x5 => match n {
+            0x0 => SeReg { x, y },
+            0x2 => SaveRange { x, y },
+            0x3 => LoadRange { x, y },
+            _ => return unknown,
+        },
+        0x6 => LdByte { x, nn },
+        0x7 => AddByte { x, nn },
+        0x8 => match n {
+            0x0 => LdReg { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => AddReg { x, y },
+            0x5 => Sub { x, y },
+            0x6 => Shr { x, y },
+            0x7 => Subn { x, y },
+            0xE => Shl { x, y },
+            _ => return unknown,
+        },
+        0x9 if n == 0 => SneReg { x, y },
+        0xA => LdI(nnn),
+        0xB => JpOffset { x, nnn },
+        0xC => Rnd { x, nn },
+        0xD => Drw { x, y, n },
+        0xE => match nn {
+            0x9E => Skp(x),
+            0xA1 => Sknp(x),
+            _ => return unknown,
+        },
+        0xF => match opcode {
+            0xF000 => LdILong(0),
+            0xF002 => Audio,
+            _ => match nn {
+                0x01 => Plane(x),
+                0x07 => LdVxDt(x),
+                0x0A => LdKey(x),
+                0x15 => LdDt(x),
+                0x18 => LdSt(x),
+                0x1E => AddI(x),
+                0x29 => LdFont(x),
+                0x30 => LdBigFont(x),
+                0x33 => Bcd(x),
+                0x3A => Pitch(x),
+                0x55 => Store(x),
+                0x65 => Load(x),
+                0x75 => StoreRpl(x),
+                0x85 => LoadRpl(x),
+                _ => return unknown,
+            },
+        },
+        _ => return unknown,
+    };
+    Ok(instruction)
+}
+
+/// [`decode`] of every possible opcode, worked out on first use and shared
+/// from then on, so the interpreter's decode is a single lookup.
+#[cfg(feature = "std")]
+pub fn decode_table() -> &'static [Result<Instruction, UnknownOpcode>] {
+    static TABLE: OnceLock<Box<[Result<Instruction, UnknownOpcode>]>> = OnceLock::new();
+    TABLE.get_or_init(|| (0..=u16::MAX).map(decode).collect())
+}
+
+/// What the interpreter decodes with: [`decode_table`], or without `std`,
+/// which has no `OnceLock`, [`decode`] itself.
+pub(crate) fn decode_fast(opcode: u16) -> Result<Instruction, UnknownOpcode> {
+    #[cfg(feature = "std")]
+    return decode_table()[opcode as usize];
+    #[cfg(not(feature = "std"))]
+    return decode(opcode);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Instruction::*;
+
+    #[test]
+    fn operands_are_extracted() {
+        assert_eq!(decode(0x1ABC), Ok(Jp(0xABC)));
+        assert_eq!(decode(0x2ABC), Ok(Call(0xABC)));
+        assert_eq!(decode(0x3C42), Ok(SeByte { x: 0xC, nn: 0x42 }));
+        assert_eq!(decode(0x7F01), Ok(AddByte { x: 0xF, nn: 0x01 }));
+        assert_eq!(decode(0x8AB4), Ok(AddReg { x: 0xA, y: 0xB }));
+        assert_eq!(decode(0xD12F), Ok(Drw { x: 1, y: 2, n: 0xF }));
+        assert_eq!(decode(0xB123), Ok(JpOffset { x: 1, nnn: 0x123 }));
+        assert_eq!(decode(0xE59E), Ok(Skp(5)));
+        assert_eq!(decode(0xFA65), Ok(Load(0xA)));
+    }
+
+    #[test]
+    fn system_opcodes_are_matched_before_sys() {
+        assert_eq!(decode(0x00E0), Ok(Cls));
+        assert_eq!(decode(0x00EE), Ok(Ret));
+        assert_eq!(decode(0x00C7), Ok(ScrollDown(7)));
+        assert_eq!(decode(0x00D3), Ok(ScrollUp(3)));
+        assert_eq!(decode(0x00FD), Ok(Exit));
+        assert_eq!(decode(0x0000), Ok(Sys(0)));
+        assert_eq!(decode(0x0A23), Ok(Sys(0xA23)));
+        assert_eq!(decode(0x0011), Ok(MegaOn));
+        assert_eq!(decode(0x0123), Ok(LdIHuge(0x23_0000)));
+        assert_eq!(decode(0x0610), Ok(Sys(0x610)));
+    }
+
+    #[test]
+    fn low_nibble_selects_within_a_group() {
+        assert_eq!(decode(0x5120), Ok(SeReg { x: 1, y: 2 }));
+        assert_eq!(decode(0x5122), Ok(SaveRange { x: 1, y: 2 }));
+        assert_eq!(decode(0x5123), Ok(LoadRange { x: 1, y: 2 }));
+        assert_eq!(decode(0x5121), Err(UnknownOpcode(0x5121)));
+        assert_eq!(decode(0x9120), Ok(SneReg { x: 1, y: 2 }));
+        assert_eq!(decode(0x9121), Err(UnknownOpcode(0x9121)));
+        assert_eq!(decode(0x8128), Err(UnknownOpcode(0x8128)));
+    }
+
+    #[test]
+    fn f_group_special_cases_win_over_the_low_byte() {
+        assert_eq!(decode(0xF000), Ok(LdILong(0)));
+        assert_eq!(decode(0xF002), Ok(Audio));
+        assert_eq!(decode(0xF101), Ok(Plane(1)));
+        assert_eq!(decode(0xF201), Ok(Plane(2)));
+        assert_eq!(decode(0xF033), Ok(Bcd(0)));
+        assert_eq!(decode(0xF0FF), Err(UnknownOpcode(0xF0FF)));
+        assert_eq!(decode(0xE0FF), Err(UnknownOpcode(0xE0FF)));
+    }
+
+    #[test]
+    fn the_table_matches_decoding() {
+        let table = decode_table();
+        assert_eq!(table.len(), 0x10000);
+        assert!((0..=u16::MAX).all(|opcode| table[opcode as usize] == decode(opcode)));
+    }
+
+    #[test]
+    fn every_opcode_decodes_to_something_of_its_own_size() {
+        for opcode in 0..=u16::MAX {
+            if let Ok(instruction) = decode(opcode) {
+                assert_ne!(instruction, Unknown(opcode));
+                let long = opcode == 0xF000 || opcode & 0xFF00 == 0x0100;
+                assert_eq!(instruction.size(), if long { 4 } else { 2 });
+            }
+        }
+    }
+}
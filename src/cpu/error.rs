@@ -0,0 +1,126 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use super::UnknownOpcode;
+use crate::disasm::{disassemble, Instruction};
+
+/// Everything that can stop the interpreter short of a normal halt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// CALL with all stack slots in use.
+    StackOverflow,
+    /// RET with an empty stack.
+    StackUnderflow,
+    /// An opcode no instruction or registered extension claims.
+    UnknownOpcode(u16),
+    /// An access of `len` bytes at `address` would run past the end of memory.
+    MemoryOutOfBounds { address: usize, len: usize },
+    /// A write below the program area while it is write protected, see
+    /// [`super::Memory::set_write_protected`], or to a region from
+    /// [`super::Memory::protect`].
+    ProtectedWrite { address: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow => write!(f, "Stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "Stack underflow"),
+            Chip8Error::UnknownOpcode(opcode) => write!(f, "unknown opcode {:04x}", opcode),
+            Chip8Error::MemoryOutOfBounds { address, len } => write!(
+                f,
+                "memory access of {} bytes at {:#05x} is out of bounds",
+                len, address
+            ),
+            Chip8Error::ProtectedWrite { address } => {
+                write!(f, "write to protected address {:#05x}", address)
+            }
+        }
+    }
+}
+
+impl error::Error for Chip8Error {}
+
+impl Chip8Error {
+    /// Whether a [`Strictness::Lenient`] CPU carries on past this error.
+    pub fn is_skippable(&self) -> bool {
+        matches!(
+            self,
+            Chip8Error::UnknownOpcode(_) | Chip8Error::MemoryOutOfBounds { .. }
+        )
+    }
+}
+
+/// What the CPU does about unknown opcodes and memory accesses out of
+/// bounds. Archived ROMs often run into data after a jump that an
+/// interpreter of the day took differently, and many of them play on
+/// fine if that data is skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Stop with a [`Fault`].
+    #[default]
+    Strict,
+    /// Skip the instruction and note the fault, see
+    /// [`super::CPU::take_skipped`].
+    Lenient,
+}
+
+impl From<UnknownOpcode> for Chip8Error {
+    fn from(err: UnknownOpcode) -> Self {
+        Chip8Error::UnknownOpcode(err.0)
+    }
+}
+
+/// A [`Chip8Error`] with enough context to tell what the program was doing:
+/// the faulting instruction, when it ran and how execution got there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    pub error: Chip8Error,
+    /// Address of the faulting instruction.
+    pub pc: usize,
+    /// `None` when the PC itself was out of bounds.
+    pub opcode: Option<u16>,
+    /// Frames elapsed so far, counted by [`super::CPU::tick_timers`].
+    pub frame: u64,
+    /// Addresses of the last instructions executed before the fault,
+    /// oldest first.
+    pub history: Vec<usize>,
+}
+
+impl Fault {
+    pub fn instruction(&self) -> Option<Instruction> {
+        self.opcode.map(disassemble)
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        match (self.opcode, self.instruction()) {
+            (Some(opcode), Some(instruction)) => write!(
+                f,
+                "\n  at {:#05x}: {:04X}  {}",
+                self.pc, opcode, instruction
+            )?,
+            _ => write!(f, "\n  at {:#05x}", self.pc)?,
+        }
+        write!(f, "\n  frame {}", self.frame)?;
+        if !self.history.is_empty() {
+            let trail: Vec<String> = self
+                .history
+                .iter()
+                .map(|pc| format!("{:#05x}", pc))
+                .collect();
+            write!(f, ", after {}", trail.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Fault {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
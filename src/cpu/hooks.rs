@@ -0,0 +1,142 @@
+use super::CPU;
+use crate::disasm::Instruction;
+
+/// What a [`PreExecuteHook`] wants done with the instruction about to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Execute it as decoded.
+    Continue,
+    /// Don't execute it. PC still moves past it.
+    Skip,
+    /// Execute this instead, e.g. a patched jump target.
+    Replace(Instruction),
+}
+
+/// Called before every instruction the interpreter understands, with
+/// PC already past it. Installed with [`CPU::add_pre_execute_hook`].
+pub type PreExecuteHook = fn(&mut CPU, &Instruction) -> HookAction;
+
+/// Called after every instruction that executed without a fault, with the
+/// instruction that actually ran. Installed with
+/// [`CPU::add_post_execute_hook`].
+pub type PostExecuteHook = fn(&mut CPU, &Instruction);
+
+impl CPU {
+    /// Adds a hook that sees every instruction before it runs and may skip
+    /// or replace it, for cheats and game-specific patches. Hooks run in
+    /// the order they were added, each seeing any replacement from the one
+    /// before; the first to skip ends the chain. Instructions the mode
+    /// doesn't have, and opcodes only a [`CPU::register_opcode_handler`]
+    /// extension understands, aren't offered.
+    pub fn add_pre_execute_hook(&mut self, hook: PreExecuteHook) {
+        self.pre_hooks.push(hook);
+    }
+
+    /// Adds a hook that runs after every instruction, e.g. to hold a
+    /// register at a fixed value or to collect statistics.
+    pub fn add_post_execute_hook(&mut self, hook: PostExecuteHook) {
+        self.post_hooks.push(hook);
+    }
+
+    /// Removes every pre- and post-execution hook.
+    pub fn clear_execute_hooks(&mut self) {
+        self.pre_hooks.clear();
+        self.post_hooks.clear();
+    }
+
+    /// What the pre-execution hooks make of `instruction`, or `None` to
+    /// skip it.
+    pub(super) fn run_pre_hooks(&mut self, mut instruction: Instruction) -> Option<Instruction> {
+        // by index, a hook may add others
+        let mut index = 0;
+        while let Some(&hook) = self.pre_hooks.get(index) {
+            match hook(self, &instruction) {
+                HookAction::Continue => {}
+                HookAction::Skip => return None,
+                HookAction::Replace(replacement) => instruction = replacement,
+            }
+            index += 1;
+        }
+        Some(instruction)
+    }
+
+    pub(super) fn run_post_hooks(&mut self, instruction: Instruction) {
+        let mut index = 0;
+        while let Some(&hook) = self.post_hooks.get(index) {
+            hook(self, &instruction);
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Chip8Error, Status};
+
+    /// V0 = 3; V0 -= 1 (as V0 += 0xFF); V1 = 9; halt
+    const PROGRAM: [u8; 8] = [0x60, 0x03, 0x70, 0xFF, 0x61, 0x09, 0x00, 0x00];
+
+    fn infinite_lives(cpu: &mut CPU, _: &Instruction) {
+        cpu.registers[0] = 3;
+    }
+
+    fn no_decrement(_: &mut CPU, instruction: &Instruction) -> HookAction {
+        match instruction {
+            Instruction::AddByte { x: 0, .. } => HookAction::Skip,
+            _ => HookAction::Continue,
+        }
+    }
+
+    fn patch_v1(_: &mut CPU, instruction: &Instruction) -> HookAction {
+        match *instruction {
+            Instruction::LdByte { x: 1, .. } => {
+                HookAction::Replace(Instruction::LdByte { x: 1, nn: 7 })
+            }
+            _ => HookAction::Continue,
+        }
+    }
+
+    #[test]
+    fn pre_hooks_skip_and_replace_instructions() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&PROGRAM).unwrap();
+        cpu.add_pre_execute_hook(no_decrement);
+        cpu.add_pre_execute_hook(patch_v1);
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(&cpu.registers[..2], [3, 7]);
+        assert_eq!(cpu.instructions(), 4);
+    }
+
+    fn skip_everything(_: &mut CPU, _: &Instruction) -> HookAction {
+        HookAction::Skip
+    }
+
+    #[test]
+    fn pre_hooks_dont_see_what_the_mode_lacks() {
+        let mut cpu = CPU::new();
+        // SCD 1 is SUPER-CHIP
+        cpu.load_rom(&[0x00, 0xC1]).unwrap();
+        cpu.add_pre_execute_hook(skip_everything);
+
+        let fault = cpu.step().unwrap_err();
+        assert_eq!(fault.error, Chip8Error::UnknownOpcode(0x00C1));
+    }
+
+    #[test]
+    fn post_hooks_run_after_every_instruction() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&PROGRAM).unwrap();
+        cpu.add_post_execute_hook(infinite_lives);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers[0], 3);
+
+        cpu.clear_execute_hooks();
+        cpu.soft_reset();
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 2);
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use super::CPU;
+use crate::clock::Clock;
+#[cfg(feature = "std")]
+use crate::clock::SystemClock;
+use crate::keypad::KEY_COUNT;
+
+/// How long one key press took to reach the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyLatency {
+    pub key: u8,
+    /// Frames from [`CPU::set_key`] until EX9E / EXA1 first read the
+    /// key as pressed.
+    pub frames: u64,
+    /// Time on the probe's clock over the same span.
+    pub elapsed: Duration,
+}
+
+/// Records the latency of every key press while enabled with
+/// [`CPU::set_measuring_latency`].
+///
+/// Only presses the program polls for count; a key released before any
+/// EX9E / EXA1 looked at it leaves no sample.
+#[derive(Clone)]
+pub struct LatencyProbe {
+    clock: Rc<dyn Clock>,
+    /// Frame and clock time of each press not yet seen by the program.
+    pending: [Option<(u64, Duration)>; KEY_COUNT],
+    samples: Vec<KeyLatency>,
+}
+
+impl fmt::Debug for LatencyProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LatencyProbe")
+            .field("pending", &self.pending)
+            .field("samples", &self.samples)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for LatencyProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyProbe {
+    /// Times presses with the [`SystemClock`].
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::with_clock(Rc::new(SystemClock::new()))
+    }
+
+    pub fn with_clock(clock: Rc<dyn Clock>) -> Self {
+        LatencyProbe {
+            clock,
+            pending: [None; KEY_COUNT],
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn samples(&self) -> &[KeyLatency] {
+        &self.samples
+    }
+
+    pub(super) fn key_changed(&mut self, key: u8, pressed: bool, frame: u64) {
+        self.pending[key as usize] = pressed.then(|| (frame, self.clock.now()));
+    }
+
+    pub(super) fn key_observed(&mut self, key: u8, frame: u64) {
+        if let Some((pressed_at, since)) = self.pending[key as usize].take() {
+            self.samples.push(KeyLatency {
+                key,
+                frames: frame - pressed_at,
+                elapsed: self.clock.now().saturating_sub(since),
+            });
+        }
+    }
+}
+
+impl fmt::Display for LatencyProbe {
+    /// One line summary, e.g. `4 presses, 1-3 frames (mean 2.0), 33.1 ms mean`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.samples.len();
+        if count == 0 {
+            return write!(f, "no key presses observed");
+        }
+        let frames = self.samples.iter().map(|sample| sample.frames);
+        let (min, max) = (frames.clone().min().unwrap(), frames.clone().max().unwrap());
+        let mean_frames = frames.sum::<u64>() as f64 / count as f64;
+        let elapsed: Duration = self.samples.iter().map(|sample| sample.elapsed).sum();
+        write!(
+            f,
+            "{} presses, {}-{} frames (mean {:.1}), {:.1} ms mean",
+            count,
+            min,
+            max,
+            mean_frames,
+            elapsed.as_secs_f64() * 1000.0 / count as f64
+        )
+    }
+}
+
+impl CPU {
+    /// Starts or stops timing key presses. Starting discards any samples
+    /// from an earlier measurement. Without `std`, use
+    /// [`CPU::measure_latency_with`].
+    #[cfg(feature = "std")]
+    pub fn set_measuring_latency(&mut self, enabled: bool) {
+        self.latency = enabled.then(LatencyProbe::new);
+    }
+
+    /// Like [`CPU::set_measuring_latency`], timing presses on `clock`.
+    pub fn measure_latency_with(&mut self, clock: Rc<dyn Clock>) {
+        self.latency = Some(LatencyProbe::with_clock(clock));
+    }
+
+    /// The running measurement, if any.
+    pub fn latency_probe(&self) -> Option<&LatencyProbe> {
+        self.latency.as_ref()
+    }
+
+    /// Called by EX9E and EXA1 when they check `key`.
+    pub(super) fn observe_key(&mut self, key: u8) {
+        if !self.keypad.is_pressed(key) {
+            return;
+        }
+        if let Some(probe) = &mut self.latency {
+            probe.key_observed(key, self.frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    /// Polls key 5 with EX9E forever: V0 := 5, SKP V0, JP 0x002, JP 0x002.
+    fn polling_cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.memory[..8].copy_from_slice(&[0x60, 0x05, 0xE0, 0x9E, 0x10, 0x02, 0x10, 0x02]);
+        cpu.set_measuring_latency(true);
+        cpu
+    }
+
+    #[test]
+    fn counts_frames_until_the_program_polls() {
+        let mut cpu = polling_cpu();
+
+        cpu.set_key(5, true);
+        cpu.tick_timers();
+        cpu.tick_timers();
+        cpu.run_for(2).unwrap();
+        cpu.run_for(10).unwrap();
+
+        let samples = cpu.latency_probe().unwrap().samples();
+        assert_eq!(samples.len(), 1);
+        assert_eq!((samples[0].key, samples[0].frames), (5, 2));
+    }
+
+    #[test]
+    fn elapsed_time_comes_from_the_probe_clock() {
+        let clock = Rc::new(ManualClock::new());
+        let mut cpu = polling_cpu();
+        cpu.measure_latency_with(clock.clone());
+
+        cpu.set_key(5, true);
+        clock.advance(Duration::from_millis(40));
+        cpu.run_for(10).unwrap();
+
+        let probe = cpu.latency_probe().unwrap();
+        assert_eq!(probe.samples()[0].elapsed, Duration::from_millis(40));
+        assert_eq!(
+            probe.to_string(),
+            "1 presses, 0-0 frames (mean 0.0), 40.0 ms mean"
+        );
+    }
+
+    #[test]
+    fn unobserved_presses_leave_no_sample() {
+        let mut cpu = polling_cpu();
+
+        cpu.set_key(7, true);
+        cpu.set_key(5, true);
+        cpu.set_key(5, false);
+        cpu.run_for(10).unwrap();
+
+        let probe = cpu.latency_probe().unwrap();
+        assert!(probe.samples().is_empty());
+        assert_eq!(probe.to_string(), "no key presses observed");
+    }
+}
@@ -0,0 +1,345 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Ref, RefCell};
+use core::ops::{Deref, DerefMut, Range};
+
+use super::{Chip8Error, PROGRAM_START};
+
+/// How often the program read and wrote each address and ran the
+/// instruction there, see [`Memory::set_tracking_access`]. Instruction
+/// fetches count as executes, not reads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessStats {
+    pub reads: Vec<u32>,
+    pub writes: Vec<u32>,
+    /// By the address an instruction starts at.
+    pub executes: Vec<u32>,
+}
+
+impl AccessStats {
+    fn new(size: usize) -> Self {
+        AccessStats {
+            reads: vec![0; size],
+            writes: vec![0; size],
+            executes: vec![0; size],
+        }
+    }
+
+    /// Total reads and writes in each of `count` equal slices of memory,
+    /// for drawing a heatmap. The last slice takes any remainder.
+    pub fn buckets(&self, count: usize) -> Vec<(u64, u64)> {
+        let size = (self.reads.len() / count.max(1)).max(1);
+        let sum = |counts: &[u32]| counts.iter().map(|n| *n as u64).sum::<u64>();
+        (0..count)
+            .map(|bucket| {
+                let start = (bucket * size).min(self.reads.len());
+                let end = if bucket + 1 == count {
+                    self.reads.len()
+                } else {
+                    (start + size).min(self.reads.len())
+                };
+                (sum(&self.reads[start..end]), sum(&self.writes[start..end]))
+            })
+            .collect()
+    }
+
+    /// Addresses with any accesses, most accessed first, as
+    /// `(address, reads, writes)`.
+    pub fn hottest(&self, count: usize) -> Vec<(usize, u32, u32)> {
+        let mut hot: Vec<(usize, u32, u32)> = (0..self.reads.len())
+            .filter(|a| self.reads[*a] > 0 || self.writes[*a] > 0)
+            .map(|a| (a, self.reads[a], self.writes[a]))
+            .collect();
+        hot.sort_by_key(|(address, reads, writes)| {
+            (core::cmp::Reverse(*reads as u64 + *writes as u64), *address)
+        });
+        hot.truncate(count);
+        hot
+    }
+}
+
+/// The CPU's address space. Programs go through the checked accessors,
+/// which can wrap addresses past the end and refuse writes to the
+/// interpreter area and other protected regions; the host can still index the bytes directly, e.g. to
+/// poke a value from a debugger or to load the font.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    /// Addresses wrap around the end instead of faulting. Kept in step
+    /// with [`super::Quirks::wrap_memory`] by the CPU.
+    pub(super) wrap: bool,
+    write_protected: bool,
+    /// Read-only regions the host asked for with [`Memory::protect`].
+    regions: Vec<Range<usize>>,
+    /// Behind a `RefCell` so that reads can be counted through `&self`.
+    stats: Option<RefCell<AccessStats>>,
+}
+
+impl Memory {
+    pub fn new(size: usize) -> Self {
+        Memory {
+            bytes: vec![0; size],
+            wrap: false,
+            write_protected: false,
+            regions: Vec::new(),
+            stats: None,
+        }
+    }
+
+    /// Starts counting accesses from zero, or stops and discards the
+    /// counts.
+    pub fn set_tracking_access(&mut self, tracking: bool) {
+        self.stats = tracking.then(|| RefCell::new(AccessStats::new(self.bytes.len())));
+    }
+
+    pub fn access_stats(&self) -> Option<Ref<'_, AccessStats>> {
+        self.stats.as_ref().map(RefCell::borrow)
+    }
+
+    /// Counts an execute of the instruction at `address`.
+    pub(super) fn count_execute(&mut self, address: usize) {
+        if let Some(count) = self
+            .stats
+            .as_mut()
+            .and_then(|stats| stats.get_mut().executes.get_mut(address))
+        {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    fn count(&self, start: usize, len: usize, write: bool) {
+        if let Some(stats) = &self.stats {
+            let mut stats = stats.borrow_mut();
+            let counts = if write {
+                &mut stats.writes
+            } else {
+                &mut stats.reads
+            };
+            for offset in 0..len {
+                let count = &mut counts[(start + offset) % self.bytes.len()];
+                *count = count.saturating_add(1);
+            }
+        }
+    }
+
+    /// Makes program writes below [`PROGRAM_START`], where the font and
+    /// a real interpreter live, fault instead of succeeding.
+    pub fn set_write_protected(&mut self, protected: bool) {
+        self.write_protected = protected;
+    }
+
+    pub fn is_write_protected(&self) -> bool {
+        self.write_protected
+    }
+
+    /// Makes program writes to `range` fault too, e.g. to catch a game
+    /// overwriting its own code or a table. Regions add up, and stay until
+    /// [`Memory::clear_protected`].
+    pub fn protect(&mut self, range: Range<usize>) {
+        if !range.is_empty() {
+            self.regions.push(range);
+        }
+    }
+
+    /// Drops every region from [`Memory::protect`]. The interpreter area
+    /// stays as [`Memory::set_write_protected`] left it.
+    pub fn clear_protected(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Every protected region: the interpreter area if it is protected,
+    /// then those from [`Memory::protect`].
+    pub fn protected_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.write_protected
+            .then_some(0..PROGRAM_START)
+            .into_iter()
+            .chain(self.regions.iter().cloned())
+    }
+
+    pub fn is_protected(&self, address: usize) -> bool {
+        self.protected_ranges()
+            .any(|range| range.contains(&address))
+    }
+
+    /// Swaps in new contents, possibly of another size, keeping the
+    /// protection and wrapping settings.
+    pub fn replace(&mut self, bytes: Vec<u8>) {
+        self.bytes = bytes;
+        if let Some(stats) = &mut self.stats {
+            let stats = stats.get_mut();
+            stats.reads.resize(self.bytes.len(), 0);
+            stats.writes.resize(self.bytes.len(), 0);
+            stats.executes.resize(self.bytes.len(), 0);
+        }
+    }
+
+    /// Where an access of `len` bytes at `address` starts, after wrapping.
+    fn resolve(&self, address: usize, len: usize) -> Result<usize, Chip8Error> {
+        if address
+            .checked_add(len)
+            .is_some_and(|end| end <= self.bytes.len())
+        {
+            Ok(address)
+        } else if self.wrap && !self.bytes.is_empty() {
+            Ok(address % self.bytes.len())
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds { address, len })
+        }
+    }
+
+    pub fn read_u8(&self, address: usize) -> Result<u8, Chip8Error> {
+        let address = self.resolve(address, 1)?;
+        self.count(address, 1, false);
+        Ok(self.bytes[address])
+    }
+
+    /// Big-endian, like opcodes.
+    pub fn read_u16(&self, address: usize) -> Result<u16, Chip8Error> {
+        let word = self.fetch_u16(address)?;
+        self.count(self.resolve(address, 2)?, 2, false);
+        Ok(word)
+    }
+
+    /// [`Memory::read_u16`] for instruction fetches, which are not counted
+    /// in the access statistics.
+    pub fn fetch_u16(&self, address: usize) -> Result<u16, Chip8Error> {
+        if let Some(&[high, low]) = self.bytes.get(address..address.wrapping_add(2)) {
+            return Ok(u16::from_be_bytes([high, low]));
+        }
+        let start = self.resolve(address, 2)?;
+        let high = self.bytes[start];
+        let low = self.bytes[(start + 1) % self.bytes.len()];
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
+    pub fn write_u8(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        let address = self.resolve(address, 1)?;
+        if self.is_protected(address) {
+            return Err(Chip8Error::ProtectedWrite { address });
+        }
+        self.bytes[address] = value;
+        self.count(address, 1, true);
+        Ok(())
+    }
+
+    /// Fills `out` from `address` on, wrapping byte by byte if enabled.
+    pub fn read(&self, address: usize, out: &mut [u8]) -> Result<(), Chip8Error> {
+        let start = self.resolve(address, out.len())?;
+        self.count(start, out.len(), false);
+        if start + out.len() <= self.bytes.len() {
+            out.copy_from_slice(&self.bytes[start..start + out.len()]);
+            return Ok(());
+        }
+        for (offset, byte) in out.iter_mut().enumerate() {
+            *byte = self.bytes[(start + offset) % self.bytes.len()];
+        }
+        Ok(())
+    }
+
+    /// Stores `data` at `address` on. Nothing is written if any byte would
+    /// fault.
+    pub fn write(&mut self, address: usize, data: &[u8]) -> Result<(), Chip8Error> {
+        let start = self.resolve(address, data.len())?;
+        if self.write_protected || !self.regions.is_empty() {
+            let hit = (0..data.len())
+                .map(|offset| (start + offset) % self.bytes.len())
+                .find(|address| self.is_protected(*address));
+            if let Some(address) = hit {
+                return Err(Chip8Error::ProtectedWrite { address });
+            }
+        }
+        for (offset, byte) in data.iter().enumerate() {
+            let address = (start + offset) % self.bytes.len();
+            self.bytes[address] = *byte;
+        }
+        self.count(start, data.len(), true);
+        Ok(())
+    }
+}
+
+impl Deref for Memory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl DerefMut for Memory {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accesses_past_the_end_fault_or_wrap() {
+        let mut memory = Memory::new(0x1000);
+        memory[0xFFF] = 0x12;
+        memory[0x000] = 0x34;
+
+        assert_eq!(
+            memory.read_u16(0xFFF),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFF,
+                len: 2
+            })
+        );
+        assert!(memory.write_u8(0x1000, 1).is_err());
+
+        memory.wrap = true;
+        assert_eq!(memory.read_u16(0xFFF), Ok(0x1234));
+        memory.write(0xFFF, &[0xAA, 0xBB]).unwrap();
+        assert_eq!((memory[0xFFF], memory[0x000]), (0xAA, 0xBB));
+    }
+
+    #[test]
+    fn protected_writes_leave_memory_untouched() {
+        let mut memory = Memory::new(0x1000);
+        memory.set_write_protected(true);
+
+        assert_eq!(
+            memory.write_u8(0x050, 1),
+            Err(Chip8Error::ProtectedWrite { address: 0x050 })
+        );
+        assert!(memory.write(0x1FE, &[1, 2, 3]).is_err());
+        assert_eq!(&memory[0x1FE..0x201], &[0, 0, 0]);
+        assert_eq!(memory.write(0x200, &[1, 2, 3]), Ok(()));
+        assert_eq!(memory.read_u8(0x050), Ok(0));
+
+        memory.protect(0x300..0x310);
+        assert_eq!(
+            memory.write(0x2FF, &[1, 2]),
+            Err(Chip8Error::ProtectedWrite { address: 0x300 })
+        );
+        assert_eq!(memory[0x2FF], 0);
+        assert_eq!(memory.write_u8(0x310, 1), Ok(()));
+        let ranges: Vec<_> = memory.protected_ranges().collect();
+        assert_eq!(ranges, [0..0x200, 0x300..0x310]);
+        memory.clear_protected();
+        assert_eq!(memory.write_u8(0x300, 1), Ok(()));
+    }
+
+    #[test]
+    fn tracked_accesses_are_counted_per_address() {
+        let mut memory = Memory::new(0x1000);
+        memory.set_tracking_access(true);
+
+        memory.write(0x300, &[1, 2]).unwrap();
+        memory.read_u8(0x300).unwrap();
+        memory.read_u16(0x300).unwrap();
+        memory.fetch_u16(0x200).unwrap();
+
+        let stats = memory.access_stats().unwrap();
+        assert_eq!((stats.reads[0x300], stats.writes[0x300]), (2, 1));
+        assert_eq!((stats.reads[0x301], stats.writes[0x301]), (1, 1));
+        assert_eq!(stats.reads[0x200], 0);
+        assert_eq!(stats.hottest(1), [(0x300, 2, 1)]);
+        let buckets = stats.buckets(16);
+        assert_eq!(buckets[3], (3, 2));
+        assert_eq!(buckets.iter().map(|b| b.0).sum::<u64>(), 3);
+    }
+}
@@ -1,136 +1,852 @@
-use std::{panic, todo};
+mod audio;
+mod decode;
+mod error;
+mod font;
+mod hooks;
+mod latency;
+mod memory;
+mod mode;
+mod quirks;
+mod rng;
+mod rom;
+mod state;
+mod timing;
+mod trace;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use crate::disasm::{disassemble, CallChain, Instruction};
+use crate::display::{FrameBuffer, PLANES};
+use crate::keypad::Keypad;
+use decode::decode_fast;
+
+pub use audio::{AudioPlayback, DEFAULT_PATTERN};
+#[cfg(feature = "std")]
+pub use decode::decode_table;
+pub use decode::{decode, UnknownOpcode};
+pub use error::{Chip8Error, Fault, Strictness};
+pub use font::{
+    BIG_FONT_ADDRESS, BIG_FONT_SET, BIG_GLYPH_SIZE, FONT_ADDRESS, FONT_SET, GLYPH_SIZE,
+};
+pub use hooks::{HookAction, PostExecuteHook, PreExecuteHook};
+pub use latency::{KeyLatency, LatencyProbe};
+pub use memory::{AccessStats, Memory};
+pub use mode::EmulatorMode;
+pub use quirks::Quirks;
+pub use rng::{RandomSource, XorShift};
+pub use rom::{RomError, PROGRAM_START};
+pub use state::{DisplayState, MegaState, SaveState, StateError, SAVE_STATE_VERSION};
+pub use timing::{cycle_cost, Accuracy, DISPLAY_CYCLES, FRAME_CYCLES};
+pub use trace::{TraceEvent, TraceHook};
+
+/// How many recently executed addresses a [`Fault`] reports.
+pub const HISTORY_LEN: usize = 8;
+
+/// The last [`HISTORY_LEN`] addresses executed, kept in a fixed ring so
+/// that noting one down on every step is just a store.
+#[derive(Debug, Clone, Copy, Default)]
+struct History {
+    addresses: [usize; HISTORY_LEN],
+    len: usize,
+    next: usize,
+}
+
+impl History {
+    fn push(&mut self, address: usize) {
+        self.addresses[self.next] = address;
+        self.next = (self.next + 1) % HISTORY_LEN;
+        self.len = (self.len + 1).min(HISTORY_LEN);
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Oldest first.
+    fn to_vec(self) -> Vec<usize> {
+        let oldest = self.next + HISTORY_LEN - self.len;
+        (0..self.len)
+            .map(|index| self.addresses[(oldest + index) % HISTORY_LEN])
+            .collect()
+    }
+}
 
+/// Calls the stack holds unless [`CPU::stack_depth`] is changed, as in
+/// CHIP-48 and SUPER-CHIP.
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+
+/// Stack depth at which recursion gets reported, see
+/// [`CPU::take_stack_warning`].
+pub const STACK_WARNING_DEPTH: usize = 12;
+
+/// Faults [`CPU::take_skipped`] keeps between calls.
+pub const SKIPPED_LIMIT: usize = 64;
+
+/// FX3A pitch at which the audio pattern plays at 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// Outcome of executing (or trying to execute) an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The instruction ran and the program can keep going.
+    Continue,
+    /// Opcode 0000 (see [`CPU::halt_on_zero`]) or 00FD was reached.
+    /// Nothing else will execute.
+    Halted,
+    /// FX0A is blocking until a key is pressed with [`CPU::set_key`].
+    WaitingForKey,
+}
+
+/// Host callback invoked for an opcode claimed through
+/// [`CPU::register_opcode_handler`]. Receives the full 16-bit opcode.
+pub type OpcodeHandler = fn(&mut CPU, u16);
+
+/// Host routine for 0NNN, "call the RCA 1802 machine code at NNN",
+/// installed with [`CPU::set_syscall_handler`]. Receives NNN.
+pub type SyscallHandler = fn(&mut CPU, u16);
+
+struct OpcodeExtension {
+    mask: u16,
+    pattern: u16,
+    handler: OpcodeHandler,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     pub registers: [u8; 16],
     pub memory_position: usize,
-    //todo: first 512bytes of memory are used for system
-    pub memory: [u8; 0x1000],
-    stack_pointer: usize,
-    stack: [u16; 16],
+    // first 512 bytes are reserved for the interpreter, the font lives there.
+    // 4 KiB long, or 64 KiB in XO-CHIP mode
+    pub memory: Memory,
+    pub i: u16,
+    pub display: FrameBuffer,
+    pub keypad: Keypad,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub quirks: Quirks,
+    pub mode: EmulatorMode,
+    /// Timing model used by [`CPU::run_frame`].
+    pub accuracy: Accuracy,
+    pub strictness: Strictness,
+    /// Whether 0000 halts the program. It's a call to machine code at
+    /// address 0 as far as the original interpreter goes, but nothing puts
+    /// code there, so interpreters treat it as the end of the program.
+    /// Without this, 0000 goes to the syscall handler like any other 0NNN.
+    pub halt_on_zero: bool,
+    /// Nested calls allowed before 2NNN overflows the stack. The VIP had
+    /// room for 12, some later interpreters for many more.
+    pub stack_depth: usize,
+    /// SUPER-CHIP's persistent "RPL user flags", written by FX75.
+    pub rpl_flags: [u8; 16],
+    /// XO-CHIP's 128-bit audio pattern, loaded by F002 and played
+    /// one bit at a time while the sound timer runs.
+    pub audio_pattern: [u8; 16],
+    /// XO-CHIP playback pitch set by FX3A, see [`CPU::audio_playback_rate`].
+    pub pitch: u8,
+    /// Where [`CPU::render_audio`] is in the pattern and timer period.
+    pub audio: AudioPlayback,
+    rng: Box<dyn RandomSource>,
+    waiting_for_key: Option<u8>,
+    halted: bool,
+    frame: u64,
+    instructions: u64,
+    history: History,
+    /// The program as passed to [`CPU::load_rom`], kept for resets.
+    rom: Vec<u8>,
+    trace_hook: Option<TraceHook>,
+    tracing: bool,
+    latency: Option<LatencyProbe>,
+    /// Cycles left in the current frame under [`Accuracy::Cycle`],
+    /// negative after an overrun.
+    cycles: i32,
+    stack: Vec<u16>,
+    stack_warning: Option<CallChain>,
+    /// Faults skipped under [`Strictness::Lenient`], oldest first.
+    skipped: Vec<Fault>,
+    extensions: Vec<OpcodeExtension>,
+    syscall: Option<SyscallHandler>,
+    pre_hooks: Vec<PreExecuteHook>,
+    post_hooks: Vec<PostExecuteHook>,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CPU {
     pub fn new() -> Self {
-        return CPU {
+        Self::new_with_quirks(Quirks::default())
+    }
+
+    /// Creates a CPU for a CHIP-8 variant, with that variant's usual quirks.
+    pub fn new_with_mode(mode: EmulatorMode) -> Self {
+        let mut cpu = Self::new_with_quirks(mode.default_quirks());
+        cpu.mode = mode;
+        cpu.memory = Memory::new(mode.memory_size());
+        cpu.load_font();
+        cpu
+    }
+
+    /// Creates a CPU emulating a specific interpreter, e.g.
+    /// `CPU::new_with_quirks(Quirks::cosmac_vip())`.
+    pub fn new_with_quirks(quirks: Quirks) -> Self {
+        let mut cpu = CPU {
             registers: [0; 16],
             memory_position: 0,
-            memory: [0; 0x1000],
-            stack: [0; 16],
-            stack_pointer: 0,
+            memory: Memory::new(EmulatorMode::Chip8.memory_size()),
+            i: 0,
+            display: FrameBuffer::new(),
+            keypad: Keypad::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks,
+            mode: EmulatorMode::Chip8,
+            accuracy: Accuracy::Fast,
+            strictness: Strictness::Strict,
+            halt_on_zero: true,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            rpl_flags: [0; 16],
+            audio_pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+            audio: AudioPlayback::default(),
+            rng: Box::new(XorShift::from_time()),
+            waiting_for_key: None,
+            halted: false,
+            frame: 0,
+            instructions: 0,
+            history: History::default(),
+            rom: Vec::new(),
+            trace_hook: None,
+            tracing: false,
+            latency: None,
+            cycles: 0,
+            stack: Vec::with_capacity(DEFAULT_STACK_DEPTH),
+            stack_warning: None,
+            skipped: Vec::new(),
+            extensions: Vec::new(),
+            syscall: None,
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
         };
+        cpu.load_font();
+        cpu
+    }
+
+    /// Claims every otherwise-invalid opcode for which
+    /// `opcode & mask == pattern` and routes it to `handler`.
+    ///
+    /// Opcodes the interpreter already understands never reach the
+    /// registry, so stock ROMs behave the same with or without extensions.
+    /// When several handlers match, the first one registered wins.
+    pub fn register_opcode_handler(&mut self, mask: u16, pattern: u16, handler: OpcodeHandler) {
+        self.extensions.push(OpcodeExtension {
+            mask,
+            pattern,
+            handler,
+        });
+    }
+
+    /// Runs 0NNN through `handler`, to emulate the RCA 1802 routines a
+    /// program calls or to give test ROMs host services such as logging.
+    /// Without one, 0NNN is an unknown opcode, which
+    /// [`CPU::register_opcode_handler`] can still claim.
+    pub fn set_syscall_handler(&mut self, handler: Option<SyscallHandler>) {
+        self.syscall = handler;
+    }
+
+    /// Replaces the random source used by CXNN, e.g. with a seeded
+    /// [`XorShift`] for reproducible runs.
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    /// Copies `rom` to [`PROGRAM_START`], loads the font set into the reserved
+    /// low memory and points the CPU at the first instruction.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.check_rom(rom)?;
+
+        self.load_font();
+        self.memory[PROGRAM_START..].fill(0);
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        self.memory_position = PROGRAM_START;
+        self.rom = rom.to_vec();
+        Ok(())
+    }
+
+    /// The program as last loaded, before it had any chance to modify
+    /// itself.
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Whether `rom` fits in memory, without loading it.
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), RomError> {
+        let max = self.memory.len() - PROGRAM_START;
+        if rom.len() > max {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Replaces the program and power-cycles, as after editing the ROM on
+    /// disk. Nothing changes if `rom` doesn't fit, so a bad image never
+    /// replaces a running one.
+    pub fn reload_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.check_rom(rom)?;
+        self.rom = rom.to_vec();
+        self.hard_reset();
+        Ok(())
+    }
+
+    /// Restarts the loaded program, like the reset button: memory is
+    /// reloaded and the machine state cleared, but the RPL flags survive.
+    pub fn soft_reset(&mut self) {
+        let rpl_flags = self.rpl_flags;
+        self.hard_reset();
+        self.rpl_flags = rpl_flags;
+    }
+
+    /// Returns to the power-on state with the same ROM loaded. Only the
+    /// configuration survives: mode, quirks, random source, opcode
+    /// extensions, the syscall handler and execution hooks.
+    pub fn hard_reset(&mut self) {
+        self.registers = [0; 16];
+        self.i = 0;
+        let tracking_draws = self.display.is_tracking_draws();
+        self.display = FrameBuffer::new();
+        self.display.set_tracking_draws(tracking_draws);
+        self.keypad = Keypad::new();
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.rpl_flags = [0; 16];
+        self.audio_pattern = DEFAULT_PATTERN;
+        self.pitch = DEFAULT_PITCH;
+        self.audio = AudioPlayback::default();
+        self.waiting_for_key = None;
+        self.halted = false;
+        self.frame = 0;
+        self.instructions = 0;
+        self.cycles = 0;
+        self.history.clear();
+        self.stack.clear();
+        self.stack_warning = None;
+        self.skipped.clear();
+        self.memory.fill(0);
+        let rom = core::mem::take(&mut self.rom);
+        self.load_rom(&rom)
+            .expect("a ROM that loaded once still fits");
+    }
+
+    fn load_font(&mut self) {
+        self.memory[FONT_ADDRESS..FONT_ADDRESS + FONT_SET.len()].copy_from_slice(&FONT_SET);
+        self.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_SET.len()]
+            .copy_from_slice(&BIG_FONT_SET);
+    }
+
+    /// Reads a ROM file (usually `.ch8`) and loads it with [`CPU::load_rom`].
+    #[cfg(feature = "std")]
+    pub fn load_rom_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        let rom = fs::read(path)?;
+        self.load_rom(&rom)
+    }
+
+    /// Updates the state of a keypad key. While the CPU is blocked on FX0A,
+    /// pressing a key stores it in the awaited register and unblocks it.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(probe) = &mut self.latency {
+            if self.keypad.is_pressed(key) != pressed {
+                probe.key_changed(key, pressed, self.frame);
+            }
+        }
+        self.keypad.set(key, pressed);
+        if pressed {
+            if let Some(register) = self.waiting_for_key.take() {
+                self.registers[register as usize] = key;
+            }
+        }
+    }
+
+    /// Whether execution is blocked on FX0A until a key is pressed.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key.is_some()
+    }
+
+    /// Decrements the delay and sound timers. Must be called at 60 Hz by
+    /// whoever drives the CPU, independently of the instruction rate.
+    pub fn tick_timers(&mut self) {
+        self.frame += 1;
+        self.audio.timer_phase = 0.0;
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// The call stack: return addresses of the active subroutine calls,
+    /// innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    /// The subroutines being run, outermost first, by the address each was
+    /// called at. Found by reading the CALL before every return address,
+    /// so code that rewrote its own CALLs may show up wrong.
+    pub fn call_chain(&self) -> CallChain {
+        let entries = self.stack().iter().map(|back| {
+            let at = back.wrapping_sub(2) as usize;
+            match self.memory.get(at..at + 2) {
+                Some(&[high, low]) => match disassemble(u16::from_be_bytes([high, low])) {
+                    Instruction::Call(target) => target,
+                    _ => at as u16,
+                },
+                _ => at as u16,
+            }
+        });
+        CallChain(entries.collect())
+    }
+
+    /// The call chain as it was when a subroutine got
+    /// [`STACK_WARNING_DEPTH`] calls deep while calling itself, directly or
+    /// not, if that happened since the last call. A program doing so is
+    /// likely to overflow the stack soon, and the chain is usually gone by
+    /// the time it does.
+    pub fn take_stack_warning(&mut self) -> Option<CallChain> {
+        self.stack_warning.take()
+    }
+
+    /// The faults skipped under [`Strictness::Lenient`] since the last
+    /// call, at most [`SKIPPED_LIMIT`] of them. A program off in the
+    /// weeds can skip thousands a frame, so the rest are dropped.
+    pub fn take_skipped(&mut self) -> Vec<Fault> {
+        core::mem::take(&mut self.skipped)
+    }
+
+    /// Number of [`CPU::tick_timers`] calls so far, i.e. frames elapsed.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Instructions executed since power-on, not counting the ones that
+    /// faulted.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Whether the buzzer should currently be sounding.
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Bits of [`CPU::audio_pattern`] played per second at the current
+    /// pitch: `4000 * 2^((pitch - 64) / 48)`.
+    pub fn audio_playback_rate(&self) -> f64 {
+        #[cfg(feature = "std")]
+        return 4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0);
+        // no powf without std: whole octaves and a table for the rest
+        #[cfg(not(feature = "std"))]
+        {
+            let steps = self.pitch as i32 - DEFAULT_PITCH as i32;
+            let octaves = steps.div_euclid(48);
+            let octave = if octaves < 0 {
+                1.0 / (1u32 << -octaves) as f64
+            } else {
+                (1u32 << octaves) as f64
+            };
+            4000.0 * octave * PITCH_STEPS[steps.rem_euclid(48) as usize]
+        }
     }
 
-    pub fn run(&mut self) {
+    /// Executes until opcode 0000 is reached, or until FX0A blocks waiting
+    /// for a key. In the latter case call `run` again after `set_key`.
+    pub fn run(&mut self) -> Result<Status, Fault> {
         loop {
-            let opcode = self.read_op_code();
-            self.memory_position += 2;
+            let status = self.step()?;
+            if status != Status::Continue {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Executes at most `n_instructions`, stopping early on halt or FX0A.
+    /// Returns `Continue` when the whole budget was used.
+    pub fn run_for(&mut self, n_instructions: usize) -> Result<Status, Fault> {
+        for _ in 0..n_instructions {
+            let status = self.step()?;
+            if status != Status::Continue {
+                return Ok(status);
+            }
+        }
+        Ok(Status::Continue)
+    }
 
-            let c = ((opcode & 0xF000) >> 12) as u8;
-            let x = ((opcode & 0x0F00) >> 8) as u8;
-            let y = ((opcode & 0x00F0) >> 4) as u8;
-            let op_minor = ((opcode & 0x000F) >> 0) as u8;
+    /// [`CPU::run`] for programs that can't be trusted to stop, e.g. from a
+    /// fuzzer: returns `Continue` after `max_instructions` at the latest.
+    /// Whatever is in memory and however the public fields are set, this
+    /// either returns or reports a [`Fault`]; it never panics.
+    pub fn run_bounded(&mut self, max_instructions: usize) -> Result<Status, Fault> {
+        self.run_for(max_instructions)
+    }
 
-            let addr = (opcode & 0x0FFF) as u16;
-            let kk = (opcode & 0x00FF) as u8;
+    /// Executes exactly one instruction. While halted or blocked on FX0A
+    /// nothing is executed and the corresponding status is returned again.
+    ///
+    /// On error the program counter has already moved past the faulting
+    /// instruction, whose address is reported in the [`Fault`].
+    pub fn step(&mut self) -> Result<Status, Fault> {
+        if self.halted {
+            return Ok(Status::Halted);
+        }
+        if self.waiting_for_key.is_some() {
+            return Ok(Status::WaitingForKey);
+        }
 
-            match opcode {
-                0x0000 => {
-                    return;
-                }
-                0x00E0 => { /* CLEAR SCREEN */ }
-                0x00EE => {
-                    self.ret();
-                }
-                0x1000..=0x1FFF => {
-                    self.jmp(addr);
-                }
-                0x2000..=0x2FFF => {
-                    self.call(addr);
-                }
-                0x3000..=0x3FFF => {
-                    self.se(x, kk);
-                }
-                0x4000..=0x4FFF => {
-                    self.sne(x, kk);
+        let pc = self.memory_position;
+        // taken before executing, the instruction may overwrite itself
+        let before = match self.tracing {
+            true => self.memory.get(pc..pc.saturating_add(2)).map(|word| {
+                (
+                    u16::from_be_bytes([word[0], word[1]]),
+                    self.registers,
+                    self.i,
+                )
+            }),
+            false => None,
+        };
+        match self.execute_next() {
+            Ok(status) => {
+                self.instructions += 1;
+                self.history.push(pc);
+                self.memory.count_execute(pc);
+                if let Some((opcode, registers, i)) = before {
+                    self.trace(pc, opcode, registers, i);
                 }
-                0x5000..=0x5FFF => {
-                    self.ser(x, y);
+                Ok(status)
+            }
+            Err(error) => {
+                let fault = Fault {
+                    error,
+                    pc,
+                    opcode: self
+                        .memory
+                        .get(pc..pc.saturating_add(2))
+                        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])),
+                    frame: self.frame,
+                    history: self.history.to_vec(),
+                };
+                // with PC itself out of bounds there is nothing to skip to
+                let skip = self.strictness == Strictness::Lenient
+                    && fault.error.is_skippable()
+                    && fault.opcode.is_some();
+                if !skip {
+                    return Err(fault);
                 }
-                0x6000..=0x6FFF => {
-                    self.ld(x, kk);
+                self.memory_position = pc.wrapping_add(2);
+                self.instructions += 1;
+                self.history.push(pc);
+                if self.skipped.len() < SKIPPED_LIMIT {
+                    self.skipped.push(fault);
                 }
-                0x7000..=0x7FFF => {
-                    self.add(x, kk);
+                Ok(self.status())
+            }
+        }
+    }
+
+    fn execute_next(&mut self) -> Result<Status, Chip8Error> {
+        // quirks are public and may have changed since the last instruction
+        self.memory.wrap = self.quirks.wrap_memory;
+        let opcode = self.read_op_code()?;
+        self.memory_position = self.memory_position.wrapping_add(2);
+
+        let decoded = match decode_fast(opcode) {
+            // Mega-Chip took these from 0NNN
+            Ok(instruction) if instruction.is_mega_chip() && !self.mode.has_mega_chip() => {
+                Ok(Instruction::Sys(opcode & 0xFFF))
+            }
+            decoded => decoded,
+        };
+        match decoded {
+            // hooks only see instructions the mode has, see [`PreExecuteHook`]
+            Ok(instruction) if !self.supports(instruction) => {
+                self.extension(opcode)?;
+                Ok(self.status())
+            }
+            Ok(instruction) if !self.pre_hooks.is_empty() || !self.post_hooks.is_empty() => {
+                self.execute_hooked(opcode, instruction)
+            }
+            Ok(instruction) => self.execute(instruction),
+            _ => {
+                self.extension(opcode)?;
+                Ok(self.status())
+            }
+        }
+    }
+
+    fn execute_hooked(
+        &mut self,
+        opcode: u16,
+        instruction: Instruction,
+    ) -> Result<Status, Chip8Error> {
+        let Some(instruction) = self.run_pre_hooks(instruction) else {
+            return Ok(self.status());
+        };
+        // a replacement may be no more supported than the original
+        if !self.supports(instruction) {
+            self.extension(opcode)?;
+            return Ok(self.status());
+        }
+        let status = self.execute(instruction)?;
+        self.run_post_hooks(instruction);
+        Ok(status)
+    }
+
+    /// Whether `instruction` exists in the current mode. Opcodes that decode
+    /// to anything else are offered to the registered extensions.
+    fn supports(&self, instruction: Instruction) -> bool {
+        use Instruction::*;
+
+        match instruction {
+            Sys(0) if self.halt_on_zero => true,
+            Sys(_) => self.syscall.is_some(),
+            ScrollDown(_) | ScrollRight | ScrollLeft | Exit | Lores | Hires | LdBigFont(_)
+            | StoreRpl(_) | LoadRpl(_) => self.mode.has_super_chip(),
+            ScrollUp(_)
+            | SaveRange { .. }
+            | LoadRange { .. }
+            | LdILong(_)
+            | Plane(_)
+            | Audio
+            | Pitch(_) => self.mode.has_xo_chip(),
+            instruction if instruction.is_mega_chip() => self.mode.has_mega_chip(),
+            Unknown(_) => false,
+            _ => true,
+        }
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<Status, Chip8Error> {
+        use Instruction::*;
+
+        match instruction {
+            Sys(addr) if addr != 0 || !self.halt_on_zero => {
+                if let Some(handler) = self.syscall {
+                    handler(self, addr);
                 }
-                0x8000..=0x8FFF => match op_minor {
-                    0 => self.ld(x, self.registers[y as usize]),
-                    1 => self.or_xy(x, y),
-                    2 => self.and_xy(x, y),
-                    3 => self.xor_xy(x, y),
-                    4 => {
-                        self.add_xy(x, y);
-                    }
-                    _ => {
-                        todo!("opcode: {:04x}", opcode);
-                    }
-                },
-                _ => todo!("opcode {:04x}", opcode),
             }
+            Sys(_) | Exit => {
+                self.halted = true;
+                return Ok(Status::Halted);
+            }
+            Cls => self.cls(),
+            Ret => self.ret()?,
+            ScrollDown(n) => self.display.scroll_down(n as usize),
+            ScrollUp(n) => self.display.scroll_up(n as usize),
+            ScrollRight => self.display.scroll_right(4),
+            ScrollLeft => self.display.scroll_left(4),
+            Lores => self.display.set_hires(false),
+            Hires => self.display.set_hires(true),
+            Jp(addr) => self.jmp(addr),
+            JpOffset { x, nnn } => self.jmp_offset(x, nnn),
+            Call(addr) => self.call(addr)?,
+            SeByte { x, nn } => self.se(x, nn),
+            SneByte { x, nn } => self.sne(x, nn),
+            SeReg { x, y } => self.ser(x, y),
+            SaveRange { x, y } => self.store_range(x, y)?,
+            LoadRange { x, y } => self.load_range(x, y)?,
+            LdByte { x, nn } => self.ld(x, nn),
+            AddByte { x, nn } => self.add(x, nn),
+            LdReg { x, y } => self.ld(x, self.registers[y as usize]),
+            Or { x, y } => self.or_xy(x, y),
+            And { x, y } => self.and_xy(x, y),
+            Xor { x, y } => self.xor_xy(x, y),
+            AddReg { x, y } => self.add_xy(x, y),
+            Sub { x, y } => self.sub_xy(x, y),
+            Shr { x, y } => self.shr(x, y),
+            Subn { x, y } => self.subn_xy(x, y),
+            Shl { x, y } => self.shl(x, y),
+            SneReg { x, y } => self.sner(x, y),
+            LdI(addr) => self.ld_i(addr),
+            Rnd { x, nn } => self.rnd(x, nn),
+            Drw { x, y, n } => self.drw(x, y, n)?,
+            Skp(x) => self.skp(x),
+            Sknp(x) => self.sknp(x),
+            LdILong(_) => self.ld_i_long()?,
+            Plane(mask) => self.display.select_planes(mask),
+            Audio => self.ld_audio()?,
+            LdVxDt(x) => self.ld(x, self.delay_timer),
+            LdKey(x) => self.ld_key(x),
+            LdDt(x) => self.ld_dt(x),
+            LdSt(x) => self.ld_st(x),
+            AddI(x) => self.add_i(x),
+            LdFont(x) => self.ld_font(x),
+            LdBigFont(x) => self.ld_big_font(x),
+            Pitch(x) => self.pitch = self.registers[x as usize],
+            Store(x) => self.store_registers(x)?,
+            Load(x) => self.load_registers(x)?,
+            StoreRpl(x) => self.store_rpl(x),
+            LoadRpl(x) => self.load_rpl(x),
+            Bcd(x) => self.bcd(x)?,
+            MegaOff => self.display.set_mega(false),
+            MegaOn => self.display.set_mega(true),
+            LdIHuge(high) => self.ld_i_huge(high)?,
+            LdPalette(count) => self.ld_palette(count)?,
+            SpriteWidth(nn) => self.set_sprite_size(Some(nn), None),
+            SpriteHeight(nn) => self.set_sprite_size(None, Some(nn)),
+            // accepted so demos run, but there is no sound or blending
+            Alpha(_) | DigiSound(_) | StopSound | BlendMode(_) | CollisionColor(_) => {}
+            // never supported, so only reached when called directly
+            Unknown(opcode) => return Err(Chip8Error::UnknownOpcode(opcode)),
+        }
+
+        Ok(self.status())
+    }
+
+    fn status(&self) -> Status {
+        if self.waiting_for_key.is_some() {
+            Status::WaitingForKey
+        } else {
+            Status::Continue
+        }
+    }
+
+    fn read_op_code(&self) -> Result<u16, Chip8Error> {
+        self.memory.fetch_u16(self.memory_position)
+    }
+
+    fn extension(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let handler = self
+            .extensions
+            .iter()
+            .find(|ext| opcode & ext.mask == ext.pattern)
+            .map(|ext| ext.handler);
+        match handler {
+            Some(handler) => {
+                handler(self, opcode);
+                Ok(())
+            }
+            None => Err(Chip8Error::UnknownOpcode(opcode)),
         }
     }
 
-    fn read_op_code(&self) -> u16 {
-        let op1 = self.memory[self.memory_position] as u16;
-        let op2 = self.memory[self.memory_position + 1] as u16;
-        return (op1 << 8) | op2;
+    /// Stores the result of a flag-setting ALU instruction. VF is written
+    /// last, so the flag wins when VF is also the destination.
+    fn set_with_flag(&mut self, x: u8, value: u8, flag: u8) {
+        self.registers[x as usize] = value;
+        self.registers[0xF] = flag;
     }
 
     fn add_xy(&mut self, x: u8, y: u8) {
         let arg1 = self.registers[x as usize];
         let arg2 = self.registers[y as usize];
 
-        let (val, overflow) = arg1.overflowing_add(arg2);
-        self.registers[x as usize] = val;
-        self.registers[0xF] = overflow as u8;
+        let (val, carry) = arg1.overflowing_add(arg2);
+        self.set_with_flag(x, val, carry as u8);
+    }
+
+    fn sub_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, borrow) = arg1.overflowing_sub(arg2);
+        self.set_with_flag(x, val, !borrow as u8);
+    }
+
+    fn subn_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, borrow) = arg2.overflowing_sub(arg1);
+        self.set_with_flag(x, val, !borrow as u8);
     }
 
-    fn call(&mut self, mem_pos: u16) {
-        if self.stack_pointer == self.stack.len() {
-            panic!("Stack overflow");
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[y as usize]
+        } else {
+            self.registers[x as usize]
         }
-        self.stack[self.stack_pointer] = self.memory_position as u16;
-        self.stack_pointer += 1;
-        self.memory_position = mem_pos as usize;
     }
 
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow");
+    fn shr(&mut self, x: u8, y: u8) {
+        let value = self.shift_source(x, y);
+        self.set_with_flag(x, value >> 1, value & 0x01);
+    }
+
+    fn shl(&mut self, x: u8, y: u8) {
+        let value = self.shift_source(x, y);
+        self.set_with_flag(x, value << 1, value >> 7);
+    }
+
+    fn call(&mut self, mem_pos: u16) -> Result<(), Chip8Error> {
+        if self.stack.len() >= self.stack_depth {
+            return Err(Chip8Error::StackOverflow);
+        }
+        self.stack.push(self.memory_position as u16);
+        self.memory_position = mem_pos as usize;
+        if self.stack.len() == STACK_WARNING_DEPTH && self.stack_warning.is_none() {
+            let chain = self.call_chain();
+            if chain.is_recursive() {
+                self.stack_warning = Some(chain);
+            }
         }
-        self.stack_pointer -= 1;
-        let previous_mem_position = self.stack[self.stack_pointer] as usize;
-        self.memory_position = previous_mem_position;
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<(), Chip8Error> {
+        let previous_mem_position = self.stack.pop().ok_or(Chip8Error::StackUnderflow)?;
+        self.memory_position = previous_mem_position as usize;
+        Ok(())
+    }
+
+    /// Skips the next instruction, which may be XO-CHIP's four-byte
+    /// F000 NNNN or Mega-Chip's 01NN NNNN.
+    fn skip_next(&mut self) {
+        let pc = self.memory_position;
+        let long = match self.memory.get(pc..pc.saturating_add(2)) {
+            Some(&[0xF0, 0x00]) => self.mode.has_xo_chip(),
+            Some(&[0x01, _]) => self.mode.has_mega_chip(),
+            _ => false,
+        };
+        self.memory_position = self.memory_position.wrapping_add(if long { 4 } else { 2 });
     }
 
     fn jmp(&mut self, addr: u16) {
         self.memory_position = addr as usize;
     }
 
+    /// BNNN: jump to NNN + V0, or to XNN + VX with the CHIP-48 quirk, where
+    /// X is the top digit of NNN.
+    fn jmp_offset(&mut self, x: u8, nnn: u16) {
+        let register = if self.quirks.jump_uses_vx { x } else { 0 };
+        self.jmp(nnn + self.registers[register as usize] as u16);
+    }
+
     fn se(&mut self, register: u8, nn: u8) {
         if self.registers[register as usize] == nn {
-            self.memory_position += 2;
+            self.skip_next();
         }
     }
 
     fn sne(&mut self, register: u8, nn: u8) {
         if self.registers[register as usize] != nn {
-            self.memory_position += 2;
+            self.skip_next();
         }
     }
 
     fn ser(&mut self, r1: u8, r2: u8) {
         if self.registers[r1 as usize] == self.registers[r2 as usize] {
-            self.memory_position += 2;
+            self.skip_next();
+        }
+    }
+
+    fn sner(&mut self, r1: u8, r2: u8) {
+        if self.registers[r1 as usize] != self.registers[r2 as usize] {
+            self.skip_next();
         }
     }
 
@@ -138,34 +854,304 @@ impl CPU {
         self.registers[register as usize] = nn;
     }
 
+    /// 7XNN wraps and, unlike 8XY4, leaves VF alone.
     fn add(&mut self, register: u8, nn: u8) {
-        self.registers[register as usize] += nn;
+        let value = &mut self.registers[register as usize];
+        *value = value.wrapping_add(nn);
+    }
+
+    fn rnd(&mut self, register: u8, mask: u8) {
+        self.registers[register as usize] = self.rng.next_u8() & mask;
+    }
+
+    fn cls(&mut self) {
+        self.display.clear();
+    }
+
+    fn drw(&mut self, x: u8, y: u8, rows: u8) -> Result<(), Chip8Error> {
+        let px = self.registers[x as usize] as usize;
+        let py = self.registers[y as usize] as usize;
+        if let Some(mega) = self.display.mega() {
+            let mut sprite = vec![0; mega.sprite_width * mega.sprite_height];
+            self.memory.read(self.i as usize, &mut sprite)?;
+            let collision = self.display.draw_mega_sprite(px, py, &sprite);
+            self.registers[0xF] = collision as u8;
+            return Ok(());
+        }
+        let wrap = self.quirks.wrap_sprites;
+        // XO-CHIP reads one sprite per selected plane
+        let planes = self.display.selected_planes().count_ones() as usize;
+
+        let mut sprite = [0; 32 * PLANES];
+        let collisions = if rows == 0 && self.mode.has_super_chip() {
+            let sprite = &mut sprite[..32 * planes];
+            self.memory.read(self.i as usize, sprite)?;
+            self.display.draw_large_sprite_rows(px, py, sprite, wrap)
+        } else {
+            let sprite = &mut sprite[..rows as usize * planes];
+            self.memory.read(self.i as usize, sprite)?;
+            self.display.draw_sprite_rows(px, py, sprite, wrap)
+        };
+        self.registers[0xF] = if self.quirks.collision_counts_rows && self.display.is_hires() {
+            (collisions.rows + collisions.clipped_rows) as u8
+        } else {
+            collisions.any() as u8
+        };
+        Ok(())
+    }
+
+    fn skp(&mut self, register: u8) {
+        let key = self.registers[register as usize] & 0x0F;
+        self.observe_key(key);
+        if self.keypad.is_pressed(key) {
+            self.skip_next();
+        }
+    }
+
+    fn sknp(&mut self, register: u8) {
+        let key = self.registers[register as usize] & 0x0F;
+        self.observe_key(key);
+        if !self.keypad.is_pressed(key) {
+            self.skip_next();
+        }
+    }
+
+    fn ld_key(&mut self, register: u8) {
+        self.waiting_for_key = Some(register);
+    }
+
+    fn ld_dt(&mut self, register: u8) {
+        self.delay_timer = self.registers[register as usize];
+    }
+
+    fn ld_st(&mut self, register: u8) {
+        self.sound_timer = self.registers[register as usize];
+    }
+
+    fn ld_i(&mut self, addr: u16) {
+        self.i = addr;
+    }
+
+    /// F000 NNNN: the address is the word following the opcode.
+    fn ld_i_long(&mut self) -> Result<(), Chip8Error> {
+        self.i = self.read_op_code()?;
+        self.memory_position = self.memory_position.wrapping_add(2);
+        Ok(())
+    }
+
+    /// 01NN NNNN. Memory stops at 64 KiB, so addresses past it fault
+    /// rather than wrap.
+    fn ld_i_huge(&mut self, high: u32) -> Result<(), Chip8Error> {
+        let address = high as usize | self.read_op_code()? as usize;
+        self.memory_position = self.memory_position.wrapping_add(2);
+        self.i = u16::try_from(address)
+            .map_err(|_| Chip8Error::MemoryOutOfBounds { address, len: 1 })?;
+        Ok(())
+    }
+
+    /// 02NN: `count` palette entries of four bytes each, ARGB, from I.
+    /// Ignored before the Mega-Chip screen is on.
+    fn ld_palette(&mut self, count: u8) -> Result<(), Chip8Error> {
+        let mut bytes = vec![0; count as usize * 4];
+        self.memory.read(self.i as usize, &mut bytes)?;
+        if let Some(mega) = self.display.mega_mut() {
+            let colors = bytes
+                .chunks(4)
+                .map(|argb| u32::from_be_bytes([argb[0], argb[1], argb[2], argb[3]]));
+            mega.load_palette(colors);
+        }
+        Ok(())
+    }
+
+    /// 03NN and 04NN, where 0 stands for 256.
+    fn set_sprite_size(&mut self, width: Option<u8>, height: Option<u8>) {
+        let size = |nn: u8| if nn == 0 { 256 } else { nn as usize };
+        if let Some(mega) = self.display.mega_mut() {
+            if let Some(width) = width {
+                mega.sprite_width = size(width);
+            }
+            if let Some(height) = height {
+                mega.sprite_height = size(height);
+            }
+        }
+    }
+
+    fn ld_audio(&mut self) -> Result<(), Chip8Error> {
+        self.memory.read(self.i as usize, &mut self.audio_pattern)
+    }
+
+    fn add_i(&mut self, register: u8) {
+        self.i = self
+            .i
+            .wrapping_add(self.registers[register as usize] as u16);
+    }
+
+    fn ld_font(&mut self, register: u8) {
+        let digit = self.registers[register as usize] & 0x0F;
+        self.i = (FONT_ADDRESS + digit as usize * GLYPH_SIZE) as u16;
+    }
+
+    fn ld_big_font(&mut self, register: u8) {
+        let digit = self.registers[register as usize] & 0x0F;
+        self.i = (BIG_FONT_ADDRESS + digit as usize * BIG_GLYPH_SIZE) as u16;
+    }
+
+    fn store_rpl(&mut self, last: u8) {
+        let count = last as usize + 1;
+        self.rpl_flags[..count].copy_from_slice(&self.registers[..count]);
+    }
+
+    fn load_rpl(&mut self, last: u8) {
+        let count = last as usize + 1;
+        self.registers[..count].copy_from_slice(&self.rpl_flags[..count]);
+    }
+
+    /// FX33: the hundreds, tens and ones digits of VX at I, I+1 and I+2.
+    fn bcd(&mut self, register: u8) -> Result<(), Chip8Error> {
+        let value = self.registers[register as usize];
+        self.memory
+            .write(self.i as usize, &[value / 100, value / 10 % 10, value % 10])
+    }
+
+    fn store_registers(&mut self, last: u8) -> Result<(), Chip8Error> {
+        let count = last as usize + 1;
+        self.memory
+            .write(self.i as usize, &self.registers[..count])?;
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(count as u16);
+        }
+        Ok(())
+    }
+
+    fn load_registers(&mut self, last: u8) -> Result<(), Chip8Error> {
+        let count = last as usize + 1;
+        self.memory
+            .read(self.i as usize, &mut self.registers[..count])?;
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(count as u16);
+        }
+        Ok(())
+    }
+
+    /// 5XY2: stores VX through VY at I, in descending order when X > Y.
+    /// I is left unchanged.
+    fn store_range(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let registers = register_range(x, y);
+        let len = registers.len();
+        let mut values = [0; 16];
+        for (value, register) in values.iter_mut().zip(registers) {
+            *value = self.registers[register];
+        }
+        self.memory.write(self.i as usize, &values[..len])
+    }
+
+    /// 5XY3: the inverse of 5XY2.
+    fn load_range(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let registers = register_range(x, y);
+        let mut values = [0; 16];
+        let values = &mut values[..registers.len()];
+        self.memory.read(self.i as usize, values)?;
+        for (register, value) in registers.zip(values) {
+            self.registers[register] = *value;
+        }
+        Ok(())
     }
 
     fn or_xy(&mut self, r1: u8, r2: u8) {
         let r1_value = self.registers[r1 as usize];
         let r2_value = self.registers[r2 as usize];
         self.registers[r1 as usize] = r1_value | r2_value;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn and_xy(&mut self, r1: u8, r2: u8) {
         let r1_value = self.registers[r1 as usize];
         let r2_value = self.registers[r2 as usize];
         self.registers[r1 as usize] = r1_value & r2_value;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn xor_xy(&mut self, r1: u8, r2: u8) {
         let r1_value = self.registers[r1 as usize];
         let r2_value = self.registers[r2 as usize];
         self.registers[r1 as usize] = r1_value ^ r2_value;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 }
 
+/// Register indices from `x` to `y` inclusive, counting down if `x > y`.
+/// An iterator rather than a `Vec`, so 5XY2 and 5XY3 don't allocate.
+fn register_range(x: u8, y: u8) -> impl ExactSizeIterator<Item = usize> {
+    let (x, y) = (x as usize, y as usize);
+    (0..x.abs_diff(y) + 1).map(move |step| if x <= y { x + step } else { x - step })
+}
+
+/// `2^(k / 48)`, the pitch steps within an octave.
+#[cfg(not(feature = "std"))]
+const PITCH_STEPS: [f64; 48] = [
+    1.0,
+    1.0145453349375237,
+    1.029302236643492,
+    1.0442737824274138,
+    1.0594630943592953,
+    1.0748733399206964,
+    1.0905077326652577,
+    1.1063695328888334,
+    1.122462048309373,
+    1.1387886347566916,
+    1.155352696872273,
+    1.1721576888192515,
+    1.189207115002721,
+    1.2065045308005218,
+    1.2240535433046553,
+    1.241857812073484,
+    1.2599210498948732,
+    1.2782470235604306,
+    1.2968395546510096,
+    1.3157025203336377,
+    1.3348398541700344,
+    1.3542555469368927,
+    1.3739536474580891,
+    1.3939382634489994,
+    core::f64::consts::SQRT_2,
+    1.4347837723110002,
+    1.4556531828421873,
+    1.4768261459394993,
+    1.4983070768766815,
+    1.5201004551491148,
+    1.5422108254079407,
+    1.5646427984077742,
+    1.5874010519681994,
+    1.6104903319492543,
+    1.6339154532411,
+    1.6576813007680873,
+    1.681792830507429,
+    1.7062550705226855,
+    1.731073122012286,
+    1.7562521603732995,
+    1.7817974362806785,
+    1.8077142767822019,
+    1.8340080864093424,
+    1.8606843483042932,
+    1.8877486253633868,
+    1.9152065613971474,
+    1.9430638823072117,
+    1.9713263972803752,
+];
+
 #[cfg(test)]
 mod tests {
     use std::assert_eq;
 
     use super::*;
+    use crate::asm::encode;
+
     #[test]
     fn add_three_registers_to_first_register() {
         let mut cpu = CPU::new();
@@ -182,7 +1168,7 @@ mod tests {
         mem[4] = 0x80;
         mem[5] = 0x34;
 
-        cpu.run();
+        cpu.run().unwrap();
 
         assert_eq!(cpu.registers[0], 35);
     }
@@ -192,9 +1178,40 @@ mod tests {
         let mut cpu = CPU {
             registers: [0; 16],
             memory_position: 0,
-            memory: [0; 0x1000],
-            stack: [0; 16],
-            stack_pointer: 0,
+            memory: Memory::new(0x1000),
+            i: 0,
+            display: FrameBuffer::new(),
+            keypad: Keypad::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks: Quirks::default(),
+            mode: EmulatorMode::Chip8,
+            accuracy: Accuracy::Fast,
+            strictness: Strictness::Strict,
+            halt_on_zero: true,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            rpl_flags: [0; 16],
+            audio_pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+            audio: AudioPlayback::default(),
+            rng: Box::new(XorShift::new(1)),
+            waiting_for_key: None,
+            halted: false,
+            frame: 0,
+            instructions: 0,
+            history: History::default(),
+            rom: Vec::new(),
+            trace_hook: None,
+            tracing: false,
+            latency: None,
+            cycles: 0,
+            stack: Vec::with_capacity(DEFAULT_STACK_DEPTH),
+            stack_warning: None,
+            skipped: Vec::new(),
+            extensions: Vec::new(),
+            syscall: None,
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
         };
 
         cpu.registers[0] = 5;
@@ -216,57 +1233,105 @@ mod tests {
         mem[0x104] = 0x00;
         mem[0x105] = 0xEE;
 
-        cpu.run();
+        cpu.run().unwrap();
 
         assert_eq!(cpu.registers[0], 45);
     }
 
     #[test]
-    #[should_panic(expected = "Stack overflow")]
     fn stack_overflow() {
         let mut cpu = CPU::new();
+        // each call goes to the next one, one more than the stack holds
+        let calls: Vec<Instruction> = (1..=17).map(|n| Instruction::Call(2 * n)).collect();
+        let rom = encode(&calls);
+        cpu.memory[..rom.len()].copy_from_slice(&rom);
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::StackOverflow)
+        );
+    }
 
-        let mem = &mut cpu.memory;
-        mem[0x000] = 0x20;
-        mem[0x001] = 0x02; //call
-        mem[0x002] = 0x20;
-        mem[0x003] = 0x04; //call
-        mem[0x004] = 0x20;
-        mem[0x005] = 0x06; //call
-        mem[0x006] = 0x20;
-        mem[0x007] = 0x08; //call
-        mem[0x008] = 0x20;
-        mem[0x009] = 0x0a; //call
-        mem[0x00a] = 0x20;
-        mem[0x00b] = 0x0c; //call
-        mem[0x00c] = 0x20;
-        mem[0x00d] = 0x0e; //call
-        mem[0x00e] = 0x20;
-        mem[0x00f] = 0x10; //call
-        mem[0x010] = 0x20;
-        mem[0x011] = 0x12; //call
-        mem[0x012] = 0x20;
-        mem[0x013] = 0x14; //call
-        mem[0x014] = 0x20;
-        mem[0x015] = 0x16; //call
-        mem[0x016] = 0x20;
-        mem[0x017] = 0x18; //call
-        mem[0x018] = 0x20;
-        mem[0x019] = 0x1a; //call
-        mem[0x01a] = 0x20;
-        mem[0x01b] = 0x1c; //call
-        mem[0x01c] = 0x20;
-        mem[0x01d] = 0x1e; //call
-        mem[0x01e] = 0x20;
-        mem[0x01f] = 0x20; //call
-        mem[0x020] = 0x20;
-        mem[0x021] = 0x22; //call
-
-        cpu.run();
-    }
-
-    #[test]
-    #[should_panic(expected = "Stack underflow")]
+    #[test]
+    fn the_stack_depth_is_configurable() {
+        let mut cpu = CPU::new();
+        let calls: Vec<Instruction> = (1..=3).map(|n| Instruction::Call(2 * n)).collect();
+        let rom = encode(&calls);
+        cpu.memory[..rom.len()].copy_from_slice(&rom);
+        cpu.stack_depth = 2;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| (fault.error, fault.pc)),
+            Err((Chip8Error::StackOverflow, 0x004))
+        );
+        assert_eq!(cpu.stack(), [0x002, 0x004]);
+
+        // the fault is recoverable: with more room the same call goes through
+        cpu.stack_depth = 64;
+        cpu.memory_position = 0x004;
+        cpu.step().unwrap();
+        assert_eq!(cpu.stack(), [0x002, 0x004, 0x006]);
+    }
+
+    #[test]
+    fn recursion_is_reported_before_the_stack_overflows() {
+        let mut cpu = CPU::new();
+        let rom = encode(&[
+            Instruction::Call(0x004),
+            Instruction::Exit,
+            Instruction::Call(0x004),
+        ]);
+        cpu.memory[..rom.len()].copy_from_slice(&rom);
+
+        for _ in 0..STACK_WARNING_DEPTH - 1 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.take_stack_warning(), None);
+        cpu.step().unwrap();
+        let chain = cpu.take_stack_warning().unwrap();
+        assert_eq!(chain.0.len(), STACK_WARNING_DEPTH);
+        assert!(chain.0.iter().all(|entry| *entry == 0x004));
+        assert_eq!(cpu.take_stack_warning(), None);
+        assert_eq!(cpu.call_chain(), chain);
+    }
+
+    #[test]
+    fn arbitrary_programs_never_panic() {
+        let mut rng = XorShift::new(7);
+        let modes = [
+            EmulatorMode::Chip8,
+            EmulatorMode::SuperChip,
+            EmulatorMode::XoChip,
+        ];
+        for round in 0..300 {
+            let rom: Vec<u8> = (0..64).map(|_| rng.next_u8()).collect();
+            let mut cpu = CPU::new_with_mode(modes[round % modes.len()]);
+            cpu.quirks.wrap_memory = round % 2 == 0;
+            cpu.load_rom(&rom).unwrap();
+            let _ = cpu.run_bounded(1_000);
+        }
+        // public fields set to anything at all
+        for wrap in [false, true] {
+            let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+            cpu.quirks.wrap_memory = wrap;
+            for pc in [usize::MAX, usize::MAX - 1, 0xFFFF, 0x10000] {
+                cpu.memory_position = pc;
+                cpu.i = u16::MAX;
+                let _ = cpu.run_bounded(100);
+            }
+        }
+        let mut cpu = CPU::new();
+        cpu.memory_position = usize::MAX;
+        assert_eq!(
+            cpu.run_bounded(1).map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: usize::MAX,
+                len: 2
+            })
+        );
+    }
+
+    #[test]
     fn stack_underflow() {
         let mut cpu = CPU::new();
 
@@ -274,7 +1339,10 @@ mod tests {
         mem[0x000] = 0x00;
         mem[0x001] = 0xEE;
 
-        cpu.run();
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::StackUnderflow)
+        );
     }
 
     #[test]
@@ -285,7 +1353,7 @@ mod tests {
         mem[0x000] = 0x12;
         mem[0x001] = 0x22;
 
-        cpu.run();
+        cpu.run().unwrap();
         // 0x222 + 2 because of the last run
         assert_eq!(cpu.memory_position, 0x224);
     }
@@ -300,7 +1368,7 @@ mod tests {
         mem[0x000] = 0x30;
         mem[0x001] = 0x05;
 
-        cpu.run();
+        cpu.run().unwrap();
         // 0x004 + 2 because of the last run
         assert_eq!(cpu.memory_position, 0x006);
     }
@@ -316,7 +1384,7 @@ mod tests {
         mem[0x000] = 0x50;
         mem[0x001] = 0x10;
 
-        cpu.run();
+        cpu.run().unwrap();
         // 0x004 + 2 because of the last run
         assert_eq!(cpu.memory_position, 0x006);
     }
@@ -332,11 +1400,29 @@ mod tests {
         mem[0x000] = 0x40;
         mem[0x001] = 0x10;
 
-        cpu.run();
+        cpu.run().unwrap();
         // 0x004 + 2 because of the last run
         assert_eq!(cpu.memory_position, 0x006);
     }
 
+    #[test]
+    fn skip_if_registers_differ() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 5;
+        cpu.registers[1] = 10;
+        cpu.registers[2] = 5;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x90;
+        mem[0x001] = 0x20; // equal, no skip
+        mem[0x002] = 0x90;
+        mem[0x003] = 0x10; // differ, skips the halt at 0x004
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.memory_position, 0x008);
+    }
+
     #[test]
     fn load_to_register() {
         let mut cpu = CPU::new();
@@ -349,7 +1435,7 @@ mod tests {
         mem[0x000] = 0x60;
         mem[0x001] = 0x0A;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 10);
         assert_eq!(cpu.registers[1], 5);
     }
@@ -365,7 +1451,7 @@ mod tests {
         mem[0x000] = 0x80;
         mem[0x001] = 0x10;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 15);
         assert_eq!(cpu.registers[1], 15);
     }
@@ -380,22 +1466,68 @@ mod tests {
         mem[0x000] = 0x70;
         mem[0x001] = 0x0A;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 15);
     }
 
     #[test]
-    fn or() {
-        let mut cpu = CPU::new();
+    fn add_immediate_wraps_without_touching_flag() {
+        for (start, nn, expected) in [(0xFF, 0x01, 0x00), (0x80, 0xFF, 0x7F), (0xFE, 0x01, 0xFF)] {
+            let mut cpu = CPU::new();
+            cpu.registers[0] = start;
+            cpu.registers[0xF] = 0x42;
+            cpu.memory[..2].copy_from_slice(&[0x70, nn]);
+
+            cpu.run().unwrap();
+            assert_eq!(cpu.registers[0], expected, "{:#04x} + {:#04x}", start, nn);
+            assert_eq!(cpu.registers[0xF], 0x42);
+        }
+    }
 
-        cpu.registers[0] = 5;
+    #[test]
+    fn arithmetic_flags_at_the_overflow_boundary() {
+        // (opcode, VX, VY, result, VF)
+        let cases = [
+            (0x8014, 0xFF, 0x01, 0x00, 1),
+            (0x8014, 0xFE, 0x01, 0xFF, 0),
+            (0x8014, 0xFF, 0xFF, 0xFE, 1),
+            (0x8015, 0x01, 0x01, 0x00, 1),
+            (0x8015, 0x00, 0x01, 0xFF, 0),
+            (0x8015, 0x00, 0xFF, 0x01, 0),
+            (0x8017, 0x01, 0x01, 0x00, 1),
+            (0x8017, 0x01, 0x00, 0xFF, 0),
+            (0x8017, 0xFF, 0x00, 0x01, 0),
+        ];
+        for (opcode, vx, vy, result, flag) in cases {
+            let mut cpu = CPU::new();
+            cpu.registers[0] = vx;
+            cpu.registers[1] = vy;
+            cpu.memory[..2].copy_from_slice(&u16::to_be_bytes(opcode));
+
+            cpu.run().unwrap();
+            assert_eq!(
+                (cpu.registers[0], cpu.registers[0xF]),
+                (result, flag),
+                "{:04X} with {:#04x}, {:#04x}",
+                opcode,
+                vx,
+                vy
+            );
+        }
+    }
+
+    #[test]
+    fn or() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 5;
         cpu.registers[1] = 15;
 
         let mem = &mut cpu.memory;
         mem[0x000] = 0x80;
         mem[0x001] = 0x11;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 5 | 15);
     }
 
@@ -410,7 +1542,7 @@ mod tests {
         mem[0x000] = 0x80;
         mem[0x001] = 0x12;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 5 & 15);
     }
 
@@ -425,7 +1557,1344 @@ mod tests {
         mem[0x000] = 0x80;
         mem[0x001] = 0x13;
 
-        cpu.run();
+        cpu.run().unwrap();
         assert_eq!(cpu.registers[0], 5 ^ 15);
     }
+
+    #[test]
+    fn registered_handler_runs_for_reserved_opcode() {
+        let mut cpu = CPU::new();
+        cpu.register_opcode_handler(0xF000, 0x0000, |cpu, opcode| {
+            cpu.registers[0xA] = (opcode & 0x00FF) as u8;
+        });
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x01;
+        mem[0x001] = 0x2A;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xA], 0x2A);
+    }
+
+    #[test]
+    fn registered_handler_does_not_shadow_known_opcodes() {
+        let mut cpu = CPU::new();
+        cpu.register_opcode_handler(0xF000, 0x6000, |cpu, _| {
+            cpu.registers[0] = 0xFF;
+        });
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x60;
+        mem[0x001] = 0x0A;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 10);
+    }
+
+    #[test]
+    fn unregistered_reserved_opcode_is_still_unknown() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x01;
+        mem[0x001] = 0x23;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::UnknownOpcode(0x0123))
+        );
+    }
+
+    /// Adds NNN's low byte to V0.
+    fn syscall(cpu: &mut CPU, addr: u16) {
+        cpu.registers[0] = cpu.registers[0].wrapping_add(addr as u8);
+    }
+
+    #[test]
+    fn machine_routine_calls_go_to_the_syscall_handler() {
+        // SYS 0x123; CLS; SYS 0x001; then 0000 halts
+        let mut cpu = CPU::new();
+        cpu.memory[..6].copy_from_slice(&[0x01, 0x23, 0x00, 0xE0, 0x00, 0x01]);
+        cpu.set_syscall_handler(Some(syscall));
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(cpu.registers[0], 0x24);
+        assert_eq!(cpu.memory_position, 0x008);
+    }
+
+    #[test]
+    fn zero_is_a_syscall_unless_it_halts() {
+        let mut cpu = CPU::new();
+        cpu.halt_on_zero = false;
+        assert_eq!(
+            cpu.step().map_err(|fault| fault.error),
+            Err(Chip8Error::UnknownOpcode(0x0000))
+        );
+
+        cpu.set_syscall_handler(Some(syscall));
+        cpu.memory_position = 0;
+        cpu.registers[0] = 7;
+        assert_eq!(cpu.run_for(3), Ok(Status::Continue));
+        assert_eq!((cpu.registers[0], cpu.memory_position), (7, 6));
+    }
+
+    #[test]
+    fn sub_without_borrow_sets_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 15;
+        cpu.registers[1] = 5;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x15;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 10);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn sub_equal_registers_does_not_borrow() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 5;
+        cpu.registers[1] = 5;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x15;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 0);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn sub_with_borrow_clears_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 5;
+        cpu.registers[1] = 15;
+        cpu.registers[0xF] = 1;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x15;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 5u8.wrapping_sub(15));
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn subn_without_borrow_sets_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 5;
+        cpu.registers[1] = 15;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x17;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 10);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn subn_with_borrow_clears_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 15;
+        cpu.registers[1] = 5;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x17;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 5u8.wrapping_sub(15));
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn shift_right_stores_lowest_bit_in_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0b0000_0101;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x06;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn shift_left_stores_highest_bit_in_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0b1000_0001;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x0E;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn flag_result_wins_when_vf_is_the_target() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0xF] = 5;
+        cpu.registers[1] = 15;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x8F;
+        mem[0x001] = 0x15;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn load_i() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xA3;
+        mem[0x001] = 0x21;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i, 0x321);
+    }
+
+    #[test]
+    fn add_register_to_i() {
+        let mut cpu = CPU::new();
+
+        cpu.i = 0x300;
+        cpu.registers[2] = 0x10;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF2;
+        mem[0x001] = 0x1E;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i, 0x310);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn i_points_to_font_digit() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0x0B;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x29;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i as usize, FONT_ADDRESS + 0x0B * GLYPH_SIZE);
+        let glyph = &cpu.memory[cpu.i as usize..cpu.i as usize + GLYPH_SIZE];
+        assert_eq!(glyph, &[0xE0, 0x90, 0xE0, 0x90, 0xE0]);
+    }
+
+    #[test]
+    fn store_and_load_registers() {
+        let mut cpu = CPU::new();
+
+        cpu.i = 0x300;
+        cpu.registers[0] = 1;
+        cpu.registers[1] = 2;
+        cpu.registers[2] = 3;
+        cpu.registers[3] = 4;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF2;
+        mem[0x001] = 0x55; // store V0..V2
+        mem[0x002] = 0x60;
+        mem[0x003] = 0x00; // V0 = 0
+        mem[0x004] = 0x61;
+        mem[0x005] = 0x00; // V1 = 0
+        mem[0x006] = 0xF1;
+        mem[0x007] = 0x65; // load V0..V1
+
+        cpu.run().unwrap();
+        assert_eq!(&cpu.memory[0x300..0x304], &[1, 2, 3, 0]);
+        assert_eq!(cpu.registers[0], 1);
+        assert_eq!(cpu.registers[1], 2);
+        assert_eq!(cpu.i, 0x300);
+    }
+
+    #[test]
+    fn store_and_load_increment_i_with_quirk() {
+        let mut cpu = CPU::new();
+        cpu.quirks.load_store_increments_i = true;
+
+        cpu.i = 0x300;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF2;
+        mem[0x001] = 0x55;
+        mem[0x002] = 0xF0;
+        mem[0x003] = 0x65;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i, 0x304);
+    }
+
+    #[test]
+    fn draw_sprite_from_i() {
+        let mut cpu = CPU::new();
+
+        cpu.i = 0x300;
+        cpu.registers[0] = 2;
+        cpu.registers[1] = 3;
+        cpu.memory[0x300] = 0b1100_0000;
+        cpu.memory[0x301] = 0b0011_0000;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x12;
+
+        cpu.run().unwrap();
+        assert!(cpu.display.get(2, 3));
+        assert!(cpu.display.get(3, 3));
+        assert!(cpu.display.get(4, 4));
+        assert!(cpu.display.get(5, 4));
+        assert!(!cpu.display.get(2, 4));
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn draw_sprite_collision_sets_flag() {
+        let mut cpu = CPU::new();
+
+        cpu.i = 0x300;
+        cpu.memory[0x300] = 0xFF;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x01;
+        mem[0x002] = 0xD0;
+        mem[0x003] = 0x01;
+
+        cpu.run().unwrap();
+        assert!(!cpu.display.get(0, 0));
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn clear_screen() {
+        let mut cpu = CPU::new();
+        cpu.display.draw_sprite(0, 0, &[0xFF]);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xE0;
+
+        cpu.run().unwrap();
+        assert!(cpu.display.pixels().iter().all(|p| !p));
+    }
+
+    #[test]
+    fn skip_if_key_pressed() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0x5;
+        cpu.set_key(0x5, true);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xE0;
+        mem[0x001] = 0x9E;
+
+        cpu.run().unwrap();
+        // 0x004 + 2 because of the last run
+        assert_eq!(cpu.memory_position, 0x006);
+    }
+
+    #[test]
+    fn no_skip_if_key_not_pressed() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0x5;
+        cpu.set_key(0x6, true);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xE0;
+        mem[0x001] = 0x9E;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.memory_position, 0x004);
+    }
+
+    #[test]
+    fn skip_if_key_not_pressed() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0x5;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xE0;
+        mem[0x001] = 0xA1;
+
+        cpu.run().unwrap();
+        // 0x004 + 2 because of the last run
+        assert_eq!(cpu.memory_position, 0x006);
+    }
+
+    #[test]
+    fn wait_for_key_blocks_until_pressed() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF3;
+        mem[0x001] = 0x0A;
+
+        assert_eq!(cpu.run(), Ok(Status::WaitingForKey));
+        assert!(cpu.is_waiting_for_key());
+        assert_eq!(cpu.memory_position, 0x002);
+
+        assert_eq!(cpu.run(), Ok(Status::WaitingForKey));
+
+        cpu.set_key(0xC, true);
+        assert!(!cpu.is_waiting_for_key());
+        assert_eq!(cpu.registers[3], 0xC);
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        // 0x002 + 2 because of the last run
+        assert_eq!(cpu.memory_position, 0x004);
+    }
+
+    #[test]
+    fn set_and_read_delay_timer() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 10;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x15; // DT = V0
+        mem[0x002] = 0xF1;
+        mem[0x003] = 0x07; // V1 = DT
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.delay_timer, 10);
+        assert_eq!(cpu.registers[1], 10);
+    }
+
+    #[test]
+    fn set_sound_timer() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[4] = 3;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF4;
+        mem[0x001] = 0x18;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.sound_timer, 3);
+        assert!(cpu.is_beeping());
+    }
+
+    #[test]
+    fn timers_count_down_to_zero() {
+        let mut cpu = CPU::new();
+        cpu.delay_timer = 2;
+        cpu.sound_timer = 1;
+
+        cpu.tick_timers();
+        assert_eq!(cpu.delay_timer, 1);
+        assert_eq!(cpu.sound_timer, 0);
+        assert!(!cpu.is_beeping());
+
+        cpu.tick_timers();
+        cpu.tick_timers();
+        assert_eq!(cpu.delay_timer, 0);
+        assert_eq!(cpu.sound_timer, 0);
+    }
+
+    #[test]
+    fn new_cpu_has_font_loaded() {
+        let cpu = CPU::new();
+
+        assert_eq!(
+            &cpu.memory[FONT_ADDRESS..FONT_ADDRESS + FONT_SET.len()],
+            &FONT_SET
+        );
+    }
+
+    #[test]
+    fn draw_font_digit() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0] = 0x1;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x29; // I = sprite for digit 1
+        mem[0x002] = 0xD1;
+        mem[0x003] = 0x15; // draw it at (0, 0)
+
+        cpu.run().unwrap();
+        // 0x20 == ..X.
+        assert!(!cpu.display.get(1, 0));
+        assert!(cpu.display.get(2, 0));
+        // 0x70 == .XXX
+        assert!(cpu.display.get(1, 4));
+        assert!(cpu.display.get(3, 4));
+    }
+
+    #[test]
+    fn load_rom_copies_program_and_font() {
+        let mut cpu = CPU::new();
+
+        cpu.load_rom(&[0x60, 0x0A]).unwrap();
+
+        assert_eq!(cpu.memory_position, PROGRAM_START);
+        assert_eq!(&cpu.memory[PROGRAM_START..PROGRAM_START + 2], &[0x60, 0x0A]);
+        assert_eq!(
+            &cpu.memory[FONT_ADDRESS..FONT_ADDRESS + FONT_SET.len()],
+            &FONT_SET
+        );
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 10);
+    }
+
+    #[test]
+    fn load_rom_replaces_previous_program() {
+        let mut cpu = CPU::new();
+
+        cpu.load_rom(&[0x60, 0x0A, 0x61, 0x0B]).unwrap();
+        cpu.load_rom(&[0x60, 0x0C]).unwrap();
+
+        assert_eq!(
+            &cpu.memory[PROGRAM_START..PROGRAM_START + 4],
+            &[0x60, 0x0C, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn load_rom_rejects_oversized_program() {
+        let mut cpu = CPU::new();
+        let rom = vec![0; 0x1000 - PROGRAM_START + 1];
+
+        let err = cpu.load_rom(&rom).unwrap_err();
+
+        assert!(matches!(
+            err,
+            RomError::TooLarge {
+                size: 0xE01,
+                max: 0xE00
+            }
+        ));
+    }
+
+    #[test]
+    fn load_rom_from_path_reads_file() {
+        let path = std::env::temp_dir().join("chip8_load_rom_from_path.ch8");
+        std::fs::write(&path, [0x61, 0x07]).unwrap();
+        let mut cpu = CPU::new();
+
+        cpu.load_rom_from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&cpu.memory[PROGRAM_START..PROGRAM_START + 2], &[0x61, 0x07]);
+    }
+
+    #[test]
+    fn load_rom_from_missing_path_fails() {
+        let mut cpu = CPU::new();
+
+        let err = cpu.load_rom_from_path("does/not/exist.ch8").unwrap_err();
+
+        assert!(matches!(err, RomError::Io(_)));
+    }
+
+    fn reset_test_cpu() -> CPU {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+        // V0 = 9; save V0 as RPL flag; store V0 over the first opcode; draw
+        cpu.load_rom(&[0x60, 0x09, 0xF0, 0x75, 0xA2, 0x00, 0xF0, 0x55, 0xD0, 0x01])
+            .unwrap();
+        cpu.run_for(5).unwrap();
+        cpu.delay_timer = 10;
+        cpu
+    }
+
+    #[test]
+    fn soft_reset_restarts_program_and_keeps_rpl_flags() {
+        let mut cpu = reset_test_cpu();
+        assert_eq!(cpu.memory[0x200], 0x09);
+
+        cpu.soft_reset();
+
+        assert_eq!(cpu.memory_position, PROGRAM_START);
+        assert_eq!(cpu.memory[0x200], 0x60);
+        assert_eq!(cpu.registers[0], 0);
+        assert_eq!(cpu.delay_timer, 0);
+        assert!(cpu.display.pixels().iter().all(|p| !p));
+        assert_eq!(cpu.rpl_flags[0], 9);
+        assert_eq!(cpu.mode, EmulatorMode::SuperChip);
+    }
+
+    #[test]
+    fn hard_reset_clears_rpl_flags_too() {
+        let mut cpu = reset_test_cpu();
+
+        cpu.hard_reset();
+
+        assert_eq!(cpu.memory[0x200], 0x60);
+        assert_eq!(cpu.rpl_flags, [0; 16]);
+        assert_eq!(cpu.memory[FONT_ADDRESS], FONT_SET[0]);
+        assert_eq!(cpu.run_for(5), Ok(Status::Continue));
+    }
+
+    #[test]
+    fn random_value_is_masked() {
+        let mut cpu = CPU::new();
+        cpu.set_rng(Box::new(|| 0xAB));
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xC0;
+        mem[0x001] = 0x0F;
+        mem[0x002] = 0xC1;
+        mem[0x003] = 0xFF;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 0x0B);
+        assert_eq!(cpu.registers[1], 0xAB);
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        let run_with_seed = |seed| {
+            let mut cpu = CPU::new();
+            cpu.set_rng(Box::new(XorShift::new(seed)));
+            let mem = &mut cpu.memory;
+            mem[0x000] = 0xC0;
+            mem[0x001] = 0xFF;
+            mem[0x002] = 0xC1;
+            mem[0x003] = 0xFF;
+            cpu.run().unwrap();
+            (cpu.registers[0], cpu.registers[1])
+        };
+
+        assert_eq!(run_with_seed(42), run_with_seed(42));
+    }
+
+    #[test]
+    fn xorshift_never_gets_stuck_at_zero() {
+        let mut rng = XorShift::new(0);
+
+        assert!((0..16).any(|_| rng.next_u8() != 0));
+    }
+
+    #[test]
+    fn step_executes_one_instruction() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x60;
+        mem[0x001] = 0x01;
+        mem[0x002] = 0x61;
+        mem[0x003] = 0x02;
+
+        assert_eq!(cpu.step(), Ok(Status::Continue));
+        assert_eq!(cpu.registers[0], 1);
+        assert_eq!(cpu.registers[1], 0);
+        assert_eq!(cpu.memory_position, 0x002);
+
+        assert_eq!(cpu.step(), Ok(Status::Continue));
+        assert_eq!(cpu.registers[1], 2);
+    }
+
+    #[test]
+    fn halt_is_sticky() {
+        let mut cpu = CPU::new();
+
+        assert_eq!(cpu.step(), Ok(Status::Halted));
+        assert_eq!(cpu.step(), Ok(Status::Halted));
+        assert_eq!(cpu.memory_position, 0x002);
+    }
+
+    #[test]
+    fn run_for_stops_after_budget() {
+        let mut cpu = CPU::new();
+
+        // infinite loop: JP 0x000
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x70;
+        mem[0x001] = 0x01;
+        mem[0x002] = 0x10;
+        mem[0x003] = 0x00;
+
+        assert_eq!(cpu.run_for(10), Ok(Status::Continue));
+        assert_eq!(cpu.registers[0], 5);
+    }
+
+    #[test]
+    fn run_for_stops_early_on_halt() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x60;
+        mem[0x001] = 0x01;
+
+        assert_eq!(cpu.run_for(10), Ok(Status::Halted));
+        assert_eq!(cpu.memory_position, 0x004);
+    }
+
+    #[test]
+    fn running_off_the_end_of_memory_is_an_error() {
+        let mut cpu = CPU::new();
+        cpu.memory_position = 0xFFF;
+
+        assert_eq!(
+            cpu.step().map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFF,
+                len: 2
+            })
+        );
+    }
+
+    #[test]
+    fn bcd_stores_decimal_digits() {
+        for (value, digits) in [
+            (0, [0, 0, 0]),
+            (7, [0, 0, 7]),
+            (42, [0, 4, 2]),
+            (255, [2, 5, 5]),
+        ] {
+            let mut cpu = CPU::new();
+            cpu.registers[3] = value;
+            cpu.i = 0x300;
+            cpu.memory[..2].copy_from_slice(&[0xF3, 0x33]);
+
+            cpu.run().unwrap();
+            assert_eq!(cpu.memory[0x300..0x303], digits, "{}", value);
+            assert_eq!(cpu.i, 0x300);
+        }
+    }
+
+    #[test]
+    fn bcd_digits_round_trip_through_registers() {
+        // LD I, 0x300; LD B, V5; LD V2, [I]
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xA3, 0x00, 0xF5, 0x33, 0xF2, 0x65]).unwrap();
+        cpu.registers[5] = 197;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[..3], [1, 9, 7]);
+        assert_eq!(cpu.registers[5], 197);
+    }
+
+    #[test]
+    fn bcd_past_end_of_memory_is_an_error() {
+        let mut cpu = CPU::new();
+        cpu.i = 0xFFE;
+        cpu.memory[..2].copy_from_slice(&[0xF0, 0x33]);
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFE,
+                len: 3
+            })
+        );
+    }
+
+    #[test]
+    fn store_registers_past_end_of_memory_is_an_error() {
+        let mut cpu = CPU::new();
+        cpu.i = 0xFFE;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF3;
+        mem[0x001] = 0x55;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFE,
+                len: 4
+            })
+        );
+        assert_eq!(cpu.memory[0xFFE], 0);
+    }
+
+    #[test]
+    fn draw_past_end_of_memory_is_an_error() {
+        let mut cpu = CPU::new();
+        cpu.i = 0xFFC;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x05;
+
+        assert!(matches!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn lenient_cpus_skip_illegal_instructions() {
+        let mut cpu = CPU::new();
+        cpu.strictness = Strictness::Lenient;
+        cpu.i = 0xFFE;
+        // unknown; LD B, V0 past the end of memory; LD V0, 7
+        cpu.memory[..6].copy_from_slice(&[0x51, 0x21, 0xF0, 0x33, 0x60, 0x07]);
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(cpu.registers[0], 7);
+        let skipped = cpu.take_skipped();
+        let errors: Vec<(usize, Chip8Error)> = skipped
+            .into_iter()
+            .map(|fault| (fault.pc, fault.error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (0x000, Chip8Error::UnknownOpcode(0x5121)),
+                (
+                    0x002,
+                    Chip8Error::MemoryOutOfBounds {
+                        address: 0xFFE,
+                        len: 3
+                    }
+                ),
+            ]
+        );
+        assert!(cpu.take_skipped().is_empty());
+
+        // running off the end leaves nothing to skip to
+        let mut cpu = CPU::new();
+        cpu.strictness = Strictness::Lenient;
+        cpu.memory_position = 0xFFF;
+        assert!(cpu.step().is_err());
+    }
+
+    #[test]
+    fn wrap_memory_quirk_wraps_register_loads() {
+        let mut cpu = CPU::new();
+        cpu.quirks.wrap_memory = true;
+        cpu.i = 0xFFF;
+        cpu.memory[0xFFF] = 0xAA;
+        // LD V1, [I]
+        cpu.memory[0x000..0x002].copy_from_slice(&[0xF1, 0x65]);
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(cpu.registers[..2], [0xAA, 0xF1]);
+    }
+
+    #[test]
+    fn protected_memory_rejects_program_writes() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xA0, 0x50, 0xF0, 0x55]).unwrap();
+        cpu.memory.set_write_protected(true);
+        cpu.registers[0] = 0xFF;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::ProtectedWrite { address: 0x050 })
+        );
+        assert_eq!(cpu.memory[0x050], FONT_SET[0]);
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(Chip8Error::StackOverflow.to_string(), "Stack overflow");
+        assert_eq!(
+            Chip8Error::UnknownOpcode(0x5121).to_string(),
+            "unknown opcode 5121"
+        );
+    }
+
+    #[test]
+    fn fault_reports_instruction_frame_and_history() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x51, 0x21]).unwrap();
+        cpu.tick_timers();
+
+        let fault = cpu.run().unwrap_err();
+
+        assert_eq!(fault.error, Chip8Error::UnknownOpcode(0x5121));
+        assert_eq!(fault.pc, 0x204);
+        assert_eq!(fault.opcode, Some(0x5121));
+        assert_eq!(fault.frame, 1);
+        assert_eq!(fault.history, [0x200, 0x202]);
+        assert_eq!(
+            fault.to_string(),
+            "unknown opcode 5121\n  at 0x204: 5121  DW 0x5121\n  frame 1, after 0x200 -> 0x202"
+        );
+    }
+
+    #[test]
+    fn fault_history_keeps_the_latest_addresses() {
+        let mut cpu = CPU::new();
+        let mut rom = [0x70, 0x01].repeat(10);
+        rom.extend([0x51, 0x21]);
+        cpu.load_rom(&rom).unwrap();
+
+        let fault = cpu.run().unwrap_err();
+
+        let latest: Vec<usize> = (0x204..0x214).step_by(2).collect();
+        assert_eq!(fault.history, latest);
+    }
+
+    #[test]
+    fn shift_uses_vy_with_quirk() {
+        let mut cpu = CPU::new_with_quirks(Quirks::cosmac_vip());
+
+        cpu.registers[0] = 0xFF;
+        cpu.registers[1] = 0b0000_0110;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x16; // V0 = V1 >> 1
+        mem[0x002] = 0x82;
+        mem[0x003] = 0x1E; // V2 = V1 << 1
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 0b0000_0011);
+        assert_eq!(cpu.registers[2], 0b0000_1100);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn logic_ops_reset_vf_with_quirk() {
+        let mut cpu = CPU::new_with_quirks(Quirks::cosmac_vip());
+
+        cpu.registers[0xF] = 1;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x11;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn logic_ops_keep_vf_by_default() {
+        let mut cpu = CPU::new();
+
+        cpu.registers[0xF] = 1;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x80;
+        mem[0x001] = 0x13;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn sprites_wrap_with_quirk() {
+        let mut cpu = CPU::new_with_quirks(Quirks {
+            wrap_sprites: true,
+            ..Quirks::default()
+        });
+
+        cpu.i = 0x300;
+        cpu.memory[0x300] = 0xFF;
+        cpu.registers[0] = 60;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x11;
+
+        cpu.run().unwrap();
+        assert!(cpu.display.get(63, 0));
+        assert!(cpu.display.get(0, 0));
+        assert!(cpu.display.get(3, 0));
+    }
+
+    #[test]
+    fn jump_with_offset_adds_v0() {
+        // JP V0, 0x200 lands on 0x204: LD VA, 1
+        let mut cpu = CPU::new();
+        cpu.memory[..2].copy_from_slice(&[0xB2, 0x00]);
+        cpu.memory[0x204..0x206].copy_from_slice(&[0x6A, 0x01]);
+        cpu.memory[0x208..0x20A].copy_from_slice(&[0x6A, 0x02]);
+        cpu.registers[0] = 4;
+        cpu.registers[2] = 8;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xA], 1);
+    }
+
+    #[test]
+    fn jump_with_offset_quirk_adds_vx() {
+        // JP V2, 0x200 lands on 0x208: LD VA, 2
+        let mut cpu = CPU::new_with_quirks(Quirks::super_chip());
+        cpu.memory[..2].copy_from_slice(&[0xB2, 0x00]);
+        cpu.memory[0x204..0x206].copy_from_slice(&[0x6A, 0x01]);
+        cpu.memory[0x208..0x20A].copy_from_slice(&[0x6A, 0x02]);
+        cpu.registers[0] = 4;
+        cpu.registers[2] = 8;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xA], 2);
+    }
+
+    #[test]
+    fn quirk_presets() {
+        assert!(Quirks::cosmac_vip().load_store_increments_i);
+        assert!(!Quirks::chip48().shift_uses_vy);
+        assert!(Quirks::super_chip().jump_uses_vx);
+        assert_eq!(CPU::new().quirks, Quirks::default());
+        assert_eq!(Quirks::preset("schip"), Some(Quirks::super_chip()));
+        assert_eq!(Quirks::preset("vip"), Some(Quirks::cosmac_vip()));
+        assert_eq!(Quirks::preset("s-chip"), None);
+    }
+
+    #[test]
+    fn super_chip_opcodes_are_unknown_in_chip8_mode() {
+        let mut cpu = CPU::new();
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xFF;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::UnknownOpcode(0x00FF))
+        );
+        assert!(!cpu.display.is_hires());
+    }
+
+    #[test]
+    fn super_chip_mode_uses_super_chip_quirks() {
+        let cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        assert_eq!(cpu.mode, EmulatorMode::SuperChip);
+        assert_eq!(cpu.quirks, Quirks::super_chip());
+    }
+
+    #[test]
+    fn switch_resolution() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xFF;
+
+        cpu.run().unwrap();
+        assert!(cpu.display.is_hires());
+        assert_eq!(cpu.display.width(), 128);
+
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+        cpu.display.set_hires(true);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xFE;
+
+        cpu.run().unwrap();
+        assert!(!cpu.display.is_hires());
+    }
+
+    #[test]
+    fn exit_halts() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xFD;
+        mem[0x002] = 0x60;
+        mem[0x003] = 0x01;
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(cpu.registers[0], 0);
+        assert_eq!(cpu.step(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn scroll_opcodes() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+        cpu.display.draw_sprite(8, 0, &[0x80]);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x00;
+        mem[0x001] = 0xC3; // down 3
+        mem[0x002] = 0x00;
+        mem[0x003] = 0xFB; // right 4
+        mem[0x004] = 0x00;
+        mem[0x005] = 0xFC; // left 4
+        mem[0x006] = 0x00;
+        mem[0x007] = 0xFC; // left 4
+
+        cpu.run().unwrap();
+        assert!(cpu.display.get(4, 3));
+        assert_eq!(cpu.display.pixels().iter().filter(|p| **p).count(), 1);
+    }
+
+    #[test]
+    fn draw_large_sprite() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+        cpu.display.set_hires(true);
+
+        cpu.i = 0x300;
+        cpu.memory[0x300..0x320].fill(0xFF);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x10;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.display.pixels().iter().filter(|p| **p).count(), 256);
+        assert!(cpu.display.get(15, 15));
+        assert!(!cpu.display.get(16, 15));
+    }
+
+    /// Draws a 4-row block at (0, 0), then again at (0, V2).
+    fn overlapping_blocks(mode: EmulatorMode, hires: bool, v2: u8) -> CPU {
+        let mut cpu = CPU::new_with_mode(mode);
+        cpu.display.set_hires(hires);
+        cpu.registers[2] = v2;
+
+        cpu.i = 0x300;
+        cpu.memory[0x300..0x304].fill(0xFF);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xD0;
+        mem[0x001] = 0x04;
+        mem[0x002] = 0xD0;
+        mem[0x003] = 0x24;
+
+        cpu.run().unwrap();
+        cpu
+    }
+
+    #[test]
+    fn hires_collision_counts_rows() {
+        let cpu = overlapping_blocks(EmulatorMode::SuperChip, true, 1);
+        assert_eq!(cpu.registers[0xF], 3);
+    }
+
+    #[test]
+    fn hires_collision_counts_clipped_rows() {
+        // rows 62 and 63 are drawn, the other two fall off the bottom
+        let cpu = overlapping_blocks(EmulatorMode::SuperChip, true, 62);
+        assert_eq!(cpu.registers[0xF], 2);
+    }
+
+    #[test]
+    fn collision_is_a_flag_in_lores_and_without_quirk() {
+        let cpu = overlapping_blocks(EmulatorMode::SuperChip, false, 1);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let cpu = overlapping_blocks(EmulatorMode::XoChip, true, 1);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn big_font_digit() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        cpu.registers[0] = 0x3;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x30;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i as usize, BIG_FONT_ADDRESS + 3 * BIG_GLYPH_SIZE);
+        assert_eq!(cpu.memory[cpu.i as usize], 0x3C);
+    }
+
+    #[test]
+    fn rpl_flags_round_trip() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        cpu.registers[0] = 7;
+        cpu.registers[1] = 8;
+        cpu.registers[2] = 9;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF2;
+        mem[0x001] = 0x75; // save V0..V2
+        mem[0x002] = 0x60;
+        mem[0x003] = 0x00;
+        mem[0x004] = 0x61;
+        mem[0x005] = 0x00;
+        mem[0x006] = 0xF1;
+        mem[0x007] = 0x85; // restore V0..V1
+
+        cpu.run().unwrap();
+        assert_eq!(&cpu.rpl_flags[..3], &[7, 8, 9]);
+        assert_eq!(cpu.registers[0], 7);
+        assert_eq!(cpu.registers[1], 8);
+    }
+
+    #[test]
+    fn xo_chip_has_extended_memory_and_long_load() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+        assert_eq!(cpu.memory.len(), 0x10000);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x00;
+        mem[0x002] = 0xBE;
+        mem[0x003] = 0xEF; // I := 0xBEEF
+        mem[0x004] = 0x60;
+        mem[0x005] = 0x2A;
+        mem[0x006] = 0xF0;
+        mem[0x007] = 0x55;
+        mem[0x008] = 0x00;
+        mem[0x009] = 0x00;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.memory[0xBEEF], 0x2A);
+        assert_eq!(cpu.i, 0xBEF0);
+    }
+
+    #[test]
+    fn skip_jumps_over_long_instructions() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x30;
+        mem[0x001] = 0x00; // V0 == 0, skip
+        mem[0x002] = 0xF0;
+        mem[0x003] = 0x00;
+        mem[0x004] = 0x12;
+        mem[0x005] = 0x34;
+        mem[0x006] = 0x00;
+        mem[0x007] = 0x00;
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.i, 0);
+        assert_eq!(cpu.memory_position, 0x008);
+
+        let mut cpu = CPU::new_with_mode(EmulatorMode::MegaChip);
+        // Mega-Chip on, skip LDHI 0x121E00, whose second half is JP 0xE00
+        cpu.load_rom(&[0x00, 0x11, 0x30, 0x00, 0x01, 0x12, 0x1E, 0x00])
+            .unwrap();
+        cpu.run().unwrap();
+        assert_eq!(cpu.i, 0);
+        assert_eq!(cpu.memory_position, 0x20A);
+    }
+
+    #[test]
+    fn save_and_load_register_ranges() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+        cpu.registers[1] = 1;
+        cpu.registers[2] = 2;
+        cpu.registers[3] = 3;
+        cpu.i = 0x300;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x51;
+        mem[0x001] = 0x32; // save V1..V3
+        mem[0x002] = 0x5C;
+        mem[0x003] = 0xA3; // load VC..VA
+
+        cpu.run_for(2).unwrap();
+        assert_eq!(&cpu.memory[0x300..0x303], &[1, 2, 3]);
+        assert_eq!(&cpu.registers[0xA..=0xC], &[3, 2, 1]);
+        assert_eq!(cpu.i, 0x300);
+    }
+
+    #[test]
+    fn plane_select_draws_on_both_planes() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+        cpu.i = 0x300;
+        cpu.memory[0x300] = 0x80;
+        cpu.memory[0x301] = 0xC0;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF3;
+        mem[0x001] = 0x01; // plane 3
+        mem[0x002] = 0xD0;
+        mem[0x003] = 0x01;
+
+        cpu.run_for(2).unwrap();
+        assert_eq!(cpu.display.color(0, 0), 0b11);
+        assert_eq!(cpu.display.color(1, 0), 0b10);
+    }
+
+    #[test]
+    fn audio_pattern_and_pitch() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::XoChip);
+        assert_eq!(cpu.audio_playback_rate(), 4000.0);
+        cpu.i = 0x300;
+        cpu.memory[0x300..0x310].fill(0xAA);
+        cpu.registers[0] = DEFAULT_PITCH + 48;
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0xF0;
+        mem[0x001] = 0x02;
+        mem[0x002] = 0xF0;
+        mem[0x003] = 0x3A;
+
+        cpu.run_for(2).unwrap();
+        assert_eq!(cpu.audio_pattern, [0xAA; 16]);
+        assert_eq!(cpu.audio_playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn mega_chip_draws_indexed_sprites() {
+        use Instruction::*;
+
+        let mut cpu = CPU::new_with_mode(EmulatorMode::MegaChip);
+        let program = encode(&[
+            MegaOn,
+            LdIHuge(0x300),
+            LdPalette(2),
+            LdIHuge(0x308),
+            SpriteWidth(2),
+            SpriteHeight(1),
+            Drw { x: 0, y: 1, n: 0 },
+            Drw { x: 0, y: 1, n: 0 },
+            LdIHuge(0x01_0000),
+        ]);
+        cpu.memory[..program.len()].copy_from_slice(&program);
+        cpu.memory[0x300..0x308].copy_from_slice(&[0xFF, 0xFF, 0, 0, 0xFF, 0, 0xFF, 0]);
+        cpu.memory[0x308..0x30A].copy_from_slice(&[1, 2]);
+        cpu.registers[1] = 100;
+
+        cpu.run_for(7).unwrap();
+        assert_eq!((cpu.display.width(), cpu.display.height()), (256, 192));
+        let mega = cpu.display.mega().unwrap();
+        assert_eq!((mega.rgb(0, 100), mega.rgb(1, 100)), (0xFF0000, 0x00FF00));
+        assert_eq!(cpu.registers[0xF], 0);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers[0xF], 1);
+        assert_eq!(
+            cpu.step().map_err(|fault| fault.error),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0x01_0000,
+                len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn xo_chip_opcodes_are_unknown_in_other_modes() {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+
+        let mem = &mut cpu.memory;
+        mem[0x000] = 0x51;
+        mem[0x001] = 0x32;
+
+        assert_eq!(
+            cpu.run().map_err(|fault| fault.error),
+            Err(Chip8Error::UnknownOpcode(0x5132))
+        );
+    }
 }
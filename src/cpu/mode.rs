@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use super::Quirks;
+
+/// Which member of the CHIP-8 family the CPU emulates. Extended
+/// instructions are only decoded in modes that define them, so plain
+/// CHIP-8 programs see exactly the original instruction set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EmulatorMode {
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: hires mode, scrolling, 16x16 sprites, big font and
+    /// RPL flags.
+    SuperChip,
+    /// Octo's XO-CHIP: everything SUPER-CHIP has plus 64 KiB of memory, a
+    /// second display plane, programmable audio and the long `I := NNNN`.
+    XoChip,
+    /// Mega-Chip, as far as its demos need it: SUPER-CHIP plus a 256x192
+    /// screen of palette-indexed pixels and 64 KiB of memory. Sound,
+    /// alpha and blend opcodes are accepted and ignored.
+    MegaChip,
+}
+
+impl EmulatorMode {
+    /// The quirks programs written for this mode usually expect.
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            EmulatorMode::Chip8 => Quirks::default(),
+            EmulatorMode::SuperChip => Quirks::super_chip(),
+            EmulatorMode::XoChip => Quirks::xo_chip(),
+            EmulatorMode::MegaChip => Quirks::super_chip(),
+        }
+    }
+
+    pub fn has_super_chip(self) -> bool {
+        matches!(
+            self,
+            EmulatorMode::SuperChip | EmulatorMode::XoChip | EmulatorMode::MegaChip
+        )
+    }
+
+    pub fn has_xo_chip(self) -> bool {
+        matches!(self, EmulatorMode::XoChip)
+    }
+
+    pub fn has_mega_chip(self) -> bool {
+        matches!(self, EmulatorMode::MegaChip)
+    }
+
+    /// Bytes of addressable memory.
+    pub fn memory_size(self) -> usize {
+        if self.has_xo_chip() || self.has_mega_chip() {
+            0x10000
+        } else {
+            0x1000
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Behaviors that differ between CHIP-8 interpreters. The defaults follow
+/// the CHIP-48 / SUPER-CHIP lineage that most ROMs in circulation expect,
+/// except for the BNNN jump which keeps its original V0 form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Quirks {
+    /// 8XY6 / 8XYE shift VY into VX instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX55 / FX65 leave I pointing past the last register transferred
+    /// (`I = I + X + 1`), as the original COSMAC VIP interpreter did.
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to `XNN + VX` (CHIP-48's BXNN) instead of `NNN + V0`.
+    pub jump_uses_vx: bool,
+    /// DXYN wraps sprites around the screen edges instead of clipping them.
+    pub wrap_sprites: bool,
+    /// 8XY1 / 8XY2 / 8XY3 clear VF.
+    pub logic_resets_vf: bool,
+    /// In hires mode DXYN sets VF to the number of sprite rows that
+    /// collided or were clipped at the bottom edge, as SUPER-CHIP 1.1 does,
+    /// instead of just 0 or 1.
+    pub collision_counts_rows: bool,
+    /// Memory accesses past the end wrap around to address 0 instead of
+    /// faulting.
+    pub wrap_memory: bool,
+    /// DXYN ends the frame, the way the COSMAC VIP waited for the vertical
+    /// blank before drawing. Games written for it run slower and flicker
+    /// less. [`super::Accuracy::Cycle`] always waits.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the RCA COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            wrap_sprites: false,
+            logic_resets_vf: true,
+            collision_counts_rows: false,
+            wrap_memory: false,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 for the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            wrap_sprites: false,
+            logic_resets_vf: false,
+            collision_counts_rows: false,
+            wrap_memory: false,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1, which inherited CHIP-48's behavior and reports
+    /// hires collisions per row.
+    pub fn super_chip() -> Self {
+        Quirks {
+            collision_counts_rows: true,
+            ..Self::chip48()
+        }
+    }
+
+    /// Looks a preset up by the short name used on command lines: `vip`,
+    /// `chip48`, `schip` or `xo`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "vip" | "cosmac" => Some(Self::cosmac_vip()),
+            "chip48" => Some(Self::chip48()),
+            "schip" | "superchip" => Some(Self::super_chip()),
+            "xo" | "xochip" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo.
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            wrap_sprites: true,
+            logic_resets_vf: false,
+            collision_counts_rows: false,
+            wrap_memory: false,
+            display_wait: false,
+        }
+    }
+}
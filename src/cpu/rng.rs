@@ -0,0 +1,78 @@
+/// Source of the random bytes consumed by CXNN.
+///
+/// Any `FnMut() -> u8` closure is a `RandomSource`, which keeps test doubles
+/// short: `cpu.set_rng(Box::new(|| 0xAA))`.
+pub trait RandomSource {
+    fn next_u8(&mut self) -> u8;
+
+    /// The generator's state, for [`super::SaveState`] to capture so a
+    /// restored machine draws the same numbers. Only [`XorShift`] has one
+    /// that can be captured; other sources return `None` and are left in
+    /// place by [`super::CPU::load_state`].
+    fn state(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl<F: FnMut() -> u8> RandomSource for F {
+    fn next_u8(&mut self) -> u8 {
+        self()
+    }
+}
+
+/// Small xorshift32 generator, the default random source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    /// A zero seed would make xorshift stick at zero, so it is replaced.
+    pub fn new(seed: u32) -> Self {
+        XorShift {
+            state: if seed == 0 { 0x2545_F491 } else { seed },
+        }
+    }
+
+    /// Seeds from the system clock, for frontends that want a different
+    /// sequence on every run. On `wasm32-unknown-unknown` and without
+    /// `std`, where there is no clock, the seed is fixed and the program
+    /// embedding the CPU should pass its own.
+    #[cfg(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    ))]
+    pub fn from_time() -> Self {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() ^ d.as_secs() as u32)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    #[cfg(not(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    )))]
+    pub fn from_time() -> Self {
+        Self::new(0)
+    }
+}
+
+impl RandomSource for XorShift {
+    fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        (x >> 24) as u8
+    }
+
+    /// Never zero, so [`XorShift::new`] picks up exactly where this left
+    /// off.
+    fn state(&self) -> Option<u32> {
+        Some(self.state)
+    }
+}
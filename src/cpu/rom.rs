@@ -0,0 +1,43 @@
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+/// Address programs are loaded at and start executing from.
+pub const PROGRAM_START: usize = 0x200;
+
+#[derive(Debug)]
+pub enum RomError {
+    /// The program does not fit between [`PROGRAM_START`] and the end of memory.
+    TooLarge { size: usize, max: usize },
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::TooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} bytes fit", size, max)
+            }
+            #[cfg(feature = "std")]
+            RomError::Io(err) => write!(f, "could not read ROM: {}", err),
+        }
+    }
+}
+
+impl error::Error for RomError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            RomError::Io(err) => Some(err),
+            RomError::TooLarge { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for RomError {
+    fn from(err: io::Error) -> Self {
+        RomError::Io(err)
+    }
+}
@@ -0,0 +1,386 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::{AudioPlayback, EmulatorMode, Quirks, XorShift, CPU};
+use crate::display::{MEGA_HEIGHT, MEGA_WIDTH, PLANES};
+
+/// Bumped whenever [`SaveState`] changes shape, so old snapshots are
+/// rejected instead of being misread.
+pub const SAVE_STATE_VERSION: u32 = 8;
+
+/// Everything needed to resume a program mid-game, see
+/// [`CPU::save_state`]. Together with the keys pressed on each frame
+/// after it, a state replays bit for bit: the random source and the
+/// counters are in it too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub mode: EmulatorMode,
+    pub quirks: Quirks,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub pc: u32,
+    /// Return addresses, innermost last.
+    pub stack: Vec<u16>,
+    /// [`CPU::stack_depth`].
+    pub stack_depth: u32,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub memory: Vec<u8>,
+    pub display: DisplayState,
+    pub rpl_flags: [u8; 16],
+    pub audio_pattern: [u8; 16],
+    pub pitch: u8,
+    /// Sound timer phase and pattern position, so a beep in progress
+    /// resumes seamlessly.
+    pub audio: AudioPlayback,
+    pub waiting_for_key: Option<u8>,
+    pub halted: bool,
+    /// The [`super::XorShift`] state, or `None` if the CPU had another
+    /// [`super::RandomSource`].
+    pub rng: Option<u32>,
+    /// [`CPU::frame`] and [`CPU::instructions`].
+    pub frame: u64,
+    pub instructions: u64,
+    /// Cycles carried into the next frame under [`super::Accuracy::Cycle`].
+    pub cycles: i32,
+}
+
+/// The screen, with each plane packed eight pixels per byte, leftmost
+/// pixel in the high bit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayState {
+    pub hires: bool,
+    pub selected_planes: u8,
+    pub planes: Vec<Vec<u8>>,
+    /// The Mega-Chip screen, while it is on.
+    pub mega: Option<MegaState>,
+}
+
+/// A [`crate::display::MegaScreen`], one palette index per pixel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MegaState {
+    pub pixels: Vec<u8>,
+    pub palette: Vec<u32>,
+    pub sprite_width: u32,
+    pub sprite_height: u32,
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    UnsupportedVersion(u32),
+    /// The snapshot decoded but cannot belong to a real machine, e.g. its
+    /// memory size does not match its mode.
+    Invalid(&'static str),
+    #[cfg(feature = "std")]
+    Json(serde_json::Error),
+    Binary(bincode::error::DecodeError),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported (expected {})",
+                version, SAVE_STATE_VERSION
+            ),
+            StateError::Invalid(reason) => write!(f, "invalid save state: {}", reason),
+            #[cfg(feature = "std")]
+            StateError::Json(err) => write!(f, "could not parse save state: {}", err),
+            StateError::Binary(err) => write!(f, "could not decode save state: {}", err),
+        }
+    }
+}
+
+impl error::Error for StateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            StateError::Json(err) => Some(err),
+            // bincode's errors are only errors with std
+            #[cfg(feature = "std")]
+            StateError::Binary(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SaveState {
+    /// Pretty-printed JSON, handy for inspecting a snapshot by hand.
+    #[cfg(feature = "std")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("save states always serialize")
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_json(json: &str) -> Result<Self, StateError> {
+        serde_json::from_str(json).map_err(StateError::Json)
+    }
+
+    /// Compact bincode encoding for save slots.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .expect("save states always serialize")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let (state, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .map_err(StateError::Binary)?;
+        Ok(state)
+    }
+}
+
+fn pack(pixels: &[bool]) -> Vec<u8> {
+    pixels
+        .chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .enumerate()
+                .fold(0, |acc, (bit, lit)| acc | ((*lit as u8) << (7 - bit)))
+        })
+        .collect()
+}
+
+fn unpack(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+        .collect()
+}
+
+impl CPU {
+    /// Captures the complete machine state.
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            version: SAVE_STATE_VERSION,
+            mode: self.mode,
+            quirks: self.quirks,
+            registers: self.registers,
+            i: self.i,
+            pc: self.memory_position as u32,
+            stack: self.stack.clone(),
+            stack_depth: self.stack_depth as u32,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            memory: self.memory.to_vec(),
+            display: DisplayState {
+                hires: self.display.is_hires(),
+                selected_planes: self.display.selected_planes(),
+                planes: (0..PLANES)
+                    .map(|plane| pack(self.display.plane(plane)))
+                    .collect(),
+                mega: self.display.mega().map(|mega| MegaState {
+                    pixels: mega.pixels().to_vec(),
+                    palette: mega.palette().to_vec(),
+                    sprite_width: mega.sprite_width as u32,
+                    sprite_height: mega.sprite_height as u32,
+                }),
+            },
+            rpl_flags: self.rpl_flags,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            audio: self.audio,
+            waiting_for_key: self.waiting_for_key,
+            halted: self.halted,
+            rng: self.rng.state(),
+            frame: self.frame,
+            instructions: self.instructions,
+            cycles: self.cycles,
+        }
+    }
+
+    /// Restores a state captured by [`CPU::save_state`]. The CPU is left
+    /// untouched if the state is rejected.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), StateError> {
+        if state.version != SAVE_STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(state.version));
+        }
+        if state.memory.len() != state.mode.memory_size() {
+            return Err(StateError::Invalid("memory size does not match mode"));
+        }
+        if state.stack.len() > state.stack_depth as usize {
+            return Err(StateError::Invalid("stack is too deep"));
+        }
+        if state.display.planes.len() != PLANES {
+            return Err(StateError::Invalid("wrong number of display planes"));
+        }
+        if let Some(mega) = &state.display.mega {
+            if mega.pixels.len() != MEGA_WIDTH * MEGA_HEIGHT {
+                return Err(StateError::Invalid("wrong size of Mega-Chip screen"));
+            }
+        }
+        if state.waiting_for_key.is_some_and(|register| register > 0xF) {
+            return Err(StateError::Invalid("key wait targets a missing register"));
+        }
+
+        self.mode = state.mode;
+        self.quirks = state.quirks;
+        self.registers = state.registers;
+        self.i = state.i;
+        self.memory_position = state.pc as usize;
+        self.stack.clone_from(&state.stack);
+        self.stack_depth = state.stack_depth as usize;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.memory.replace(state.memory.clone());
+        self.display.set_hires(state.display.hires);
+        for (plane, pixels) in state.display.planes.iter().enumerate() {
+            self.display.load_plane(plane, &unpack(pixels));
+        }
+        self.display.select_planes(state.display.selected_planes);
+        self.display.set_mega(state.display.mega.is_some());
+        if let (Some(saved), Some(mega)) = (&state.display.mega, self.display.mega_mut()) {
+            mega.load(&saved.pixels, &saved.palette);
+            mega.sprite_width = saved.sprite_width as usize;
+            mega.sprite_height = saved.sprite_height as usize;
+        }
+        self.rpl_flags = state.rpl_flags;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.audio = state.audio;
+        self.waiting_for_key = state.waiting_for_key;
+        self.halted = state.halted;
+        if let Some(rng) = state.rng {
+            self.rng = Box::new(XorShift::new(rng));
+        }
+        self.frame = state.frame;
+        self.instructions = state.instructions;
+        self.cycles = state.cycles;
+        self.history.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_cpu() -> CPU {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::SuperChip);
+        // V0 = 1; CALL 0x206; halt; I = font 0; DRW V0, V0, 5; RET
+        cpu.load_rom(&[
+            0x60, 0x01, 0x22, 0x06, 0x00, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x00, 0xEE,
+        ])
+        .unwrap();
+        cpu.delay_timer = 30;
+        cpu.run_for(4).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn state_round_trips_through_json_and_bincode() {
+        let state = running_cpu().save_state();
+        assert_eq!(state.stack.len(), 1);
+
+        assert_eq!(SaveState::from_json(&state.to_json()).unwrap(), state);
+        assert_eq!(SaveState::from_bytes(&state.to_bytes()).unwrap(), state);
+    }
+
+    #[test]
+    fn restored_cpu_continues_identically() {
+        let mut original = running_cpu();
+        original.stack_depth = 32;
+        let state = original.save_state();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.display, original.display);
+        assert_eq!(restored.mode, EmulatorMode::SuperChip);
+        assert_eq!(restored.stack(), [0x204]);
+        assert_eq!(restored.stack_depth, 32);
+
+        original.run().unwrap();
+        restored.run().unwrap();
+        assert_eq!(restored.save_state(), original.save_state());
+        assert_eq!(restored.memory_position, 0x206);
+    }
+
+    #[test]
+    fn random_numbers_and_counters_carry_over() {
+        let mut original = CPU::new();
+        // RND V0, 0xFF; JP 0x200
+        original.load_rom(&[0xC0, 0xFF, 0x12, 0x00]).unwrap();
+        original.set_rng(Box::new(XorShift::new(99)));
+        original.step_frame(7).unwrap();
+        let state = original.save_state();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!((restored.frame(), restored.instructions()), (1, 7));
+        let draws = |cpu: &mut CPU| -> Vec<u8> {
+            (0..8)
+                .map(|_| {
+                    cpu.run_for(2).unwrap();
+                    cpu.registers[0]
+                })
+                .collect()
+        };
+        assert_eq!(draws(&mut restored), draws(&mut original));
+
+        original.set_rng(Box::new(|| 0xAA));
+        assert_eq!(original.save_state().rng, None);
+    }
+
+    #[test]
+    fn the_mega_chip_screen_is_saved() {
+        let mut original = CPU::new_with_mode(EmulatorMode::MegaChip);
+        original.display.set_mega(true);
+        let mega = original.display.mega_mut().unwrap();
+        mega.load_palette([0xFF12_3456]);
+        mega.sprite_width = 3;
+        original.display.draw_mega_sprite(5, 6, &[1, 1, 1]);
+        let state = SaveState::from_bytes(&original.save_state().to_bytes()).unwrap();
+
+        let mut restored = CPU::new();
+        restored.display.set_hires(true);
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.display, original.display);
+        assert_eq!(restored.display.mega().unwrap().rgb(7, 6), 0x123456);
+        assert_eq!(restored.display.mega().unwrap().sprite_width, 3);
+
+        restored.load_state(&CPU::new().save_state()).unwrap();
+        assert!(restored.display.mega().is_none());
+    }
+
+    #[test]
+    fn restoring_mid_beep_resumes_the_tone() {
+        let mut original = CPU::new();
+        original.sound_timer = 3;
+        let mut first = [0.0; 1000];
+        original.render_audio(&mut first, 48_000);
+        let state = SaveState::from_bytes(&original.save_state().to_bytes()).unwrap();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        let mut expected = [0.0; 3000];
+        let mut resumed = [0.0; 3000];
+        original.render_audio(&mut expected, 48_000);
+        restored.render_audio(&mut resumed, 48_000);
+
+        assert_eq!(resumed, expected);
+        assert!(resumed[1399] != 0.0 && resumed[1400] == 0.0);
+    }
+
+    #[test]
+    fn mismatched_states_are_rejected() {
+        let mut cpu = CPU::new();
+        let mut state = running_cpu().save_state();
+        state.version = SAVE_STATE_VERSION + 1;
+        assert!(matches!(
+            cpu.load_state(&state),
+            Err(StateError::UnsupportedVersion(_))
+        ));
+
+        state.version = SAVE_STATE_VERSION;
+        state.memory.truncate(0x100);
+        assert!(matches!(
+            cpu.load_state(&state),
+            Err(StateError::Invalid(_))
+        ));
+        assert_eq!(cpu.mode, EmulatorMode::Chip8);
+    }
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+use super::{decode_fast, Fault, Status, CPU};
+use crate::disasm::Instruction;
+
+/// How closely execution speed follows the original hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Accuracy {
+    /// A fixed number of instructions per frame, however long they took on
+    /// real hardware.
+    #[default]
+    Fast,
+    /// Every instruction costs the machine cycles it took in the COSMAC VIP
+    /// interpreter, and DXYN waits for the vertical blank like the VIP did.
+    Cycle,
+}
+
+/// Machine cycles in a 60 Hz frame of the 1.76 MHz COSMAC VIP.
+pub const FRAME_CYCLES: i32 = 3668;
+
+/// Cycles per frame lost to the display interrupt and its DMA, which
+/// fetches 8 bytes for each of the 128 scanlines.
+pub const DISPLAY_CYCLES: i32 = 1024;
+
+/// Approximate cost of `instruction` in VIP machine cycles, after published
+/// measurements of the original interpreter. Instructions the VIP never had
+/// get a nominal cost.
+pub fn cycle_cost(instruction: Instruction) -> i32 {
+    use Instruction::*;
+
+    match instruction {
+        Cls => 24,
+        Ret | Jp(_) | Call(_) | JpOffset { .. } => 23,
+        SeByte { .. } | SneByte { .. } | LdI(_) => 12,
+        SeReg { .. } | SneReg { .. } | Skp(_) | Sknp(_) => 16,
+        LdByte { .. } => 6,
+        AddByte { .. } | LdVxDt(_) | LdKey(_) | LdDt(_) | LdSt(_) => 10,
+        LdReg { .. }
+        | Or { .. }
+        | And { .. }
+        | Xor { .. }
+        | AddReg { .. }
+        | Sub { .. }
+        | Shr { .. }
+        | Subn { .. }
+        | Shl { .. } => 44,
+        Rnd { .. } => 36,
+        Drw { n, .. } => 50 + 8 * n as i32,
+        AddI(_) => 19,
+        LdFont(_) => 20,
+        Bcd(_) => 204,
+        Store(x) | Load(x) => 30 + 14 * (x as i32 + 1),
+        _ => 10,
+    }
+}
+
+impl CPU {
+    /// Runs one 60 Hz frame. Under [`Accuracy::Fast`] that is
+    /// `instructions` instructions, or up to the first DXYN with the
+    /// [`super::Quirks::display_wait`] quirk. Under [`Accuracy::Cycle`] it
+    /// is as many as fit in the frame's cycle budget, with any overrun
+    /// carried into the next frame. Timers are not ticked.
+    pub fn run_frame(&mut self, instructions: usize) -> Result<Status, Fault> {
+        match self.accuracy {
+            Accuracy::Fast if self.quirks.display_wait => self.run_to_draw(instructions),
+            Accuracy::Fast => self.run_for(instructions),
+            Accuracy::Cycle => self.run_cycles(),
+        }
+    }
+
+    /// One whole 60 Hz frame: [`CPU::run_frame`], then the timers tick.
+    /// `display` is then the picture for this frame's vertical blank, so a
+    /// frontend calling this once for every frame it shows gets them all,
+    /// evenly paced.
+    pub fn step_frame(&mut self, instructions: usize) -> Result<Status, Fault> {
+        let status = self.run_frame(instructions)?;
+        self.tick_timers();
+        Ok(status)
+    }
+
+    /// What the instruction at PC decodes to, if PC is in memory.
+    fn next_instruction(&self) -> Option<Instruction> {
+        let pc = self.memory_position;
+        match self.memory.get(pc..pc.saturating_add(2)) {
+            Some(&[high, low]) => decode_fast(u16::from_be_bytes([high, low])).ok(),
+            _ => None,
+        }
+    }
+
+    fn run_to_draw(&mut self, instructions: usize) -> Result<Status, Fault> {
+        for _ in 0..instructions {
+            let instruction = self.next_instruction();
+            let status = self.step()?;
+            if status != Status::Continue {
+                return Ok(status);
+            }
+            if let Some(Instruction::Drw { .. }) = instruction {
+                break;
+            }
+        }
+        Ok(Status::Continue)
+    }
+
+    fn run_cycles(&mut self) -> Result<Status, Fault> {
+        self.cycles += FRAME_CYCLES - DISPLAY_CYCLES;
+        while self.cycles > 0 {
+            let instruction = self.next_instruction();
+            let status = self.step()?;
+            if status != Status::Continue {
+                self.cycles = 0;
+                return Ok(status);
+            }
+            self.cycles -= instruction.map_or(10, cycle_cost);
+            if let Some(Instruction::Drw { .. }) = instruction {
+                // the sprite shows up with the next vertical blank, which
+                // is also where the VIP resumes
+                self.cycles = self.cycles.min(0);
+                break;
+            }
+        }
+        Ok(Status::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ADD V0, 1 in a loop.
+    fn counting_cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.memory[..4].copy_from_slice(&[0x70, 0x01, 0x10, 0x00]);
+        cpu.accuracy = Accuracy::Cycle;
+        cpu
+    }
+
+    #[test]
+    fn fast_mode_runs_the_instruction_budget() {
+        let mut cpu = counting_cpu();
+        cpu.accuracy = Accuracy::Fast;
+
+        cpu.run_frame(10).unwrap();
+        assert_eq!(cpu.registers[0], 5);
+    }
+
+    #[test]
+    fn cycle_mode_runs_what_fits_in_a_frame() {
+        let mut cpu = counting_cpu();
+
+        // each iteration costs 10 + 23 cycles, about 80 of them fit
+        cpu.run_frame(10).unwrap();
+        assert!((80..=81).contains(&cpu.registers[0]));
+
+        cpu.run_frame(10).unwrap();
+        assert!((160..=161).contains(&cpu.registers[0]));
+    }
+
+    #[test]
+    fn drawing_waits_for_the_vertical_blank() {
+        let mut cpu = CPU::new();
+        // DRW V0, V0, 1; ADD V1, 1; JP 0x000
+        cpu.memory[..6].copy_from_slice(&[0xD0, 0x01, 0x71, 0x01, 0x10, 0x00]);
+        cpu.accuracy = Accuracy::Cycle;
+
+        cpu.run_frame(10).unwrap();
+        assert_eq!(cpu.memory_position, 0x002);
+        cpu.run_frame(10).unwrap();
+        assert_eq!(cpu.memory_position, 0x002);
+        assert_eq!(cpu.registers[1], 1);
+    }
+
+    #[test]
+    fn the_display_wait_quirk_ends_fast_frames_at_a_draw() {
+        let mut cpu = CPU::new();
+        // DRW V0, V0, 1; ADD V1, 1; JP 0x000
+        cpu.memory[..6].copy_from_slice(&[0xD0, 0x01, 0x71, 0x01, 0x10, 0x00]);
+        cpu.quirks.display_wait = true;
+        cpu.delay_timer = 5;
+
+        assert_eq!(cpu.step_frame(10), Ok(Status::Continue));
+        assert_eq!((cpu.memory_position, cpu.delay_timer), (0x002, 4));
+        cpu.step_frame(10).unwrap();
+        assert_eq!((cpu.memory_position, cpu.registers[1]), (0x002, 1));
+
+        cpu.quirks.display_wait = false;
+        cpu.step_frame(10).unwrap();
+        assert_eq!(cpu.registers[1], 5);
+        assert_eq!(cpu.frame(), 3);
+    }
+
+    #[test]
+    fn bulk_memory_instructions_cost_more_per_register() {
+        assert!(cycle_cost(Instruction::Store(0xF)) > cycle_cost(Instruction::Store(0)));
+        assert!(cycle_cost(Instruction::Bcd(0)) > cycle_cost(Instruction::LdByte { x: 0, nn: 0 }));
+    }
+}
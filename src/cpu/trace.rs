@@ -0,0 +1,133 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::CPU;
+use crate::disasm::Instruction;
+
+/// One executed instruction, as passed to the hook installed with
+/// [`CPU::set_trace_hook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub pc: usize,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// `(register, old, new)` for every V register the instruction changed.
+    pub registers: Vec<(u8, u8, u8)>,
+    /// `(old, new)` if I changed.
+    pub i: Option<(u16, u16)>,
+}
+
+/// Formats one line per instruction, e.g.
+/// `0x202: 7005  ADD V0, 0x05  V0 01->06`, convenient for diffing against
+/// traces from other emulators.
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#05x}: {:04X}  {}",
+            self.pc, self.opcode, self.instruction
+        )?;
+        for (register, old, new) in &self.registers {
+            write!(f, "  V{:X} {:02x}->{:02x}", register, old, new)?;
+        }
+        if let Some((old, new)) = self.i {
+            write!(f, "  I {:03x}->{:03x}", old, new)?;
+        }
+        Ok(())
+    }
+}
+
+pub type TraceHook = Box<dyn FnMut(&TraceEvent)>;
+
+impl CPU {
+    /// Installs a callback invoked after every executed instruction while
+    /// tracing is enabled, replacing any previous one. Installing a hook
+    /// turns tracing on.
+    pub fn set_trace_hook(&mut self, hook: impl FnMut(&TraceEvent) + 'static) {
+        self.trace_hook = Some(Box::new(hook));
+        self.tracing = true;
+    }
+
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+        self.tracing = false;
+    }
+
+    /// Pauses or resumes the installed hook without removing it.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracing && self.trace_hook.is_some()
+    }
+
+    /// Reports the instruction at `pc` that just ran, given the register
+    /// file and I from before it.
+    pub(super) fn trace(&mut self, pc: usize, opcode: u16, registers: [u8; 16], i: u16) {
+        let Some(mut hook) = self.trace_hook.take() else {
+            return;
+        };
+        let event = TraceEvent {
+            pc,
+            opcode,
+            instruction: crate::disasm::disassemble(opcode),
+            registers: (0..16)
+                .filter(|r| registers[*r] != self.registers[*r])
+                .map(|r| (r as u8, registers[r], self.registers[r]))
+                .collect(),
+            i: (i != self.i).then_some((i, self.i)),
+        };
+        hook(&event);
+        self.trace_hook = Some(hook);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn traced_cpu() -> (CPU, Rc<RefCell<Vec<String>>>) {
+        let mut cpu = CPU::new();
+        // V0 = 1; V0 += 5; I = 0x300; halt
+        cpu.load_rom(&[0x60, 0x01, 0x70, 0x05, 0xA3, 0x00, 0x00, 0x00])
+            .unwrap();
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&lines);
+        cpu.set_trace_hook(move |event| sink.borrow_mut().push(event.to_string()));
+        (cpu, lines)
+    }
+
+    #[test]
+    fn every_instruction_is_reported_with_its_changes() {
+        let (mut cpu, lines) = traced_cpu();
+
+        cpu.run().unwrap();
+
+        assert_eq!(
+            *lines.borrow(),
+            [
+                "0x200: 6001  LD V0, 0x01  V0 00->01",
+                "0x202: 7005  ADD V0, 0x05  V0 01->06",
+                "0x204: A300  LD I, 0x300  I 000->300",
+                "0x206: 0000  SYS 0x000",
+            ]
+        );
+    }
+
+    #[test]
+    fn tracing_can_be_paused() {
+        let (mut cpu, lines) = traced_cpu();
+
+        cpu.set_tracing(false);
+        cpu.step().unwrap();
+        cpu.set_tracing(true);
+        cpu.step().unwrap();
+
+        assert_eq!(lines.borrow().len(), 1);
+        assert!(lines.borrow()[0].starts_with("0x202"));
+    }
+}
@@ -0,0 +1,256 @@
+//! Settings known to work for particular ROMs, looked up by the SHA-1 of
+//! the file: quirks, speed, colors and keys. Descriptors only exist for
+//! ROMs that ship with one; the database covers the rest, so a game that
+//! misbehaves under the default quirks can just be listed once.
+//!
+//! A small database is built in, and users can overlay their own in the
+//! [`crate::config`] format, e.g. `roms.toml` in the frontend's data
+//! directory.
+
+mod sha1;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{self, ConfigError, Entry, Value};
+use crate::cpu::{EmulatorMode, Quirks};
+use crate::display::Palette;
+use crate::keypad::{key_at, Keymap};
+use crate::metadata::parse_color;
+
+pub use sha1::{sha1, sha1_hex};
+
+/// The built-in entries.
+pub const BUILTIN: &str = include_str!("roms.toml");
+
+/// What the database knows about one ROM. Unset fields keep whatever the
+/// descriptor or the defaults say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomSettings {
+    pub title: Option<String>,
+    pub mode: Option<EmulatorMode>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    /// Colors for pixels lit on no plane, the first, the second and both.
+    /// A shorter list leaves the remaining colors alone.
+    pub palette: Vec<u32>,
+    /// Host inputs to bind, named as in [`crate::keypad::KeymapConfig`].
+    pub keys: Keymap,
+}
+
+impl RomSettings {
+    /// Takes every field `other` sets.
+    fn merge(&mut self, other: RomSettings) {
+        self.title = other.title.or(self.title.take());
+        self.mode = other.mode.or(self.mode);
+        self.quirks = other.quirks.or(self.quirks);
+        self.tickrate = other.tickrate.or(self.tickrate);
+        if !other.palette.is_empty() {
+            self.palette = other.palette;
+        }
+        for (input, key) in other.keys.bindings() {
+            self.keys.bind(input, *key);
+        }
+    }
+
+    pub fn apply_palette(&self, palette: &mut Palette) {
+        let mut colors = palette.colors();
+        for (color, set) in colors.iter_mut().zip(&self.palette) {
+            *color = *set;
+        }
+        *palette = Palette::new(colors);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomDatabase {
+    /// By lowercase hex SHA-1.
+    roms: BTreeMap<String, RomSettings>,
+}
+
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("the built-in ROM database parses")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut database = RomDatabase::new();
+        for section in config::parse(text)? {
+            let (hash, keys) = match section.path.as_slice() {
+                [rom, hash] if rom == "rom" => (hash, false),
+                [rom, hash, keys] if rom == "rom" && keys == "keys" => (hash, true),
+                _ => {
+                    let header = section.path.join(".");
+                    return Err(section.error(format!("unknown section [{}]", header)));
+                }
+            };
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(section.error(format!("not a SHA-1: {}", hash)));
+            }
+            let mut settings = RomSettings::default();
+            for entry in &section.entries {
+                if keys {
+                    settings.keys.bind(&entry.key, key_at(entry)?);
+                } else {
+                    set_field(&mut settings, entry)?;
+                }
+            }
+            database
+                .roms
+                .entry(hash.to_ascii_lowercase())
+                .or_default()
+                .merge(settings);
+        }
+        Ok(database)
+    }
+
+    /// Adds the entries of a database file, its fields taking precedence
+    /// over those already known for the same ROM.
+    pub fn load_overlay(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError {
+            line: 0,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        self.extend(Self::parse(&text)?);
+        Ok(())
+    }
+
+    pub fn extend(&mut self, other: RomDatabase) {
+        for (hash, settings) in other.roms {
+            self.roms.entry(hash).or_default().merge(settings);
+        }
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomSettings> {
+        self.get(&sha1_hex(rom))
+    }
+
+    /// The entry for a hex SHA-1 in either case.
+    pub fn get(&self, sha1: &str) -> Option<&RomSettings> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+fn set_field(settings: &mut RomSettings, entry: &Entry) -> Result<(), ConfigError> {
+    match entry.key.as_str() {
+        "title" => settings.title = Some(entry.string()?.to_string()),
+        "mode" => {
+            settings.mode = Some(match entry.string()? {
+                "chip8" => EmulatorMode::Chip8,
+                "schip" => EmulatorMode::SuperChip,
+                "xo" => EmulatorMode::XoChip,
+                "mega" => EmulatorMode::MegaChip,
+                mode => return Err(entry.error(format!("unknown mode {}", mode))),
+            })
+        }
+        "quirks" => {
+            let name = entry.string()?;
+            let quirks = Quirks::preset(name)
+                .ok_or_else(|| entry.error(format!("unknown quirks {}", name)))?;
+            settings.quirks = Some(quirks);
+        }
+        "tickrate" => match u32::try_from(entry.integer()?) {
+            Ok(tickrate) if tickrate > 0 => settings.tickrate = Some(tickrate),
+            _ => return Err(entry.error("tickrate must be a positive number")),
+        },
+        "palette" => {
+            let Value::Array(colors) = &entry.value else {
+                return Err(entry.error("palette must be a list of colors"));
+            };
+            settings.palette = colors
+                .iter()
+                .take(4)
+                .map(|color| match color {
+                    Value::String(color) => parse_color(color),
+                    _ => None,
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| entry.error("palette colors are written #RRGGBB"))?;
+        }
+        other => return Err(entry.error(format!("unknown setting {}", other))),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &[u8] = &[0x12, 0x00];
+
+    fn database(hash: &str) -> String {
+        format!(
+            "
+            [rom.{hash}]
+            title = \"Loop\"
+            quirks = \"vip\"
+            tickrate = 15
+            palette = [\"#102030\", \"#FFF\"]
+
+            [rom.{hash}.keys]
+            space = 5
+            "
+        )
+    }
+
+    #[test]
+    fn roms_are_found_by_hash() {
+        let hash = sha1_hex(ROM).to_ascii_uppercase();
+        let database = RomDatabase::parse(&database(&hash)).unwrap();
+
+        let settings = database.lookup(ROM).unwrap();
+        assert_eq!(settings.title.as_deref(), Some("Loop"));
+        assert_eq!(settings.quirks, Some(Quirks::cosmac_vip()));
+        assert_eq!(settings.tickrate, Some(15));
+        assert_eq!(settings.keys.bindings(), [("space".to_string(), 5)]);
+        let mut palette = Palette::new([0; 4]);
+        settings.apply_palette(&mut palette);
+        assert_eq!(palette.colors(), [0x102030, 0xFFFFFF, 0, 0]);
+        assert_eq!(database.lookup(&[0x00, 0xE0]), None);
+        assert_eq!(RomDatabase::builtin().lookup(ROM), None);
+    }
+
+    #[test]
+    fn overlays_take_precedence_field_by_field() {
+        let hash = sha1_hex(ROM);
+        let mut database = RomDatabase::parse(&database(&hash)).unwrap();
+        let overlay = format!("[rom.{}]\ntickrate = 20\nmode = \"schip\"", hash);
+        database.extend(RomDatabase::parse(&overlay).unwrap());
+
+        let settings = database.get(&hash).unwrap();
+        assert_eq!(settings.tickrate, Some(20));
+        assert_eq!(settings.mode, Some(EmulatorMode::SuperChip));
+        assert_eq!(settings.quirks, Some(Quirks::cosmac_vip()));
+        assert_eq!(database.len(), 1);
+    }
+
+    #[test]
+    fn bad_entries_are_reported() {
+        let bad = |text: &str| RomDatabase::parse(text).unwrap_err().message;
+        let hash = sha1_hex(ROM);
+
+        assert_eq!(bad("[rom.abc]"), "not a SHA-1: abc");
+        assert_eq!(
+            bad(&format!("[rom.{}]\nspeed = 3", hash)),
+            "unknown setting speed"
+        );
+        assert_eq!(
+            bad(&format!("[rom.{}]\npalette = [\"red\"]", hash)),
+            "palette colors are written #RRGGBB"
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+
+use super::{Debugger, StopReason};
+
+/// The registers as gdb numbers them: V0 to VF, then I, PC, SP and the
+/// delay and sound timers. Multi-byte registers go over the wire little
+/// endian, as gdb expects for a target that doesn't say otherwise.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+const REGISTER_COUNT: usize = 21;
+
+/// Signals reported in stop replies.
+const SIGTRAP: u8 = 5;
+const SIGILL: u8 = 4;
+const SIGINT: u8 = 2;
+
+/// Serves one gdb (or lldb, or IDE) session over the GDB remote serial
+/// protocol, driving `debugger` from it. Returns when the debugger
+/// detaches, kills the target or hangs up.
+///
+/// Registers, memory, breakpoints, stepping and continuing are supported,
+/// and `monitor CMD` runs any [`Debugger::execute`] command, e.g.
+/// `monitor key 0x5` to answer a program waiting on FX0A.
+pub fn serve(debugger: &mut Debugger, mut stream: impl Read + Write) -> io::Result<()> {
+    let mut session = GdbSession::new(debugger);
+    while let Some(packet) = read_packet(&mut stream, session.acks)? {
+        let Some(reply) = session.handle(&packet) else {
+            break;
+        };
+        write_packet(&mut stream, &reply)?;
+        if session.done {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Waits for one debugger to connect at `address`, e.g. `127.0.0.1:1234`
+/// for gdb's `target remote :1234`, and [`serve`]s it.
+pub fn listen(debugger: &mut Debugger, address: impl ToSocketAddrs) -> io::Result<()> {
+    let (stream, _) = TcpListener::bind(address)?.accept()?;
+    stream.set_nodelay(true)?;
+    serve(debugger, stream)
+}
+
+/// The protocol state of one connection, minus the packet framing.
+pub struct GdbSession<'a> {
+    debugger: &'a mut Debugger,
+    /// Cleared once gdb asks for QStartNoAckMode.
+    acks: bool,
+    done: bool,
+}
+
+impl<'a> GdbSession<'a> {
+    pub fn new(debugger: &'a mut Debugger) -> Self {
+        GdbSession {
+            debugger,
+            acks: true,
+            done: false,
+        }
+    }
+
+    /// The reply to one packet's contents, empty for anything not
+    /// supported, as the protocol asks. `None` after `k`, which gets no
+    /// reply.
+    pub fn handle(&mut self, packet: &str) -> Option<String> {
+        // by char, not byte: a client may send anything
+        let first = packet.chars().next().map_or(0, char::len_utf8);
+        let (command, rest) = packet.split_at(first);
+        let reply = match command {
+            "?" => stop_reply(&StopReason::Stepped),
+            "g" => (0..REGISTER_COUNT)
+                .map(|register| hex(&self.register(register)))
+                .collect(),
+            "G" => self.write_registers(rest),
+            "p" => usize::from_str_radix(rest, 16)
+                .ok()
+                .filter(|register| *register < REGISTER_COUNT)
+                .map_or_else(
+                    || "E00".to_string(),
+                    |register| hex(&self.register(register)),
+                ),
+            "P" => self.write_register(rest),
+            "m" => self.read_memory(rest),
+            "M" => self.write_memory(rest),
+            "c" => self.resume(rest, Debugger::continue_execution),
+            "s" => self.resume(rest, Debugger::step),
+            "Z" | "z" => self.breakpoint(command == "Z", rest),
+            "H" => "OK".to_string(),
+            "T" => "OK".to_string(),
+            "D" => {
+                self.done = true;
+                "OK".to_string()
+            }
+            "k" => return None,
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if let Some(command) = packet.strip_prefix("qRcmd,") {
+            return self.monitor(command);
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return read_chunk(TARGET_XML, range);
+        }
+        match packet.split(':').next().unwrap_or_default() {
+            "qSupported" => "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+".to_string(),
+            "QStartNoAckMode" => {
+                self.acks = false;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// A register's bytes as they go over the wire.
+    fn register(&self, register: usize) -> Vec<u8> {
+        let cpu = &self.debugger.cpu;
+        match register {
+            0..=15 => vec![cpu.registers[register]],
+            16 => cpu.i.to_le_bytes().to_vec(),
+            17 => (cpu.memory_position as u16).to_le_bytes().to_vec(),
+            18 => vec![cpu.stack().len() as u8],
+            19 => vec![cpu.delay_timer],
+            _ => vec![cpu.sound_timer],
+        }
+    }
+
+    /// Sets a register from its wire bytes. The stack pointer only moves
+    /// with CALL and RET, so writes to it are ignored.
+    fn set_register(&mut self, register: usize, bytes: &[u8]) {
+        let cpu = &mut self.debugger.cpu;
+        let word = || u16::from_le_bytes([bytes[0], bytes.get(1).copied().unwrap_or(0)]);
+        match register {
+            0..=15 => cpu.registers[register] = bytes[0],
+            16 => cpu.i = word(),
+            17 => cpu.memory_position = word() as usize,
+            19 => cpu.delay_timer = bytes[0],
+            20 => cpu.sound_timer = bytes[0],
+            _ => {}
+        }
+    }
+
+    fn write_registers(&mut self, data: &str) -> String {
+        let Some(mut bytes) = unhex(data) else {
+            return "E00".to_string();
+        };
+        for register in 0..REGISTER_COUNT {
+            let size = self.register(register).len();
+            if bytes.len() < size {
+                break;
+            }
+            let rest = bytes.split_off(size);
+            self.set_register(register, &bytes);
+            bytes = rest;
+        }
+        "OK".to_string()
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let parsed = args.split_once('=').and_then(|(register, value)| {
+            let register = usize::from_str_radix(register, 16).ok()?;
+            let value = unhex(value)?;
+            (register < REGISTER_COUNT && value.len() == self.register(register).len())
+                .then_some((register, value))
+        });
+        match parsed {
+            Some((register, value)) => {
+                self.set_register(register, &value);
+                "OK".to_string()
+            }
+            None => "E00".to_string(),
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        match address_and_len(args).and_then(|range| self.debugger.cpu.memory.get(range)) {
+            Some(bytes) => hex(bytes),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let parsed = args
+            .split_once(':')
+            .and_then(|(range, data)| Some((address_and_len(range)?, unhex(data)?)));
+        let Some((range, data)) = parsed.filter(|(range, data)| range.len() == data.len()) else {
+            return "E00".to_string();
+        };
+        match self.debugger.cpu.memory.get_mut(range) {
+            Some(memory) => {
+                memory.copy_from_slice(&data);
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    /// `c` and `s`, optionally resuming at another address first.
+    fn resume(&mut self, address: &str, run: fn(&mut Debugger) -> StopReason) -> String {
+        if let Ok(address) = usize::from_str_radix(address, 16) {
+            self.debugger.cpu.memory_position = address;
+        }
+        stop_reply(&run(self.debugger))
+    }
+
+    /// Software and hardware breakpoints are the same thing here; the
+    /// other kinds, watchpoints, are left to `monitor watch`.
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let (Some("0" | "1"), Some(address)) = (fields.next(), fields.next()) else {
+            return String::new();
+        };
+        let Ok(address) = usize::from_str_radix(address, 16) else {
+            return "E00".to_string();
+        };
+        if insert {
+            self.debugger.add_breakpoint(address);
+        } else {
+            self.debugger.remove_breakpoint(address);
+        }
+        "OK".to_string()
+    }
+
+    fn monitor(&mut self, command: &str) -> String {
+        let Some(command) = unhex(command).and_then(|bytes| String::from_utf8(bytes).ok()) else {
+            return "E00".to_string();
+        };
+        let output = match self.debugger.execute(&command) {
+            Ok(text) => text,
+            Err(err) => format!("error: {}", err),
+        };
+        match output.is_empty() {
+            true => "OK".to_string(),
+            false => hex(format!("{}\n", output).as_bytes()),
+        }
+    }
+}
+
+fn stop_reply(reason: &StopReason) -> String {
+    let signal = match reason {
+        StopReason::Halted => return "W00".to_string(),
+        StopReason::Fault(_) => SIGILL,
+        StopReason::Limit => SIGINT,
+        _ => SIGTRAP,
+    };
+    format!("S{:02x}", signal)
+}
+
+/// `ADDR,LEN` in hex.
+fn address_and_len(args: &str) -> Option<std::ops::Range<usize>> {
+    let (address, len) = args.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    Some(address..address.checked_add(len)?)
+}
+
+/// The part of a qXfer document asked for by `OFFSET,LEN`, `l` marking
+/// the last one.
+fn read_chunk(document: &str, range: &str) -> String {
+    let Some(range) = address_and_len(range) else {
+        return "E00".to_string();
+    };
+    let start = range.start.min(document.len());
+    let end = range.end.min(document.len());
+    let marker = if end == document.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, &document[start..end])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(text.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))
+}
+
+/// The next `$data#xx` packet, acknowledged if `acks` is on. Acks from the
+/// other side and interrupts between packets are skipped. `None` once the
+/// connection closes.
+fn read_packet(stream: &mut (impl Read + Write), acks: bool) -> io::Result<Option<String>> {
+    let mut byte = [0];
+    loop {
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut sum = [0; 2];
+        stream.read_exact(&mut sum)?;
+        let data = String::from_utf8_lossy(&data).into_owned();
+        let valid = std::str::from_utf8(&sum)
+            .ok()
+            .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+            == Some(checksum(&data));
+        if acks {
+            stream.write_all(if valid { b"+" } else { b"-" })?;
+        }
+        if valid || !acks {
+            return Ok(Some(data));
+        }
+    }
+}
+
+fn write_packet(stream: &mut impl Write, data: &str) -> io::Result<()> {
+    write!(stream, "${}#{:02x}", data, checksum(data))?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    fn debugger() -> Debugger {
+        let mut cpu = CPU::new();
+        // LD V0, 0x2A; ADD V0, 1; JP 0x002
+        cpu.memory[..6].copy_from_slice(&[0x60, 0x2A, 0x70, 0x01, 0x10, 0x02]);
+        Debugger::new(cpu)
+    }
+
+    #[test]
+    fn registers_memory_and_breakpoints() {
+        let mut debugger = debugger();
+        let mut gdb = GdbSession::new(&mut debugger);
+        let reply = |gdb: &mut GdbSession, packet: &str| gdb.handle(packet).unwrap();
+
+        assert_eq!(reply(&mut gdb, "s"), "S05");
+        assert_eq!(reply(&mut gdb, "p0"), "2a");
+        assert_eq!(reply(&mut gdb, "p11"), "0200");
+        assert_eq!(reply(&mut gdb, "g").len(), (16 + 2 + 2 + 3) * 2);
+
+        assert_eq!(reply(&mut gdb, "Z0,4,2"), "OK");
+        assert_eq!(reply(&mut gdb, "c"), "S05");
+        assert_eq!(reply(&mut gdb, "p11"), "0400");
+        assert_eq!(reply(&mut gdb, "p0"), "2b");
+
+        assert_eq!(reply(&mut gdb, "P0=07"), "OK");
+        assert_eq!(reply(&mut gdb, "M300,2:beef"), "OK");
+        assert_eq!(reply(&mut gdb, "m2ff,3"), "00beef");
+        assert_eq!(reply(&mut gdb, "m1000,1"), "E01");
+        assert_eq!(reply(&mut gdb, "vMustReplyEmpty"), "");
+        assert_eq!(reply(&mut gdb, "\u{fffd}x"), "");
+        assert_eq!(reply(&mut gdb, "G\u{e9}\u{e9}"), "E00");
+        assert!(reply(&mut gdb, "qXfer:features:read:target.xml:0,ffff").starts_with("l<?xml"));
+        assert_eq!(gdb.handle("k"), None);
+        assert_eq!(debugger.cpu.registers[0], 7);
+    }
+
+    #[test]
+    fn monitor_runs_debugger_commands() {
+        let mut debugger = debugger();
+        let mut gdb = GdbSession::new(&mut debugger);
+        let reply = gdb.handle(&format!("qRcmd,{}", hex(b"break 0x4"))).unwrap();
+        let output = String::from_utf8(unhex(&reply).unwrap()).unwrap();
+        assert_eq!(output, "breakpoint at 0x004\n");
+        assert!(debugger.breakpoints().eq([4]));
+    }
+
+    /// Bytes from gdb in, bytes to gdb out.
+    struct Wire {
+        incoming: io::Cursor<Vec<u8>>,
+        outgoing: Vec<u8>,
+    }
+
+    impl Read for Wire {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.incoming.read(buf)
+        }
+    }
+
+    impl Write for Wire {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.outgoing.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn packets_are_framed_and_acknowledged() {
+        let mut debugger = debugger();
+        let incoming = b"+$p0#a0$p0#00$?#3f$D#44".to_vec();
+        let mut wire = Wire {
+            incoming: io::Cursor::new(incoming),
+            outgoing: Vec::new(),
+        };
+        serve(&mut debugger, &mut wire).unwrap();
+        // the packet with the bad checksum is refused, then the rest answered
+        assert_eq!(
+            String::from_utf8(wire.outgoing).unwrap(),
+            "+$00#60-+$S05#b8+$OK#9a"
+        );
+    }
+}
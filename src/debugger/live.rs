@@ -0,0 +1,150 @@
+use crate::cpu::CPU;
+
+/// How many bytes from I on a [`LiveWatch`] follows.
+pub const LIVE_WINDOW: usize = 16;
+
+/// Never changed while being watched.
+const NEVER: u64 = u64::MAX;
+
+/// Something a [`LiveWatch`] shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveSlot {
+    DelayTimer,
+    SoundTimer,
+    I,
+    /// The byte at `I + offset`.
+    AtI(usize),
+}
+
+/// The timers, I and the memory I points at, which most game logic
+/// revolves around, remembered across updates so that a frontend can
+/// highlight what just changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveWatch {
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub i: u16,
+    /// Up to [`LIVE_WINDOW`] bytes, fewer when I is near the end of memory.
+    pub at_i: Vec<u8>,
+    updates: u64,
+    /// Update on which each of DT, ST and I last changed.
+    changed: [u64; 3],
+    /// Same for each byte in `at_i`. Tracked per address, so moving I
+    /// keeps the history of bytes that stay in view.
+    bytes_changed: Vec<u64>,
+}
+
+impl LiveWatch {
+    pub fn new(cpu: &CPU) -> Self {
+        let at_i = window(cpu);
+        LiveWatch {
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            i: cpu.i,
+            bytes_changed: vec![NEVER; at_i.len()],
+            at_i,
+            updates: 0,
+            changed: [NEVER; 3],
+        }
+    }
+
+    /// Reads the current values from `cpu`, noting which ones differ.
+    pub fn update(&mut self, cpu: &CPU) {
+        self.updates += 1;
+        let now = self.updates;
+        for (slot, (old, new)) in [
+            (self.delay_timer as u16, cpu.delay_timer as u16),
+            (self.sound_timer as u16, cpu.sound_timer as u16),
+            (self.i, cpu.i),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if old != new {
+                self.changed[slot] = now;
+            }
+        }
+
+        let at_i = window(cpu);
+        let bytes_changed = at_i
+            .iter()
+            .enumerate()
+            .map(|(offset, byte)| {
+                let address = cpu.i as usize + offset;
+                match address.checked_sub(self.i as usize) {
+                    Some(old) if old < self.at_i.len() => {
+                        if self.at_i[old] != *byte {
+                            now
+                        } else {
+                            self.bytes_changed[old]
+                        }
+                    }
+                    _ => NEVER,
+                }
+            })
+            .collect();
+
+        self.delay_timer = cpu.delay_timer;
+        self.sound_timer = cpu.sound_timer;
+        self.i = cpu.i;
+        self.at_i = at_i;
+        self.bytes_changed = bytes_changed;
+    }
+
+    /// Whether `slot` changed during the last `updates` updates, e.g. 1 for
+    /// just the latest one.
+    pub fn changed_within(&self, slot: LiveSlot, updates: u64) -> bool {
+        let when = match slot {
+            LiveSlot::DelayTimer => self.changed[0],
+            LiveSlot::SoundTimer => self.changed[1],
+            LiveSlot::I => self.changed[2],
+            LiveSlot::AtI(offset) => self.bytes_changed.get(offset).copied().unwrap_or(NEVER),
+        };
+        when != NEVER && self.updates - when < updates
+    }
+}
+
+fn window(cpu: &CPU) -> Vec<u8> {
+    let start = (cpu.i as usize).min(cpu.memory.len());
+    let end = (start + LIVE_WINDOW).min(cpu.memory.len());
+    cpu.memory[start..end].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_highlighted_per_address() {
+        let mut cpu = CPU::new();
+        cpu.i = 0x300;
+        let mut live = LiveWatch::new(&cpu);
+
+        cpu.delay_timer = 10;
+        cpu.memory[0x302] = 7;
+        live.update(&cpu);
+        assert!(live.changed_within(LiveSlot::DelayTimer, 1));
+        assert!(!live.changed_within(LiveSlot::SoundTimer, 1));
+        assert!(live.changed_within(LiveSlot::AtI(2), 1));
+        assert!(!live.changed_within(LiveSlot::AtI(1), 1));
+
+        // moving I keeps the byte's history at its new offset
+        cpu.i = 0x301;
+        live.update(&cpu);
+        assert_eq!(live.at_i[1], 7);
+        assert!(live.changed_within(LiveSlot::I, 1));
+        assert!(!live.changed_within(LiveSlot::AtI(1), 1));
+        assert!(live.changed_within(LiveSlot::AtI(1), 2));
+        assert!(!live.changed_within(LiveSlot::AtI(15), 2));
+    }
+
+    #[test]
+    fn window_shrinks_at_the_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.i = 0xFFA;
+
+        assert_eq!(LiveWatch::new(&cpu).at_i.len(), 6);
+        cpu.i = 0xFFFF;
+        assert!(LiveWatch::new(&cpu).at_i.is_empty());
+    }
+}
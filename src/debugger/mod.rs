@@ -0,0 +1,794 @@
+//! Interactive debugging on top of [`CPU::step`]: breakpoints, optionally
+//! conditional, register, memory and expression watchpoints, stepping over
+//! subroutine calls, and a text command interface for frontends and the
+//! `chip8 debug` command, which [`gdb`] also serves to gdb and other
+//! debuggers over the GDB remote protocol. [`DebugView`] holds what a
+//! debugger window needs on top, for GUI frontends to draw with whatever
+//! toolkit they use.
+
+pub mod gdb;
+mod live;
+mod view;
+
+use std::io::{self, BufRead};
+use std::{collections::BTreeMap, error, fmt, fmt::Write};
+
+use crate::cpu::{Fault, Status, CPU};
+use crate::disasm::disassemble_at;
+use crate::expr::{Expr, ExprError};
+
+pub use live::{LiveSlot, LiveWatch, LIVE_WINDOW};
+pub use view::{DebugAction, DebugView, DisassemblyLine, MemoryRow, RegisterPanel, MEMORY_ROW};
+
+/// Upper bound for `continue`, so a program spinning without hitting a
+/// breakpoint hands control back eventually.
+pub const CONTINUE_LIMIT: usize = 1_000_000;
+
+/// Why execution stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested number of instructions ran.
+    Stepped,
+    Breakpoint(usize),
+    RegisterChanged {
+        register: u8,
+        old: u8,
+        new: u8,
+    },
+    MemoryChanged {
+        address: usize,
+        old: u8,
+        new: u8,
+    },
+    /// A watched expression, as it was written, has a new value.
+    ExpressionChanged {
+        expression: String,
+        old: i64,
+        new: i64,
+    },
+    Halted,
+    WaitingForKey,
+    Fault(Fault),
+    /// `continue` ran [`CONTINUE_LIMIT`] instructions without stopping.
+    Limit,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Stepped => write!(f, "stepped"),
+            StopReason::Breakpoint(address) => write!(f, "breakpoint at {:#05x}", address),
+            StopReason::RegisterChanged { register, old, new } => {
+                write!(f, "V{:X} changed {:#04x} -> {:#04x}", register, old, new)
+            }
+            StopReason::MemoryChanged { address, old, new } => write!(
+                f,
+                "memory {:#05x} changed {:#04x} -> {:#04x}",
+                address, old, new
+            ),
+            StopReason::ExpressionChanged {
+                expression,
+                old,
+                new,
+            } => write!(f, "{} changed {} -> {}", expression, old, new),
+            StopReason::Halted => write!(f, "halted"),
+            StopReason::WaitingForKey => write!(f, "waiting for a key"),
+            StopReason::Fault(fault) => write!(f, "{}", fault),
+            StopReason::Limit => write!(f, "still running after {} instructions", CONTINUE_LIMIT),
+        }
+    }
+}
+
+/// A command that could not be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandError(pub String);
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for CommandError {}
+
+/// An expression together with how it was written, to show it back.
+struct Written {
+    text: String,
+    expr: Expr,
+}
+
+impl Written {
+    fn parse(text: &str) -> Result<Self, ExprError> {
+        Ok(Written {
+            text: text.trim().to_string(),
+            expr: Expr::parse(text)?,
+        })
+    }
+}
+
+struct ExprWatch {
+    expression: Written,
+    last: i64,
+}
+
+struct MemoryWatch {
+    address: usize,
+    len: usize,
+    last: Vec<u8>,
+}
+
+/// Owns a CPU and executes it under debugger control. Timers tick once
+/// every `instructions_per_frame` instructions, so delay loops make
+/// progress while stepping.
+pub struct Debugger {
+    pub cpu: CPU,
+    pub instructions_per_frame: usize,
+    /// Each with the condition it only stops on, if any.
+    breakpoints: BTreeMap<usize, Option<Written>>,
+    register_watches: Vec<(u8, u8)>,
+    memory_watches: Vec<MemoryWatch>,
+    expr_watches: Vec<ExprWatch>,
+    executed: usize,
+    /// What the `live` command compares against.
+    live: LiveWatch,
+}
+
+impl Debugger {
+    pub fn new(cpu: CPU) -> Self {
+        Debugger {
+            live: LiveWatch::new(&cpu),
+            cpu,
+            instructions_per_frame: 11,
+            breakpoints: BTreeMap::new(),
+            register_watches: Vec::new(),
+            memory_watches: Vec::new(),
+            expr_watches: Vec::new(),
+            executed: 0,
+        }
+    }
+
+    /// Replaces any conditional breakpoint at `address`.
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address, None);
+    }
+
+    /// Stops at `address` only when `condition`, an [`Expr`], holds there,
+    /// e.g. `V3 == 0x1F`.
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        address: usize,
+        condition: &str,
+    ) -> Result<(), ExprError> {
+        let condition = Written::parse(condition)?;
+        self.breakpoints.insert(address, Some(condition));
+        Ok(())
+    }
+
+    /// Returns whether there was a breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    /// The condition of the breakpoint at `address`, as it was written.
+    pub fn breakpoint_condition(&self, address: usize) -> Option<&str> {
+        let condition = self.breakpoints.get(&address)?.as_ref()?;
+        Some(&condition.text)
+    }
+
+    /// Whether a breakpoint at `address` stops the CPU as it is now.
+    fn breaks_at(&self, address: usize) -> bool {
+        match self.breakpoints.get(&address) {
+            Some(Some(condition)) => condition.expr.holds(&self.cpu),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Stops execution whenever VX changes.
+    pub fn watch_register(&mut self, register: u8) {
+        let register = register & 0x0F;
+        self.register_watches.retain(|(r, _)| *r != register);
+        self.register_watches
+            .push((register, self.cpu.registers[register as usize]));
+    }
+
+    /// Stops execution whenever a byte in `address..address + len` changes.
+    pub fn watch_memory(&mut self, address: usize, len: usize) -> Result<(), CommandError> {
+        let last = self.memory_range(address, len)?.to_vec();
+        self.memory_watches.push(MemoryWatch { address, len, last });
+        Ok(())
+    }
+
+    /// Makes program writes to `len` bytes at `address` fault, which stops
+    /// execution with [`StopReason::Fault`] unless the CPU is lenient.
+    pub fn protect(&mut self, address: usize, len: usize) -> Result<(), CommandError> {
+        self.memory_range(address, len)?;
+        self.cpu.memory.protect(address..address + len);
+        Ok(())
+    }
+
+    /// Stops execution whenever `expression`, an [`Expr`] such as
+    /// `mem[0x300] + V1`, changes value.
+    pub fn watch_expression(&mut self, expression: &str) -> Result<(), ExprError> {
+        let expression = Written::parse(expression)?;
+        let last = expression.expr.eval(&self.cpu);
+        self.expr_watches.push(ExprWatch { expression, last });
+        Ok(())
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.register_watches.clear();
+        self.memory_watches.clear();
+        self.expr_watches.clear();
+    }
+
+    /// Executes one instruction, reporting a triggered watchpoint or
+    /// anything else that would stop a longer run.
+    pub fn step(&mut self) -> StopReason {
+        let status = match self.cpu.step() {
+            Ok(status) => status,
+            Err(fault) => return StopReason::Fault(fault),
+        };
+        self.executed += 1;
+        if self
+            .executed
+            .is_multiple_of(self.instructions_per_frame.max(1))
+        {
+            self.cpu.tick_timers();
+        }
+        if let Some(reason) = self.check_watches() {
+            return reason;
+        }
+        match status {
+            Status::Continue => StopReason::Stepped,
+            Status::Halted => StopReason::Halted,
+            Status::WaitingForKey => StopReason::WaitingForKey,
+        }
+    }
+
+    /// Runs until a breakpoint, watchpoint, halt, key wait or fault. A
+    /// breakpoint on the current instruction does not stop it again.
+    pub fn continue_execution(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    /// Like [`Debugger::step`], but a CALL runs until its subroutine
+    /// returns.
+    pub fn step_over(&mut self) -> StopReason {
+        let pc = self.cpu.memory_position;
+        let is_call = self
+            .cpu
+            .memory
+            .get(pc)
+            .is_some_and(|byte| byte & 0xF0 == 0x20);
+        if !is_call {
+            return self.step();
+        }
+        let depth = self.cpu.stack().len();
+        self.run_until(|cpu| cpu.memory_position == pc + 2 && cpu.stack().len() == depth)
+    }
+
+    fn run_until(&mut self, done: impl Fn(&CPU) -> bool) -> StopReason {
+        for _ in 0..CONTINUE_LIMIT {
+            let reason = self.step();
+            if reason != StopReason::Stepped || done(&self.cpu) {
+                return reason;
+            }
+            let pc = self.cpu.memory_position;
+            if self.breaks_at(pc) {
+                return StopReason::Breakpoint(pc);
+            }
+        }
+        StopReason::Limit
+    }
+
+    fn check_watches(&mut self) -> Option<StopReason> {
+        let mut reason = None;
+        for (register, last) in &mut self.register_watches {
+            let new = self.cpu.registers[*register as usize];
+            if new != *last {
+                reason.get_or_insert(StopReason::RegisterChanged {
+                    register: *register,
+                    old: *last,
+                    new,
+                });
+                *last = new;
+            }
+        }
+        for watch in &mut self.memory_watches {
+            let current = &self.cpu.memory[watch.address..watch.address + watch.len];
+            if let Some(offset) = (0..watch.len).find(|i| current[*i] != watch.last[*i]) {
+                reason.get_or_insert(StopReason::MemoryChanged {
+                    address: watch.address + offset,
+                    old: watch.last[offset],
+                    new: current[offset],
+                });
+                watch.last.copy_from_slice(current);
+            }
+        }
+        for watch in &mut self.expr_watches {
+            let new = watch.expression.expr.eval(&self.cpu);
+            if new != watch.last {
+                reason.get_or_insert(StopReason::ExpressionChanged {
+                    expression: watch.expression.text.clone(),
+                    old: watch.last,
+                    new,
+                });
+                watch.last = new;
+            }
+        }
+        reason
+    }
+
+    /// Runs one text command and returns what to print.
+    ///
+    /// | command                    | effect                                 |
+    /// |----------------------------|----------------------------------------|
+    /// | `break ADDR` / `b`         | add a breakpoint                       |
+    /// | `break ADDR if EXPR`       | stop there only when EXPR holds        |
+    /// | `delete ADDR`              | remove a breakpoint                    |
+    /// | `watch VX`                 | stop when a register changes           |
+    /// | `watch ADDR [LEN]`         | stop when memory changes               |
+    /// | `watch EXPR`               | stop when an expression changes value  |
+    /// | `unwatch`                  | remove every watchpoint                |
+    /// | `protect ADDR LEN`         | stop when the program writes there     |
+    /// | `step [N]` / `s`           | execute N instructions (default 1)     |
+    /// | `next` / `n`               | step, running CALLs to completion      |
+    /// | `continue` / `c`           | run until something stops execution    |
+    /// | `regs`                     | show registers                         |
+    /// | `mem ADDR [LEN]`           | hex dump (default 16 bytes)            |
+    /// | `disasm [ADDR] [N]`        | disassemble (default PC, 8)            |
+    /// | `live`                     | timers, I and memory at I, `*` marking |
+    /// |                            | changes since the last `live`          |
+    /// | `key K [up]`               | press or release keypad key K          |
+    pub fn execute(&mut self, command: &str) -> Result<String, CommandError> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            return Ok(String::new());
+        };
+        match (*name, args) {
+            ("break" | "b", [address]) => {
+                let address = parse_number(address)?;
+                self.add_breakpoint(address);
+                Ok(format!("breakpoint at {:#05x}", address))
+            }
+            ("break" | "b", [address, "if", condition @ ..]) if !condition.is_empty() => {
+                let address = parse_number(address)?;
+                self.add_conditional_breakpoint(address, &condition.join(" "))
+                    .map_err(expr_error)?;
+                let condition = self.breakpoint_condition(address).unwrap_or_default();
+                Ok(format!("breakpoint at {:#05x} if {}", address, condition))
+            }
+            ("delete", [address]) => {
+                let address = parse_number(address)?;
+                if self.remove_breakpoint(address) {
+                    Ok(format!("deleted breakpoint at {:#05x}", address))
+                } else {
+                    Err(CommandError(format!("no breakpoint at {:#05x}", address)))
+                }
+            }
+            ("watch", [target]) if parse_register(target).is_some() => {
+                let register = parse_register(target).unwrap_or_default();
+                self.watch_register(register);
+                Ok(format!("watching V{:X}", register))
+            }
+            ("watch", [target, rest @ ..])
+                if rest.len() <= 1 && args.iter().all(|arg| parse_number(arg).is_ok()) =>
+            {
+                let address = parse_number(target)?;
+                let len = rest.first().map(|len| parse_number(len)).transpose()?;
+                self.watch_memory(address, len.unwrap_or(1))?;
+                Ok(format!("watching memory at {:#05x}", address))
+            }
+            ("watch", expression @ [_, ..]) => {
+                let expression = expression.join(" ");
+                self.watch_expression(&expression).map_err(expr_error)?;
+                Ok(format!("watching {}", expression))
+            }
+            ("protect", [address, len]) => {
+                let (address, len) = (parse_number(address)?, parse_number(len)?);
+                self.protect(address, len)?;
+                Ok(format!("protected {} bytes at {:#05x}", len, address))
+            }
+            ("unwatch", []) => {
+                self.clear_watches();
+                Ok("removed all watchpoints".to_string())
+            }
+            ("step" | "s", rest) if rest.len() <= 1 => {
+                let count = rest.first().map(|n| parse_number(n)).transpose()?;
+                let mut reason = StopReason::Stepped;
+                for _ in 0..count.unwrap_or(1) {
+                    reason = self.step();
+                    if reason != StopReason::Stepped {
+                        break;
+                    }
+                }
+                Ok(self.report(&reason))
+            }
+            ("next" | "n", []) => {
+                let reason = self.step_over();
+                Ok(self.report(&reason))
+            }
+            ("continue" | "c", []) => {
+                let reason = self.continue_execution();
+                Ok(self.report(&reason))
+            }
+            ("regs", []) => Ok(self.registers()),
+            ("mem", [address, rest @ ..]) if rest.len() <= 1 => {
+                let address = parse_number(address)?;
+                let len = rest.first().map(|len| parse_number(len)).transpose()?;
+                self.dump(address, len.unwrap_or(16))
+            }
+            ("disasm", rest) if rest.len() <= 2 => {
+                let address = rest.first().map(|a| parse_number(a)).transpose()?;
+                let count = rest.get(1).map(|n| parse_number(n)).transpose()?;
+                Ok(self.disassembly(
+                    address.unwrap_or(self.cpu.memory_position),
+                    count.unwrap_or(8),
+                ))
+            }
+            ("live", []) => Ok(self.live()),
+            ("key", [key, rest @ ..]) if matches!(rest, [] | ["up"]) => {
+                let key = parse_number(key)?;
+                if key > 0xF {
+                    return Err(CommandError(format!("no key {:#x}", key)));
+                }
+                let pressed = rest.is_empty();
+                self.cpu.set_key(key as u8, pressed);
+                let action = if pressed { "pressed" } else { "released" };
+                Ok(format!("{} key {:X}", action, key))
+            }
+            _ => Err(CommandError(format!("unknown command: {}", command.trim()))),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or end of input, printing
+    /// a `(chip8)` prompt and each command's output to `output`.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl io::Write) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            if matches!(line.trim(), "quit" | "q") {
+                return Ok(());
+            }
+            match self.execute(&line) {
+                Ok(text) if text.is_empty() => {}
+                Ok(text) => writeln!(output, "{}", text)?,
+                Err(err) => writeln!(output, "error: {}", err)?,
+            }
+        }
+    }
+
+    /// The stop reason followed by the next instruction.
+    fn report(&self, reason: &StopReason) -> String {
+        let next = self.disassembly(self.cpu.memory_position, 1);
+        match reason {
+            StopReason::Stepped => next,
+            reason => format!("{}\n{}", reason, next),
+        }
+    }
+
+    fn registers(&self) -> String {
+        let cpu = &self.cpu;
+        let mut out = format!(
+            "PC {:#05x}  I {:#05x}  DT {:#04x}  ST {:#04x}  stack [",
+            cpu.memory_position, cpu.i, cpu.delay_timer, cpu.sound_timer
+        );
+        let stack: Vec<String> = cpu.stack().iter().map(|a| format!("{:#05x}", a)).collect();
+        out.push_str(&stack.join(", "));
+        out.push(']');
+        for row in cpu.registers.chunks(8).enumerate() {
+            out.push('\n');
+            let cells: Vec<String> = row
+                .1
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X} {:02x}", row.0 * 8 + i, value))
+                .collect();
+            out.push_str(&cells.join("  "));
+        }
+        out
+    }
+
+    fn live(&mut self) -> String {
+        self.live.update(&self.cpu);
+        let live = &self.live;
+        let mark = |slot| {
+            if live.changed_within(slot, 1) {
+                '*'
+            } else {
+                ' '
+            }
+        };
+        let mut out = format!(
+            "DT {:#04x}{}  ST {:#04x}{}  I {:#05x}{}\n[I]",
+            live.delay_timer,
+            mark(LiveSlot::DelayTimer),
+            live.sound_timer,
+            mark(LiveSlot::SoundTimer),
+            live.i,
+            mark(LiveSlot::I)
+        );
+        for (offset, byte) in live.at_i.iter().enumerate() {
+            let _ = write!(out, " {:02x}{}", byte, mark(LiveSlot::AtI(offset)));
+        }
+        out.trim_end().to_string()
+    }
+
+    /// `len` bytes of memory from `address`, as typed by the user.
+    fn memory_range(&self, address: usize, len: usize) -> Result<&[u8], CommandError> {
+        address
+            .checked_add(len)
+            .and_then(|end| self.cpu.memory.get(address..end))
+            .ok_or_else(|| CommandError(format!("{:#05x} is outside memory", address)))
+    }
+
+    fn dump(&self, address: usize, len: usize) -> Result<String, CommandError> {
+        let bytes = self.memory_range(address, len)?;
+        let lines: Vec<String> = bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let mut line = format!("{:#05x}:", address + row * 16);
+                for byte in chunk {
+                    let _ = write!(line, " {:02x}", byte);
+                }
+                line
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn disassembly(&self, address: usize, count: usize) -> String {
+        let memory = &self.cpu.memory;
+        let mut lines = Vec::new();
+        let mut at = address;
+        while lines.len() < count {
+            let Some(instruction) = disassemble_at(memory, at) else {
+                break;
+            };
+            let marker = if self.breakpoints.contains_key(&at) {
+                '*'
+            } else {
+                ' '
+            };
+            let end = (at + instruction.size()).min(memory.len());
+            let bytes: String = memory[at..end]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            lines.push(format!(
+                "{}{:#05x}: {:<4}  {}",
+                marker, at, bytes, instruction
+            ));
+            at += instruction.size();
+        }
+        lines.join("\n")
+    }
+}
+
+fn expr_error(err: ExprError) -> CommandError {
+    CommandError(format!("bad expression {}", err))
+}
+
+/// Hex with `0x`, or decimal.
+pub(crate) fn parse_number(text: &str) -> Result<usize, CommandError> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| CommandError(format!("not a number: {}", text)))
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn debugger(source: &str) -> Debugger {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&assemble(source).unwrap()).unwrap();
+        Debugger::new(cpu)
+    }
+
+    const PROGRAM: &str = "
+        LD V0, 1         ; 0x200
+        CALL sub         ; 0x202
+        LD V2, 3         ; 0x204
+        LD I, 0x300      ; 0x206
+        LD [I], V2       ; 0x208
+        SYS 0            ; 0x20A halt
+    sub:
+        LD V1, 2         ; 0x20C
+        RET              ; 0x20E
+    ";
+
+    #[test]
+    fn repl_runs_commands_until_quit() {
+        let mut dbg = debugger(PROGRAM);
+        let mut output = Vec::new();
+
+        dbg.repl(&b"break 0x206\nfrob\nquit\nstep\n"[..], &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "(chip8) breakpoint at 0x206\n(chip8) error: unknown command: frob\n(chip8) "
+        );
+        assert_eq!(dbg.cpu.memory_position, 0x200);
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut dbg = debugger(PROGRAM);
+        dbg.add_breakpoint(0x206);
+
+        assert_eq!(dbg.continue_execution(), StopReason::Breakpoint(0x206));
+        assert_eq!(dbg.cpu.registers[2], 3);
+        assert_eq!(dbg.continue_execution(), StopReason::Halted);
+    }
+
+    #[test]
+    fn conditional_breakpoints_wait_for_their_condition() {
+        let mut dbg = debugger("loop:\nADD V3, 1\nJP loop");
+        assert_eq!(
+            dbg.execute("break 0x202 if V3 == 0x1F").unwrap(),
+            "breakpoint at 0x202 if V3 == 0x1F"
+        );
+        assert_eq!(
+            dbg.execute("continue").unwrap(),
+            "breakpoint at 0x202\n*0x202: 1200  JP 0x200"
+        );
+        assert_eq!(dbg.cpu.registers[3], 0x1F);
+        assert!(dbg.execute("break 0x202 if V3 ==").is_err());
+        assert_eq!(dbg.breakpoint_condition(0x202), Some("V3 == 0x1F"));
+    }
+
+    #[test]
+    fn expression_watches_stop_when_the_value_changes() {
+        let mut dbg = debugger(PROGRAM);
+        assert_eq!(
+            dbg.execute("watch V1 + mem[0x300]").unwrap(),
+            "watching V1 + mem[0x300]"
+        );
+        assert_eq!(
+            dbg.continue_execution(),
+            StopReason::ExpressionChanged {
+                expression: "V1 + mem[0x300]".to_string(),
+                old: 0,
+                new: 2,
+            }
+        );
+        assert_eq!(dbg.cpu.memory_position, 0x20E);
+        // LD [I], V2 stores V0 = 1 at 0x300
+        assert_eq!(
+            dbg.execute("c").unwrap(),
+            "V1 + mem[0x300] changed 2 -> 3\n 0x20a: 0000  SYS 0x000"
+        );
+    }
+
+    #[test]
+    fn step_over_runs_the_whole_call() {
+        let mut dbg = debugger(PROGRAM);
+
+        assert_eq!(dbg.step_over(), StopReason::Stepped);
+        assert_eq!(dbg.step_over(), StopReason::Stepped);
+        assert_eq!(dbg.cpu.memory_position, 0x204);
+        assert_eq!(dbg.cpu.registers[1], 2);
+    }
+
+    #[test]
+    fn watchpoints_report_changes() {
+        let mut dbg = debugger(PROGRAM);
+        dbg.watch_register(1);
+        dbg.watch_memory(0x300, 4).unwrap();
+
+        assert_eq!(
+            dbg.continue_execution(),
+            StopReason::RegisterChanged {
+                register: 1,
+                old: 0,
+                new: 2
+            }
+        );
+        assert_eq!(
+            dbg.continue_execution(),
+            StopReason::MemoryChanged {
+                address: 0x300,
+                old: 0,
+                new: 1
+            }
+        );
+    }
+
+    #[test]
+    fn text_commands() {
+        let mut dbg = debugger(PROGRAM);
+
+        assert_eq!(dbg.execute("break 0x20c").unwrap(), "breakpoint at 0x20c");
+        assert_eq!(
+            dbg.execute("c").unwrap(),
+            "breakpoint at 0x20c\n*0x20c: 6102  LD V1, 0x02"
+        );
+        assert_eq!(dbg.execute("step").unwrap(), " 0x20e: 00EE  RET");
+        assert!(dbg
+            .execute("regs")
+            .unwrap()
+            .starts_with("PC 0x20e  I 0x000"));
+        assert_eq!(dbg.execute("mem 0x200 4").unwrap(), "0x200: 60 01 22 0c");
+        assert!(dbg.execute("frobnicate").is_err());
+        assert!(dbg.execute("mem zzz").is_err());
+        assert!(dbg.execute("mem 0xffffffffffffffff 16").is_err());
+        assert!(dbg.watch_memory(usize::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn protected_writes_stop_execution() {
+        let mut dbg = debugger(PROGRAM);
+
+        assert_eq!(
+            dbg.execute("protect 0x301 2").unwrap(),
+            "protected 2 bytes at 0x301"
+        );
+        let stop = dbg.execute("c").unwrap();
+        assert!(
+            stop.contains("write to protected address 0x301"),
+            "{}",
+            stop
+        );
+        assert_eq!(dbg.cpu.memory[0x300], 0);
+        assert!(dbg.execute("protect 0xfff 2").is_err());
+    }
+
+    #[test]
+    fn long_loads_are_listed_whole() {
+        let mut dbg = debugger("LD I, LONG 0x1234\nCLS");
+
+        assert_eq!(
+            dbg.execute("disasm 0x200 2").unwrap(),
+            " 0x200: F0001234  LD I, 0x1234\n 0x204: 00E0  CLS"
+        );
+    }
+
+    #[test]
+    fn live_marks_changes_since_last_time() {
+        let mut dbg = debugger(PROGRAM);
+        dbg.add_breakpoint(0x208);
+        dbg.continue_execution();
+        assert!(dbg
+            .execute("live")
+            .unwrap()
+            .starts_with("DT 0x00   ST 0x00   I 0x300*"));
+        dbg.step();
+
+        assert_eq!(
+            dbg.execute("live").unwrap(),
+            "DT 0x00   ST 0x00   I 0x300 \n[I] 01* 02* 03* 00  00  00  00  00  \
+             00  00  00  00  00  00  00  00"
+        );
+        assert!(!dbg.execute("live").unwrap().contains('*'));
+    }
+
+    #[test]
+    fn faults_stop_execution() {
+        let mut dbg = debugger("RET");
+
+        assert!(matches!(dbg.continue_execution(), StopReason::Fault(_)));
+    }
+}
@@ -0,0 +1,330 @@
+use super::{Debugger, LiveWatch, StopReason};
+use crate::audio::{AudioPattern, AudioSource};
+use crate::disasm::disassemble_at;
+
+/// Bytes per [`MemoryRow`].
+pub const MEMORY_ROW: usize = 16;
+
+/// The buttons of a debugger window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    Step,
+    /// Step, running CALLs to completion.
+    StepOver,
+    Run,
+    Pause,
+    /// Restart the program, see [`crate::cpu::CPU::soft_reset`].
+    Reset,
+}
+
+/// What a register and stack panel shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterPanel {
+    pub pc: usize,
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub registers: [u8; 16],
+    /// Return addresses, innermost last.
+    pub stack: Vec<u16>,
+}
+
+/// A line of a hex memory view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRow {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    /// For each byte, whether it belongs to the instruction at PC.
+    pub current: Vec<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    pub address: usize,
+    pub opcode: u16,
+    pub text: String,
+    /// The instruction at PC.
+    pub current: bool,
+    pub breakpoint: bool,
+}
+
+/// The state of a debugger GUI that isn't in the [`Debugger`] itself:
+/// whether the program is running, why it last stopped and where the
+/// memory view is scrolled to. Everything it shows is plain data, so any
+/// toolkit can draw it; call [`DebugView::update`] once per frame it
+/// draws.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugView {
+    /// First address in the memory view.
+    pub memory_top: usize,
+    running: bool,
+    /// Breakpoint that running starts from, which mustn't stop it again.
+    resumed_at: Option<usize>,
+    stopped: Option<StopReason>,
+}
+
+impl DebugView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Why execution last stopped, for a status line.
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.stopped.as_ref()
+    }
+
+    pub fn act(&mut self, debugger: &mut Debugger, action: DebugAction) {
+        match action {
+            DebugAction::Step | DebugAction::StepOver if !self.running => {
+                self.stopped = Some(match action {
+                    DebugAction::Step => debugger.step(),
+                    _ => debugger.step_over(),
+                });
+            }
+            DebugAction::Step | DebugAction::StepOver => {}
+            DebugAction::Run => {
+                self.running = true;
+                self.resumed_at = Some(debugger.cpu.memory_position);
+                self.stopped = None;
+            }
+            DebugAction::Pause => self.running = false,
+            DebugAction::Reset => {
+                debugger.cpu.soft_reset();
+                debugger.executed = 0;
+                debugger.live = LiveWatch::new(&debugger.cpu);
+                self.stopped = None;
+            }
+        }
+    }
+
+    /// While running, executes a frame's worth of instructions, stopping
+    /// at breakpoints, watchpoints, halts and faults. Waiting for a key
+    /// only ends the frame early, so games can be played in the window.
+    pub fn update(&mut self, debugger: &mut Debugger) {
+        if !self.running {
+            return;
+        }
+        for _ in 0..debugger.instructions_per_frame.max(1) {
+            let pc = debugger.cpu.memory_position;
+            if self.resumed_at.take() != Some(pc) && debugger.breaks_at(pc) {
+                self.stop(StopReason::Breakpoint(pc));
+                return;
+            }
+            match debugger.step() {
+                StopReason::Stepped => {}
+                StopReason::WaitingForKey => return,
+                reason => {
+                    self.stop(reason);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.running = false;
+        self.stopped = Some(reason);
+    }
+
+    pub fn registers(debugger: &Debugger) -> RegisterPanel {
+        let cpu = &debugger.cpu;
+        RegisterPanel {
+            pc: cpu.memory_position,
+            i: cpu.i,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            registers: cpu.registers,
+            stack: cpu.stack().to_vec(),
+        }
+    }
+
+    /// The XO-CHIP audio pattern and where playback is in it, to draw with
+    /// [`AudioPattern::waveform`].
+    pub fn audio(debugger: &Debugger) -> AudioPattern {
+        debugger.cpu.pattern()
+    }
+
+    /// `rows` rows of memory from [`DebugView::memory_top`], fewer at the
+    /// end of memory.
+    pub fn memory(&self, debugger: &Debugger, rows: usize) -> Vec<MemoryRow> {
+        let memory = &debugger.cpu.memory;
+        let pc = debugger.cpu.memory_position;
+        let current = pc..pc + instruction_len(debugger, pc);
+        (0..rows)
+            .map(|row| self.memory_top + row * MEMORY_ROW)
+            .take_while(|address| *address < memory.len())
+            .map(|address| {
+                let end = (address + MEMORY_ROW).min(memory.len());
+                MemoryRow {
+                    address,
+                    bytes: memory[address..end].to_vec(),
+                    current: (address..end).map(|at| current.contains(&at)).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Scrolls the memory view just enough for the row with PC to be
+    /// among the `rows` shown.
+    pub fn follow_pc(&mut self, debugger: &Debugger, rows: usize) {
+        let row = debugger.cpu.memory_position / MEMORY_ROW * MEMORY_ROW;
+        if row < self.memory_top {
+            self.memory_top = row;
+        } else if row >= self.memory_top + rows * MEMORY_ROW {
+            self.memory_top = row + MEMORY_ROW - rows.max(1) * MEMORY_ROW;
+        }
+    }
+
+    /// `lines` instructions around PC, a quarter of them before it.
+    /// Four-byte loads take one line, with their address word.
+    pub fn disassembly(debugger: &Debugger, lines: usize) -> Vec<DisassemblyLine> {
+        let memory = &debugger.cpu.memory;
+        let pc = debugger.cpu.memory_position;
+        // a guess at where the instructions before PC start, going back two
+        // bytes at a time unless that lands on a long load's address word
+        let mut address = pc;
+        for _ in 0..lines / 4 {
+            let long = address
+                .checked_sub(4)
+                .and_then(|at| disassemble_at(memory, at))
+                .is_some_and(|instruction| instruction.size() == 4);
+            match address.checked_sub(if long { 4 } else { 2 }) {
+                Some(before) => address = before,
+                None => break,
+            }
+        }
+        let mut listing = Vec::new();
+        while listing.len() < lines {
+            let Some(instruction) = disassemble_at(memory, address) else {
+                break;
+            };
+            listing.push(DisassemblyLine {
+                address,
+                opcode: u16::from_be_bytes([memory[address], memory[address + 1]]),
+                text: instruction.to_string(),
+                current: address == pc,
+                breakpoint: debugger.breakpoints.contains_key(&address),
+            });
+            // a long load read from a wrong guess mustn't hide PC
+            address = match address + instruction.size() {
+                next if address < pc && next > pc => pc,
+                next => next,
+            };
+        }
+        listing
+    }
+}
+
+/// Bytes the instruction at `address` takes, four for XO-CHIP's F000.
+fn instruction_len(debugger: &Debugger, address: usize) -> usize {
+    let cpu = &debugger.cpu;
+    let long = cpu.mode.has_xo_chip()
+        && cpu.memory.get(address) == Some(&0xF0)
+        && cpu.memory.get(address + 1) == Some(&0x00);
+    if long {
+        4
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::CPU;
+
+    fn debugger() -> Debugger {
+        let program = "
+                LD V0, 1      ; 0x200
+            loop:
+                ADD V1, 1     ; 0x202
+                CALL sub      ; 0x204
+                JP loop       ; 0x206
+            sub:
+                RET           ; 0x208
+        ";
+        let mut cpu = CPU::new();
+        cpu.load_rom(&assemble(program).unwrap()).unwrap();
+        Debugger::new(cpu)
+    }
+
+    #[test]
+    fn running_stops_at_breakpoints_and_resumes_past_them() {
+        let mut debugger = debugger();
+        let mut view = DebugView::new();
+        debugger.add_breakpoint(0x208);
+        view.act(&mut debugger, DebugAction::Run);
+        view.update(&mut debugger);
+        assert!(!view.is_running());
+        assert_eq!(view.stop_reason(), Some(&StopReason::Breakpoint(0x208)));
+        let panel = DebugView::registers(&debugger);
+        assert_eq!((panel.pc, panel.registers[1]), (0x208, 1));
+        assert_eq!(panel.stack, [0x206]);
+
+        view.act(&mut debugger, DebugAction::Run);
+        view.update(&mut debugger);
+        assert_eq!(DebugView::registers(&debugger).registers[1], 2);
+
+        view.act(&mut debugger, DebugAction::Reset);
+        assert_eq!(debugger.cpu.memory_position, 0x200);
+        view.act(&mut debugger, DebugAction::Step);
+        assert_eq!(debugger.cpu.registers[0], 1);
+    }
+
+    #[test]
+    fn memory_view_follows_pc_and_highlights_it() {
+        let mut debugger = debugger();
+        let mut view = DebugView::new();
+        view.follow_pc(&debugger, 4);
+        assert_eq!(view.memory_top, 0x1D0);
+        let rows = view.memory(&debugger, 4);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[3].address, 0x200);
+        assert_eq!(rows[3].bytes[..2], [0x60, 0x01]);
+        assert_eq!(rows[3].current[..3], [true, true, false]);
+
+        debugger.cpu.memory_position = 0x100;
+        view.follow_pc(&debugger, 4);
+        assert_eq!(view.memory_top, 0x100);
+    }
+
+    #[test]
+    fn disassembly_marks_pc_and_breakpoints() {
+        let mut debugger = debugger();
+        debugger.add_breakpoint(0x206);
+        view_step(&mut debugger, 3);
+        let lines = DebugView::disassembly(&debugger, 4);
+        let addresses: Vec<usize> = lines.iter().map(|line| line.address).collect();
+        assert_eq!(addresses, [0x206, 0x208, 0x20A, 0x20C]);
+        assert!(lines[1].current && !lines[0].current);
+        assert!(lines[0].breakpoint && !lines[1].breakpoint);
+        assert_eq!(lines[1].text, "RET");
+    }
+
+    #[test]
+    fn long_loads_take_one_line() {
+        let mut cpu = CPU::new_with_mode(crate::cpu::EmulatorMode::XoChip);
+        let rom = assemble("LD I, LONG 0x1234\nCLS\nCLS").unwrap();
+        cpu.load_rom(&rom).unwrap();
+        let mut debugger = Debugger::new(cpu);
+        view_step(&mut debugger, 1);
+
+        let lines = DebugView::disassembly(&debugger, 4);
+        let addresses: Vec<usize> = lines.iter().map(|line| line.address).collect();
+        assert_eq!(addresses, [0x200, 0x204, 0x206, 0x208]);
+        assert_eq!(lines[0].text, "LD I, 0x1234");
+        assert!(lines[1].current);
+    }
+
+    fn view_step(debugger: &mut Debugger, steps: usize) {
+        let mut view = DebugView::new();
+        for _ in 0..steps {
+            view.act(debugger, DebugAction::Step);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use alloc::collections::btree_map::Entry;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{disassemble, Instruction};
+use crate::cpu::PROGRAM_START;
+
+/// Subroutines entered one from the other, outermost first, each by the
+/// address CALL jumped to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallChain(pub Vec<u16>);
+
+impl CallChain {
+    /// Whether some subroutine appears more than once, i.e. is being
+    /// called from within itself.
+    pub fn is_recursive(&self) -> bool {
+        let mut seen = BTreeSet::new();
+        !self.0.iter().all(|entry| seen.insert(*entry))
+    }
+}
+
+/// E.g. `0x210 -> 0x220 -> 0x210`.
+impl fmt::Display for CallChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{:#05x}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds cycles of subroutines calling each other where none of them can
+/// get back out without going round again, so running any of them fills
+/// the stack. Each comes back as the chain from the lowest subroutine in
+/// the cycle round to itself.
+///
+/// Code is followed from [`PROGRAM_START`] through jumps, both outcomes of
+/// every skip and into every CALL. RET, EXIT, a loop that doesn't call
+/// back in and anything this can't follow, such as BNNN or data, all count
+/// as ways out, so only recursion that is certain to overflow is reported.
+pub fn find_unbounded_recursion(rom: &[u8]) -> Vec<CallChain> {
+    // every subroutine and the ones it calls
+    let mut calls: BTreeMap<u16, BTreeSet<u16>> = BTreeMap::new();
+    let mut pending = vec![PROGRAM_START as u16];
+    while let Some(entry) = pending.pop() {
+        if let Entry::Vacant(slot) = calls.entry(entry) {
+            let targets = called_from(rom, entry);
+            pending.extend(&targets);
+            slot.insert(targets);
+        }
+    }
+    let reach: BTreeMap<u16, BTreeSet<u16>> = calls
+        .keys()
+        .map(|entry| (*entry, reachable(&calls, *entry)))
+        .collect();
+
+    let mut found = Vec::new();
+    let mut done: BTreeSet<u16> = BTreeSet::new();
+    for entry in calls.keys() {
+        if done.contains(entry) || !reach[entry].contains(entry) {
+            continue;
+        }
+        // this one and the ones that call back into it
+        let cycle: BTreeSet<u16> = reach[entry]
+            .iter()
+            .copied()
+            .filter(|other| reach[other].contains(entry))
+            .collect();
+        done.extend(&cycle);
+        if cycle.iter().all(|member| !escapes(rom, *member, &cycle)) {
+            found.push(CallChain(chain(&calls, *entry, &cycle)));
+        }
+    }
+    found
+}
+
+/// The instruction at `address` of a ROM loaded at [`PROGRAM_START`],
+/// `None` outside it.
+fn instruction_at(rom: &[u8], address: u16) -> Option<Instruction> {
+    let offset = (address as usize).checked_sub(PROGRAM_START)?;
+    let word = |at: usize| Some(((*rom.get(at)? as u16) << 8) | *rom.get(at + 1)? as u16);
+    match disassemble(word(offset)?) {
+        Instruction::LdILong(_) => word(offset + 2).map(Instruction::LdILong),
+        Instruction::LdIHuge(high) => {
+            word(offset + 2).map(|low| Instruction::LdIHuge(high | low as u32))
+        }
+        instruction => Some(instruction),
+    }
+}
+
+/// Where execution can go after the instruction at `address`, taking every
+/// CALL to return. Empty for the instructions that end a path.
+fn successors(rom: &[u8], address: u16, instruction: Instruction) -> Vec<u16> {
+    let next = address.wrapping_add(instruction.size() as u16);
+    match instruction {
+        Instruction::Ret
+        | Instruction::Exit
+        | Instruction::Sys(_)
+        | Instruction::JpOffset { .. }
+        | Instruction::Unknown(_) => Vec::new(),
+        Instruction::Jp(target) => vec![target],
+        Instruction::SeByte { .. }
+        | Instruction::SneByte { .. }
+        | Instruction::SeReg { .. }
+        | Instruction::SneReg { .. }
+        | Instruction::Skp(_)
+        | Instruction::Sknp(_) => {
+            let skipped = instruction_at(rom, next).map_or(2, |after| after.size() as u16);
+            vec![next, next.wrapping_add(skipped)]
+        }
+        _ => vec![next],
+    }
+}
+
+/// Targets of every CALL the subroutine at `entry` can reach.
+fn called_from(rom: &[u8], entry: u16) -> BTreeSet<u16> {
+    let mut targets = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(address) = pending.pop() {
+        if !seen.insert(address) {
+            continue;
+        }
+        let Some(instruction) = instruction_at(rom, address) else {
+            continue;
+        };
+        if let Instruction::Call(target) = instruction {
+            targets.insert(target);
+        }
+        pending.extend(successors(rom, address, instruction));
+    }
+    targets
+}
+
+/// Subroutines `entry` ends up calling, directly or not.
+fn reachable(calls: &BTreeMap<u16, BTreeSet<u16>>, entry: u16) -> BTreeSet<u16> {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(from) = pending.pop() {
+        for target in &calls[&from] {
+            if seen.insert(*target) {
+                pending.push(*target);
+            }
+        }
+    }
+    seen
+}
+
+/// Whether the subroutine at `entry` has a way out that doesn't call into
+/// `cycle`, those calls being taken to never return.
+fn escapes(rom: &[u8], entry: u16, cycle: &BTreeSet<u16>) -> bool {
+    let trapped = |address: u16| {
+        matches!(instruction_at(rom, address),
+            Some(Instruction::Call(target)) if cycle.contains(&target))
+    };
+    if trapped(entry) {
+        return false;
+    }
+    let mut flow: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+    let mut pending = vec![entry];
+    while let Some(address) = pending.pop() {
+        if flow.contains_key(&address) {
+            continue;
+        }
+        let Some(instruction) = instruction_at(rom, address) else {
+            return true;
+        };
+        let next = successors(rom, address, instruction);
+        if next.is_empty() {
+            return true;
+        }
+        let next: Vec<u16> = next.into_iter().filter(|next| !trapped(*next)).collect();
+        pending.extend(&next);
+        flow.insert(address, next);
+    }
+    // nothing ends, but a loop that never calls back in is just as safe
+    has_loop(&flow)
+}
+
+fn has_loop(flow: &BTreeMap<u16, Vec<u16>>) -> bool {
+    let mut incoming: BTreeMap<u16, usize> = flow.keys().map(|address| (*address, 0)).collect();
+    for next in flow.values().flatten() {
+        *incoming.get_mut(next).unwrap() += 1;
+    }
+    let mut ready: Vec<u16> = incoming
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(address, _)| *address)
+        .collect();
+    let mut removed = 0;
+    while let Some(address) = ready.pop() {
+        removed += 1;
+        for next in &flow[&address] {
+            let count = incoming.get_mut(next).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(*next);
+            }
+        }
+    }
+    removed < flow.len()
+}
+
+/// A shortest way round `cycle` from `entry` back to itself.
+fn chain(calls: &BTreeMap<u16, BTreeSet<u16>>, entry: u16, cycle: &BTreeSet<u16>) -> Vec<u16> {
+    let mut came_from: BTreeMap<u16, u16> = BTreeMap::new();
+    let mut queue = VecDeque::from([entry]);
+    while let Some(from) = queue.pop_front() {
+        for target in &calls[&from] {
+            if *target == entry {
+                let mut path = vec![entry, from];
+                while let Some(previous) = came_from.get(path.last().unwrap()) {
+                    path.push(*previous);
+                }
+                path.reverse();
+                return path;
+            }
+            if cycle.contains(target) && !came_from.contains_key(target) {
+                came_from.insert(*target, from);
+                queue.push_back(*target);
+            }
+        }
+    }
+    vec![entry]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn recursion(source: &str) -> Vec<String> {
+        let rom = assemble(source).unwrap();
+        find_unbounded_recursion(&rom)
+            .iter()
+            .map(CallChain::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn cycles_without_a_way_out_are_found() {
+        let found = recursion(
+            "
+                CALL first
+            halt:
+                JP halt
+            first:
+                CALL second
+                RET
+            second:
+                ADD V0, 1
+                CALL first
+                RET
+            ",
+        );
+        assert_eq!(found, ["0x204 -> 0x208 -> 0x204"]);
+
+        let found = recursion(
+            "
+                CALL self
+                EXIT
+            self:
+                CALL self
+            ",
+        );
+        assert_eq!(found, ["0x204 -> 0x204"]);
+    }
+
+    #[test]
+    fn conditional_returns_bound_the_recursion() {
+        let found = recursion(
+            "
+                CALL countdown
+                EXIT
+            countdown:
+                SE V0, 0
+                JP again
+                RET
+            again:
+                ADD V0, 255
+                CALL countdown
+                RET
+            ",
+        );
+        assert!(found.is_empty(), "{:?}", found);
+    }
+
+    #[test]
+    fn chains_know_when_they_repeat() {
+        assert!(CallChain(vec![0x300, 0x310, 0x300]).is_recursive());
+        assert!(!CallChain(vec![0x300, 0x310]).is_recursive());
+        assert_eq!(CallChain(vec![0x300, 0x310]).to_string(), "0x300 -> 0x310");
+    }
+}
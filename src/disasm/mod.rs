@@ -0,0 +1,408 @@
+//! Turns CHIP-8 bytecode back into readable assembly.
+//!
+//! Every instruction of the family is recognized regardless of which
+//! variant is running; check [`crate::reference`] to find out where an
+//! instruction is available.
+
+mod calls;
+mod report;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use crate::cpu::{decode, PROGRAM_START};
+
+pub use calls::{find_unbounded_recursion, CallChain};
+pub use report::{collect_coverage, html_report, profile_report};
+
+/// One decoded instruction. Register operands are indices `0..=0xF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN, a call into COSMAC VIP machine code.
+    Sys(u16),
+    Cls,
+    Ret,
+    ScrollDown(u8),
+    ScrollUp(u8),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    Jp(u16),
+    Call(u16),
+    SeByte {
+        x: u8,
+        nn: u8,
+    },
+    SneByte {
+        x: u8,
+        nn: u8,
+    },
+    SeReg {
+        x: u8,
+        y: u8,
+    },
+    SaveRange {
+        x: u8,
+        y: u8,
+    },
+    LoadRange {
+        x: u8,
+        y: u8,
+    },
+    LdByte {
+        x: u8,
+        nn: u8,
+    },
+    AddByte {
+        x: u8,
+        nn: u8,
+    },
+    LdReg {
+        x: u8,
+        y: u8,
+    },
+    Or {
+        x: u8,
+        y: u8,
+    },
+    And {
+        x: u8,
+        y: u8,
+    },
+    Xor {
+        x: u8,
+        y: u8,
+    },
+    AddReg {
+        x: u8,
+        y: u8,
+    },
+    Sub {
+        x: u8,
+        y: u8,
+    },
+    Shr {
+        x: u8,
+        y: u8,
+    },
+    Subn {
+        x: u8,
+        y: u8,
+    },
+    Shl {
+        x: u8,
+        y: u8,
+    },
+    SneReg {
+        x: u8,
+        y: u8,
+    },
+    LdI(u16),
+    /// BNNN. `x` is the register CHIP-48's BXNN reading would use.
+    JpOffset {
+        x: u8,
+        nnn: u16,
+    },
+    Rnd {
+        x: u8,
+        nn: u8,
+    },
+    Drw {
+        x: u8,
+        y: u8,
+        n: u8,
+    },
+    Skp(u8),
+    Sknp(u8),
+    /// F000 NNNN. The address is the word after the opcode, see
+    /// [`disassemble_rom`]; [`disassemble`] alone leaves it 0.
+    LdILong(u16),
+    Plane(u8),
+    Audio,
+    LdVxDt(u8),
+    LdKey(u8),
+    LdDt(u8),
+    LdSt(u8),
+    AddI(u8),
+    LdFont(u8),
+    LdBigFont(u8),
+    Bcd(u8),
+    Pitch(u8),
+    Store(u8),
+    Load(u8),
+    StoreRpl(u8),
+    LoadRpl(u8),
+    /// Mega-Chip's 0010, back to the SUPER-CHIP screen.
+    MegaOff,
+    /// Mega-Chip's 0011, the 256x192 indexed-color screen.
+    MegaOn,
+    /// 01NN NNNN, Mega-Chip's 24-bit `I := NNNNNN`. Like
+    /// [`Instruction::LdILong`] the low word is only filled in by
+    /// [`disassemble_rom`].
+    LdIHuge(u32),
+    LdPalette(u8),
+    SpriteWidth(u8),
+    SpriteHeight(u8),
+    Alpha(u8),
+    DigiSound(u8),
+    StopSound,
+    BlendMode(u8),
+    CollisionColor(u8),
+    /// Not an instruction of any variant, most likely sprite or other data.
+    Unknown(u16),
+}
+
+impl Instruction {
+    /// Size in bytes, 4 for the XO-CHIP and Mega-Chip long loads and 2 for
+    /// everything else.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LdILong(_) | Instruction::LdIHuge(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether this is one of the Mega-Chip instructions, which take over
+    /// part of the 0NNN range.
+    pub fn is_mega_chip(&self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            MegaOff
+                | MegaOn
+                | LdIHuge(_)
+                | LdPalette(_)
+                | SpriteWidth(_)
+                | SpriteHeight(_)
+                | Alpha(_)
+                | DigiSound(_)
+                | StopSound
+                | BlendMode(_)
+                | CollisionColor(_)
+        )
+    }
+}
+
+/// Decodes a single opcode, mapping anything that is not an instruction
+/// to [`Instruction::Unknown`].
+pub fn disassemble(opcode: u16) -> Instruction {
+    decode(opcode).unwrap_or(Instruction::Unknown(opcode))
+}
+
+/// The instruction at `memory[address]`, with the address word of a
+/// four-byte load filled in as [`disassemble_rom`] does. A load cut off by
+/// the end of memory is [`Instruction::Unknown`]; `None` if not even the
+/// opcode fits.
+pub fn disassemble_at(memory: &[u8], address: usize) -> Option<Instruction> {
+    let word = |at: usize| {
+        let bytes = memory.get(at..at.checked_add(2)?)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    let opcode = word(address)?;
+    let low = address.checked_add(2).and_then(word);
+    Some(match (disassemble(opcode), low) {
+        (Instruction::LdILong(_), Some(low)) => Instruction::LdILong(low),
+        (Instruction::LdIHuge(high), Some(low)) => Instruction::LdIHuge(high | low as u32),
+        (instruction, None) if instruction.size() == 4 => Instruction::Unknown(opcode),
+        (instruction, _) => instruction,
+    })
+}
+
+/// Disassembles a whole ROM as loaded at [`crate::cpu::PROGRAM_START`].
+///
+/// Returns `(address, instruction, text)` for every instruction. Sprite
+/// data mixed in with code comes out as whatever it happens to decode to.
+/// A trailing odd byte is reported as [`Instruction::Unknown`].
+pub fn disassemble_rom(rom: &[u8]) -> Vec<(u16, Instruction, String)> {
+    let mut listing = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let word = |at: usize| {
+            let high = rom[at] as u16;
+            let low = rom.get(at + 1).copied().unwrap_or(0) as u16;
+            (high << 8) | low
+        };
+        let mut instruction = disassemble(word(offset));
+        if offset + 1 == rom.len() {
+            instruction = Instruction::Unknown(word(offset));
+        } else if instruction.size() == 4 {
+            let opcode = word(offset);
+            instruction = match instruction {
+                _ if offset + 4 > rom.len() => Instruction::Unknown(opcode),
+                Instruction::LdIHuge(high) => Instruction::LdIHuge(high | word(offset + 2) as u32),
+                _ => Instruction::LdILong(word(offset + 2)),
+            };
+        }
+        let address = (PROGRAM_START + offset) as u16;
+        listing.push((address, instruction, instruction.to_string()));
+        offset += instruction.size();
+    }
+    listing
+}
+
+/// [`disassemble_rom`] as text, one `address: opcode  mnemonic` line per
+/// instruction, laid out like the debugger's `disasm` command.
+pub fn listing(rom: &[u8]) -> String {
+    let mut text = String::new();
+    for (address, instruction, mnemonic) in disassemble_rom(rom) {
+        let offset = address as usize - PROGRAM_START;
+        let end = (offset + instruction.size()).min(rom.len());
+        let opcode: String = rom[offset..end]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let _ = writeln!(text, "{:#05x}: {:<4}  {}", address, opcode, mnemonic);
+    }
+    text
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Jp(nnn) => write!(f, "JP {:#05X}", nnn),
+            Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            SeByte { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            SneByte { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            SeReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            LdByte { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            AddByte { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            LdReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SneReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            JpOffset { nnn, .. } => write!(f, "JP V0, {:#05X}", nnn),
+            Rnd { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp(x) => write!(f, "SKP V{:X}", x),
+            Sknp(x) => write!(f, "SKNP V{:X}", x),
+            LdILong(nnnn) => write!(f, "LD I, {:#06X}", nnnn),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            LdKey(x) => write!(f, "LD V{:X}, K", x),
+            LdDt(x) => write!(f, "LD DT, V{:X}", x),
+            LdSt(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            LdFont(x) => write!(f, "LD F, V{:X}", x),
+            LdBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Load(x) => write!(f, "LD V{:X}, [I]", x),
+            StoreRpl(x) => write!(f, "LD R, V{:X}", x),
+            LoadRpl(x) => write!(f, "LD V{:X}, R", x),
+            MegaOff => write!(f, "MEGAOFF"),
+            MegaOn => write!(f, "MEGAON"),
+            LdIHuge(nnnnnn) => write!(f, "LDHI {:#08X}", nnnnnn),
+            LdPalette(nn) => write!(f, "LDPAL {}", nn),
+            SpriteWidth(nn) => write!(f, "SPRW {}", nn),
+            SpriteHeight(nn) => write!(f, "SPRH {}", nn),
+            Alpha(nn) => write!(f, "ALPHA {:#04X}", nn),
+            DigiSound(n) => write!(f, "DIGISND {}", n),
+            StopSound => write!(f, "STOPSND"),
+            BlendMode(n) => write!(f, "BMODE {}", n),
+            CollisionColor(nn) => write!(f, "CCOL {}", nn),
+            Unknown(opcode) => write!(f, "DW {:#06X}", opcode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_shows_addresses_and_opcodes() {
+        assert_eq!(
+            listing(&[0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x6A]),
+            "0x200: 00E0  CLS\n0x202: F0001234  LD I, 0x1234\n0x206: 6A    DW 0x6A00\n"
+        );
+    }
+
+    #[test]
+    fn readable_mnemonics() {
+        assert_eq!(disassemble(0x600A).to_string(), "LD V0, 0x0A");
+        assert_eq!(disassemble(0x1222).to_string(), "JP 0x222");
+        assert_eq!(disassemble(0xD12F).to_string(), "DRW V1, V2, 15");
+        assert_eq!(disassemble(0xFA65).to_string(), "LD VA, [I]");
+        assert_eq!(disassemble(0x8AB6).to_string(), "SHR VA, VB");
+    }
+
+    #[test]
+    fn fields_are_extracted() {
+        assert_eq!(
+            disassemble(0x3C42),
+            Instruction::SeByte { x: 0xC, nn: 0x42 }
+        );
+        assert_eq!(
+            disassemble(0xB123),
+            Instruction::JpOffset { x: 1, nnn: 0x123 }
+        );
+        assert_eq!(disassemble(0x00C7), Instruction::ScrollDown(7));
+    }
+
+    #[test]
+    fn invalid_encodings_are_unknown() {
+        assert_eq!(disassemble(0x5121), Instruction::Unknown(0x5121));
+        assert_eq!(disassemble(0x9121), Instruction::Unknown(0x9121));
+        assert_eq!(disassemble(0xE0FF).to_string(), "DW 0xE0FF");
+    }
+
+    #[test]
+    fn agrees_with_the_reference_table() {
+        for info in crate::reference::OPCODES {
+            let instruction = disassemble(info.pattern);
+            assert!(
+                !matches!(instruction, Instruction::Unknown(_)),
+                "{} is not disassembled",
+                info.encoding
+            );
+        }
+    }
+
+    #[test]
+    fn rom_listing_has_addresses_and_long_loads() {
+        let listing = disassemble_rom(&[0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xFF]);
+
+        let text: Vec<(u16, &str)> = listing
+            .iter()
+            .map(|(addr, _, text)| (*addr, text.as_str()))
+            .collect();
+        assert_eq!(
+            text,
+            [
+                (0x200, "CLS"),
+                (0x202, "LD I, 0x1234"),
+                (0x206, "JP 0x200"),
+                (0x208, "DW 0xFF00"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::{disassemble, disassemble_rom, Instruction};
+use crate::cpu::{AccessStats, Status, CPU, PROGRAM_START};
+use crate::reference;
+
+/// Runs `cpu` for up to `frames` frames and returns the address of every
+/// instruction that executed, for [`html_report`]. Stops early on halt or
+/// fault; a program blocked on FX0A simply stops gathering coverage.
+pub fn collect_coverage(
+    cpu: &mut CPU,
+    frames: usize,
+    instructions_per_frame: usize,
+) -> BTreeSet<u16> {
+    let mut executed = BTreeSet::new();
+    for _ in 0..frames {
+        for _ in 0..instructions_per_frame {
+            let pc = cpu.memory_position as u16;
+            match cpu.step() {
+                Ok(Status::Continue) => {
+                    executed.insert(pc);
+                }
+                Ok(Status::WaitingForKey) => {
+                    executed.insert(pc);
+                    break;
+                }
+                Ok(Status::Halted) => {
+                    executed.insert(pc);
+                    return executed;
+                }
+                Err(_) => return executed,
+            }
+        }
+        cpu.tick_timers();
+    }
+    executed
+}
+
+const STYLE: &str = "\
+body { font-family: monospace; background: #111; color: #ccc; }
+table { border-collapse: collapse; }
+td { padding: 0 1em 0 0; }
+tr.hit { background: #243; color: #fff; }
+tr:target { outline: 1px solid #fc6; }
+a { color: #fc6; }
+.addr { color: #888; }
+";
+
+/// Renders `rom` as a standalone HTML page: one row per instruction, jump
+/// and call targets linked to their row, rows in `executed` highlighted and
+/// the reference description of each opcode shown on hover.
+pub fn html_report(title: &str, rom: &[u8], executed: &BTreeSet<u16>) -> String {
+    let listing = disassemble_rom(rom);
+    let targets: BTreeSet<u16> = listing
+        .iter()
+        .filter_map(|(_, instruction, _)| target(*instruction))
+        .collect();
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p>{} instructions, {} executed</p>\n<table>\n",
+        listing.len(),
+        executed.len(),
+        title = escape(title),
+    );
+    for (address, instruction, text) in &listing {
+        let class = if executed.contains(address) {
+            " class=\"hit\""
+        } else {
+            ""
+        };
+        let label = if targets.contains(address) {
+            format!("L{:03X}:", address)
+        } else {
+            String::new()
+        };
+        let offset = *address as usize - PROGRAM_START;
+        let bytes = &rom[offset..(offset + instruction.size()).min(rom.len())];
+        let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let hint = match instruction {
+            Instruction::Unknown(_) => "Not an instruction, likely data.".to_string(),
+            _ => reference::lookup(u16::from_be_bytes([bytes[0], bytes[1]]))
+                .map(|info| format!("{}: {}", info.encoding, info.description))
+                .unwrap_or_default(),
+        };
+        let text = match target(*instruction) {
+            Some(to) => format!("<a href=\"#L{:03X}\">{}</a>", to, escape(text)),
+            None => escape(text),
+        };
+        let _ = writeln!(
+            html,
+            "<tr id=\"L{address:03X}\"{class} title=\"{}\"><td>{label}</td>\
+             <td class=\"addr\">{address:03X}</td><td>{hex}</td><td>{text}</td></tr>",
+            escape(&hint),
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Loops and instructions listed in a [`profile_report`].
+const HOTTEST: usize = 10;
+
+/// Summarises a profiling run, `stats` from a CPU with
+/// [`crate::cpu::Memory::set_tracking_access`] on: how much of `rom` ran
+/// as code or was read as data, the ranges never touched at all, which are
+/// dead code or unused data, and the loops and instructions that ran the
+/// most. Loops are found by their backward jumps.
+pub fn profile_report(title: &str, rom: &[u8], stats: &AccessStats) -> String {
+    let count =
+        |counts: &[u32], offset: usize| counts.get(PROGRAM_START + offset).copied().unwrap_or(0);
+    let opcode = |offset: usize| {
+        let byte = |at: usize| rom.get(at).copied().unwrap_or(0) as u16;
+        byte(offset) << 8 | byte(offset + 1)
+    };
+
+    let mut code = vec![false; rom.len()];
+    let mut hot = Vec::new();
+    let mut loops = Vec::new();
+    let mut executed = 0u64;
+    for offset in (0..rom.len()).filter(|offset| count(&stats.executes, *offset) > 0) {
+        let times = count(&stats.executes, offset);
+        let instruction = disassemble(opcode(offset));
+        for byte in code.iter_mut().skip(offset).take(instruction.size()) {
+            *byte = true;
+        }
+        let address = PROGRAM_START + offset;
+        if let Instruction::Jp(to) = instruction {
+            if (to as usize) <= address {
+                loops.push((times, to as usize, address));
+            }
+        }
+        executed += times as u64;
+        hot.push((times, address, opcode(offset), instruction));
+    }
+    let read = |offset: usize| count(&stats.reads, offset) > 0 || count(&stats.writes, offset) > 0;
+    let data = (0..rom.len())
+        .filter(|offset| !code[*offset] && read(*offset))
+        .count();
+    let ran = code.iter().filter(|byte| **byte).count();
+
+    let mut out = format!(
+        "{}
+{} instructions executed; {} of {} ROM bytes ({}%) ran as code, {} read as data
+",
+        title,
+        executed,
+        ran,
+        rom.len(),
+        ran * 100 / rom.len().max(1),
+        data
+    );
+    out.push_str("\nnever touched:\n");
+    let mut offset = 0;
+    while offset < rom.len() {
+        if code[offset] || read(offset) {
+            offset += 1;
+            continue;
+        }
+        let start = offset;
+        while offset < rom.len() && !code[offset] && !read(offset) {
+            offset += 1;
+        }
+        let _ = writeln!(
+            out,
+            "  {:#05x}..{:#05x}  {} bytes",
+            PROGRAM_START + start,
+            PROGRAM_START + offset,
+            offset - start
+        );
+    }
+
+    out.push_str("\nhottest loops:\n");
+    loops.sort_by_key(|(times, start, _)| (core::cmp::Reverse(*times), *start));
+    for (times, start, end) in loops.iter().take(HOTTEST) {
+        let _ = writeln!(out, "  {:#05x}..={:#05x}  {} iterations", start, end, times);
+    }
+
+    out.push_str("\nhottest instructions:\n");
+    hot.sort_by_key(|(times, address, ..)| (core::cmp::Reverse(*times), *address));
+    for (times, address, opcode, instruction) in hot.iter().take(HOTTEST) {
+        let _ = writeln!(
+            out,
+            "  {:#05x}: {:04X}  {:<16} {}",
+            address,
+            opcode,
+            instruction.to_string(),
+            times
+        );
+    }
+    out
+}
+
+/// The address a jump or call transfers control to.
+fn target(instruction: Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Jp(addr)
+        | Instruction::Call(addr)
+        | Instruction::JpOffset { nnn: addr, .. } => Some(addr),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CLS; CALL 0x206; halt; RET
+    const ROM: [u8; 8] = [0x00, 0xE0, 0x22, 0x06, 0x00, 0x00, 0x00, 0xEE];
+
+    #[test]
+    fn coverage_follows_execution() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&ROM).unwrap();
+
+        let executed = collect_coverage(&mut cpu, 1, 100);
+
+        assert_eq!(executed, BTreeSet::from([0x200, 0x202, 0x204, 0x206]));
+    }
+
+    #[test]
+    fn report_links_targets_and_highlights_hits() {
+        let executed = BTreeSet::from([0x200, 0x202]);
+
+        let html = html_report("test <rom>", &ROM, &executed);
+
+        assert!(html.contains("<title>test &lt;rom&gt;</title>"));
+        assert!(html.contains("<a href=\"#L206\">"));
+        assert!(html
+            .contains("<tr id=\"L206\" title=\"00EE: Return from a subroutine.\"><td>L206:</td>"));
+        assert!(html.contains("<tr id=\"L200\" class=\"hit\""));
+        assert!(!html.contains("<tr id=\"L204\" class=\"hit\""));
+    }
+
+    #[test]
+    fn profiles_find_dead_code_and_hot_loops() {
+        let rom = crate::asm::assemble(
+            "
+                LD I, table    ; 0x200
+                LD V0, [I]     ; 0x202
+            loop:
+                ADD V1, 1      ; 0x204
+                SE V1, 10      ; 0x206
+                JP loop        ; 0x208
+                SYS 0          ; 0x20A halt
+                LD V2, 2       ; 0x20C never runs
+            table:
+                DB 0x05, 0x06  ; 0x20E, only the first byte is read
+            ",
+        )
+        .unwrap();
+        let mut cpu = CPU::new();
+        cpu.load_rom(&rom).unwrap();
+        cpu.memory.set_tracking_access(true);
+        collect_coverage(&mut cpu, 1, 100);
+
+        let report = profile_report("test", &rom, &cpu.memory.access_stats().unwrap());
+        assert!(
+            report.starts_with(
+                "test\n32 instructions executed; 12 of 16 ROM bytes (75%) ran as code, \
+                 1 read as data\n"
+            ),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("never touched:\n  0x20c..0x20e  2 bytes\n  0x20f..0x210  1 bytes\n")
+        );
+        assert!(report.contains("hottest loops:\n  0x204..=0x208  9 iterations\n"));
+        assert!(
+            report.contains("  0x204: 7101  ADD V1, 0x01     10\n"),
+            "{}",
+            report
+        );
+    }
+}
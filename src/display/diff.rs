@@ -0,0 +1,123 @@
+use super::{DirtyRows, FrameBuffer};
+use alloc::vec::Vec;
+
+/// What a frontend last showed, so that it can update just the pixels that
+/// changed since, as embedded displays and terminals want to.
+///
+/// Only the rows [`FrameBuffer::take_dirty`] reports are compared, so a
+/// diff must be the only thing taking them from its framebuffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameDiff {
+    width: usize,
+    height: usize,
+    /// Palette index of every pixel as last reported.
+    shown: Vec<u8>,
+}
+
+impl FrameDiff {
+    /// A diff against a blank screen.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pixels that differ from what was reported before, as `(x, y,
+    /// color)` with the color as in [`FrameBuffer::color`]. After a change
+    /// of resolution the frontend should clear its screen, and gets every
+    /// lit pixel of the new one.
+    pub fn changes<'a>(&'a mut self, fb: &'a mut FrameBuffer) -> Changes<'a> {
+        let mut rows = fb.take_dirty();
+        if (self.width, self.height) != (fb.width(), fb.height()) {
+            (self.width, self.height) = (fb.width(), fb.height());
+            self.shown.clear();
+            self.shown.resize(self.width * self.height, 0);
+            rows = DirtyRows::ALL.below(self.height);
+        }
+        Changes {
+            fb,
+            shown: &mut self.shown,
+            rows,
+            row: None,
+            x: 0,
+        }
+    }
+}
+
+/// Iterator returned by [`FrameDiff::changes`]. Pixels not yet iterated
+/// over stay unreported; drop it early and they are missed until the row
+/// changes again.
+pub struct Changes<'a> {
+    fb: &'a FrameBuffer,
+    shown: &'a mut [u8],
+    rows: DirtyRows,
+    row: Option<usize>,
+    x: usize,
+}
+
+impl Iterator for Changes<'_> {
+    type Item = (usize, usize, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let width = self.fb.width();
+        loop {
+            let y = match self.row {
+                Some(y) => y,
+                None => {
+                    let y = self.rows.rows().next()?;
+                    self.rows.remove(y);
+                    self.row = Some(y);
+                    self.x = 0;
+                    y
+                }
+            };
+            while self.x < width {
+                let x = self.x;
+                self.x += 1;
+                let color = self.fb.color(x, y);
+                let shown = &mut self.shown[y * width + x];
+                if *shown != color {
+                    *shown = color;
+                    return Some((x, y, color));
+                }
+            }
+            self.row = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_pixels_are_reported() {
+        let mut fb = FrameBuffer::new();
+        let mut diff = FrameDiff::new();
+        assert_eq!(diff.changes(&mut fb).count(), 0);
+
+        fb.draw_sprite(4, 2, &[0xC0]);
+        assert_eq!(
+            diff.changes(&mut fb).collect::<Vec<_>>(),
+            [(4, 2, 1), (5, 2, 1)]
+        );
+        // redrawing a row with one pixel moved reports just those two
+        fb.draw_sprite(4, 2, &[0xC0]);
+        fb.draw_sprite(5, 2, &[0xC0]);
+        assert_eq!(
+            diff.changes(&mut fb).collect::<Vec<_>>(),
+            [(4, 2, 0), (6, 2, 1)]
+        );
+        assert_eq!(diff.changes(&mut fb).count(), 0);
+    }
+
+    #[test]
+    fn a_new_resolution_reports_every_lit_pixel() {
+        let mut fb = FrameBuffer::new();
+        let mut diff = FrameDiff::new();
+        diff.changes(&mut fb).count();
+
+        fb.set_hires(true);
+        fb.draw_sprite(120, 60, &[0x80]);
+
+        assert_eq!(diff.changes(&mut fb).collect::<Vec<_>>(), [(120, 60, 1)]);
+    }
+}
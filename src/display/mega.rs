@@ -0,0 +1,163 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Mega-Chip's screen size.
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+
+/// Palette entries a Mega-Chip program can set with 02NN.
+pub const MEGA_COLORS: usize = 256;
+
+/// The 256x192 screen [`super::FrameBuffer`] switches to for Mega-Chip's
+/// 0011: one byte per pixel, each an index into a palette the program
+/// loads itself. Index 0 is transparent in sprites, and the background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaScreen {
+    pixels: Vec<u8>,
+    /// `0xAARRGGBB`, as the program loaded them.
+    palette: Vec<u32>,
+    /// Size of the sprites DXYN draws, set by 03NN and 04NN.
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+}
+
+impl Default for MegaScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MegaScreen {
+    /// A blank screen with an all-black palette and 1x1 sprites.
+    pub fn new() -> Self {
+        MegaScreen {
+            pixels: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+            palette: vec![0xFF00_0000; MEGA_COLORS],
+            sprite_width: 1,
+            sprite_height: 1,
+        }
+    }
+
+    /// Row-major palette indices, `MEGA_WIDTH * MEGA_HEIGHT` of them.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * MEGA_WIDTH + x]
+    }
+
+    pub fn palette(&self) -> &[u32] {
+        &self.palette
+    }
+
+    /// The color of a pixel as `0x00RRGGBB`, ready for a frontend. Alpha
+    /// is dropped: the screen is opaque.
+    pub fn rgb(&self, x: usize, y: usize) -> u32 {
+        self.palette[self.index(x, y) as usize] & 0x00FF_FFFF
+    }
+
+    /// 02NN: `colors` as entries 1 onward, the way the program supplies
+    /// them. Entries past the end of the palette are dropped.
+    pub fn load_palette(&mut self, colors: impl IntoIterator<Item = u32>) {
+        for (slot, color) in self.palette[1..].iter_mut().zip(colors) {
+            *slot = color;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+
+    /// Copies a [`MegaScreen::sprite_width`] by
+    /// [`MegaScreen::sprite_height`] sprite, one palette index per byte,
+    /// onto the screen at `(x, y)`, clipped at the edges. Index 0 leaves the
+    /// screen as it is. Returns whether a pixel that wasn't background was
+    /// drawn over, Mega-Chip's collision.
+    pub(super) fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let (x, y) = (x % MEGA_WIDTH, y % MEGA_HEIGHT);
+        let mut collision = false;
+        let rows = sprite
+            .chunks(self.sprite_width.max(1))
+            .take(self.sprite_height);
+        for (row, indices) in rows.enumerate() {
+            let py = y + row;
+            if py >= MEGA_HEIGHT {
+                break;
+            }
+            for (column, &index) in indices.iter().enumerate() {
+                let px = x + column;
+                if px >= MEGA_WIDTH {
+                    break;
+                }
+                if index == 0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[py * MEGA_WIDTH + px];
+                collision |= *pixel != 0;
+                *pixel = index;
+            }
+        }
+        collision
+    }
+
+    /// Moves the picture by `(dx, dy)` pixels, filling with background.
+    pub(super) fn scroll(&mut self, dx: isize, dy: isize) {
+        let old = core::mem::replace(&mut self.pixels, vec![0; MEGA_WIDTH * MEGA_HEIGHT]);
+        for y in 0..MEGA_HEIGHT {
+            let Some(from_y) = y.checked_add_signed(-dy).filter(|&y| y < MEGA_HEIGHT) else {
+                continue;
+            };
+            for x in 0..MEGA_WIDTH {
+                if let Some(from_x) = x.checked_add_signed(-dx).filter(|&x| x < MEGA_WIDTH) {
+                    self.pixels[y * MEGA_WIDTH + x] = old[from_y * MEGA_WIDTH + from_x];
+                }
+            }
+        }
+    }
+
+    /// Replaces the picture and palette, as from a save state. Missing
+    /// entries are left at 0.
+    pub fn load(&mut self, pixels: &[u8], palette: &[u32]) {
+        self.clear();
+        let n = pixels.len().min(self.pixels.len());
+        self.pixels[..n].copy_from_slice(&pixels[..n]);
+        let n = palette.len().min(MEGA_COLORS);
+        self.palette[..n].copy_from_slice(&palette[..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites_copy_indices_and_skip_transparent_ones() {
+        let mut screen = MegaScreen::new();
+        screen.load_palette([0xFFFF_0000, 0xFF00_FF00]);
+        (screen.sprite_width, screen.sprite_height) = (2, 2);
+
+        assert!(!screen.draw(10, 5, &[1, 0, 2, 2]));
+        assert_eq!((screen.index(10, 5), screen.index(11, 5)), (1, 0));
+        assert_eq!(screen.rgb(10, 6), 0x00FF00);
+        assert_eq!(screen.rgb(11, 5), 0);
+
+        assert!(screen.draw(11, 5, &[0, 0, 1, 0]));
+        assert_eq!(screen.index(11, 6), 1);
+
+        // clipped, not wrapped
+        assert!(!screen.draw(MEGA_WIDTH - 1, 0, &[1, 1, 1, 1]));
+        assert_eq!(screen.index(0, 0), 0);
+    }
+
+    #[test]
+    fn scrolling_fills_with_background() {
+        let mut screen = MegaScreen::new();
+        screen.draw(0, 0, &[3]);
+
+        screen.scroll(4, 2);
+        assert_eq!((screen.index(0, 0), screen.index(4, 2)), (0, 3));
+        screen.scroll(-4, -2);
+        assert_eq!(screen.index(0, 0), 3);
+    }
+}
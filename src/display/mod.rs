@@ -0,0 +1,757 @@
+mod diff;
+mod mega;
+mod overlay;
+mod palette;
+mod panel;
+mod preset;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub use diff::{Changes, FrameDiff};
+pub use mega::{MegaScreen, MEGA_COLORS, MEGA_HEIGHT, MEGA_WIDTH};
+pub use overlay::{DrawOverlay, DrawStats};
+pub use palette::Palette;
+pub use panel::{Panel, PanelRenderer};
+pub use preset::{DisplayPreset, FrameBlender};
+
+/// Low resolution, the only one plain CHIP-8 has.
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+/// SUPER-CHIP high resolution.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Bit planes available to XO-CHIP programs. Plain CHIP-8 only ever uses
+/// the first.
+pub const PLANES: usize = 2;
+
+/// Screen of 64x32, or 128x64 in hires mode, made of [`PLANES`] bit planes.
+/// Pixels are stored row-major for the current resolution, `true` meaning
+/// lit.
+///
+/// Drawing, clearing and scrolling only touch the planes picked with
+/// [`FrameBuffer::select_planes`], which is just the first one unless an
+/// XO-CHIP program asks otherwise.
+///
+/// Mega-Chip programs switch to a separate 256x192 indexed-color screen
+/// instead, see [`FrameBuffer::set_mega`].
+///
+/// Rows touched since the last [`FrameBuffer::take_dirty`] are tracked so
+/// that frontends can redraw only those; see [`FrameDiff`]. Debugging
+/// frontends can also have each sprite's pixels tracked; see
+/// [`FrameBuffer::set_tracking_draws`].
+#[derive(Debug, Clone)]
+pub struct FrameBuffer {
+    planes: [[bool; HIRES_WIDTH * HIRES_HEIGHT]; PLANES],
+    selected: u8,
+    width: usize,
+    height: usize,
+    dirty: DirtyRows,
+    draws: Option<Box<DrawStats>>,
+    mega: Option<Box<MegaScreen>>,
+}
+
+/// Compares what is on screen, not which rows are dirty or were drawn.
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.selected == other.selected
+            && (0..PLANES).all(|plane| self.plane(plane) == other.plane(plane))
+            && self.mega == other.mega
+    }
+}
+
+impl Eq for FrameBuffer {}
+
+/// A set of screen rows, one bit each, enough for the 192 of the
+/// Mega-Chip screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirtyRows([u64; DIRTY_WORDS]);
+
+const DIRTY_WORDS: usize = MEGA_HEIGHT.div_ceil(64);
+
+impl DirtyRows {
+    pub const ALL: DirtyRows = DirtyRows([u64::MAX; DIRTY_WORDS]);
+
+    pub fn is_empty(self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    pub fn contains(self, row: usize) -> bool {
+        row < DIRTY_WORDS * 64 && self.0[row / 64] & (1 << (row % 64)) != 0
+    }
+
+    fn insert(&mut self, row: usize) {
+        self.0[row / 64] |= 1 << (row % 64);
+    }
+
+    pub(super) fn remove(&mut self, row: usize) {
+        self.0[row / 64] &= !(1 << (row % 64));
+    }
+
+    /// The rows, top to bottom.
+    pub fn rows(self) -> impl Iterator<Item = usize> {
+        self.0.into_iter().enumerate().flat_map(|(word, mut bits)| {
+            core::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let row = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(word * 64 + row)
+            })
+        })
+    }
+
+    /// Without the rows from `height` down, which aren't on screen.
+    fn below(mut self, height: usize) -> DirtyRows {
+        for (word, bits) in self.0.iter_mut().enumerate() {
+            match height.saturating_sub(word * 64) {
+                64.. => {}
+                left => *bits &= (1 << left) - 1,
+            }
+        }
+        self
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        FrameBuffer {
+            planes: [[false; HIRES_WIDTH * HIRES_HEIGHT]; PLANES],
+            selected: 0b01,
+            width: WIDTH,
+            height: HEIGHT,
+            dirty: DirtyRows::ALL,
+            draws: None,
+            mega: None,
+        }
+    }
+
+    /// Starts or stops recording what sprites draw, which costs a little
+    /// on every sprite, so it is off unless asked for.
+    pub fn set_tracking_draws(&mut self, tracking: bool) {
+        self.draws = tracking.then(|| Box::new(DrawStats::new(self.width, self.height)));
+    }
+
+    pub fn is_tracking_draws(&self) -> bool {
+        self.draws.is_some()
+    }
+
+    /// What was drawn since drawing started being tracked or the last call,
+    /// starting over. Call it once a frame. `None` while not tracking.
+    pub fn take_draw_stats(&mut self) -> Option<DrawStats> {
+        let fresh = Box::new(DrawStats::new(self.width, self.height));
+        self.draws
+            .as_mut()
+            .map(|draws| *core::mem::replace(draws, fresh))
+    }
+
+    /// The rows changed since the last call, all of them on a new screen
+    /// or after a change of resolution. Only one consumer should take them,
+    /// since taking resets the set.
+    pub fn take_dirty(&mut self) -> DirtyRows {
+        core::mem::take(&mut self.dirty).below(self.height())
+    }
+
+    pub fn width(&self) -> usize {
+        match self.mega {
+            Some(_) => MEGA_WIDTH,
+            None => self.width,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self.mega {
+            Some(_) => MEGA_HEIGHT,
+            None => self.height,
+        }
+    }
+
+    /// Switches to the Mega-Chip screen, Mega-Chip's 0011, or back to the
+    /// bit planes with 0010. Either way the screen starts out blank.
+    ///
+    /// While it is on, [`FrameBuffer::get`] and [`FrameBuffer::color`]
+    /// report any pixel that isn't background as lit on the first plane,
+    /// so frontends that only know the planes still show a picture; the
+    /// real colors are in [`FrameBuffer::mega`]. Clearing and scrolling
+    /// apply to the Mega-Chip screen, the planes are left alone, and
+    /// drawing isn't tracked.
+    pub fn set_mega(&mut self, on: bool) {
+        self.mega = on.then(Box::default);
+        self.dirty = DirtyRows::ALL;
+    }
+
+    pub fn mega(&self) -> Option<&MegaScreen> {
+        self.mega.as_deref()
+    }
+
+    pub fn mega_mut(&mut self) -> Option<&mut MegaScreen> {
+        self.mega.as_deref_mut()
+    }
+
+    /// Copies a [`MegaScreen::sprite_width`] by
+    /// [`MegaScreen::sprite_height`] sprite, one palette index per byte, to
+    /// `(x, y)`, clipped at the edges, with index 0 transparent. Returns
+    /// whether a pixel that wasn't background was drawn over. Does nothing
+    /// unless [`FrameBuffer::set_mega`] is on.
+    pub fn draw_mega_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let Some(mega) = &mut self.mega else {
+            return false;
+        };
+        let top = y % MEGA_HEIGHT;
+        for row in top..(top + mega.sprite_height).min(MEGA_HEIGHT) {
+            self.dirty.insert(row);
+        }
+        mega.draw(x, y, sprite)
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.width == HIRES_WIDTH
+    }
+
+    /// Switches between 64x32 and 128x64. Every plane is cleared, since the
+    /// old contents have no meaning at the new resolution.
+    pub fn set_hires(&mut self, hires: bool) {
+        (self.width, self.height) = if hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (WIDTH, HEIGHT)
+        };
+        self.planes = [[false; HIRES_WIDTH * HIRES_HEIGHT]; PLANES];
+        self.dirty = DirtyRows::ALL;
+        if let Some(draws) = &mut self.draws {
+            **draws = DrawStats::new(self.width, self.height);
+        }
+    }
+
+    /// Picks the planes later operations apply to, bit 0 being the first
+    /// plane. XO-CHIP's FN01.
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected = mask & ((1 << PLANES) - 1);
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+
+    /// Row-major view of the first plane, `width() * height()` entries long.
+    pub fn pixels(&self) -> &[bool] {
+        self.plane(0)
+    }
+
+    /// Row-major view of one plane, `width() * height()` entries long.
+    pub fn plane(&self, plane: usize) -> &[bool] {
+        &self.planes[plane][..self.width * self.height]
+    }
+
+    /// Overwrites one plane with `pixels`, row-major at the current
+    /// resolution. Extra entries are ignored, missing ones left unlit.
+    pub fn load_plane(&mut self, plane: usize, pixels: &[bool]) {
+        let len = self.width * self.height;
+        let target = &mut self.planes[plane][..len];
+        target.fill(false);
+        let n = pixels.len().min(len);
+        target[..n].copy_from_slice(&pixels[..n]);
+        self.dirty = DirtyRows::ALL;
+    }
+
+    /// Whether a pixel is lit on the first plane.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        if let Some(mega) = &self.mega {
+            return mega.index(x, y) != 0;
+        }
+        self.planes[0][y * self.width + x]
+    }
+
+    /// The palette index of a pixel: bit 0 from the first plane, bit 1 from
+    /// the second.
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+        if let Some(mega) = &self.mega {
+            return (mega.index(x, y) != 0) as u8;
+        }
+        let index = y * self.width + x;
+        (0..PLANES).fold(0, |color, plane| {
+            color | ((self.planes[plane][index] as u8) << plane)
+        })
+    }
+
+    /// The screen as text for tests and debug output, one line per row:
+    /// `.` for unlit pixels and `#` for lit ones, or with XO-CHIP's second
+    /// plane in use `2` for that plane alone and `3` for both.
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::with_capacity((self.width() + 1) * self.height());
+        for y in 0..self.height() {
+            out.extend((0..self.width()).map(|x| ['.', '#', '2', '3'][self.color(x, y) as usize]));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Clears the selected planes.
+    pub fn clear(&mut self) {
+        if let Some(mega) = &mut self.mega {
+            mega.clear();
+            self.dirty = DirtyRows::ALL;
+            return;
+        }
+        for plane in self.selected_indices() {
+            self.planes[plane] = [false; HIRES_WIDTH * HIRES_HEIGHT];
+        }
+        self.dirty = DirtyRows::ALL;
+    }
+
+    /// XORs an 8-pixel-wide sprite onto the screen, one byte per row.
+    ///
+    /// The starting position wraps around the screen, but the sprite itself
+    /// is clipped at the right and bottom edges. Returns whether any lit
+    /// pixel was switched off.
+    ///
+    /// With several planes selected, `sprite` holds one sprite per plane,
+    /// back to back, each `sprite.len() / planes` rows tall.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        with_rows(widen(sprite), |rows| self.draw_planes(x, y, rows, 8, false)).any()
+    }
+
+    /// Like [`FrameBuffer::draw_sprite`], but pixels falling off an edge
+    /// reappear on the opposite side.
+    pub fn draw_sprite_wrapped(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        with_rows(widen(sprite), |rows| self.draw_planes(x, y, rows, 8, true)).any()
+    }
+
+    /// Draws an 8-pixel-wide sprite like [`FrameBuffer::draw_sprite`] or
+    /// [`FrameBuffer::draw_sprite_wrapped`], reporting collisions per row.
+    pub fn draw_sprite_rows(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        wrap: bool,
+    ) -> Collisions {
+        with_rows(widen(sprite), |rows| self.draw_planes(x, y, rows, 8, wrap))
+    }
+
+    /// Draws a SUPER-CHIP 16x16 sprite: 32 bytes, two per row, big endian.
+    /// With several planes selected, the 32-byte sprites follow each other.
+    pub fn draw_large_sprite(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        self.draw_large_sprite_rows(x, y, sprite, wrap).any()
+    }
+
+    /// [`FrameBuffer::draw_large_sprite`], reporting collisions per row.
+    pub fn draw_large_sprite_rows(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        wrap: bool,
+    ) -> Collisions {
+        let rows = sprite
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]));
+        with_rows(rows, |rows| self.draw_planes(x, y, rows, 16, wrap))
+    }
+
+    /// Moves everything down `n` rows, blanking the rows scrolled in.
+    pub fn scroll_down(&mut self, n: usize) {
+        if let Some(mega) = &mut self.mega {
+            mega.scroll(0, n as isize);
+            self.dirty = DirtyRows::ALL;
+            return;
+        }
+        let (w, h) = (self.width, self.height);
+        let n = n.min(h);
+        for plane in self.selected_indices() {
+            let pixels = &mut self.planes[plane];
+            pixels.copy_within(0..(h - n) * w, n * w);
+            pixels[..n * w].fill(false);
+        }
+        self.dirty = DirtyRows::ALL;
+    }
+
+    /// Moves everything up `n` rows, blanking the rows scrolled in.
+    pub fn scroll_up(&mut self, n: usize) {
+        if let Some(mega) = &mut self.mega {
+            mega.scroll(0, -(n as isize));
+            self.dirty = DirtyRows::ALL;
+            return;
+        }
+        let (w, h) = (self.width, self.height);
+        let n = n.min(h);
+        for plane in self.selected_indices() {
+            let pixels = &mut self.planes[plane];
+            pixels.copy_within(n * w..h * w, 0);
+            pixels[(h - n) * w..h * w].fill(false);
+        }
+        self.dirty = DirtyRows::ALL;
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        if let Some(mega) = &mut self.mega {
+            mega.scroll(n as isize, 0);
+            self.dirty = DirtyRows::ALL;
+            return;
+        }
+        let (w, h) = (self.width, self.height);
+        let n = n.min(w);
+        for plane in self.selected_indices() {
+            for row in self.planes[plane][..w * h].chunks_mut(w) {
+                row.copy_within(0..w - n, n);
+                row[..n].fill(false);
+            }
+        }
+        self.dirty = DirtyRows::ALL;
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        if let Some(mega) = &mut self.mega {
+            mega.scroll(-(n as isize), 0);
+            self.dirty = DirtyRows::ALL;
+            return;
+        }
+        let (w, h) = (self.width, self.height);
+        let n = n.min(w);
+        for plane in self.selected_indices() {
+            for row in self.planes[plane][..w * h].chunks_mut(w) {
+                row.copy_within(n..w, 0);
+                row[w - n..].fill(false);
+            }
+        }
+        self.dirty = DirtyRows::ALL;
+    }
+
+    fn selected_indices(&self) -> impl Iterator<Item = usize> {
+        let selected = self.selected;
+        (0..PLANES).filter(move |plane| selected & (1 << plane) != 0)
+    }
+
+    /// Splits `rows` evenly between the selected planes.
+    fn draw_planes(
+        &mut self,
+        x: usize,
+        y: usize,
+        rows: &[u16],
+        width: usize,
+        wrap: bool,
+    ) -> Collisions {
+        let planes = self.selected_indices().count();
+        if planes == 0 {
+            return Collisions::default();
+        }
+        if let Some(draws) = &mut self.draws {
+            draws.sprites += 1;
+        }
+        let per_plane = rows.len() / planes;
+        let mut collisions = Collisions::default();
+        for (plane, rows) in self.selected_indices().zip(rows.chunks(per_plane.max(1))) {
+            let plane = self.xor_sprite(plane, x, y, rows, width, wrap);
+            collisions.rows += plane.rows;
+            collisions.clipped_rows = collisions.clipped_rows.max(plane.clipped_rows);
+        }
+        collisions
+    }
+
+    /// `rows` hold the sprite left-aligned: bit 15 is the leftmost pixel.
+    fn xor_sprite(
+        &mut self,
+        plane: usize,
+        x: usize,
+        y: usize,
+        rows: &[u16],
+        width: usize,
+        wrap: bool,
+    ) -> Collisions {
+        let (w, h) = (self.width, self.height);
+        let x = x % w;
+        let y = y % h;
+        let mut collisions = Collisions::default();
+
+        for (row, bits) in rows.iter().enumerate() {
+            let mut py = y + row;
+            if py >= h {
+                if !wrap {
+                    collisions.clipped_rows = rows.len() - row;
+                    break;
+                }
+                py %= h;
+            }
+            let mut collision = false;
+            for bit in 0..width {
+                let mut px = x + bit;
+                if px >= w {
+                    if !wrap {
+                        break;
+                    }
+                    px %= w;
+                }
+                if bits & (0x8000 >> bit) == 0 {
+                    continue;
+                }
+                let pixel = &mut self.planes[plane][py * w + px];
+                collision |= *pixel;
+                *pixel ^= true;
+                self.dirty.insert(py);
+                if let Some(draws) = &mut self.draws {
+                    draws.record(px, py, *pixel);
+                }
+            }
+            collisions.rows += collision as usize;
+        }
+
+        collisions
+    }
+}
+
+/// What a sprite ran into while being drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Collisions {
+    /// Rows in which a lit pixel was switched off, summed over planes.
+    pub rows: usize,
+    /// Rows that fell off the bottom edge and were not drawn.
+    pub clipped_rows: usize,
+}
+
+impl Collisions {
+    /// Whether any lit pixel was switched off, the classic VF result.
+    pub fn any(&self) -> bool {
+        self.rows > 0
+    }
+}
+
+fn widen(sprite: &[u8]) -> impl ExactSizeIterator<Item = u16> + '_ {
+    sprite.iter().map(|byte| (*byte as u16) << 8)
+}
+
+/// Calls `draw` with `rows` collected, on the stack for any sprite the CPU
+/// can draw, which saves an allocation per sprite.
+fn with_rows<T>(rows: impl ExactSizeIterator<Item = u16>, draw: impl FnOnce(&[u16]) -> T) -> T {
+    let mut inline = [0; 32 * PLANES];
+    if rows.len() > inline.len() {
+        return draw(&rows.collect::<Vec<_>>());
+    }
+    let len = rows.len();
+    for (slot, row) in inline.iter_mut().zip(rows) {
+        *slot = row;
+    }
+    draw(&inline[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_sets_pixels() {
+        let mut fb = FrameBuffer::new();
+
+        let collision = fb.draw_sprite(0, 0, &[0b1010_0000]);
+
+        assert!(!collision);
+        assert!(fb.get(0, 0));
+        assert!(!fb.get(1, 0));
+        assert!(fb.get(2, 0));
+    }
+
+    #[test]
+    fn drawing_twice_erases_and_reports_collision() {
+        let mut fb = FrameBuffer::new();
+
+        fb.draw_sprite(10, 10, &[0xFF, 0xFF]);
+        let collision = fb.draw_sprite(10, 10, &[0xFF, 0xFF]);
+
+        assert!(collision);
+        assert!(fb.pixels().iter().all(|p| !p));
+    }
+
+    #[test]
+    fn ascii_shows_both_planes() {
+        let mut fb = FrameBuffer::new();
+
+        fb.draw_sprite(0, 0, &[0b1100_0000]);
+        fb.select_planes(0b10);
+        fb.draw_sprite(1, 1, &[0b1000_0000]);
+        fb.select_planes(0b11);
+        fb.draw_sprite(0, 1, &[0b1000_0000, 0b1000_0000]);
+
+        let ascii = fb.to_ascii();
+        assert_eq!(ascii.lines().count(), HEIGHT);
+        assert!(ascii.starts_with(&format!("##{}\n32{}\n", ".".repeat(62), ".".repeat(62))));
+    }
+
+    #[test]
+    fn start_position_wraps() {
+        let mut fb = FrameBuffer::new();
+
+        fb.draw_sprite(WIDTH + 1, HEIGHT + 2, &[0x80]);
+
+        assert!(fb.get(1, 2));
+    }
+
+    #[test]
+    fn sprite_is_clipped_at_edges() {
+        let mut fb = FrameBuffer::new();
+
+        fb.draw_sprite(WIDTH - 4, HEIGHT - 1, &[0xFF, 0xFF]);
+
+        assert!(fb.get(WIDTH - 1, HEIGHT - 1));
+        assert!(!fb.get(0, HEIGHT - 1));
+        assert!(!fb.get(WIDTH - 4, 0));
+        assert_eq!(fb.pixels().iter().filter(|p| **p).count(), 4);
+    }
+
+    #[test]
+    fn collisions_are_reported_per_row() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0x80, 0x00, 0x80]);
+
+        let collisions = fb.draw_sprite_rows(0, HEIGHT - 3, &[0x80; 5], false);
+        assert_eq!(
+            collisions,
+            Collisions {
+                rows: 0,
+                clipped_rows: 2
+            }
+        );
+
+        let collisions = fb.draw_sprite_rows(0, 0, &[0x80, 0x80, 0x80], false);
+        assert_eq!(
+            collisions,
+            Collisions {
+                rows: 2,
+                clipped_rows: 0
+            }
+        );
+        assert!(collisions.any());
+    }
+
+    #[test]
+    fn wrapped_sprite_reappears_on_opposite_edges() {
+        let mut fb = FrameBuffer::new();
+
+        fb.draw_sprite_wrapped(WIDTH - 4, HEIGHT - 1, &[0xFF, 0xFF]);
+
+        assert!(fb.get(WIDTH - 1, HEIGHT - 1));
+        assert!(fb.get(3, HEIGHT - 1));
+        assert!(fb.get(WIDTH - 4, 0));
+        assert!(fb.get(0, 0));
+        assert_eq!(fb.pixels().iter().filter(|p| **p).count(), 16);
+    }
+
+    #[test]
+    fn clear_turns_everything_off() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0xFF]);
+
+        fb.clear();
+
+        assert!(fb.pixels().iter().all(|p| !p));
+    }
+
+    #[test]
+    fn hires_doubles_resolution_and_clears() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0xFF]);
+
+        fb.set_hires(true);
+
+        assert!(fb.is_hires());
+        assert_eq!((fb.width(), fb.height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(fb.pixels().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(fb.pixels().iter().all(|p| !p));
+
+        fb.draw_sprite(120, 60, &[0xFF]);
+        assert!(fb.get(127, 60));
+    }
+
+    #[test]
+    fn large_sprite_is_sixteen_wide() {
+        let mut fb = FrameBuffer::new();
+        fb.set_hires(true);
+        let mut sprite = [0u8; 32];
+        sprite[0] = 0x80;
+        sprite[1] = 0x01;
+        sprite[31] = 0x01;
+
+        fb.draw_large_sprite(0, 0, &sprite, false);
+
+        assert!(fb.get(0, 0));
+        assert!(fb.get(15, 0));
+        assert!(fb.get(15, 15));
+        assert_eq!(fb.pixels().iter().filter(|p| **p).count(), 3);
+    }
+
+    #[test]
+    fn scrolling_moves_pixels_and_blanks_the_rest() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(8, 8, &[0x80]);
+
+        fb.scroll_down(2);
+        assert!(fb.get(8, 10));
+        fb.scroll_right(4);
+        assert!(fb.get(12, 10));
+        fb.scroll_left(4);
+        fb.scroll_up(2);
+        assert!(fb.get(8, 8));
+        assert_eq!(fb.pixels().iter().filter(|p| **p).count(), 1);
+
+        fb.scroll_left(WIDTH);
+        assert!(fb.pixels().iter().all(|p| !p));
+    }
+
+    #[test]
+    fn only_selected_planes_are_drawn_and_cleared() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0x80]);
+
+        fb.select_planes(0b10);
+        fb.draw_sprite(1, 0, &[0x80]);
+        assert_eq!(fb.color(0, 0), 0b01);
+        assert_eq!(fb.color(1, 0), 0b10);
+        assert_eq!(fb.color(2, 0), 0b00);
+
+        fb.clear();
+        assert!(fb.plane(1).iter().all(|p| !p));
+        assert!(fb.get(0, 0));
+    }
+
+    #[test]
+    fn drawing_marks_rows_dirty() {
+        let mut fb = FrameBuffer::new();
+        assert_eq!(fb.take_dirty().rows().count(), HEIGHT);
+        assert!(fb.take_dirty().is_empty());
+
+        fb.draw_sprite(0, 30, &[0x80, 0x00, 0x80, 0x80]);
+        let dirty = fb.take_dirty();
+        assert_eq!(dirty.rows().collect::<Vec<_>>(), [30]);
+        assert!(!dirty.contains(31));
+
+        fb.draw_sprite_wrapped(0, 31, &[0x80, 0x80]);
+        assert_eq!(fb.take_dirty().rows().collect::<Vec<_>>(), [0, 31]);
+        fb.scroll_left(4);
+        assert_eq!(fb.take_dirty().rows().count(), HEIGHT);
+        fb.set_hires(true);
+        assert_eq!(fb.take_dirty().rows().count(), HIRES_HEIGHT);
+    }
+
+    #[test]
+    fn both_planes_take_consecutive_sprites() {
+        let mut fb = FrameBuffer::new();
+        fb.select_planes(0b11);
+
+        let collision = fb.draw_sprite(0, 0, &[0xC0, 0x80]);
+
+        assert!(!collision);
+        assert_eq!(fb.color(0, 0), 0b11);
+        assert_eq!(fb.color(1, 0), 0b01);
+        assert_eq!(fb.color(0, 1), 0b00);
+        assert!(fb.draw_sprite(0, 0, &[0x00, 0x80]));
+    }
+}
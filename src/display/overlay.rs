@@ -0,0 +1,151 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// What was drawn since drawing started being tracked or the stats were
+/// last taken, normally one frame; see
+/// [`crate::display::FrameBuffer::set_tracking_draws`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawStats {
+    /// Sprites drawn, however many planes each went to.
+    pub sprites: usize,
+    /// Pixels flipped on.
+    pub lit: usize,
+    /// Pixels flipped off, the ones that flicker.
+    pub erased: usize,
+    width: usize,
+    drawn: Vec<bool>,
+}
+
+impl DrawStats {
+    pub(super) fn new(width: usize, height: usize) -> Self {
+        DrawStats {
+            sprites: 0,
+            lit: 0,
+            erased: 0,
+            width,
+            drawn: vec![false; width * height],
+        }
+    }
+
+    pub(super) fn record(&mut self, x: usize, y: usize, now_lit: bool) {
+        self.drawn[y * self.width + x] = true;
+        if now_lit {
+            self.lit += 1;
+        } else {
+            self.erased += 1;
+        }
+    }
+
+    /// Whether a sprite touched the pixel, in the resolution the stats were
+    /// taken at.
+    pub fn was_drawn(&self, x: usize, y: usize) -> bool {
+        x < self.width && self.drawn.get(y * self.width + x).copied().unwrap_or(false)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.drawn.len() / self.width
+    }
+}
+
+/// How a debugging frontend marks the pixels drawn in a frame, so authors
+/// can see what flickers and when a frame draws more than it should.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawOverlay {
+    /// Mixed into pixels drawn in a frame within the budget.
+    pub tint: u32,
+    /// Mixed in instead when the frame drew more sprites than the budget.
+    pub over_budget_tint: u32,
+    /// Sprites a frame may draw. The COSMAC VIP waits for the display
+    /// before every sprite, so 1 is what a game must stay within to run
+    /// at full speed there.
+    pub budget: Option<usize>,
+}
+
+impl DrawOverlay {
+    pub const fn new(budget: Option<usize>) -> Self {
+        DrawOverlay {
+            tint: 0x00CC00,
+            over_budget_tint: 0xFF0000,
+            budget,
+        }
+    }
+
+    pub fn is_over_budget(&self, stats: &DrawStats) -> bool {
+        self.budget.is_some_and(|budget| stats.sprites > budget)
+    }
+
+    /// Tints the drawn pixels of `colors`, a frame of `stats`' resolution
+    /// row by row, such as [`crate::display::FrameBlender::blend`] makes.
+    pub fn apply(&self, stats: &DrawStats, colors: &mut [u32]) {
+        let tint = if self.is_over_budget(stats) {
+            self.over_budget_tint
+        } else {
+            self.tint
+        };
+        for (color, drawn) in colors.iter_mut().zip(&stats.drawn) {
+            if *drawn {
+                *color = mix(*color, tint);
+            }
+        }
+    }
+}
+
+impl Default for DrawOverlay {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Halfway between two colors, channel by channel.
+fn mix(a: u32, b: u32) -> u32 {
+    ((a >> 1) & 0x007F_7F7F) + ((b >> 1) & 0x007F_7F7F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::FrameBuffer;
+
+    #[test]
+    fn draws_are_counted_per_frame() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0x80]);
+        assert_eq!(fb.take_draw_stats(), None);
+
+        fb.set_tracking_draws(true);
+        fb.draw_sprite(0, 0, &[0xC0]);
+        fb.draw_sprite(0, 1, &[0x80]);
+        let stats = fb.take_draw_stats().unwrap();
+        assert_eq!((stats.sprites, stats.lit, stats.erased), (2, 2, 1));
+        assert!(stats.was_drawn(1, 0));
+        assert!(!stats.was_drawn(2, 0));
+        assert_eq!((stats.width(), stats.height()), (64, 32));
+
+        let next = fb.take_draw_stats().unwrap();
+        assert_eq!(next.sprites, 0);
+        assert!(!next.was_drawn(1, 0));
+    }
+
+    #[test]
+    fn overlay_tints_drawn_pixels_by_budget() {
+        let mut fb = FrameBuffer::new();
+        fb.set_tracking_draws(true);
+        fb.draw_sprite(0, 0, &[0x80]);
+        let stats = fb.take_draw_stats().unwrap();
+        let mut colors = vec![0x000000; 64 * 32];
+
+        DrawOverlay::new(Some(1)).apply(&stats, &mut colors);
+        assert_eq!(colors[0], 0x006600);
+        assert_eq!(colors[1], 0);
+
+        let mut colors = vec![0xFFFFFF; 64 * 32];
+        let overlay = DrawOverlay::new(Some(0));
+        assert!(overlay.is_over_budget(&stats));
+        overlay.apply(&stats, &mut colors);
+        assert_eq!(colors[0], 0xFE7F7F);
+    }
+}
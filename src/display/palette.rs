@@ -0,0 +1,135 @@
+/// The colors a frontend shows the screen in, as `0x00RRGGBB`. Plain
+/// CHIP-8 and SUPER-CHIP programs only light the first plane, so only
+/// `background` and `foreground` matter to them; XO-CHIP ones can use all
+/// four.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Pixels lit on no plane.
+    pub background: u32,
+    /// Pixels lit on the first plane only.
+    pub foreground: u32,
+    /// Pixels lit on the second plane only.
+    pub plane2: u32,
+    /// Pixels lit on both planes.
+    pub both: u32,
+}
+
+impl Palette {
+    /// Black and white, with orange and brown for the XO-CHIP planes.
+    pub const CLASSIC: Palette = Palette::new([0x000000, 0xFFFFFF, 0xFF6600, 0x662200]);
+
+    /// Octo's defaults, which most XO-CHIP games were drawn with.
+    pub const OCTO: Palette = Palette::new([0x996600, 0xFFCC00, 0xFF6600, 0x662200]);
+
+    /// An amber monochrome monitor.
+    pub const AMBER: Palette = Palette::new([0x1A0F00, 0xFFB000, 0xB36B00, 0xFFD27F]);
+
+    /// A green phosphor terminal.
+    pub const GREEN: Palette = Palette::new([0x001A00, 0x33FF33, 0x1A8C1A, 0x99FF99]);
+
+    /// The four shades of the original Game Boy screen.
+    pub const GAMEBOY: Palette = Palette::new([0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]);
+
+    /// White, yellow and cyan on black.
+    pub const HIGH_CONTRAST: Palette = Palette::new([0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]);
+
+    /// The built-in themes by the name used on command lines, in the order
+    /// [`Palette::next_theme`] cycles through them.
+    pub const THEMES: [(&'static str, Palette); 6] = [
+        ("classic", Self::CLASSIC),
+        ("octo", Self::OCTO),
+        ("amber", Self::AMBER),
+        ("green", Self::GREEN),
+        ("gameboy", Self::GAMEBOY),
+        ("high-contrast", Self::HIGH_CONTRAST),
+    ];
+
+    /// A palette from the colors for no plane, the first, the second and
+    /// both, the order [`crate::display::FrameBuffer::color`] numbers them
+    /// in.
+    pub const fn new([background, foreground, plane2, both]: [u32; 4]) -> Self {
+        Palette {
+            background,
+            foreground,
+            plane2,
+            both,
+        }
+    }
+
+    /// The colors in the order [`Palette::new`] takes them, for indexing
+    /// with a pixel's color.
+    pub const fn colors(&self) -> [u32; 4] {
+        [self.background, self.foreground, self.plane2, self.both]
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::THEMES
+            .iter()
+            .find(|(theme, _)| *theme == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// The name of the built-in theme with exactly these colors.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::THEMES
+            .iter()
+            .find(|(_, palette)| palette == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// The built-in theme after this one, wrapping around, or the first
+    /// one if these colors aren't a theme, e.g. a ROM's own. For frontends
+    /// that switch palettes with a key.
+    pub fn next_theme(&self) -> Self {
+        let next = Self::THEMES
+            .iter()
+            .position(|(_, palette)| palette == self)
+            .map_or(0, |index| (index + 1) % Self::THEMES.len());
+        Self::THEMES[next].1
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+impl From<[u32; 4]> for Palette {
+    fn from(colors: [u32; 4]) -> Self {
+        Self::new(colors)
+    }
+}
+
+impl From<Palette> for [u32; 4] {
+    fn from(palette: Palette) -> Self {
+        palette.colors()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_follow_the_pixel_color_order() {
+        let palette = Palette::new([1, 2, 3, 4]);
+        assert_eq!(palette.foreground, 2);
+        assert_eq!(palette.both, 4);
+        assert_eq!(palette.colors(), [1, 2, 3, 4]);
+        assert_eq!(<[u32; 4]>::from(palette), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn themes_by_name_and_in_turn() {
+        assert_eq!(Palette::by_name("gameboy"), Some(Palette::GAMEBOY));
+        assert_eq!(Palette::by_name("sepia"), None);
+        assert_eq!(Palette::OCTO.name(), Some("octo"));
+
+        assert_eq!(Palette::CLASSIC.next_theme(), Palette::OCTO);
+        assert_eq!(Palette::HIGH_CONTRAST.next_theme(), Palette::CLASSIC);
+        let own = Palette::new([0x123456, 0, 0, 0]);
+        assert_eq!(own.name(), None);
+        assert_eq!(own.next_theme(), Palette::CLASSIC);
+    }
+}
@@ -0,0 +1,155 @@
+use super::{FrameBuffer, FrameDiff};
+
+/// A display that pixels can be set on one at a time, such as an SSD1306
+/// or ST7789 on a microcontroller. It is modelled on `embedded-graphics`'
+/// `DrawTarget`, but it is its own trait: there is no adapter onto
+/// `DrawTarget` yet, so drivers written for embedded-graphics need a
+/// wrapper of their own.
+pub trait Panel {
+    type Error;
+
+    /// Width and height in pixels.
+    fn size(&self) -> (usize, usize);
+
+    /// Sets each pixel to a color as in [`FrameBuffer::color`], 0 being the
+    /// background. Monochrome panels light everything but 0.
+    fn draw_pixels<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = (usize, usize, u8)>;
+
+    /// Sets every pixel to the background.
+    fn clear(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Draws a framebuffer on a [`Panel`] as large as fits, centered, sending
+/// only the pixels that changed since the last frame; panels on a slow
+/// bus can't take the whole screen every frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PanelRenderer {
+    diff: FrameDiff,
+    /// Resolution the panel was last drawn at.
+    shown: Option<(usize, usize)>,
+}
+
+impl PanelRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panel pixels per CHIP-8 pixel and where the picture starts, for a
+    /// `width` by `height` screen. A panel smaller than the screen shows the
+    /// top left of it.
+    pub fn layout(panel: (usize, usize), width: usize, height: usize) -> (usize, (usize, usize)) {
+        let scale = (panel.0 / width).min(panel.1 / height).max(1);
+        let left = panel.0.saturating_sub(width * scale) / 2;
+        let top = panel.1.saturating_sub(height * scale) / 2;
+        (scale, (left, top))
+    }
+
+    /// Brings `panel` up to date with `fb`, clearing it first whenever the
+    /// resolution changed. This takes the framebuffer's dirty rows, see
+    /// [`FrameDiff`].
+    pub fn render<P: Panel>(
+        &mut self,
+        fb: &mut FrameBuffer,
+        panel: &mut P,
+    ) -> Result<(), P::Error> {
+        let resolution = (fb.width(), fb.height());
+        if self.shown != Some(resolution) {
+            panel.clear()?;
+            self.shown = Some(resolution);
+        }
+        let size = panel.size();
+        let (scale, (left, top)) = Self::layout(size, resolution.0, resolution.1);
+        let pixels = self.diff.changes(fb).flat_map(move |(x, y, color)| {
+            (0..scale * scale).map(move |at| {
+                let px = left + x * scale + at % scale;
+                let py = top + y * scale + at / scale;
+                (px, py, color)
+            })
+        });
+        panel.draw_pixels(pixels.filter(|(x, y, _)| *x < size.0 && *y < size.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A panel that remembers what it was sent.
+    struct Recorder {
+        size: (usize, usize),
+        lit: Vec<bool>,
+        sent: usize,
+        clears: usize,
+    }
+
+    impl Recorder {
+        fn new(width: usize, height: usize) -> Self {
+            Recorder {
+                size: (width, height),
+                lit: vec![false; width * height],
+                sent: 0,
+                clears: 0,
+            }
+        }
+
+        fn lit(&self, x: usize, y: usize) -> bool {
+            self.lit[y * self.size.0 + x]
+        }
+    }
+
+    impl Panel for Recorder {
+        type Error = ();
+
+        fn size(&self) -> (usize, usize) {
+            self.size
+        }
+
+        fn draw_pixels<I>(&mut self, pixels: I) -> Result<(), ()>
+        where
+            I: IntoIterator<Item = (usize, usize, u8)>,
+        {
+            for (x, y, color) in pixels {
+                self.lit[y * self.size.0 + x] = color != 0;
+                self.sent += 1;
+            }
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), ()> {
+            self.lit.fill(false);
+            self.clears += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn low_resolution_is_doubled_on_a_128_by_64_panel() {
+        let mut fb = FrameBuffer::new();
+        let mut panel = Recorder::new(128, 64);
+        let mut renderer = PanelRenderer::new();
+        fb.draw_sprite(1, 1, &[0x80]);
+        renderer.render(&mut fb, &mut panel).unwrap();
+        assert!(panel.lit(2, 2) && panel.lit(3, 3));
+        assert!(!panel.lit(1, 1) && !panel.lit(4, 2));
+        assert_eq!((panel.sent, panel.clears), (4, 1));
+
+        // nothing changed, nothing sent
+        renderer.render(&mut fb, &mut panel).unwrap();
+        assert_eq!(panel.sent, 4);
+
+        fb.set_hires(true);
+        fb.draw_sprite(127, 63, &[0x80]);
+        renderer.render(&mut fb, &mut panel).unwrap();
+        assert_eq!(panel.clears, 2);
+        assert!(panel.lit(127, 63) && !panel.lit(2, 2));
+    }
+
+    #[test]
+    fn pictures_are_centered_on_odd_sized_panels() {
+        // an ST7789 is 240 by 240
+        assert_eq!(PanelRenderer::layout((240, 240), 64, 32), (3, (24, 72)));
+        assert_eq!(PanelRenderer::layout((96, 16), 64, 32), (1, (16, 0)));
+    }
+}
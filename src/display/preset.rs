@@ -0,0 +1,165 @@
+use super::{FrameBuffer, Palette};
+use alloc::vec::Vec;
+
+/// How a frontend presents the screen, bundled so that one flag can pick
+/// everything a user with low vision or photosensitivity needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayPreset {
+    /// Colors replacing the ROM's own. `None` keeps them.
+    pub palette: Option<Palette>,
+    /// Smallest window pixels per low resolution CHIP-8 pixel.
+    pub min_scale: usize,
+    /// Share of each pixel's previous color kept every frame, from 0.0 for
+    /// none to just below 1.0. See [`FrameBlender`].
+    pub persistence: f32,
+}
+
+impl DisplayPreset {
+    pub const STANDARD: DisplayPreset = DisplayPreset {
+        palette: None,
+        min_scale: 1,
+        persistence: 0.0,
+    };
+
+    /// [`Palette::HIGH_CONTRAST`] at a large size.
+    pub const HIGH_CONTRAST: DisplayPreset = DisplayPreset {
+        palette: Some(Palette::HIGH_CONTRAST),
+        min_scale: 12,
+        persistence: 0.0,
+    };
+
+    /// Heavy blending between frames, so sprites that games erase and
+    /// redraw every frame hold steady instead of flashing.
+    pub const REDUCED_FLICKER: DisplayPreset = DisplayPreset {
+        palette: None,
+        min_scale: 1,
+        persistence: 0.6,
+    };
+
+    /// Both of the above.
+    pub const ACCESSIBLE: DisplayPreset = DisplayPreset {
+        persistence: 0.6,
+        ..Self::HIGH_CONTRAST
+    };
+
+    /// Looks a preset up by the name used on command lines: `standard`,
+    /// `high-contrast`, `reduced-flicker` or `accessible`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Self::STANDARD),
+            "high-contrast" => Some(Self::HIGH_CONTRAST),
+            "reduced-flicker" => Some(Self::REDUCED_FLICKER),
+            "accessible" => Some(Self::ACCESSIBLE),
+            _ => None,
+        }
+    }
+}
+
+impl Default for DisplayPreset {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+/// Blends each frame into the ones before it, like the slow phosphor of old
+/// screens. A pixel that turns off fades out over a few frames and one that
+/// flickers shows as a steady, dimmer color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameBlender {
+    persistence: f32,
+    /// Red, green and blue of every pixel as last shown.
+    shown: Vec<[f32; 3]>,
+    out: Vec<u32>,
+}
+
+impl FrameBlender {
+    pub fn new(persistence: f32) -> Self {
+        FrameBlender {
+            persistence: persistence.clamp(0.0, 0.99),
+            shown: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+
+    /// The colors to show for `fb` this frame, row-major at its resolution,
+    /// the program's own on the Mega-Chip screen.
+    /// Call once per frame.
+    pub fn blend(&mut self, fb: &FrameBuffer, palette: &Palette) -> &[u32] {
+        let palette = palette.colors();
+        let len = fb.width() * fb.height();
+        if self.shown.len() != len {
+            // nothing to fade from at a new resolution
+            self.shown.clear();
+            self.shown.resize(len, rgb(palette[0]));
+        }
+        self.out.resize(len, 0);
+        let keep = self.persistence;
+        for (index, (shown, out)) in self.shown.iter_mut().zip(&mut self.out).enumerate() {
+            let (x, y) = (index % fb.width(), index / fb.width());
+            let wanted = rgb(match fb.mega() {
+                Some(mega) => mega.rgb(x, y),
+                None => palette[fb.color(x, y) as usize],
+            });
+            for (channel, wanted) in shown.iter_mut().zip(wanted) {
+                *channel = *channel * keep + wanted * (1.0 - keep);
+            }
+            *out = pack(*shown);
+        }
+        &self.out
+    }
+}
+
+fn rgb(color: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((color >> shift) & 0xFF) as f32)
+}
+
+fn pack([r, g, b]: [f32; 3]) -> u32 {
+    // rounds without `f32::round`, which needs std
+    let channel = |value: f32| (value.clamp(0.0, 255.0) + 0.5) as u32;
+    (channel(r) << 16) | (channel(g) << 8) | channel(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = Palette::new([0x000000, 0xFFFFFF, 0xFF0000, 0x0000FF]);
+
+    #[test]
+    fn no_persistence_shows_the_palette_as_is() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(0, 0, &[0x80]);
+        let mut blender = FrameBlender::new(0.0);
+
+        let colors = blender.blend(&fb, &PALETTE);
+        assert_eq!(&colors[..2], &[0xFFFFFF, 0x000000]);
+    }
+
+    #[test]
+    fn flickering_pixels_settle_on_a_steady_color() {
+        let mut fb = FrameBuffer::new();
+        let mut blender = FrameBlender::new(0.5);
+
+        fb.draw_sprite(0, 0, &[0x80]);
+        assert_eq!(blender.blend(&fb, &PALETTE)[0], 0x808080);
+        // erased and redrawn every other frame
+        let mut shown = Vec::new();
+        for _ in 0..20 {
+            fb.draw_sprite(0, 0, &[0x80]);
+            shown.push(blender.blend(&fb, &PALETTE)[0] & 0xFF);
+        }
+        let (low, high) = (shown[18].min(shown[19]), shown[18].max(shown[19]));
+        assert!((80..=90).contains(&low), "{:?}", shown);
+        assert!((165..=175).contains(&high), "{:?}", shown);
+    }
+
+    #[test]
+    fn presets_by_name() {
+        assert_eq!(
+            DisplayPreset::by_name("accessible"),
+            Some(DisplayPreset::ACCESSIBLE)
+        );
+        assert_eq!(DisplayPreset::ACCESSIBLE.min_scale, 12);
+        assert_eq!(DisplayPreset::by_name("contrast"), None);
+    }
+}
@@ -0,0 +1,132 @@
+use super::{Input, Screen};
+use crate::display::{FrameBuffer, Palette};
+use crate::keypad::{KeypadState, KEY_COUNT};
+
+/// A [`Screen`] that keeps the last frame as RGBA bytes, row by row at the
+/// screen's own resolution, ready to be uploaded as a texture by GUI
+/// toolkits such as egui (`ColorImage::from_rgba_unmultiplied`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureScreen {
+    pub palette: Palette,
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+    changed: bool,
+}
+
+impl TextureScreen {
+    pub fn new(palette: Palette) -> Self {
+        TextureScreen {
+            palette,
+            width: 0,
+            height: 0,
+            rgba: Vec::new(),
+            changed: false,
+        }
+    }
+
+    /// Width and height in pixels of the last frame, 0 before the first.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Four bytes per pixel, alpha always opaque.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Whether the picture changed since the last call, so hosts only
+    /// upload a new texture when they have to.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+impl Default for TextureScreen {
+    fn default() -> Self {
+        Self::new(Palette::default())
+    }
+}
+
+impl Screen for TextureScreen {
+    fn draw(&mut self, fb: &FrameBuffer) {
+        let colors = self.palette.colors();
+        let (width, height) = (fb.width(), fb.height());
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let [_, r, g, b] = colors[fb.color(x, y) as usize].to_be_bytes();
+                rgba.extend_from_slice(&[r, g, b, 0xFF]);
+            }
+        }
+        if rgba != self.rgba {
+            self.changed = true;
+            self.rgba = rgba;
+        }
+        (self.width, self.height) = (width, height);
+    }
+}
+
+/// An [`Input`] for hosts that hear about keys as events rather than
+/// being able to ask for them: pass every press and release on, and call
+/// [`HeldKeys::release_all`] when the view loses focus so no key sticks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeldKeys {
+    held: KeypadState,
+}
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys outside the keypad are ignored.
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        if let Some(held) = self.held.get_mut(key as usize) {
+            *held = pressed;
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.held = [false; KEY_COUNT];
+    }
+}
+
+impl Input for HeldKeys {
+    fn keys(&self) -> KeypadState {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_follows_the_screen() {
+        let mut screen = TextureScreen::new(Palette::new([0x000000, 0x336699, 0, 0]));
+        let mut fb = FrameBuffer::new();
+        fb.draw_sprite(1, 0, &[0x80]);
+
+        screen.draw(&fb);
+        assert_eq!(screen.size(), (64, 32));
+        assert_eq!(
+            &screen.rgba()[..8],
+            &[0, 0, 0, 0xFF, 0x33, 0x66, 0x99, 0xFF]
+        );
+        assert!(screen.take_changed());
+        screen.draw(&fb);
+        assert!(!screen.take_changed());
+    }
+
+    #[test]
+    fn held_keys_can_be_dropped_at_once() {
+        let mut keys = HeldKeys::new();
+        keys.set(0xA, true);
+        keys.set(0x10, true);
+        assert!(keys.keys()[0xA]);
+
+        keys.release_all();
+        assert_eq!(keys.keys(), [false; KEY_COUNT]);
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::mpsc::Sender;
+
+use crate::disasm::CallChain;
+
+/// Something a frontend may want to react to, so it doesn't have to poll
+/// the CPU every frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulatorEvent {
+    /// The screen shows something different from the last frame.
+    ScreenUpdated,
+    BeepStarted,
+    BeepStopped,
+    /// FX0A started waiting for a key press.
+    WaitingForKey,
+    /// The program reached 0000 or EXIT.
+    Halted,
+    /// The program was restarted with [`super::Emulator::soft_reset`], or
+    /// with [`super::Emulator::hard_reset`] when `hard`.
+    Reset {
+        hard: bool,
+    },
+    /// Execution stopped before the instruction at this address, one set
+    /// with [`super::Emulator::add_breakpoint`], and the emulator paused.
+    Breakpoint(usize),
+    /// A subroutine calling itself got deep enough into the stack that it
+    /// will probably overflow, see [`crate::cpu::CPU::take_stack_warning`].
+    StackWarning(CallChain),
+}
+
+/// Receives [`EmulatorEvent`]s as the emulator runs, on the thread running
+/// it. Implemented for closures and for channel senders.
+pub trait EventSink {
+    fn event(&mut self, event: EmulatorEvent);
+}
+
+impl<F: FnMut(EmulatorEvent)> EventSink for F {
+    fn event(&mut self, event: EmulatorEvent) {
+        self(event)
+    }
+}
+
+/// Events sent after the receiver hung up are dropped.
+impl EventSink for Sender<EmulatorEvent> {
+    fn event(&mut self, event: EmulatorEvent) {
+        let _ = self.send(event);
+    }
+}
@@ -0,0 +1,387 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::{error, fmt, mem};
+
+use super::{Audio, Emulator, Input, Pace, Screen};
+use crate::clock::SystemClock;
+use crate::cpu::{Fault, SaveState, StateError, CPU};
+use crate::display::FrameBuffer;
+use crate::keypad::{KeypadState, KEY_COUNT};
+
+#[derive(Debug)]
+pub enum HandleError {
+    /// The emulator thread isn't running any more, because the program
+    /// faulted or the handle was stopped.
+    Stopped,
+    State(StateError),
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandleError::Stopped => write!(f, "the emulator is not running"),
+            HandleError::State(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for HandleError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            HandleError::Stopped => None,
+            HandleError::State(err) => Some(err),
+        }
+    }
+}
+
+impl From<StateError> for HandleError {
+    fn from(err: StateError) -> Self {
+        HandleError::State(err)
+    }
+}
+
+/// Requests that need an answer from the emulator thread.
+enum Request {
+    SaveState(Sender<SaveState>),
+    LoadState(Box<SaveState>, Sender<Result<(), StateError>>),
+    Reset { hard: bool },
+}
+
+/// Everything the emulator thread and the handle share, behind one lock
+/// that either side only holds for a moment.
+struct Shared {
+    keys: KeypadState,
+    paused: bool,
+    speed: Option<u32>,
+    requests: Vec<Request>,
+    screen: FrameBuffer,
+    frames: u64,
+    beeping: bool,
+    fault: Option<Fault>,
+    running: bool,
+    stop: bool,
+}
+
+/// The [`Screen`], [`Input`] and [`Audio`] of the emulator thread.
+#[derive(Clone)]
+struct Link(Arc<Mutex<Shared>>);
+
+impl Link {
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        // a panic on the emulator thread mustn't take the host down too
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Screen for Link {
+    fn draw(&mut self, fb: &FrameBuffer) {
+        let mut shared = self.lock();
+        shared.screen.clone_from(fb);
+        shared.frames += 1;
+    }
+}
+
+impl Input for Link {
+    fn keys(&self) -> KeypadState {
+        self.lock().keys
+    }
+}
+
+impl Audio for Link {
+    fn beep(&mut self, on: bool) {
+        self.lock().beeping = on;
+    }
+}
+
+/// An [`Emulator`] running on a thread of its own, for GUI toolkits such as
+/// egui or iced that draw on their own thread and hear about keys as
+/// events. Every method takes `&self` and the handle is `Send + Sync`, so
+/// it can sit in the app's state and be shared with whatever needs it.
+///
+/// The CPU isn't `Send` (trace hooks and random sources needn't be), so
+/// it is made on the emulator thread by the function given to
+/// [`EmulatorHandle::spawn`]. Dropping the handle stops the thread.
+pub struct EmulatorHandle {
+    link: Link,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EmulatorHandle {
+    /// Starts running the CPU `make_cpu` returns at normal pace, at
+    /// `instructions_per_frame`.
+    pub fn spawn(
+        instructions_per_frame: usize,
+        make_cpu: impl FnOnce() -> CPU + Send + 'static,
+    ) -> Self {
+        let link = Link(Arc::new(Mutex::new(Shared {
+            keys: [false; KEY_COUNT],
+            paused: false,
+            speed: None,
+            requests: Vec::new(),
+            screen: FrameBuffer::new(),
+            frames: 0,
+            beeping: false,
+            fault: None,
+            running: true,
+            stop: false,
+        })));
+        let thread_link = link.clone();
+        let thread = thread::spawn(move || {
+            let finished = Finished(thread_link);
+            let link = &finished.0;
+            let cpu = make_cpu();
+            link.lock().screen.clone_from(&cpu.display);
+            let mut emulator = Emulator::new(cpu, link.clone(), link.clone(), link.clone());
+            emulator.instructions_per_frame = instructions_per_frame;
+            let result = emulator.run(&SystemClock::new(), |emulator| serve(link, emulator));
+            link.lock().fault = result.err();
+        });
+        EmulatorHandle {
+            link,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn pause(&self) {
+        self.link.lock().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.link.lock().paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.link.lock().paused
+    }
+
+    /// Keys outside the keypad are ignored. The game sees the change at the
+    /// start of the next frame.
+    pub fn set_key(&self, key: u8, pressed: bool) {
+        if let Some(held) = self.link.lock().keys.get_mut(key as usize) {
+            *held = pressed;
+        }
+    }
+
+    /// For when the host's view loses focus, so no key sticks.
+    pub fn release_all(&self) {
+        self.link.lock().keys = [false; KEY_COUNT];
+    }
+
+    /// Instructions per second from the next frame on, see
+    /// [`Emulator::set_speed`].
+    pub fn set_speed(&self, instructions_per_second: u32) {
+        self.link.lock().speed = Some(instructions_per_second);
+    }
+
+    /// A copy of the screen as of the last frame.
+    pub fn framebuffer(&self) -> FrameBuffer {
+        self.link.lock().screen.clone()
+    }
+
+    /// Frames drawn so far, so hosts can tell whether
+    /// [`EmulatorHandle::framebuffer`] has anything new.
+    pub fn frames(&self) -> u64 {
+        self.link.lock().frames
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.link.lock().beeping
+    }
+
+    /// False once the program faulted or the handle was stopped.
+    pub fn is_running(&self) -> bool {
+        self.link.lock().running
+    }
+
+    /// What stopped the program, if it faulted.
+    pub fn fault(&self) -> Option<Fault> {
+        self.link.lock().fault.clone()
+    }
+
+    /// A snapshot taken between two frames. Blocks until the emulator
+    /// thread gets to it, at most a frame even while paused.
+    pub fn save_state(&self) -> Result<SaveState, HandleError> {
+        let (sender, receiver) = mpsc::channel();
+        self.request(Request::SaveState(sender))?;
+        receiver.recv().map_err(|_| HandleError::Stopped)
+    }
+
+    /// Puts the machine back into `state` between two frames, blocking
+    /// like [`EmulatorHandle::save_state`].
+    pub fn load_state(&self, state: SaveState) -> Result<(), HandleError> {
+        let (sender, receiver) = mpsc::channel();
+        self.request(Request::LoadState(Box::new(state), sender))?;
+        Ok(receiver.recv().map_err(|_| HandleError::Stopped)??)
+    }
+
+    /// Restarts the program between two frames, see
+    /// [`Emulator::soft_reset`].
+    pub fn soft_reset(&self) -> Result<(), HandleError> {
+        self.request(Request::Reset { hard: false })
+    }
+
+    /// See [`Emulator::hard_reset`].
+    pub fn hard_reset(&self) -> Result<(), HandleError> {
+        self.request(Request::Reset { hard: true })
+    }
+
+    fn request(&self, request: Request) -> Result<(), HandleError> {
+        let mut shared = self.link.lock();
+        if !shared.running {
+            return Err(HandleError::Stopped);
+        }
+        shared.requests.push(request);
+        Ok(())
+    }
+
+    /// Stops the emulator thread and waits for it to finish.
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.link.lock().stop = true;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for EmulatorHandle {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// Applies what the handle asked for since the last frame. Returns whether
+/// to keep running.
+fn serve(link: &Link, emulator: &mut Emulator<Link, Link, Link>) -> bool {
+    let mut shared = link.lock();
+    let pace = if shared.paused {
+        Pace::Paused
+    } else {
+        Pace::NORMAL
+    };
+    if emulator.pace() != pace {
+        emulator.set_pace(pace);
+    }
+    if let Some(speed) = shared.speed.take() {
+        emulator.set_speed(speed);
+    }
+    for request in mem::take(&mut shared.requests) {
+        // the handle may have given up waiting; that's fine
+        match request {
+            Request::SaveState(reply) => {
+                let _ = reply.send(emulator.cpu.save_state());
+            }
+            Request::LoadState(state, reply) => {
+                let result = emulator.cpu.load_state(&state);
+                if result.is_ok() {
+                    shared.screen.clone_from(&emulator.cpu.display);
+                }
+                let _ = reply.send(result);
+            }
+            Request::Reset { hard } => {
+                // draws, which takes the lock
+                drop(shared);
+                if hard {
+                    emulator.hard_reset();
+                } else {
+                    emulator.soft_reset();
+                }
+                shared = link.lock();
+            }
+        }
+    }
+    !shared.stop
+}
+
+/// Marks the emulator thread finished however it ends, panics in
+/// `make_cpu` included, and drops the requests it will never answer so
+/// nobody waits on them forever.
+struct Finished(Link);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        let mut shared = self.0.lock();
+        shared.running = false;
+        shared.beeping = false;
+        shared.requests.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::asm::assemble;
+
+    fn spawn(source: &str) -> EmulatorHandle {
+        let rom = assemble(source).unwrap();
+        EmulatorHandle::spawn(11, move || {
+            let mut cpu = CPU::new();
+            cpu.load_rom(&rom).unwrap();
+            cpu
+        })
+    }
+
+    fn wait_for(mut done: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn handles_can_be_shared_between_threads() {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<EmulatorHandle>();
+    }
+
+    #[test]
+    fn keys_reach_the_game_and_the_screen_comes_back() {
+        // draws the glyph of whichever key is pressed
+        let handle = spawn("LD V0, K\nLD F, V0\nDRW V1, V1, 5\nend:\nJP end");
+        wait_for(|| handle.frames() > 0);
+        // FX0A takes the key once it's let go
+        handle.set_key(0, true);
+        let pressed = handle.frames();
+        wait_for(|| handle.frames() > pressed + 1);
+        handle.set_key(0, false);
+        wait_for(|| handle.framebuffer().color(0, 0) != 0);
+        assert!(!handle.is_paused());
+
+        let handle = Arc::new(handle);
+        let other = Arc::clone(&handle);
+        let state = thread::spawn(move || other.save_state()).join().unwrap();
+        assert_eq!(state.unwrap().registers[0], 0);
+
+        handle.soft_reset().unwrap();
+        wait_for(|| handle.framebuffer().color(0, 0) == 0);
+    }
+
+    #[test]
+    fn pausing_stops_frames_but_still_answers() {
+        let handle = spawn("loop:\nADD V0, 1\nJP loop");
+        wait_for(|| handle.frames() > 0);
+        handle.pause();
+        let state = handle.save_state().unwrap();
+        let frames = handle.frames();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(handle.frames(), frames);
+        assert_eq!(handle.save_state().unwrap(), state);
+
+        handle.resume();
+        wait_for(|| handle.frames() > frames);
+        handle.load_state(state.clone()).unwrap();
+
+        let link = handle.link.clone();
+        handle.stop();
+        assert!(!link.lock().running);
+    }
+}
@@ -0,0 +1,744 @@
+//! Frontend-agnostic driver: an [`Emulator`] owns the CPU and talks to the
+//! outside world only through the [`Screen`], [`Input`] and [`Audio`]
+//! traits, so a window, a terminal, an embedded display or a test double
+//! can be plugged in without touching the interpreter.
+//!
+//! `()` implements all three, for pieces a frontend doesn't have.
+//!
+//! Frontends that would rather be told when something happens than look
+//! every frame can take [`EmulatorEvent`]s through an [`EventSink`] or
+//! [`Emulator::subscribe`].
+//!
+//! GUI hosts embedding the emulator in their own views, such as an egui
+//! widget, can plug in a [`TextureScreen`] and [`HeldKeys`] and draw the
+//! texture wherever they like, or leave the emulator running on a thread
+//! of its own behind an [`EmulatorHandle`].
+//!
+//! For an edit-run loop, a [`RomWatcher`] notices when the ROM or its
+//! assembly source changes and [`Emulator::load_program`] swaps it in,
+//! keeping the breakpoints.
+
+mod embed;
+mod events;
+mod handle;
+mod reload;
+mod rewind;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc::{self, Receiver};
+
+use crate::cheats::{CheatKind, Cheats};
+use crate::clock::{Clock, FramePacer};
+use crate::cpu::{Fault, Status, CPU};
+use crate::display::FrameBuffer;
+use crate::keypad::{KeypadState, KEY_COUNT};
+
+pub use embed::{HeldKeys, TextureScreen};
+pub use events::{EmulatorEvent, EventSink};
+pub use handle::{EmulatorHandle, HandleError};
+pub use reload::{read_program, ReloadError, RomWatcher, SOURCE_EXTENSIONS};
+pub use rewind::RewindBuffer;
+
+/// Frames per second of real time, which the timers tick at.
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+/// How fast [`Emulator::run`] plays the game, relative to real time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    Paused,
+    /// Real time scaled by a percentage: 100 is normal, 200 turbo and 50
+    /// slow motion. Timers and sound scale along.
+    Percent(u32),
+    /// As fast as the host can go, without sleeping.
+    Unlimited,
+}
+
+impl Pace {
+    pub const NORMAL: Pace = Pace::Percent(100);
+}
+
+pub trait Screen {
+    /// Shows a finished frame, called once per [`Emulator::run_frame`].
+    fn draw(&mut self, fb: &FrameBuffer);
+}
+
+pub trait Input {
+    /// Which keys are held down right now.
+    fn keys(&self) -> KeypadState;
+}
+
+pub trait Audio {
+    /// Starts or stops the buzzer. Only called when the state changes.
+    fn beep(&mut self, on: bool);
+}
+
+impl Screen for () {
+    fn draw(&mut self, _fb: &FrameBuffer) {}
+}
+
+impl Input for () {
+    fn keys(&self) -> KeypadState {
+        [false; KEY_COUNT]
+    }
+}
+
+impl Audio for () {
+    fn beep(&mut self, _on: bool) {}
+}
+
+pub struct Emulator<S: Screen, I: Input, A: Audio> {
+    pub cpu: CPU,
+    pub screen: S,
+    pub input: I,
+    pub audio: A,
+    pub instructions_per_frame: usize,
+    /// Frames in a row [`Emulator::run`] may leave undrawn to catch up when
+    /// the host can't keep up, or 0, the default, to draw every frame and
+    /// let the game slow down instead.
+    pub max_frame_skip: u32,
+    /// Frames left undrawn in a row so far.
+    skipped: u32,
+    beeping: bool,
+    rewind: Option<RewindBuffer>,
+    pace: Pace,
+    /// What [`Emulator::toggle_pause`] goes back to.
+    resume_pace: Pace,
+    events: Option<Box<dyn EventSink>>,
+    /// The screen as of the last [`EmulatorEvent::ScreenUpdated`], only
+    /// kept while someone listens.
+    shown: Option<FrameBuffer>,
+    /// Status at the end of the last frame, to report changes only.
+    status: Status,
+    breakpoints: BTreeSet<usize>,
+    /// Breakpoint just stopped at, which lets execution past it once.
+    stopped_at: Option<usize>,
+    /// Labels of the program from the last [`Emulator::load_program`].
+    labels: BTreeMap<String, usize>,
+    cheats: Cheats,
+}
+
+impl<S: Screen, I: Input, A: Audio> Emulator<S, I, A> {
+    pub fn new(cpu: CPU, screen: S, input: I, audio: A) -> Self {
+        Emulator {
+            cpu,
+            screen,
+            input,
+            audio,
+            instructions_per_frame: 11,
+            max_frame_skip: 0,
+            skipped: 0,
+            beeping: false,
+            rewind: None,
+            pace: Pace::NORMAL,
+            resume_pace: Pace::NORMAL,
+            events: None,
+            shown: None,
+            status: Status::Continue,
+            breakpoints: BTreeSet::new(),
+            stopped_at: None,
+            labels: BTreeMap::new(),
+            cheats: Cheats::new(),
+        }
+    }
+
+    /// Sends every [`EmulatorEvent`] from now on to `sink`, replacing any
+    /// earlier one.
+    pub fn set_event_sink(&mut self, sink: impl EventSink + 'static) {
+        self.events = Some(Box::new(sink));
+        self.shown = Some(self.cpu.display.clone());
+    }
+
+    /// A channel of [`EmulatorEvent`]s, for a frontend that handles them
+    /// on another thread. Replaces any earlier sink.
+    pub fn subscribe(&mut self) -> Receiver<EmulatorEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.set_event_sink(sender);
+        receiver
+    }
+
+    /// Stops before the instruction at `address` is run, pausing and
+    /// sending [`EmulatorEvent::Breakpoint`]. Frames then run instruction
+    /// by instruction, at [`Emulator::instructions_per_frame`] whatever the
+    /// CPU's accuracy.
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Returns whether there was a breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Replaces the cheats in use, writing the enabled patches right away
+    /// and again after every reset. While a freeze is on, frames run
+    /// instruction by instruction like with breakpoints.
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        self.cheats = cheats;
+        self.cheats.apply_patches(&mut self.cpu);
+        self.cheats.enforce_freezes(&mut self.cpu);
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    /// Turns the cheat called `name` on or off, returning whether there is
+    /// one. A patch is written or undone at once; see
+    /// [`crate::cheats::Cheat::restore`].
+    pub fn set_cheat_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.set_enabled(name, enabled) else {
+            return false;
+        };
+        match (cheat.kind, enabled) {
+            (CheatKind::Patch, false) => cheat.restore(&mut self.cpu),
+            (_, true) => cheat.write(&mut self.cpu),
+            (CheatKind::Freeze, false) => {}
+        }
+        true
+    }
+
+    fn emit(&mut self, event: EmulatorEvent) {
+        if let Some(sink) = &mut self.events {
+            sink.event(event);
+        }
+    }
+
+    /// Sets the CPU speed in instructions per second, rounded down to whole
+    /// instructions per frame.
+    pub fn set_speed(&mut self, instructions_per_second: u32) {
+        let per_frame = (instructions_per_second / FRAMES_PER_SECOND).max(1);
+        self.instructions_per_frame = per_frame as usize;
+    }
+
+    /// Instructions per second at normal pace.
+    pub fn speed(&self) -> u32 {
+        self.instructions_per_frame as u32 * FRAMES_PER_SECOND
+    }
+
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    pub fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+    }
+
+    /// Pauses, or goes back to the pace from before the pause.
+    pub fn toggle_pause(&mut self) {
+        if self.pace == Pace::Paused {
+            self.pace = self.resume_pace;
+        } else {
+            self.resume_pace = self.pace;
+            self.pace = Pace::Paused;
+        }
+    }
+
+    /// Whether the last frame [`Emulator::run`] ran was left undrawn to
+    /// catch up. Frontends that present from the `keep_going` callback
+    /// should skip presenting too.
+    pub fn skipped_frame(&self) -> bool {
+        self.skipped > 0
+    }
+
+    /// Runs frames at the current [`Pace`], sleeping on `clock` in between,
+    /// until `keep_going` returns false or the program faults.
+    ///
+    /// `keep_going` is called after every frame, and 60 times a second
+    /// while paused, so the frontend can poll its controls and change the
+    /// pace or speed from there. Frames run late are skipped as allowed by
+    /// [`Emulator::max_frame_skip`]; they still run and sound, they just
+    /// aren't drawn.
+    pub fn run(
+        &mut self,
+        clock: &dyn Clock,
+        mut keep_going: impl FnMut(&mut Self) -> bool,
+    ) -> Result<(), Fault> {
+        let mut paced = None;
+        let mut pacer = FramePacer::new(FRAMES_PER_SECOND);
+        let mut skip = false;
+        loop {
+            if paced != Some(self.pace) {
+                let percent = match self.pace {
+                    Pace::Percent(percent) => percent,
+                    Pace::Paused | Pace::Unlimited => 100,
+                };
+                // in u64, as 60 times a large percentage overflows u32
+                let rate = FRAMES_PER_SECOND as u64 * percent as u64 / 100;
+                pacer = FramePacer::new(rate as u32);
+                paced = Some(self.pace);
+            }
+            if self.pace != Pace::Paused {
+                self.frame(!skip)?;
+                self.skipped = if skip { self.skipped + 1 } else { 0 };
+            }
+            if !keep_going(self) {
+                return Ok(());
+            }
+            skip = false;
+            if self.pace != Pace::Unlimited {
+                let wait = pacer.wait(clock);
+                // no time left to wait means the next frame is already late
+                skip = wait.is_zero() && self.skipped < self.max_frame_skip;
+                clock.sleep(wait);
+            }
+        }
+    }
+
+    /// Starts keeping the last `capacity` frames for [`Emulator::rewind`],
+    /// e.g. 300 for five seconds. Zero turns rewinding off again.
+    pub fn enable_rewind(&mut self, capacity: usize) {
+        self.rewind = (capacity > 0).then(|| RewindBuffer::new(capacity));
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// Goes back `steps` frames, as far as has been recorded, and redraws
+    /// the screen. Returns how many frames were undone.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let Some(buffer) = &mut self.rewind else {
+            return 0;
+        };
+        let taken = buffer.rewind(&mut self.cpu, steps);
+        if taken > 0 {
+            self.update_beeper();
+            self.screen.draw(&self.cpu.display);
+            self.report_screen();
+        }
+        taken
+    }
+
+    /// Restarts the program with [`CPU::soft_reset`]. The rewind history
+    /// is dropped, the buzzer stops and the blank screen is drawn right
+    /// away.
+    pub fn soft_reset(&mut self) {
+        self.cpu.soft_reset();
+        self.after_reset(false);
+    }
+
+    /// Like [`Emulator::soft_reset`] with [`CPU::hard_reset`], which also
+    /// forgets the RPL flags.
+    pub fn hard_reset(&mut self) {
+        self.cpu.hard_reset();
+        self.after_reset(true);
+    }
+
+    fn after_reset(&mut self, hard: bool) {
+        if let Some(buffer) = &mut self.rewind {
+            buffer.clear();
+        }
+        self.cheats.apply_patches(&mut self.cpu);
+        self.status = Status::Continue;
+        self.stopped_at = None;
+        self.emit(EmulatorEvent::Reset { hard });
+        self.update_beeper();
+        self.screen.draw(&self.cpu.display);
+        self.report_screen();
+    }
+
+    /// Runs one 60 Hz frame: polls the input, executes, updates the buzzer,
+    /// ticks the timers and draws. A fault skips the rest of the frame.
+    pub fn run_frame(&mut self) -> Result<Status, Fault> {
+        self.frame(true)
+    }
+
+    fn frame(&mut self, draw: bool) -> Result<Status, Fault> {
+        for (key, pressed) in self.input.keys().into_iter().enumerate() {
+            if self.cpu.keypad.is_pressed(key as u8) != pressed {
+                self.cpu.set_key(key as u8, pressed);
+            }
+        }
+
+        if let Some(buffer) = &mut self.rewind {
+            buffer.record(&self.cpu);
+        }
+        let result = self.execute();
+        // reported even when the stack overflowed in the same frame
+        if let Some(chain) = self.cpu.take_stack_warning() {
+            self.emit(EmulatorEvent::StackWarning(chain));
+        }
+        let status = result?;
+        if status != self.status {
+            match status {
+                Status::Halted => self.emit(EmulatorEvent::Halted),
+                Status::WaitingForKey => self.emit(EmulatorEvent::WaitingForKey),
+                Status::Continue => {}
+            }
+            self.status = status;
+        }
+
+        self.update_beeper();
+        self.cpu.tick_timers();
+        if draw {
+            self.screen.draw(&self.cpu.display);
+        }
+        self.report_screen();
+        Ok(status)
+    }
+
+    fn execute(&mut self) -> Result<Status, Fault> {
+        let freezing = self.cheats.has_freezes();
+        if self.breakpoints.is_empty() && !freezing {
+            return self.cpu.run_frame(self.instructions_per_frame);
+        }
+        for _ in 0..self.instructions_per_frame {
+            let pc = self.cpu.memory_position;
+            let resumed = self.stopped_at.take() == Some(pc);
+            if self.breakpoints.contains(&pc) && !resumed {
+                self.stopped_at = Some(pc);
+                if self.pace != Pace::Paused {
+                    self.toggle_pause();
+                }
+                self.emit(EmulatorEvent::Breakpoint(pc));
+                return Ok(Status::Continue);
+            }
+            let result = self.cpu.step();
+            if freezing {
+                self.cheats.enforce_freezes(&mut self.cpu);
+            }
+            let status = result?;
+            if status != Status::Continue {
+                return Ok(status);
+            }
+        }
+        Ok(Status::Continue)
+    }
+
+    fn update_beeper(&mut self) {
+        let beeping = self.cpu.is_beeping();
+        if beeping != self.beeping {
+            self.audio.beep(beeping);
+            self.beeping = beeping;
+            self.emit(if beeping {
+                EmulatorEvent::BeepStarted
+            } else {
+                EmulatorEvent::BeepStopped
+            });
+        }
+    }
+
+    fn report_screen(&mut self) {
+        let Some(shown) = &mut self.shown else {
+            return;
+        };
+        if *shown != self.cpu.display {
+            shown.clone_from(&self.cpu.display);
+            self.emit(EmulatorEvent::ScreenUpdated);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Recorder {
+        frames: usize,
+        beeps: Vec<bool>,
+    }
+
+    impl Screen for Recorder {
+        fn draw(&mut self, _fb: &FrameBuffer) {
+            self.frames += 1;
+        }
+    }
+
+    impl Audio for Recorder {
+        fn beep(&mut self, on: bool) {
+            self.beeps.push(on);
+        }
+    }
+
+    struct HeldKey(Option<u8>);
+
+    impl Input for HeldKey {
+        fn keys(&self) -> KeypadState {
+            let mut keys = [false; KEY_COUNT];
+            if let Some(key) = self.0 {
+                keys[key as usize] = true;
+            }
+            keys
+        }
+    }
+
+    #[test]
+    fn drives_screen_input_and_audio() {
+        let mut cpu = CPU::new();
+        // LD V0, K; LD ST, V0; JP 0x004
+        cpu.memory[..6].copy_from_slice(&[0xF0, 0x0A, 0xF0, 0x18, 0x10, 0x04]);
+        let mut emulator =
+            Emulator::new(cpu, Recorder::default(), HeldKey(None), Recorder::default());
+
+        assert_eq!(emulator.run_frame(), Ok(Status::WaitingForKey));
+        emulator.input.0 = Some(2);
+        // the sound timer is set to 2 and runs out after two frames
+        for _ in 0..4 {
+            emulator.run_frame().unwrap();
+        }
+
+        assert_eq!(emulator.screen.frames, 5);
+        assert_eq!(emulator.audio.beeps, [true, false]);
+        assert_eq!(emulator.cpu.registers[0], 2);
+    }
+
+    fn counting_emulator() -> Emulator<(), (), ()> {
+        let mut cpu = CPU::new();
+        // ADD V0, 1; JP 0x000
+        cpu.memory[..4].copy_from_slice(&[0x70, 0x01, 0x10, 0x00]);
+        let mut emulator = Emulator::new(cpu, (), (), ());
+        emulator.instructions_per_frame = 2;
+        emulator
+    }
+
+    /// Runs until `frames` callbacks happened and returns the time taken.
+    fn time_run(emulator: &mut Emulator<(), (), ()>, frames: usize) -> Duration {
+        let clock = ManualClock::new();
+        let mut left = frames;
+        emulator
+            .run(&clock, |_| {
+                left -= 1;
+                left > 0
+            })
+            .unwrap();
+        clock.now()
+    }
+
+    #[test]
+    fn run_paces_frames_in_real_time() {
+        let mut emulator = counting_emulator();
+        let frame = Duration::from_secs(1) / FRAMES_PER_SECOND;
+
+        assert_eq!(time_run(&mut emulator, 7), frame * 6);
+        assert_eq!(emulator.cpu.registers[0], 7);
+
+        emulator.set_pace(Pace::Percent(200));
+        assert_eq!(time_run(&mut emulator, 7), frame * 3);
+        emulator.set_pace(Pace::Unlimited);
+        assert_eq!(time_run(&mut emulator, 7), Duration::ZERO);
+        assert_eq!(emulator.cpu.registers[0], 21);
+        // far too fast to wait for, but no overflow
+        emulator.set_pace(Pace::Percent(u32::MAX));
+        assert!(time_run(&mut emulator, 7) < frame);
+    }
+
+    #[test]
+    fn pausing_stops_the_program_but_not_the_loop() {
+        let mut emulator = counting_emulator();
+        emulator.set_pace(Pace::Percent(50));
+        emulator.toggle_pause();
+
+        let mut polls = 0;
+        emulator
+            .run(&ManualClock::new(), |emulator| {
+                polls += 1;
+                if polls == 3 {
+                    emulator.toggle_pause();
+                }
+                polls < 5
+            })
+            .unwrap();
+
+        assert_eq!(emulator.pace(), Pace::Percent(50));
+        assert_eq!(emulator.cpu.registers[0], 2);
+    }
+
+    /// Takes 40 ms to draw, too slow for 60 frames a second.
+    struct SlowScreen {
+        clock: Rc<ManualClock>,
+        draws: usize,
+    }
+
+    impl Screen for SlowScreen {
+        fn draw(&mut self, _fb: &FrameBuffer) {
+            self.clock.advance(Duration::from_millis(40));
+            self.draws += 1;
+        }
+    }
+
+    /// Runs 60 frames on a slow screen and returns how many were drawn and
+    /// how long they took.
+    fn run_slowly(max_frame_skip: u32) -> (usize, Duration) {
+        let clock = Rc::new(ManualClock::new());
+        let screen = SlowScreen {
+            clock: clock.clone(),
+            draws: 0,
+        };
+        let mut emulator = Emulator::new(CPU::new(), screen, (), ());
+        emulator.max_frame_skip = max_frame_skip;
+        let (mut frames, mut drawn) = (0, 0);
+        emulator
+            .run(&*clock, |emulator| {
+                frames += 1;
+                assert_eq!(emulator.skipped_frame(), emulator.screen.draws == drawn);
+                drawn = emulator.screen.draws;
+                frames < 60
+            })
+            .unwrap();
+        (emulator.screen.draws, clock.now())
+    }
+
+    #[test]
+    fn frame_skip_keeps_the_game_at_full_speed() {
+        let (draws, took) = run_slowly(0);
+        assert_eq!(draws, 60);
+        assert!(took >= Duration::from_millis(2400));
+
+        let (draws, took) = run_slowly(3);
+        assert!(draws < 25, "{} frames drawn", draws);
+        assert!(took < Duration::from_millis(1100), "took {:?}", took);
+    }
+
+    #[test]
+    fn speed_is_set_in_instructions_per_second() {
+        let mut emulator = counting_emulator();
+
+        emulator.set_speed(700);
+        assert_eq!(emulator.instructions_per_frame, 11);
+        emulator.set_speed(1);
+        assert_eq!(emulator.speed(), 60);
+    }
+
+    #[test]
+    fn rewind_undoes_frames() {
+        let mut cpu = CPU::new();
+        // ADD V0, 1; JP 0x000
+        cpu.memory[..4].copy_from_slice(&[0x70, 0x01, 0x10, 0x00]);
+        let mut emulator = Emulator::new(cpu, Recorder::default(), (), ());
+        emulator.instructions_per_frame = 2;
+        emulator.enable_rewind(3);
+
+        for _ in 0..5 {
+            emulator.run_frame().unwrap();
+        }
+        assert_eq!(emulator.cpu.registers[0], 5);
+
+        assert_eq!(emulator.rewind(2), 2);
+        assert_eq!(emulator.cpu.registers[0], 3);
+        assert_eq!(emulator.screen.frames, 6);
+        // only three frames were kept, and one of them is left
+        assert_eq!(emulator.rewind(10), 1);
+        assert_eq!(emulator.cpu.registers[0], 2);
+        assert_eq!(emulator.rewind(1), 0);
+
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.registers[0], 3);
+    }
+
+    #[test]
+    fn events_report_what_changed() {
+        let mut cpu = CPU::new();
+        // LD V0, K; LD F, V0; DRW V1, V1, 5; LD ST, V0; JP 0x008
+        cpu.memory[..10]
+            .copy_from_slice(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0xF0, 0x18, 0x10, 0x08]);
+        let mut emulator = Emulator::new(cpu, (), HeldKey(None), ());
+        let events = emulator.subscribe();
+
+        emulator.run_frame().unwrap();
+        emulator.run_frame().unwrap();
+        emulator.input.0 = Some(2);
+        for _ in 0..5 {
+            emulator.run_frame().unwrap();
+        }
+
+        let events: Vec<EmulatorEvent> = events.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                EmulatorEvent::WaitingForKey,
+                // both happen in the same frame
+                EmulatorEvent::BeepStarted,
+                EmulatorEvent::ScreenUpdated,
+                EmulatorEvent::BeepStopped,
+            ]
+        );
+    }
+
+    #[test]
+    fn breakpoints_pause_once_per_visit() {
+        let mut cpu = CPU::new();
+        // ADD V0, 1; ADD V0, 1; then 0000
+        cpu.memory[..4].copy_from_slice(&[0x70, 0x01, 0x70, 0x01]);
+        let mut emulator = Emulator::new(cpu, (), (), ());
+        let mut events = Vec::new();
+        let (sender, receiver) = mpsc::channel();
+        emulator.set_event_sink(move |event| sender.send(event).unwrap());
+        emulator.add_breakpoint(0x002);
+
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.registers[0], 1);
+        assert_eq!(emulator.pace(), Pace::Paused);
+        events.extend(receiver.try_iter());
+
+        emulator.toggle_pause();
+        assert_eq!(emulator.run_frame(), Ok(Status::Halted));
+        assert_eq!(emulator.cpu.registers[0], 2);
+        assert!(emulator.remove_breakpoint(0x002));
+        events.extend(receiver.try_iter());
+
+        assert_eq!(
+            events,
+            [EmulatorEvent::Breakpoint(0x002), EmulatorEvent::Halted]
+        );
+    }
+
+    #[test]
+    fn resets_restart_the_program_and_tell_listeners() {
+        let program = "LD V0, 30\nLD ST, V0\nLD F, V0\nDRW V1, V1, 5\nend:\nJP end";
+        let mut cpu = CPU::new_with_mode(crate::cpu::EmulatorMode::SuperChip);
+        cpu.load_rom(&crate::asm::assemble(program).unwrap())
+            .unwrap();
+        cpu.rpl_flags[0] = 7;
+        let mut emulator = Emulator::new(cpu, Recorder::default(), (), Recorder::default());
+        emulator.enable_rewind(10);
+        emulator.run_frame().unwrap();
+        let events = emulator.subscribe();
+
+        emulator.soft_reset();
+        assert_eq!(emulator.cpu.memory_position, 0x200);
+        assert_eq!(emulator.cpu.rpl_flags[0], 7);
+        assert!(emulator.rewind_buffer().unwrap().is_empty());
+        assert_eq!(emulator.audio.beeps, [true, false]);
+        assert_eq!(emulator.screen.frames, 2);
+        emulator.hard_reset();
+        assert_eq!(emulator.cpu.rpl_flags[0], 0);
+
+        let events: Vec<EmulatorEvent> = events.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                EmulatorEvent::Reset { hard: false },
+                EmulatorEvent::BeepStopped,
+                EmulatorEvent::ScreenUpdated,
+                EmulatorEvent::Reset { hard: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn cheats_patch_the_program_and_freeze_memory() {
+        let program = "LD V0, 1\nLD I, 0x300\nLD [I], V0\nLD V0, [I]\nend:\nJP end";
+        let mut cpu = CPU::new();
+        cpu.load_rom(&crate::asm::assemble(program).unwrap())
+            .unwrap();
+        let mut emulator = Emulator::new(cpu, (), (), ());
+        let cheats = "[patch.two]\n0x201 = 2\n[freeze.five]\n0x300 = 5";
+        emulator.set_cheats(Cheats::parse(cheats).unwrap());
+
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.registers[0], 5);
+
+        assert!(emulator.set_cheat_enabled("five", false));
+        emulator.soft_reset();
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.registers[0], 2);
+
+        assert!(emulator.set_cheat_enabled("two", false));
+        assert_eq!(emulator.cpu.memory[0x201], 1);
+        assert!(!emulator.set_cheat_enabled("six", true));
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{error, fmt, fs};
+
+use super::{Audio, Emulator, Input, Screen};
+use crate::asm::{self, AsmError, Program};
+use crate::cpu::RomError;
+
+/// Extensions [`read_program`] assembles rather than loads as they are,
+/// besides Octo's [`asm::OCTO_EXTENSIONS`].
+pub const SOURCE_EXTENSIONS: &[&str] = &["s", "asm"];
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Rom(RomError),
+    Asm(AsmError),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Rom(err) => write!(f, "{}", err),
+            ReloadError::Asm(err) => write!(f, "could not assemble: {}", err),
+        }
+    }
+}
+
+impl error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReloadError::Rom(err) => Some(err),
+            ReloadError::Asm(err) => Some(err),
+        }
+    }
+}
+
+impl From<RomError> for ReloadError {
+    fn from(err: RomError) -> Self {
+        ReloadError::Rom(err)
+    }
+}
+
+impl From<AsmError> for ReloadError {
+    fn from(err: AsmError) -> Self {
+        ReloadError::Asm(err)
+    }
+}
+
+/// The program at `path`: assembled, labels and all, if it is source by
+/// [`SOURCE_EXTENSIONS`] or [`asm::OCTO_EXTENSIONS`], otherwise the ROM
+/// image as it is, with no labels.
+pub fn read_program(path: &Path) -> Result<Program, ReloadError> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if let Some(extension) = extension {
+        if SOURCE_EXTENSIONS.contains(&extension) {
+            let source = fs::read_to_string(path).map_err(RomError::Io)?;
+            return Ok(asm::assemble_program(&source)?);
+        }
+        if asm::OCTO_EXTENSIONS.contains(&extension) {
+            let source = fs::read_to_string(path).map_err(RomError::Io)?;
+            return Ok(asm::assemble_octo(&source)?);
+        }
+    }
+    let rom = fs::read(path).map_err(RomError::Io)?;
+    Ok(Program {
+        rom,
+        labels: Default::default(),
+    })
+}
+
+/// Looks for changes to a ROM or its source by modification time, for an
+/// edit-run loop. Polled rather than notified, so call [`RomWatcher::poll`]
+/// every so often, twice a second is plenty.
+#[derive(Debug, Clone)]
+pub struct RomWatcher {
+    path: PathBuf,
+    seen: Option<SystemTime>,
+}
+
+impl RomWatcher {
+    /// Watches `path` for changes from how it is now.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let seen = modified(&path);
+        RomWatcher { path, seen }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The program, read again with [`read_program`], if the file changed
+    /// since the last poll. A file that can't be read, e.g. caught
+    /// mid-write, is tried again on the next poll; one that doesn't
+    /// assemble is reported once.
+    pub fn poll(&mut self) -> Option<Result<Program, ReloadError>> {
+        let now = modified(&self.path);
+        if now == self.seen {
+            return None;
+        }
+        match read_program(&self.path) {
+            Err(ReloadError::Rom(RomError::Io(_))) => None,
+            result => {
+                self.seen = now;
+                Some(result)
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl<S: Screen, I: Input, A: Audio> Emulator<S, I, A> {
+    /// Reads the program at `path` with [`read_program`] and switches to
+    /// it, see [`Emulator::load_program`].
+    pub fn reload_rom(&mut self, path: &Path) -> Result<(), ReloadError> {
+        let program = read_program(path)?;
+        Ok(self.load_program(program)?)
+    }
+
+    /// Replaces the program with [`crate::cpu::CPU::reload_rom`] and starts
+    /// it over like [`Emulator::hard_reset`]. Breakpoints, cheats, input
+    /// and pace are kept. A breakpoint on a label the last program had
+    /// moves to wherever the label is now; the rest stay at their
+    /// addresses.
+    pub fn load_program(&mut self, program: Program) -> Result<(), RomError> {
+        self.cpu.reload_rom(&program.rom)?;
+        let old = std::mem::replace(&mut self.labels, program.labels);
+        self.breakpoints = std::mem::take(&mut self.breakpoints)
+            .into_iter()
+            .map(|address| {
+                old.iter()
+                    .filter(|(_, &at)| at == address)
+                    .find_map(|(label, _)| self.labels.get(label).copied())
+                    .unwrap_or(address)
+            })
+            .collect();
+        self.after_reset(true);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    const BEFORE: &str = "
+        start: LD V0, 1
+        loop: ADD V0, 1
+            JP loop
+    ";
+    const AFTER: &str = "
+        start: LD V0, 1
+            LD V1, 2
+        loop: ADD V0, 1
+            JP loop
+    ";
+
+    #[test]
+    fn breakpoints_follow_their_labels() {
+        let mut emulator = Emulator::new(CPU::new(), (), (), ());
+        emulator
+            .load_program(asm::assemble_program(BEFORE).unwrap())
+            .unwrap();
+        emulator.add_breakpoint(0x202);
+        emulator.add_breakpoint(0x300);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.memory_position, 0x202);
+
+        emulator
+            .load_program(asm::assemble_program(AFTER).unwrap())
+            .unwrap();
+        assert_eq!(emulator.cpu.memory_position, 0x200);
+        assert!(emulator.remove_breakpoint(0x204));
+        assert!(emulator.remove_breakpoint(0x300));
+        assert!(!emulator.remove_breakpoint(0x202));
+    }
+
+    #[test]
+    fn the_watcher_reassembles_changed_sources() {
+        let path = std::env::temp_dir().join("chip8_rom_watcher.s");
+        fs::write(&path, BEFORE).unwrap();
+        let mut watcher = RomWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        // make sure the time stamp moves even on coarse file systems
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::write(&path, "JP nowhere").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(later))
+            .unwrap();
+        let result = watcher.poll();
+        let again = watcher.poll();
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Some(Err(ReloadError::Asm(_)))));
+        assert!(again.is_none());
+    }
+}
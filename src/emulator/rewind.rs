@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{SaveState, CPU};
+
+/// The last `capacity` frames as save states, oldest first, so a frontend
+/// can step back in time. Snapshots are the same [`SaveState`]s that save
+/// slots use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewindBuffer {
+    capacity: usize,
+    states: VecDeque<SaveState>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            states: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many frames back [`RewindBuffer::rewind`] can go.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Snapshots `cpu`, dropping the oldest snapshot once full.
+    pub fn record(&mut self, cpu: &CPU) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(cpu.save_state());
+    }
+
+    /// Restores the snapshot from `steps` recordings ago, 1 being the most
+    /// recent, and forgets everything after it. Going further back than
+    /// recorded stops at the oldest snapshot. Returns how many steps were
+    /// actually taken.
+    pub fn rewind(&mut self, cpu: &mut CPU, steps: usize) -> usize {
+        let steps = steps.min(self.states.len());
+        if steps == 0 {
+            return 0;
+        }
+        self.states.truncate(self.states.len() - steps + 1);
+        let state = self.states.pop_back().expect("at least one state is left");
+        cpu.load_state(&state)
+            .expect("states recorded from a CPU load back into it");
+        steps
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
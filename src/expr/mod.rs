@@ -0,0 +1,278 @@
+//! Small expressions over the machine's state, such as where a game keeps
+//! its score or whether a level is done, for the parts of the crate that
+//! let users point at memory without writing Rust.
+
+use std::{error, fmt};
+
+use crate::cpu::CPU;
+
+/// An expression that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    /// Byte offset into the expression.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.position, self.message)
+    }
+}
+
+impl error::Error for ExprError {}
+
+/// A number computed from the machine's state, such as a game's score.
+/// Written with `+`, `-`, `*` and parentheses over decimal or `0x` hex
+/// numbers, the registers `V0` to `VF`, `I`, `DT`, `ST` and bytes of
+/// memory as `mem[ADDR]`, e.g. `mem[0x3F0] * 100 + mem[0x3F1] * 10 +
+/// mem[0x3F2]` for three BCD digits. One comparison, `==`, `!=`, `<`,
+/// `<=`, `>` or `>=`, may join two of those into a condition that is 1
+/// when it holds and 0 when not, e.g. `mem[0x300] >= 2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(u8),
+    I,
+    DelayTimer,
+    SoundTimer,
+    Memory(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// Longer operators first, so `<=` isn't read as `<`.
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn holds(self, a: i64, b: i64) -> bool {
+        match self {
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { text, at: 0 };
+        let expr = parser.comparison()?;
+        parser.skip_space();
+        if parser.at < text.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expr)
+    }
+
+    /// The value for `cpu` now. Memory outside the machine reads as 0 and
+    /// arithmetic wraps instead of overflowing.
+    pub fn eval(&self, cpu: &CPU) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Register(x) => cpu.registers[*x as usize] as i64,
+            Expr::I => cpu.i as i64,
+            Expr::DelayTimer => cpu.delay_timer as i64,
+            Expr::SoundTimer => cpu.sound_timer as i64,
+            Expr::Memory(address) => cpu.memory.get(*address).map_or(0, |b| *b as i64),
+            Expr::Neg(e) => e.eval(cpu).wrapping_neg(),
+            Expr::Add(a, b) => a.eval(cpu).wrapping_add(b.eval(cpu)),
+            Expr::Sub(a, b) => a.eval(cpu).wrapping_sub(b.eval(cpu)),
+            Expr::Mul(a, b) => a.eval(cpu).wrapping_mul(b.eval(cpu)),
+            Expr::Compare(comparison, a, b) => comparison.holds(a.eval(cpu), b.eval(cpu)) as i64,
+        }
+    }
+
+    /// Whether the expression is non-zero for `cpu`.
+    pub fn holds(&self, cpu: &CPU) -> bool {
+        self.eval(cpu) != 0
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ExprError {
+        ExprError {
+            position: self.at,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.at..];
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.text[self.at..].starts_with(token) {
+            self.at += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        let expr = self.sum()?;
+        for (operator, comparison) in Comparison::OPERATORS {
+            if self.eat(operator) {
+                return Ok(Expr::Compare(
+                    comparison,
+                    Box::new(expr),
+                    Box::new(self.sum()?),
+                ));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.product()?;
+        loop {
+            if self.eat("+") {
+                expr = Expr::Add(Box::new(expr), Box::new(self.product()?));
+            } else if self.eat("-") {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.product()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.unary()?;
+        while self.eat("*") {
+            expr = Expr::Mul(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.comparison()?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(expr);
+        }
+        let start = self.at;
+        let word = self.word();
+        let upper = word.to_ascii_uppercase();
+        match upper.as_str() {
+            "" => Err(self.error("expected a value")),
+            "I" => Ok(Expr::I),
+            "DT" => Ok(Expr::DelayTimer),
+            "ST" => Ok(Expr::SoundTimer),
+            "MEM" => {
+                if !self.eat("[") {
+                    return Err(self.error("expected ["));
+                }
+                self.skip_space();
+                let address = self.number()?;
+                if !self.eat("]") {
+                    return Err(self.error("expected ]"));
+                }
+                Ok(Expr::Memory(address as usize))
+            }
+            _ if upper.len() == 2 && upper.starts_with('V') => {
+                match u8::from_str_radix(&upper[1..], 16) {
+                    Ok(x) => Ok(Expr::Register(x)),
+                    Err(_) => Err(ExprError {
+                        position: start,
+                        message: format!("no register {}", word),
+                    }),
+                }
+            }
+            _ => {
+                self.at = start;
+                Ok(Expr::Number(self.number()?))
+            }
+        }
+    }
+
+    fn word(&mut self) -> &str {
+        let rest = &self.text[self.at..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        self.at += len;
+        &rest[..len]
+    }
+
+    fn number(&mut self) -> Result<i64, ExprError> {
+        let start = self.at;
+        let word = self.word();
+        let parsed = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => word.parse(),
+        };
+        parsed.map_err(|_| ExprError {
+            position: start,
+            message: format!("not a number: {:?}", word),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_read_the_machine() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x3F0..0x3F3].copy_from_slice(&[1, 2, 5]);
+        cpu.registers[0xA] = 3;
+        cpu.delay_timer = 2;
+        let eval = |text: &str| Expr::parse(text).unwrap().eval(&cpu);
+
+        assert_eq!(eval("mem[0x3F0]*100 + mem[0x3F1]*10 + mem[0x3F2]"), 125);
+        assert_eq!(eval("va - (DT * -2)"), 7);
+        assert_eq!(eval("-VA - 1 - 1"), -5);
+        assert_eq!(eval("mem[70000]"), 0);
+        assert_eq!(eval("mem[0x3F2] >= 5"), 1);
+        assert_eq!(eval("(VA != 3) + (DT < 3) * 2"), 2);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let error = |text: &str| Expr::parse(text).unwrap_err();
+
+        assert_eq!(error("V3 +").position, 4);
+        assert_eq!(error("VG").message, "no register VG");
+        assert_eq!(error("mem[1").message, "expected ]");
+        assert_eq!(error("V1 V2").position, 3);
+        assert_eq!(error("V1 < V2 < V3").position, 8);
+    }
+}
@@ -0,0 +1,286 @@
+//! A C interface for embedding the emulator in C and C++ frontends, or
+//! any language that can call C. `include/chip8.h` declares everything
+//! here; build with `--features ffi` for the shared library.
+//!
+//! Like [`crate::wasm`], only plain numbers and byte buffers cross the
+//! boundary. Functions return one of the `CHIP8_*` codes below, never
+//! panic on bad input, and treat a null handle as an error.
+
+use std::ffi::{c_char, c_int, CString};
+use std::ptr;
+use std::slice;
+
+use crate::cpu::{EmulatorMode, Status, XorShift, CPU};
+use crate::keypad::KEY_COUNT;
+
+pub const CHIP8_OK: c_int = 0;
+/// The program ran 00FD and won't run any further.
+pub const CHIP8_HALTED: c_int = 1;
+pub const CHIP8_ERROR_NULL: c_int = -1;
+/// The ROM is too large for memory.
+pub const CHIP8_ERROR_ROM: c_int = -2;
+/// The program faulted, see [`chip8_error`].
+pub const CHIP8_ERROR_FAULT: c_int = -3;
+pub const CHIP8_ERROR_KEY: c_int = -4;
+
+pub const CHIP8_MODE_CHIP8: c_int = 0;
+pub const CHIP8_MODE_SCHIP: c_int = 1;
+pub const CHIP8_MODE_XO: c_int = 2;
+
+/// Instructions per frame until [`chip8_set_speed`] is called, about 700
+/// per second.
+const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 11;
+
+/// An emulator instance, opaque to C.
+pub struct Chip8 {
+    cpu: CPU,
+    instructions_per_frame: usize,
+    /// What [`chip8_framebuffer`] last handed out, kept alive until the
+    /// next call.
+    pixels: Vec<u8>,
+    error: Option<CString>,
+}
+
+/// A new machine with no program loaded, or null for an unknown `mode`.
+/// Free it with [`chip8_free`].
+#[no_mangle]
+pub extern "C" fn chip8_new(mode: c_int) -> *mut Chip8 {
+    let mode = match mode {
+        CHIP8_MODE_CHIP8 => EmulatorMode::Chip8,
+        CHIP8_MODE_SCHIP => EmulatorMode::SuperChip,
+        CHIP8_MODE_XO => EmulatorMode::XoChip,
+        _ => return ptr::null_mut(),
+    };
+    let mut cpu = CPU::new_with_mode(mode);
+    cpu.set_rng(Box::new(XorShift::from_time()));
+    Box::into_raw(Box::new(Chip8 {
+        cpu,
+        instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+        pixels: Vec::new(),
+        error: None,
+    }))
+}
+
+/// Frees a machine from [`chip8_new`]. Null is ignored.
+///
+/// # Safety
+///
+/// `chip8` must be null or a handle from [`chip8_new`] that wasn't freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Loads `len` bytes of program and restarts from power-on, keeping the
+/// mode.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle, and `rom` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> c_int {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if rom.is_null() {
+        return CHIP8_ERROR_NULL;
+    }
+    let rom = slice::from_raw_parts(rom, len);
+    let mut cpu = CPU::new_with_mode(chip8.cpu.mode);
+    if cpu.load_rom(rom).is_err() {
+        return CHIP8_ERROR_ROM;
+    }
+    cpu.set_rng(Box::new(XorShift::from_time()));
+    chip8.cpu = cpu;
+    chip8.error = None;
+    CHIP8_OK
+}
+
+/// Instructions per 60 Hz frame from the next [`chip8_step`] on.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_speed(
+    chip8: *mut Chip8,
+    instructions_per_frame: usize,
+) -> c_int {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    chip8.instructions_per_frame = instructions_per_frame.max(1);
+    CHIP8_OK
+}
+
+/// Runs one 60 Hz frame and ticks the timers. A machine that halted or
+/// faulted stays stopped until the next [`chip8_load_rom`].
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> c_int {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if chip8.error.is_some() {
+        return CHIP8_ERROR_FAULT;
+    }
+    match chip8.cpu.step_frame(chip8.instructions_per_frame) {
+        Ok(Status::Halted) => CHIP8_HALTED,
+        Ok(_) => CHIP8_OK,
+        Err(fault) => {
+            // fault messages never hold a NUL
+            chip8.error = CString::new(fault.to_string()).ok();
+            CHIP8_ERROR_FAULT
+        }
+    }
+}
+
+/// The screen, one byte per pixel row by row, each the palette index of
+/// [`crate::display::FrameBuffer::color`]. The size, which changes with
+/// the resolution, is written to `width` and `height` when they aren't
+/// null. The buffer stays valid until the next call on this handle.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle, and `width` and `height` null
+/// or writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    chip8: *mut Chip8,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u8 {
+    let Some(chip8) = chip8.as_mut() else {
+        return ptr::null();
+    };
+    let fb = &chip8.cpu.display;
+    chip8.pixels.clear();
+    chip8
+        .pixels
+        .extend((0..fb.height()).flat_map(|y| (0..fb.width()).map(move |x| fb.color(x, y))));
+    if let Some(width) = width.as_mut() {
+        *width = fb.width();
+    }
+    if let Some(height) = height.as_mut() {
+        *height = fb.height();
+    }
+    chip8.pixels.as_ptr()
+}
+
+/// Presses or releases keypad key `key`, `0x0` to `0xF`.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_key_event(chip8: *mut Chip8, key: u8, pressed: bool) -> c_int {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if key as usize >= KEY_COUNT {
+        return CHIP8_ERROR_KEY;
+    }
+    chip8.cpu.set_key(key, pressed);
+    CHIP8_OK
+}
+
+/// Whether the buzzer should sound.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_beeping(chip8: *const Chip8) -> bool {
+    chip8.as_ref().is_some_and(|chip8| chip8.cpu.is_beeping())
+}
+
+/// Why the program stopped, as a NUL-terminated string owned by the
+/// handle, or null if it didn't fault.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_error(chip8: *const Chip8) -> *const c_char {
+    chip8
+        .as_ref()
+        .and_then(|chip8| chip8.error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    const HEADER: &str = include_str!("../../include/chip8.h");
+
+    #[test]
+    fn a_frame_draws_through_the_c_interface() {
+        // LD F, V0; DRW V0, V0, 5; JP 0x204
+        let rom = [0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04];
+        unsafe {
+            let chip8 = chip8_new(CHIP8_MODE_CHIP8);
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), CHIP8_OK);
+            assert_eq!(chip8_key_event(chip8, 0x10, true), CHIP8_ERROR_KEY);
+            assert_eq!(chip8_step(chip8), CHIP8_OK);
+
+            let (mut width, mut height) = (0, 0);
+            let pixels = chip8_framebuffer(chip8, &mut width, &mut height);
+            assert_eq!((width, height), (64, 32));
+            assert_eq!(slice::from_raw_parts(pixels, 5), [1, 1, 1, 1, 0]);
+            assert!(chip8_error(chip8).is_null());
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn faults_and_null_handles_are_reported() {
+        unsafe {
+            assert!(chip8_new(7).is_null());
+            assert_eq!(chip8_step(ptr::null_mut()), CHIP8_ERROR_NULL);
+            chip8_free(ptr::null_mut());
+
+            let chip8 = chip8_new(CHIP8_MODE_XO);
+            let rom = [0x51, 0x21];
+            chip8_load_rom(chip8, rom.as_ptr(), rom.len());
+            assert_eq!(chip8_step(chip8), CHIP8_ERROR_FAULT);
+            let error = CStr::from_ptr(chip8_error(chip8)).to_str().unwrap();
+            assert!(error.contains("unknown opcode 5121"));
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn the_header_declares_every_function() {
+        for name in [
+            "chip8_new(",
+            "chip8_free(",
+            "chip8_load_rom(",
+            "chip8_set_speed(",
+            "chip8_step(",
+            "chip8_framebuffer(",
+            "chip8_key_event(",
+            "chip8_is_beeping(",
+            "chip8_error(",
+        ] {
+            assert!(HEADER.contains(name), "{} is missing", name);
+        }
+        for (name, value) in [
+            ("CHIP8_HALTED", CHIP8_HALTED),
+            ("CHIP8_ERROR_FAULT", CHIP8_ERROR_FAULT),
+            ("CHIP8_ERROR_KEY", CHIP8_ERROR_KEY),
+            ("CHIP8_MODE_XO", CHIP8_MODE_XO),
+        ] {
+            assert!(HEADER.contains(&format!("#define {} {}", name, value)));
+        }
+    }
+}
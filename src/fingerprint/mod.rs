@@ -0,0 +1,187 @@
+//! Behavior fingerprints of the emulation core.
+//!
+//! Replays and netplay only work if every build executes a program exactly
+//! the same way. [`self_test`] runs a fixed ROM that touches most of the
+//! instruction set and hashes the machine state after every instruction;
+//! the expected hash for each release is recorded in `fingerprints.txt`.
+//!
+//! When a change to the core is intentional, accept the new fingerprint
+//! by running the tests with `CHIP8_BLESS=1` and commit the updated file.
+
+use crate::cpu::{Fault, Status, XorShift, CPU};
+
+/// Exercises arithmetic, shifts, logic, skips, subroutines, load/store,
+/// timers, the font, drawing with clipping and collisions, and a seeded
+/// CXNN.
+#[rustfmt::skip]
+pub const SELF_TEST_ROM: [u8; 0x54] = [
+    0x60, 0x05, // 200: LD V0, 5
+    0x61, 0x07, // 202: LD V1, 7
+    0x80, 0x14, // 204: ADD V0, V1
+    0x80, 0x15, // 206: SUB V0, V1
+    0x81, 0x06, // 208: SHR V1
+    0x81, 0x0E, // 20A: SHL V1
+    0x62, 0xFF, // 20C: LD V2, 0xFF
+    0x82, 0x24, // 20E: ADD V2, V2
+    0x82, 0x01, // 210: OR V2, V0
+    0x83, 0x12, // 212: AND V3, V1
+    0x83, 0x13, // 214: XOR V3, V1
+    0x80, 0x17, // 216: SUBN V0, V1
+    0x22, 0x40, // 218: CALL 0x240
+    0xA3, 0x00, // 21A: LD I, 0x300
+    0xF3, 0x55, // 21C: LD [I], V3
+    0xF3, 0x65, // 21E: LD V3, [I]
+    0xCF, 0xFF, // 220: RND VF, 0xFF
+    0x30, 0x01, // 222: SE V0, 1
+    0x6A, 0xBB, // 224: LD VA, 0xBB
+    0x40, 0x01, // 226: SNE V0, 1
+    0x6B, 0xCC, // 228: LD VB, 0xCC
+    0x50, 0x10, // 22A: SE V0, V1
+    0x6C, 0xDD, // 22C: LD VC, 0xDD
+    0xF0, 0x1E, // 22E: ADD I, V0
+    0xF0, 0x29, // 230: LD F, V0
+    0xD1, 0x25, // 232: DRW V1, V2, 5
+    0x70, 0x07, // 234: ADD V0, 7
+    0xF0, 0x15, // 236: LD DT, V0
+    0xF4, 0x07, // 238: LD V4, DT
+    0x00, 0x00, // 23A: halt
+    0x00, 0x00, // 23C
+    0x00, 0x00, // 23E
+    0xA2, 0x50, // 240: LD I, 0x250
+    0x6D, 0x3E, // 242: LD VD, 62
+    0x6E, 0x1E, // 244: LD VE, 30
+    0xDD, 0xE4, // 246: DRW VD, VE, 4
+    0xDD, 0xE4, // 248: DRW VD, VE, 4
+    0x00, 0xEE, // 24A: RET
+    0x00, 0x00, // 24C
+    0x00, 0x00, // 24E
+    0xF0, 0x90, // 250: sprite
+    0xF0, 0x90,
+];
+
+/// Upper bound on the instructions [`self_test`] executes, in case a core
+/// change sends the ROM into a loop.
+const MAX_STEPS: usize = 1000;
+
+/// 64-bit FNV-1a, chosen because it is trivial and, unlike the std
+/// hashers, guaranteed never to change.
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Stable identifier of a ROM image, the FNV-1a hash of its bytes. Used
+/// to key data kept per game, see [`crate::storage::rom_key`].
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(rom);
+    hash.0
+}
+
+/// Steps `cpu` until it stops or `max_steps` instructions have run,
+/// hashing the registers, I, PC and timers after each one, then the final
+/// memory and screen.
+pub fn trace_hash(cpu: &mut CPU, max_steps: usize) -> Result<u64, Fault> {
+    let mut hash = Fnv::new();
+    for _ in 0..max_steps {
+        let status = cpu.step()?;
+        hash.write(&cpu.registers);
+        hash.write(&cpu.i.to_be_bytes());
+        hash.write(&(cpu.memory_position as u32).to_be_bytes());
+        hash.write(&[cpu.delay_timer, cpu.sound_timer]);
+        if status != Status::Continue {
+            break;
+        }
+    }
+    hash.write(&cpu.memory);
+    let pixels: Vec<u8> = cpu.display.pixels().iter().map(|p| *p as u8).collect();
+    hash.write(&pixels);
+    Ok(hash.0)
+}
+
+/// The fingerprint of this build: [`trace_hash`] over [`SELF_TEST_ROM`] on
+/// a default CPU with a fixed random seed.
+pub fn self_test() -> Result<u64, Fault> {
+    let mut cpu = CPU::new();
+    cpu.set_rng(Box::new(XorShift::new(0x5EED)));
+    cpu.load_rom(&SELF_TEST_ROM)
+        .expect("the self-test ROM fits in memory");
+    trace_hash(&mut cpu, MAX_STEPS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    const FINGERPRINTS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/fingerprint/fingerprints.txt"
+    );
+
+    /// `version fingerprint` pairs, one per line.
+    fn recorded() -> Vec<(String, String)> {
+        fs::read_to_string(FINGERPRINTS)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(version, hash)| (version.to_string(), hash.trim().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn self_test_runs_to_completion() {
+        let mut cpu = CPU::new();
+        cpu.set_rng(Box::new(XorShift::new(0x5EED)));
+        cpu.load_rom(&SELF_TEST_ROM).unwrap();
+
+        assert_eq!(cpu.run(), Ok(Status::Halted));
+        assert_eq!(cpu.memory_position, 0x23C);
+        assert_eq!(cpu.registers[0], 1 + 7);
+        assert_eq!(cpu.registers[0xA], 0, "SE skipped");
+        assert_eq!(cpu.registers[0xB], 0xCC, "SNE did not skip");
+        assert_eq!(cpu.registers[4], cpu.delay_timer);
+    }
+
+    #[test]
+    fn self_test_is_deterministic() {
+        assert_eq!(self_test(), self_test());
+    }
+
+    #[test]
+    fn fingerprint_matches_recorded_release() {
+        let version = env!("CARGO_PKG_VERSION");
+        let actual = format!("{:016x}", self_test().unwrap());
+        let mut recorded = recorded();
+
+        if env::var_os("CHIP8_BLESS").is_some() {
+            recorded.retain(|(v, _)| v != version);
+            recorded.push((version.to_string(), actual));
+            let file: String = recorded
+                .iter()
+                .map(|(version, hash)| format!("{} {}\n", version, hash))
+                .collect();
+            fs::write(FINGERPRINTS, file).unwrap();
+            return;
+        }
+
+        let expected = recorded.iter().find(|(v, _)| v == version).map(|(_, h)| h);
+        assert_eq!(
+            expected,
+            Some(&actual),
+            "core behavior changed for {}; if intended, rerun with CHIP8_BLESS=1",
+            version
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! A reinforcement learning environment in the style of OpenAI Gym: reset,
+//! then step with the keys to hold down and get back what the agent sees,
+//! a reward, and whether the episode is over. Rewards come from an
+//! [`Expr`] over the machine's memory and registers, usually the game's
+//! score, so any ROM can be trained on without writing Rust.
+
+use crate::cpu::{EmulatorMode, Quirks, Status, XorShift, CPU};
+use crate::display::{HIRES_HEIGHT, HIRES_WIDTH};
+use crate::keypad::KeypadState;
+use crate::testing::INSTRUCTIONS_PER_FRAME;
+
+pub use crate::expr::{Expr, ExprError};
+
+/// What [`Env::observation`] shows the agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Observation {
+    /// One byte per pixel holding its palette index, at high resolution
+    /// size with low resolution pixels doubled, so the shape never changes.
+    #[default]
+    Pixels,
+    /// The 4 KiB of memory followed by the registers V0 to VF.
+    Ram,
+}
+
+impl Observation {
+    /// Bytes in every observation of this kind.
+    pub fn len(self) -> usize {
+        match self {
+            Observation::Pixels => HIRES_WIDTH * HIRES_HEIGHT,
+            Observation::Ram => 0x1000 + 16,
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        false
+    }
+}
+
+/// What came of one [`Env::step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepResult {
+    pub observation: Vec<u8>,
+    /// How much the reward expression grew during the step.
+    pub reward: i64,
+    /// The game is over: the program halted or faulted, or the done
+    /// expression became non-zero.
+    pub terminated: bool,
+    /// The episode ran out of steps.
+    pub truncated: bool,
+}
+
+/// One CHIP-8 game as an environment.
+pub struct Env {
+    rom: Vec<u8>,
+    mode: EmulatorMode,
+    quirks: Quirks,
+    cpu: CPU,
+    pub observation: Observation,
+    pub instructions_per_frame: usize,
+    /// 60 Hz frames each step runs with the same keys held.
+    pub frames_per_step: usize,
+    /// Steps before an episode is truncated, or 0 for no limit.
+    pub max_steps: usize,
+    reward: Option<Expr>,
+    done: Option<Expr>,
+    score: i64,
+    steps: usize,
+    over: bool,
+}
+
+impl Env {
+    /// An environment for `rom`, with no reward until one is set.
+    pub fn new(rom: &[u8], mode: EmulatorMode) -> Result<Self, String> {
+        let mut cpu = CPU::new_with_mode(mode);
+        cpu.load_rom(rom).map_err(|err| err.to_string())?;
+        Ok(Env {
+            rom: rom.to_vec(),
+            mode,
+            quirks: cpu.quirks,
+            cpu,
+            observation: Observation::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frames_per_step: 1,
+            max_steps: 0,
+            reward: None,
+            done: None,
+            score: 0,
+            steps: 0,
+            over: false,
+        })
+    }
+
+    /// Quirks to run the ROM with from the next reset on.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Rewards each step with how much `expression` changed in it.
+    pub fn set_reward(&mut self, expression: &str) -> Result<(), ExprError> {
+        self.reward = Some(Expr::parse(expression)?);
+        self.score = self.eval_reward();
+        Ok(())
+    }
+
+    /// Ends episodes once `expression` is non-zero, e.g. `V5 == 0` for a
+    /// lives counter running out.
+    pub fn set_done(&mut self, expression: &str) -> Result<(), ExprError> {
+        self.done = Some(Expr::parse(expression)?);
+        Ok(())
+    }
+
+    /// The machine, to inspect or adjust between steps.
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    /// Starts a new episode from power-on, with CXNN's random numbers
+    /// drawn from `seed`, and returns the first observation.
+    pub fn reset(&mut self, seed: u32) -> Vec<u8> {
+        let mut cpu = CPU::new_with_mode(self.mode);
+        cpu.quirks = self.quirks;
+        cpu.set_rng(Box::new(XorShift::new(seed)));
+        cpu.load_rom(&self.rom)
+            .expect("the ROM loaded when the environment was made");
+        self.cpu = cpu;
+        self.score = self.eval_reward();
+        self.steps = 0;
+        self.over = false;
+        self.observation()
+    }
+
+    /// Holds down the keys set in `action` for [`Env::frames_per_step`]
+    /// frames. Stepping a finished episode changes nothing until the next
+    /// [`Env::reset`].
+    pub fn step(&mut self, action: &KeypadState) -> StepResult {
+        if !self.over {
+            for (key, pressed) in action.iter().enumerate() {
+                self.cpu.set_key(key as u8, *pressed);
+            }
+            for _ in 0..self.frames_per_step.max(1) {
+                match self.cpu.run_frame(self.instructions_per_frame) {
+                    Ok(Status::Halted) | Err(_) => {
+                        self.over = true;
+                        break;
+                    }
+                    Ok(_) => self.cpu.tick_timers(),
+                }
+            }
+            self.steps += 1;
+        }
+        let score = self.eval_reward();
+        let reward = score - self.score;
+        self.score = score;
+        let finished = self
+            .done
+            .as_ref()
+            .is_some_and(|done| done.eval(&self.cpu) != 0);
+        self.over |= finished;
+        StepResult {
+            observation: self.observation(),
+            reward,
+            terminated: self.over,
+            truncated: !self.over && self.max_steps > 0 && self.steps >= self.max_steps,
+        }
+    }
+
+    pub fn observation(&self) -> Vec<u8> {
+        match self.observation {
+            Observation::Pixels => {
+                let fb = &self.cpu.display;
+                let (width, height) = (fb.width(), fb.height());
+                (0..HIRES_WIDTH * HIRES_HEIGHT)
+                    .map(|index| {
+                        fb.color(
+                            index % HIRES_WIDTH * width / HIRES_WIDTH,
+                            index / HIRES_WIDTH * height / HIRES_HEIGHT,
+                        )
+                    })
+                    .collect()
+            }
+            Observation::Ram => {
+                let mut ram = self.cpu.memory.to_vec();
+                ram.resize(0x1000, 0);
+                ram.extend_from_slice(&self.cpu.registers);
+                ram
+            }
+        }
+    }
+
+    fn eval_reward(&self) -> i64 {
+        self.reward
+            .as_ref()
+            .map_or(0, |reward| reward.eval(&self.cpu))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::keypad::KEY_COUNT;
+
+    /// Adds 1 to V3 each time key 5 is seen held, and halts once it is 3.
+    const GAME: &str = "
+        loop:
+            LD V0, 5
+            SKNP V0
+            ADD V3, 1
+            SE V3, 3
+            JP wait
+            EXIT
+        wait:
+            LD V1, DT
+            SE V1, 0
+            JP wait
+            LD V1, 1
+            LD DT, V1
+            JP loop
+    ";
+
+    fn env() -> Env {
+        Env::new(&assemble(GAME).unwrap(), EmulatorMode::SuperChip).unwrap()
+    }
+
+    #[test]
+    fn steps_reward_the_change_in_score() {
+        let mut env = env();
+        env.set_reward("V3 * 10").unwrap();
+        env.observation = Observation::Ram;
+        assert_eq!(env.reset(1).len(), Observation::Ram.len());
+
+        let mut press = [false; KEY_COUNT];
+        let idle = env.step(&press);
+        assert_eq!((idle.reward, idle.terminated), (0, false));
+        press[5] = true;
+        let mut rewards = Vec::new();
+        let mut last = env.step(&press);
+        while !last.terminated {
+            rewards.push(last.reward);
+            last = env.step(&press);
+        }
+        rewards.push(last.reward);
+        assert_eq!(rewards.iter().sum::<i64>(), 30);
+        assert_eq!(last.observation[0x1000 + 3], 3);
+
+        // finished until reset
+        assert_eq!(env.step(&press).reward, 0);
+        env.reset(1);
+        assert!(!env.step(&press).terminated);
+    }
+
+    #[test]
+    fn episodes_end_on_expressions_and_step_limits() {
+        let mut env = env();
+        env.set_done("V3").unwrap();
+        env.max_steps = 3;
+        env.reset(0);
+        let idle = [false; KEY_COUNT];
+
+        assert_eq!(env.observation().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(!env.step(&idle).truncated);
+        assert!(!env.step(&idle).truncated);
+        let third = env.step(&idle);
+        assert!(third.truncated && !third.terminated);
+
+        let mut press = [false; KEY_COUNT];
+        press[5] = true;
+        env.reset(0);
+        assert!(env.step(&press).terminated);
+    }
+}
@@ -0,0 +1,203 @@
+//! Translated messages for the frontends. Every user-facing string lives
+//! in a catalog under an id; English is built in and always complete, and
+//! other languages only need the ids they translate.
+//!
+//! To add a language, write `<code>.txt` next to `en.txt` in this module
+//! and list it in [`BUILTIN`]. A catalog file can also be tried out
+//! without rebuilding by pointing `CHIP8_MESSAGES` at it.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::{env, fs};
+
+use crate::config::LineError;
+
+/// Catalogs compiled in, by language code.
+pub const BUILTIN: [(&str, &str); 2] = [
+    ("en", include_str!("en.txt")),
+    ("es", include_str!("es.txt")),
+];
+
+/// A line of a catalog file that could not be understood.
+pub type CatalogError = LineError;
+
+/// Messages of one language, with English for any it lacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    language: String,
+    messages: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    pub fn english() -> Self {
+        let messages = parse(BUILTIN[0].1).expect("the English catalog parses");
+        Catalog {
+            language: "en".to_string(),
+            messages,
+        }
+    }
+
+    /// The built-in catalog for a locale such as `es`, `es_MX` or
+    /// `es_ES.UTF-8`, or English if there is none.
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut catalog = Self::english();
+        if let Some((code, text)) = BUILTIN.iter().find(|(code, _)| *code == language) {
+            catalog.language = code.to_string();
+            catalog
+                .messages
+                .extend(parse(text).expect("built-in catalogs parse"));
+        }
+        catalog
+    }
+
+    /// The catalog for the user's locale, from `LC_ALL`, `LC_MESSAGES` or
+    /// `LANG`, with the file in `CHIP8_MESSAGES` on top if set. If that
+    /// file can't be loaded the catalog comes without it, along with the
+    /// error for the frontend to report.
+    pub fn from_env() -> (Self, Option<CatalogError>) {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let mut catalog = Self::for_locale(&locale);
+        let error =
+            env::var_os("CHIP8_MESSAGES").and_then(|path| catalog.load(Path::new(&path)).err());
+        (catalog, error)
+    }
+
+    /// Adds the messages in a catalog file, replacing those with the same
+    /// ids.
+    pub fn load(&mut self, path: &Path) -> Result<(), CatalogError> {
+        let text = fs::read_to_string(path).map_err(|err| CatalogError {
+            line: 0,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        self.messages.extend(parse(&text)?);
+        Ok(())
+    }
+
+    /// Code of the language the catalog was made for, e.g. `es`.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The message with this id, or the id itself if no catalog has it.
+    pub fn text<'a>(&'a self, id: &'a str) -> &'a str {
+        self.messages.get(id).map_or(id, String::as_str)
+    }
+
+    /// The message with each `{name}` replaced by the value given for it.
+    pub fn format(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut text = self.text(id).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+}
+
+/// `id = text` lines, `#` comments and `\n` for line breaks.
+fn parse(text: &str) -> Result<BTreeMap<String, String>, CatalogError> {
+    let mut messages = BTreeMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((id, message)) = line.split_once('=') else {
+            return Err(CatalogError {
+                line: index + 1,
+                message: format!("expected id = text, not {:?}", line),
+            });
+        };
+        messages.insert(id.trim().to_string(), message.trim().replace("\\n", "\n"));
+    }
+    Ok(messages)
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// The process-wide catalog, [`Catalog::from_env`] unless
+/// [`set_catalog`] chose another one first. A `CHIP8_MESSAGES` file that
+/// doesn't load is left out silently here, so frontends that want to
+/// report it set the catalog themselves.
+pub fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog::from_env().0)
+}
+
+/// Picks [`Catalog::from_env`] for the rest of the process, returning
+/// why the `CHIP8_MESSAGES` file didn't load if it didn't. Frontends call
+/// it first thing, before any message is looked up.
+pub fn init_from_env() -> Option<CatalogError> {
+    let (catalog, error) = Catalog::from_env();
+    set_catalog(catalog);
+    error
+}
+
+/// Picks the catalog for the rest of the process. Returns false if one
+/// was already in use.
+pub fn set_catalog(catalog: Catalog) -> bool {
+    CATALOG.set(catalog).is_ok()
+}
+
+/// Shorthand for [`Catalog::text`] on the process-wide catalog.
+pub fn text(id: &str) -> &str {
+    catalog().text(id)
+}
+
+/// Shorthand for [`Catalog::format`] on the process-wide catalog.
+pub fn format(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    catalog().format(id, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_fall_back_to_english() {
+        let spanish = Catalog::for_locale("es_ES.UTF-8");
+        assert_eq!(spanish.language(), "es");
+        assert_eq!(spanish.text("tui-help-quit"), "Esc sale");
+        // not translated, so English
+        assert!(spanish.text("usage").starts_with("usage: chip8"));
+        assert_eq!(spanish.text("no-such-message"), "no-such-message");
+
+        assert_eq!(Catalog::for_locale("C").language(), "en");
+        assert_eq!(Catalog::for_locale("").language(), "en");
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let english = Catalog::english();
+        assert_eq!(
+            english.format("test-summary", &[("passed", &3), ("failed", &0)]),
+            "3 passed, 0 failed"
+        );
+        assert!(english.text("test-failed").contains('\n'));
+    }
+
+    #[test]
+    fn every_builtin_id_exists_in_english() {
+        let english = Catalog::english();
+        for (code, text) in BUILTIN {
+            for id in parse(text).unwrap().keys() {
+                assert!(english.messages.contains_key(id), "{}: {}", code, id);
+            }
+        }
+        assert!(parse("just words").is_err());
+
+        let missing = Path::new("does/not/exist.txt");
+        let err = Catalog::english().load(missing).unwrap_err();
+        assert_eq!(err.line, 0);
+        assert!(err.message.starts_with("does/not/exist.txt: "));
+    }
+}
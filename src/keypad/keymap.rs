@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::KEY_COUNT;
+use crate::config::{self, ConfigError, Entry};
+
+/// Host inputs bound to keypad keys. Inputs are named by the frontend,
+/// e.g. `w`, `up` or `space` for keyboard keys and `pad_south` or
+/// `pad_start` for gamepad buttons.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(String, u8)>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `input` to `key`, replacing what it was bound to before.
+    pub fn bind(&mut self, input: &str, key: u8) {
+        match self.bindings.iter_mut().find(|(name, _)| name == input) {
+            Some(binding) => binding.1 = key,
+            None => self.bindings.push((input.to_string(), key)),
+        }
+    }
+
+    /// In the order they were first bound.
+    pub fn bindings(&self) -> &[(String, u8)] {
+        &self.bindings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+/// The user's keymap file: bindings for every game, and bindings for
+/// particular games on top of those. Written in a small subset of TOML:
+///
+/// ```toml
+/// # input = keypad key
+/// [default]
+/// up = 0x2
+/// down = 0x8
+///
+/// # games are matched by title, or by file name without the extension
+/// [game."Space Invaders"]
+/// space = 0x5
+/// pad_south = 0x5
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeymapConfig {
+    pub default: Keymap,
+    pub games: BTreeMap<String, Keymap>,
+}
+
+impl KeymapConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError {
+            line: 0,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = KeymapConfig::default();
+        for section in config::parse(text)? {
+            let keymap = match section.path.as_slice() {
+                [] => &mut config.default,
+                [default] if default == "default" => &mut config.default,
+                [game, name] if game == "game" => config.games.entry(name.clone()).or_default(),
+                _ => {
+                    let header = section.path.join(".");
+                    return Err(section.error(format!("unknown section [{}]", header)));
+                }
+            };
+            for entry in &section.entries {
+                keymap.bind(&entry.key, key_at(entry)?);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The bindings for `game`: the defaults, with the game's own section
+    /// taking precedence.
+    pub fn for_game(&self, game: &str) -> Keymap {
+        let mut keymap = self.default.clone();
+        if let Some(game) = self.games.get(game) {
+            for (input, key) in game.bindings() {
+                keymap.bind(input, *key);
+            }
+        }
+        keymap
+    }
+}
+
+/// A keypad key, 0x0 to 0xF.
+pub(crate) fn key_at(entry: &Entry) -> Result<u8, ConfigError> {
+    let key = entry.integer()?;
+    if (0..KEY_COUNT as i64).contains(&key) {
+        Ok(key as u8)
+    } else {
+        Err(entry.error(format!("not a keypad key: {}", key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r##"
+        [default]
+        up = 0x2     # arrows for the usual 2/4/6/8
+        down = 8
+        pad_south = 5
+
+        [game."Space Invaders"]
+        space = 0x5
+        up = 0xA
+        [game.pong]
+        "#" = 1
+    "##;
+
+    #[test]
+    fn games_override_the_defaults() {
+        let config = KeymapConfig::parse(CONFIG).unwrap();
+        let bindings = |game: &str| config.for_game(game).bindings().to_vec();
+        let binding = |input: &str, key: u8| (input.to_string(), key);
+
+        assert_eq!(
+            bindings("Space Invaders"),
+            [
+                binding("up", 0xA),
+                binding("down", 8),
+                binding("pad_south", 5),
+                binding("space", 5)
+            ]
+        );
+        assert_eq!(bindings("pong").last(), Some(&binding("#", 1)));
+        assert_eq!(bindings("tetris"), config.default.bindings());
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let bad = |text: &str| KeymapConfig::parse(text).unwrap_err();
+
+        assert_eq!(bad("up = 2\ndown = 16").line, 2);
+        assert_eq!(bad("up = \"w\"").message, "up must be a number");
+        assert_eq!(bad("[games.pong]").message, "unknown section [games.pong]");
+        assert_eq!(bad("\n\nleft 4").line, 3);
+        assert_eq!(bad("left arrow = 4").line, 1);
+    }
+}
@@ -0,0 +1,63 @@
+// keymaps are config files
+#[cfg(feature = "std")]
+mod keymap;
+
+#[cfg(feature = "std")]
+pub(crate) use keymap::key_at;
+#[cfg(feature = "std")]
+pub use keymap::{Keymap, KeymapConfig};
+
+pub const KEY_COUNT: usize = 16;
+
+/// Held state of every key, indexed by key number.
+pub type KeypadState = [bool; KEY_COUNT];
+
+/// State of the 16-key hex keypad (keys 0x0 to 0xF).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Keypad {
+    keys: KeypadState,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Keypad {
+            keys: [false; KEY_COUNT],
+        }
+    }
+
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        self.keys[key as usize] = pressed;
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys[key as usize]
+    }
+
+    pub fn release_all(&mut self) {
+        self.keys = [false; KEY_COUNT];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_start_released() {
+        let keypad = Keypad::new();
+
+        assert!((0..KEY_COUNT as u8).all(|k| !keypad.is_pressed(k)));
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut keypad = Keypad::new();
+
+        keypad.set(0xA, true);
+        assert!(keypad.is_pressed(0xA));
+        assert!(!keypad.is_pressed(0xB));
+
+        keypad.set(0xA, false);
+        assert!(!keypad.is_pressed(0xA));
+    }
+}
@@ -0,0 +1,83 @@
+//! A CHIP-8, SUPER-CHIP, XO-CHIP and partly Mega-Chip emulator, with the
+//! frontends, debugger and tools around it.
+//!
+//! Without the default `std` feature the crate is `no_std`, for boards
+//! that drive a small display themselves. That build has only the core,
+//! [`cpu`], [`display`], [`disasm`], [`keypad`], [`clock`] and
+//! [`reference`](mod@reference), and it needs the `alloc` feature and an
+//! allocator. The CPU's memory and save states are still on the heap.
+//! File loading, system clocks and JSON save states need `std`, and so do
+//! the other modules.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod capabilities;
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(feature = "std")]
+pub mod cheats;
+#[cfg(feature = "alloc")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "alloc")]
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod database;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "alloc")]
+pub mod disasm;
+#[cfg(feature = "alloc")]
+pub mod display;
+#[cfg(feature = "std")]
+pub mod emulator;
+#[cfg(feature = "std")]
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod fingerprint;
+#[cfg(feature = "std")]
+pub mod gym;
+#[cfg(feature = "std")]
+pub mod i18n;
+#[cfg(feature = "alloc")]
+pub mod keypad;
+#[cfg(feature = "std")]
+pub mod library;
+#[cfg(feature = "std")]
+pub mod metadata;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "alloc")]
+pub mod reference;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod scheduler;
+#[cfg(feature = "std")]
+pub mod scores;
+#[cfg(feature = "std")]
+pub mod speedrun;
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod tools;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use capabilities::capabilities;
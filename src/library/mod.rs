@@ -0,0 +1,183 @@
+//! ROM collections for launchers: every ROM in a directory with the title
+//! and settings its descriptor and the ROM database give it, and a
+//! thumbnail of what it shows after running headlessly for a moment.
+
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::cpu::{EmulatorMode, Quirks, RomError, Status, CPU};
+use crate::database::RomDatabase;
+use crate::display::FrameBuffer;
+use crate::metadata;
+
+/// Frames a ROM runs for its thumbnail, one second. Title screens are
+/// usually up by then.
+pub const THUMBNAIL_FRAMES: usize = 60;
+
+/// Speed for ROMs that neither the descriptor nor the database know.
+const INSTRUCTIONS_PER_FRAME: usize = 11;
+
+/// Whether `path` is named like a ROM, `.ch8` or `.xo8`.
+pub fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ch8") || ext.eq_ignore_ascii_case("xo8"))
+}
+
+/// One ROM of a library, with its settings worked out the way the desktop
+/// frontend does: the database wins over the descriptor, except for the
+/// title, where the author's own wins.
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub title: String,
+    pub mode: EmulatorMode,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    rom: Vec<u8>,
+    thumbnail: Option<FrameBuffer>,
+}
+
+impl LibraryEntry {
+    /// Reads the ROM at `path` and its descriptor, if any. A descriptor
+    /// that doesn't parse is ignored like a missing one.
+    pub fn load(path: &Path, database: &RomDatabase) -> Result<Self, RomError> {
+        let rom = fs::read(path).map_err(RomError::Io)?;
+        let known = database.lookup(&rom);
+        let meta = metadata::load_sidecar(path).ok().flatten();
+
+        let mode = known
+            .and_then(|known| known.mode)
+            .or_else(|| meta.as_ref().and_then(|meta| meta.mode()))
+            .unwrap_or_default();
+        let mut quirks = mode.default_quirks();
+        let mut instructions_per_frame = INSTRUCTIONS_PER_FRAME;
+        if let Some(meta) = &meta {
+            meta.options.apply_quirks(&mut quirks);
+            if let Some(tickrate) = meta.options.tickrate {
+                instructions_per_frame = tickrate as usize;
+            }
+        }
+        if let Some(known) = known {
+            quirks = known.quirks.unwrap_or(quirks);
+            if let Some(tickrate) = known.tickrate {
+                instructions_per_frame = tickrate as usize;
+            }
+        }
+        let title = meta
+            .and_then(|meta| meta.title)
+            .or_else(|| known.and_then(|known| known.title.clone()))
+            .or_else(|| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| path.display().to_string());
+
+        Ok(LibraryEntry {
+            path: path.to_path_buf(),
+            title,
+            mode,
+            quirks,
+            instructions_per_frame: instructions_per_frame.max(1),
+            rom,
+            thumbnail: None,
+        })
+    }
+
+    /// A CPU with the ROM loaded and its settings applied, ready to play.
+    pub fn cpu(&self) -> Result<CPU, RomError> {
+        let mut cpu = CPU::new_with_mode(self.mode);
+        cpu.load_rom(&self.rom)?;
+        cpu.quirks = self.quirks;
+        Ok(cpu)
+    }
+
+    /// The screen after [`THUMBNAIL_FRAMES`] with no keys pressed, run the
+    /// first time it is asked for. A ROM that faults or doesn't load shows
+    /// what it had drawn by then.
+    pub fn thumbnail(&mut self) -> &FrameBuffer {
+        if self.thumbnail.is_none() {
+            self.thumbnail = Some(self.run_headless());
+        }
+        self.thumbnail.as_ref().unwrap()
+    }
+
+    fn run_headless(&self) -> FrameBuffer {
+        let Ok(mut cpu) = self.cpu() else {
+            return FrameBuffer::new();
+        };
+        for _ in 0..THUMBNAIL_FRAMES {
+            if let Ok(Status::Halted) | Err(_) = cpu.step_frame(self.instructions_per_frame) {
+                break;
+            }
+        }
+        cpu.display
+    }
+}
+
+/// Every ROM directly in `dir`, by title. Files that can't be read are
+/// left out.
+pub fn scan(dir: &Path, database: &RomDatabase) -> io::Result<Vec<LibraryEntry>> {
+    let mut entries: Vec<LibraryEntry> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_rom(path))
+        .filter_map(|path| LibraryEntry::load(&path, database).ok())
+        .collect();
+    entries.sort_by_cached_key(|entry| (entry.title.to_lowercase(), entry.path.clone()));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn library(name: &str, roms: &[(&str, &[u8])]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("chip8-library-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, bytes) in roms {
+            fs::write(dir.join(file), bytes).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn scanning_lists_roms_by_title() {
+        let dir = library(
+            "scan",
+            &[
+                ("b.ch8", &[0x12, 0x00]),
+                ("a.xo8", &[0x12, 0x00]),
+                ("notes.txt", b"not a rom"),
+                (
+                    "b.json",
+                    br#"{"title": "Zebra", "options": {"tickrate": 20}}"#,
+                ),
+            ],
+        );
+        let entries = scan(&dir, &RomDatabase::new());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let entries = entries.unwrap();
+        let titles: Vec<&str> = entries.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(titles, ["a", "Zebra"]);
+        assert_eq!(entries[0].instructions_per_frame, INSTRUCTIONS_PER_FRAME);
+        assert_eq!(entries[1].instructions_per_frame, 20);
+    }
+
+    #[test]
+    fn thumbnails_show_the_screen_after_a_second() {
+        // draws the 0 glyph, then waits for a key that never comes
+        let rom = assemble("LD V0, 0\nLD F, V0\nDRW V0, V0, 5\nLD V1, K").unwrap();
+        let dir = library("thumbnail", &[("zero.ch8", &rom)]);
+        let entry = LibraryEntry::load(&dir.join("zero.ch8"), &RomDatabase::new());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut entry = entry.unwrap();
+        let thumbnail = entry.thumbnail();
+        assert!((0..4).all(|x| thumbnail.color(x, 0) != 0));
+        assert_eq!(thumbnail.color(4, 0), 0);
+        // playing starts from the beginning, not from the thumbnail
+        assert_eq!(entry.cpu().unwrap().display.color(0, 0), 0);
+    }
+}
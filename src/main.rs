@@ -1,3 +1,1413 @@
-mod cpu;
+//! The `chip8` command. `chip8 run` (or just `chip8 <rom>`) plays games
+//! in a window; the other subcommands are thin wrappers around the library
+//! modules of the same name.
 
-fn main() {}
+use std::{
+    env, fs, io,
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cpu_emulator_chip_8::asm::{assemble, assemble_octo, OCTO_EXTENSIONS};
+use cpu_emulator_chip_8::clock::{Clock, SystemClock};
+use cpu_emulator_chip_8::cpu::{Accuracy, EmulatorMode, Quirks, Strictness, XorShift, CPU};
+use cpu_emulator_chip_8::database::RomDatabase;
+use cpu_emulator_chip_8::debugger::{gdb, Debugger};
+use cpu_emulator_chip_8::disasm;
+use cpu_emulator_chip_8::display::{
+    DisplayPreset, DrawOverlay, DrawStats, FrameBlender, FrameBuffer, Palette, HEIGHT,
+    HIRES_HEIGHT, HIRES_WIDTH, WIDTH,
+};
+use cpu_emulator_chip_8::emulator::{read_program, RomWatcher};
+use cpu_emulator_chip_8::i18n::{self, text};
+use cpu_emulator_chip_8::keypad::{Keymap, KeymapConfig};
+use cpu_emulator_chip_8::metadata::{self, Control};
+use cpu_emulator_chip_8::netplay::{NetplayError, NetplaySession, DEFAULT_DELAY};
+use cpu_emulator_chip_8::replay::{InputPlayer, InputRecorder, InputRecording};
+use cpu_emulator_chip_8::scheduler::{FrameScheduler, Machine};
+use cpu_emulator_chip_8::scores::{Leaderboard, ScoreLocation};
+use cpu_emulator_chip_8::storage::{self, FileStorage, Session};
+use cpu_emulator_chip_8::testing::{self, Completion, Scenario};
+use cpu_emulator_chip_8::verify::{self, TraceReference};
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+const INSTRUCTIONS_PER_SECOND: u32 = 700;
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// Host keys laid out like the COSMAC VIP keypad:
+///
+/// ```text
+/// 1 2 3 4      1 2 3 C
+/// Q W E R  ->  4 5 6 D
+/// A S D F      7 8 9 E
+/// Z X C V      A 0 B F
+/// ```
+const KEYMAP: [(Key, u8); 16] = [
+    (Key::Key1, 0x1),
+    (Key::Key2, 0x2),
+    (Key::Key3, 0x3),
+    (Key::Key4, 0xC),
+    (Key::Q, 0x4),
+    (Key::W, 0x5),
+    (Key::E, 0x6),
+    (Key::R, 0xD),
+    (Key::A, 0x7),
+    (Key::S, 0x8),
+    (Key::D, 0x9),
+    (Key::F, 0xE),
+    (Key::Z, 0xA),
+    (Key::X, 0x0),
+    (Key::C, 0xB),
+    (Key::V, 0xF),
+];
+
+const LETTERS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+const DIGITS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+const NUMPAD: [Key; 10] = [
+    Key::NumPad0,
+    Key::NumPad1,
+    Key::NumPad2,
+    Key::NumPad3,
+    Key::NumPad4,
+    Key::NumPad5,
+    Key::NumPad6,
+    Key::NumPad7,
+    Key::NumPad8,
+    Key::NumPad9,
+];
+
+/// The host key a keymap file means by `name`: a letter, a digit, `kp0`
+/// to `kp9` for the number pad, an arrow or one of a few named keys.
+fn key_named(name: &str) -> Option<Key> {
+    let name = name.to_ascii_lowercase();
+    let digit = |text: &str| match text.as_bytes() {
+        [digit @ b'0'..=b'9'] => Some((digit - b'0') as usize),
+        _ => None,
+    };
+    match name.as_str() {
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "space" => Some(Key::Space),
+        "enter" => Some(Key::Enter),
+        "backspace" => Some(Key::Backspace),
+        "lshift" => Some(Key::LeftShift),
+        "rshift" => Some(Key::RightShift),
+        "lctrl" => Some(Key::LeftCtrl),
+        "rctrl" => Some(Key::RightCtrl),
+        _ => match name.as_bytes() {
+            [letter @ b'a'..=b'z'] => Some(LETTERS[(letter - b'a') as usize]),
+            _ => digit(&name)
+                .map(|n| DIGITS[n])
+                .or_else(|| name.strip_prefix("kp").and_then(digit).map(|n| NUMPAD[n])),
+        },
+    }
+}
+
+struct Args {
+    roms: Vec<String>,
+    /// Instructions per second, overriding the ROM's descriptor.
+    speed: Option<u32>,
+    /// Quirks preset, overriding the mode's and the descriptor's.
+    quirks: Option<Quirks>,
+    /// Window pixels per low resolution CHIP-8 pixel.
+    scale: usize,
+    preset: DisplayPreset,
+    /// Colors for every machine, overriding the ROM's and the preset's.
+    palette: Option<Palette>,
+    mode: Option<EmulatorMode>,
+    /// Report how long key presses take to reach each program.
+    latency: bool,
+    accuracy: Accuracy,
+    /// Skip unknown opcodes and accesses out of bounds instead of stopping.
+    strictness: Strictness,
+    /// Reload a ROM whenever its file changes.
+    watch: bool,
+    keymap: Option<PathBuf>,
+    /// Save every game on exit and resume it next time.
+    autosave: bool,
+    /// Sprites a frame may draw before the draw overlay turns red. Giving
+    /// one starts with the overlay on.
+    draw_budget: Option<usize>,
+    /// Where to write the keys pressed during the session.
+    record: Option<PathBuf>,
+    /// A recording to play back instead of reading the keyboard.
+    replay: Option<PathBuf>,
+    /// Where to write a coverage and hot spot report on exit.
+    profile: Option<PathBuf>,
+    /// Port to wait on for a second player.
+    host: Option<u16>,
+    /// Host to play along with, as `address:port`.
+    join: Option<String>,
+}
+
+fn needs_value(option: &str) -> String {
+    i18n::format("option-needs-value", &[("option", &option)])
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut roms = Vec::new();
+    let mut speed = None;
+    let mut quirks = None;
+    let mut scale = 8;
+    let mut preset = DisplayPreset::STANDARD;
+    let mut palette = None;
+    let mut mode = None;
+    let mut latency = false;
+    let mut accuracy = Accuracy::Fast;
+    let mut strictness = Strictness::Strict;
+    let mut watch = false;
+    let mut keymap = None;
+    let mut autosave = false;
+    let mut draw_budget = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut profile = None;
+    let mut host = None;
+    let mut join = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let value = args.next().ok_or_else(|| needs_value("--speed"))?;
+                speed = match value.parse() {
+                    Ok(speed) if speed > 0 => Some(speed),
+                    _ => return Err(i18n::format("invalid-speed", &[("value", &value)])),
+                };
+            }
+            "--quirks" => {
+                let value = args.next().ok_or_else(|| needs_value("--quirks"))?;
+                quirks = Some(
+                    Quirks::preset(&value)
+                        .ok_or_else(|| i18n::format("unknown-quirks", &[("value", &value)]))?,
+                );
+            }
+            "--preset" => {
+                let value = args.next().ok_or_else(|| needs_value("--preset"))?;
+                preset = DisplayPreset::by_name(&value)
+                    .ok_or_else(|| i18n::format("unknown-preset", &[("value", &value)]))?;
+            }
+            "--palette" => {
+                let value = args.next().ok_or_else(|| needs_value("--palette"))?;
+                palette = Some(
+                    Palette::by_name(&value)
+                        .ok_or_else(|| i18n::format("unknown-palette", &[("value", &value)]))?,
+                );
+            }
+            "--scale" => {
+                let value = args.next().ok_or_else(|| needs_value("--scale"))?;
+                scale = match value.parse() {
+                    Ok(scale) if scale > 0 => scale,
+                    _ => return Err(i18n::format("invalid-scale", &[("value", &value)])),
+                };
+            }
+            "--draw-budget" => {
+                let value = args.next().ok_or_else(|| needs_value("--draw-budget"))?;
+                draw_budget = Some(
+                    value
+                        .parse()
+                        .map_err(|_| i18n::format("invalid-draw-budget", &[("value", &value)]))?,
+                );
+            }
+            "--schip" => mode = Some(EmulatorMode::SuperChip),
+            "--xo" => mode = Some(EmulatorMode::XoChip),
+            "--mega" => mode = Some(EmulatorMode::MegaChip),
+            "--latency" => latency = true,
+            "--cycle" => accuracy = Accuracy::Cycle,
+            "--lenient" => strictness = Strictness::Lenient,
+            "--watch" => watch = true,
+            "--autosave" => autosave = true,
+            "--keymap" => {
+                keymap = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    i18n::format("option-needs-file", &[("option", &"--keymap")])
+                })?));
+            }
+            "--record" => {
+                record = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    i18n::format("option-needs-file", &[("option", &"--record")])
+                })?));
+            }
+            "--replay" => {
+                replay = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    i18n::format("option-needs-file", &[("option", &"--replay")])
+                })?));
+            }
+            "--profile" => {
+                profile = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    i18n::format("option-needs-file", &[("option", &"--profile")])
+                })?));
+            }
+            "--host" => {
+                let value = args.next().ok_or_else(|| needs_value("--host"))?;
+                host = Some(
+                    value
+                        .parse()
+                        .map_err(|_| i18n::format("invalid-port", &[("value", &value)]))?,
+                );
+            }
+            "--join" => join = Some(args.next().ok_or_else(|| needs_value("--join"))?),
+            _ if arg.starts_with("--") => {
+                return Err(i18n::format("unknown-option", &[("option", &arg)]))
+            }
+            _ => roms.push(arg),
+        }
+    }
+
+    Ok(Args {
+        roms,
+        speed,
+        quirks,
+        scale: scale.max(preset.min_scale),
+        preset,
+        palette,
+        mode,
+        latency,
+        accuracy,
+        strictness,
+        watch,
+        keymap,
+        autosave,
+        draw_budget,
+        record,
+        replay,
+        profile,
+        host,
+        join,
+    })
+}
+
+/// Frontend settings for one machine.
+struct View {
+    palette: Palette,
+    blender: FrameBlender,
+    /// Host keys and the keypad keys they press: [`KEYMAP`], the
+    /// descriptor's control hints and the user's keymap.
+    controls: Vec<(Key, u8)>,
+    score: Option<ScoreLocation>,
+    /// Highest score seen this session.
+    best: Option<u32>,
+    /// Marks what each frame drew, when on.
+    overlay: Option<DrawOverlay>,
+    /// What the last frame drew, while the overlay is on.
+    draws: Option<DrawStats>,
+}
+
+fn host_key(control: Control) -> Key {
+    match control {
+        Control::Up => Key::Up,
+        Control::Down => Key::Down,
+        Control::Left => Key::Left,
+        Control::Right => Key::Right,
+        Control::A => Key::Space,
+        Control::B => Key::Enter,
+    }
+}
+
+fn load_machine(
+    rom: &str,
+    mode: Option<EmulatorMode>,
+    preset: DisplayPreset,
+    database: &RomDatabase,
+) -> Result<(Machine, View), String> {
+    let bytes = read_program(Path::new(rom))
+        .map(|program| program.rom)
+        .map_err(|err| format!("{}: {}", rom, err))?;
+    let known = database.lookup(&bytes);
+    let meta = metadata::load_sidecar(Path::new(rom)).unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            i18n::format("descriptor-ignored", &[("rom", &rom), ("error", &err)])
+        );
+        None
+    });
+    let mode = mode
+        .or_else(|| known.and_then(|known| known.mode))
+        .or_else(|| meta.as_ref().and_then(|meta| meta.mode()))
+        .unwrap_or_default();
+
+    let mut cpu = CPU::new_with_mode(mode);
+    cpu.load_rom(&bytes)
+        .map_err(|err| format!("{}: {}", rom, err))?;
+
+    let mut instructions_per_frame = INSTRUCTIONS_PER_SECOND / FRAMES_PER_SECOND;
+    let mut view = View {
+        palette: Palette::default(),
+        blender: FrameBlender::new(preset.persistence),
+        controls: KEYMAP.to_vec(),
+        score: None,
+        best: None,
+        overlay: None,
+        draws: None,
+    };
+    let mut name = known
+        .and_then(|known| known.title.clone())
+        .or_else(|| {
+            Path::new(rom)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| rom.to_string());
+    if let Some(meta) = meta {
+        let options = &meta.options;
+        options.apply_quirks(&mut cpu.quirks);
+        instructions_per_frame = options.tickrate.unwrap_or(instructions_per_frame);
+        options.apply_palette(&mut view.palette);
+        view.controls.extend(
+            meta.keys
+                .bindings()
+                .into_iter()
+                .map(|(control, key)| (host_key(control), key)),
+        );
+        view.score = meta.score;
+        name = meta.title.unwrap_or(name);
+    }
+    // the database's settings are known to work, so they win over the
+    // descriptor's
+    if let Some(known) = known {
+        if let Some(quirks) = known.quirks {
+            cpu.quirks = quirks;
+        }
+        instructions_per_frame = known.tickrate.unwrap_or(instructions_per_frame);
+        known.apply_palette(&mut view.palette);
+        apply_keymap(&mut view.controls, &known.keys, &name);
+    }
+    view.palette = preset.palette.unwrap_or(view.palette);
+
+    let machine = Machine::new(name, cpu, instructions_per_frame as usize);
+    Ok((machine, view))
+}
+
+/// The built-in ROM database with `roms.toml` from the data directory on
+/// top, if there is one.
+fn load_database() -> RomDatabase {
+    let mut database = RomDatabase::builtin();
+    if let Some(path) = data_dir().map(|dir| dir.join("roms.toml")) {
+        if path.exists() {
+            if let Err(err) = database.load_overlay(&path) {
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "database-ignored",
+                        &[("path", &path.display()), ("error", &err)]
+                    )
+                );
+            }
+        }
+    }
+    database
+}
+
+/// The `--keymap` file, or `keymap.toml` in the data directory if there
+/// is one.
+fn load_keymap(path: Option<&Path>) -> KeymapConfig {
+    if let Some(path) = path {
+        return KeymapConfig::load(path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path.display(), err);
+            process::exit(2);
+        });
+    }
+    let Some(path) = data_dir().map(|dir| dir.join("keymap.toml")) else {
+        return KeymapConfig::default();
+    };
+    if !path.exists() {
+        return KeymapConfig::default();
+    }
+    KeymapConfig::load(&path).unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            i18n::format(
+                "keymap-ignored",
+                &[("path", &path.display()), ("error", &err)]
+            )
+        );
+        KeymapConfig::default()
+    })
+}
+
+/// Rebinds host keys as `keymap` says. Gamepad buttons are skipped, as
+/// this frontend has no controller input yet.
+fn apply_keymap(controls: &mut Vec<(Key, u8)>, keymap: &Keymap, game: &str) {
+    let mut gamepad = false;
+    for (input, key) in keymap.bindings() {
+        if input.starts_with("pad_") {
+            gamepad = true;
+            continue;
+        }
+        match key_named(input) {
+            Some(host) => {
+                controls.retain(|(bound, _)| *bound != host);
+                controls.push((host, *key));
+            }
+            None => eprintln!(
+                "{}",
+                i18n::format("keymap-unknown-key", &[("game", &game), ("key", input)])
+            ),
+        }
+    }
+    if gamepad {
+        eprintln!("{}", i18n::format("keymap-gamepad", &[("game", &game)]));
+    }
+}
+
+/// How often `--watch` looks at the ROM files.
+const WATCH_INTERVAL_FRAMES: u32 = 30;
+
+/// Queues a reload for every machine whose ROM, or the source it is
+/// assembled from, changed since the last check.
+fn reload_changed(scheduler: &mut FrameScheduler, watchers: &mut [RomWatcher]) {
+    for (index, watcher) in watchers.iter_mut().enumerate() {
+        let Some(program) = watcher.poll() else {
+            continue;
+        };
+        let rom = watcher.path().display().to_string();
+        let result = match scheduler.machine_mut(index) {
+            Some(machine) => program.and_then(|program| Ok(machine.reload(program.rom)?)),
+            None => continue,
+        };
+        match result {
+            Ok(()) => eprintln!("{}", i18n::format("reloaded", &[("rom", &rom)])),
+            Err(err) => eprintln!(
+                "{}",
+                i18n::format("not-reloaded", &[("rom", &rom), ("error", &err)])
+            ),
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME/chip8`, or `~/.local/share/chip8` when that isn't set.
+fn data_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("chip8")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/chip8")),
+    }
+}
+
+/// Writes back the RPL flags of every SUPER-CHIP and XO-CHIP machine.
+fn save_flags(scheduler: &FrameScheduler, storage: &mut Option<FileStorage>) {
+    let Some(storage) = storage else {
+        return;
+    };
+    for machine in scheduler.machines() {
+        if machine.cpu.mode.has_super_chip() {
+            if let Err(err) = storage::save_rpl_flags(storage, &machine.cpu) {
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "flags-not-saved",
+                        &[("game", &machine.name), ("error", &err)]
+                    )
+                );
+            }
+        }
+    }
+}
+
+/// The session saved on the last exit, or an empty one.
+fn load_session(storage: &Option<FileStorage>) -> Session {
+    let Some(storage) = storage else {
+        return Session::default();
+    };
+    Session::load(storage).unwrap_or_else(|err| {
+        eprintln!("{}", i18n::format("session-not-loaded", &[("error", &err)]));
+        Session::default()
+    })
+}
+
+/// Remembers what ran for next time and, with `--autosave`, saves every
+/// game that didn't fail.
+fn save_session(
+    args: &Args,
+    scheduler: &FrameScheduler,
+    window: (usize, usize),
+    storage: &mut Option<FileStorage>,
+) {
+    let Some(storage) = storage else {
+        return;
+    };
+    let session = Session {
+        // absolute, so the session resumes from any directory
+        roms: args
+            .roms
+            .iter()
+            .map(|rom| fs::canonicalize(rom).map_or(rom.clone(), |path| path.display().to_string()))
+            .collect(),
+        mode: args.mode,
+        quirks: args.quirks,
+        window: Some(window),
+        autosave: args.autosave,
+    };
+    if let Err(err) = session.save(storage) {
+        eprintln!("{}", i18n::format("session-not-saved", &[("error", &err)]));
+    }
+    if !args.autosave {
+        return;
+    }
+    for machine in scheduler.machines() {
+        if machine.error().is_some() {
+            continue;
+        }
+        if let Err(err) = storage::save_resume_state(storage, &machine.cpu) {
+            eprintln!(
+                "{}",
+                i18n::format(
+                    "resume-not-saved",
+                    &[("game", &machine.name), ("error", &err)]
+                )
+            );
+        }
+    }
+}
+
+/// Writes the keys pressed this session where `--record` asked for them.
+fn save_recording(recorder: Option<InputRecorder>, scheduler: &FrameScheduler, args: &Args) {
+    let (Some(recorder), Some(path), [machine]) = (recorder, &args.record, scheduler.machines())
+    else {
+        return;
+    };
+    let mut recording = recorder.finish(&machine.cpu);
+    recording.instructions_per_frame = Some(machine.instructions_per_frame);
+    if let Err(err) = recording.save(path) {
+        eprintln!(
+            "{}",
+            i18n::format(
+                "recording-not-saved",
+                &[("path", &path.display()), ("error", &err)]
+            )
+        );
+    }
+}
+
+/// Writes the `--profile` report, one section per machine.
+fn save_profile(scheduler: &FrameScheduler, args: &Args) {
+    let Some(path) = &args.profile else {
+        return;
+    };
+    let reports: Vec<String> = scheduler
+        .machines()
+        .iter()
+        .filter_map(|machine| {
+            let stats = machine.cpu.memory.access_stats()?;
+            Some(disasm::profile_report(
+                &machine.name,
+                machine.cpu.rom(),
+                &stats,
+            ))
+        })
+        .collect();
+    if let Err(err) = fs::write(path, reports.join("\n")) {
+        eprintln!(
+            "{}",
+            i18n::format(
+                "profile-not-saved",
+                &[("path", &path.display()), ("error", &err)]
+            )
+        );
+    }
+}
+
+/// Enters the best score of every game that has a score location into the
+/// leaderboard and prints it.
+fn save_scores(views: &[View], scheduler: &FrameScheduler, storage: &mut Option<FileStorage>) {
+    let Some(storage) = storage else {
+        return;
+    };
+    let mut leaderboard = match Leaderboard::load(storage) {
+        Ok(leaderboard) => leaderboard,
+        Err(err) => {
+            eprintln!("{}", i18n::format("scores-not-loaded", &[("error", &err)]));
+            return;
+        }
+    };
+    let when = SystemClock::new().unix_time();
+    let mut recorded = false;
+    for (view, machine) in views.iter().zip(scheduler.machines()) {
+        if let Some(best) = view.best {
+            if let Some(place) = leaderboard.record(&machine.name, best, when) {
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "new-score",
+                        &[
+                            ("game", &machine.name),
+                            ("score", &best),
+                            ("place", &(place + 1))
+                        ]
+                    )
+                );
+                recorded = true;
+            }
+        }
+    }
+    if !recorded {
+        return;
+    }
+    if let Err(err) = leaderboard.save(storage) {
+        eprintln!("{}", i18n::format("scores-not-saved", &[("error", &err)]));
+    }
+    eprint!("{}", leaderboard);
+}
+
+/// Halves each color channel, used to tell unfocused machines apart.
+fn dim(color: u32) -> u32 {
+    (color >> 1) & 0x007F_7F7F
+}
+
+/// The window title while the draw overlay is on, with the last frame's
+/// counts.
+fn draw_title(title: &str, overlay: &DrawOverlay, draws: &DrawStats) -> String {
+    let id = if overlay.is_over_budget(draws) {
+        "draws-over-budget"
+    } else {
+        "draws"
+    };
+    let budget = overlay.budget.unwrap_or(0);
+    let counts = i18n::format(
+        id,
+        &[
+            ("sprites", &draws.sprites),
+            ("lit", &draws.lit),
+            ("erased", &draws.erased),
+            ("budget", &budget),
+        ],
+    );
+    format!("{} - {}", title, counts)
+}
+
+/// Draws every machine into its grid cell. Cells are one hires screen in
+/// size, so low resolution pixels are doubled.
+fn render(scheduler: &FrameScheduler, views: &mut [View], buffer: &mut [u32], cols: usize) {
+    let stride = cols * HIRES_WIDTH;
+    for (index, (machine, view)) in scheduler.machines().iter().zip(views).enumerate() {
+        let fb = &machine.cpu.display;
+        let palette = if scheduler.len() == 1 || index == scheduler.focus() {
+            view.palette
+        } else {
+            Palette::new(view.palette.colors().map(dim))
+        };
+        let blended = view.blender.blend(fb, &palette);
+        let tinted;
+        let colors = match (&view.overlay, &view.draws) {
+            (Some(overlay), Some(draws)) => {
+                let mut colors = blended.to_vec();
+                overlay.apply(draws, &mut colors);
+                tinted = colors;
+                &tinted
+            }
+            _ => blended,
+        };
+        let (cell_x, cell_y) = ((index % cols) * HIRES_WIDTH, (index / cols) * HIRES_HEIGHT);
+        for y in 0..HIRES_HEIGHT {
+            let row = &mut buffer[(cell_y + y) * stride + cell_x..][..HIRES_WIDTH];
+            let src_y = y * fb.height() / HIRES_HEIGHT;
+            for (x, out) in row.iter_mut().enumerate() {
+                let src_x = x * fb.width() / HIRES_WIDTH;
+                *out = colors[src_y * fb.width() + src_x];
+            }
+        }
+    }
+}
+
+fn main() {
+    if let Some(err) = i18n::init_from_env() {
+        eprintln!("CHIP8_MESSAGES: {}", err);
+    }
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let command = match args.first().map(String::as_str) {
+        Some("run" | "disasm" | "asm" | "debug" | "test" | "verify") => args.remove(0),
+        Some("help" | "-h" | "--help") => {
+            println!("{}", text("usage"));
+            return;
+        }
+        Some("version" | "-V" | "--version") => {
+            println!("{}", cpu_emulator_chip_8::capabilities());
+            return;
+        }
+        _ => "run".to_string(),
+    };
+    match command.as_str() {
+        "disasm" => disasm_command(args),
+        "asm" => asm_command(args),
+        "debug" => debug_command(args),
+        "test" => test_command(args),
+        "verify" => verify_command(args),
+        _ => run_command(args),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, text("usage"));
+    process::exit(2);
+}
+
+fn disasm_command(args: Vec<String>) {
+    let [rom] = args.as_slice() else {
+        usage_error(text("disasm-usage"));
+    };
+    let bytes = fs::read(rom).unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    });
+    print!("{}", disasm::listing(&bytes));
+    for chain in disasm::find_unbounded_recursion(&bytes) {
+        eprintln!(
+            "{}",
+            i18n::format("unbounded-recursion", &[("rom", rom), ("chain", &chain)])
+        );
+    }
+}
+
+fn asm_command(args: Vec<String>) {
+    let (input, output) = match args.as_slice() {
+        [input] => (input, Path::new(input).with_extension("ch8")),
+        [input, flag, output] if flag == "-o" => (input, PathBuf::from(output)),
+        _ => usage_error(text("asm-usage")),
+    };
+    let source = fs::read_to_string(input).unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    let is_octo = Path::new(input)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| OCTO_EXTENSIONS.contains(&extension));
+    let assembled = if is_octo {
+        assemble_octo(&source).map(|program| program.rom)
+    } else {
+        assemble(&source)
+    };
+    let rom = assembled.unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    if let Err(err) = fs::write(&output, &rom) {
+        eprintln!("{}: {}", output.display(), err);
+        process::exit(1);
+    }
+}
+
+fn debug_command(args: Vec<String>) {
+    let mut mode = EmulatorMode::Chip8;
+    let mut rom = None;
+    let mut gdb_port = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = EmulatorMode::SuperChip,
+            "--xo" => mode = EmulatorMode::XoChip,
+            "--mega" => mode = EmulatorMode::MegaChip,
+            "--gdb" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => gdb_port = Some(port),
+                _ => usage_error(&needs_value("--gdb")),
+            },
+            _ if rom.is_none() => rom = Some(arg),
+            _ => usage_error(&i18n::format("unexpected-argument", &[("argument", &arg)])),
+        }
+    }
+    let Some(rom) = rom else {
+        usage_error(text("debug-usage"));
+    };
+    let mut cpu = CPU::new_with_mode(mode);
+    if let Err(err) = cpu.load_rom_from_path(&rom) {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    }
+    let mut debugger = Debugger::new(cpu);
+    let served = match gdb_port {
+        Some(port) => {
+            let address = format!("127.0.0.1:{}", port);
+            eprintln!("{}", i18n::format("gdb-waiting", &[("address", &address)]));
+            gdb::listen(&mut debugger, address)
+        }
+        None => debugger.repl(io::stdin().lock(), io::stdout()),
+    };
+    if let Err(err) = served {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Plays each scenario, see [`Scenario`] for the format, and exits with 1
+/// if any of them failed.
+fn test_command(args: Vec<String>) {
+    if args.is_empty() {
+        usage_error(text("test-usage"));
+    }
+    let is_scenario = Path::new(&args[0])
+        .extension()
+        .is_some_and(|extension| extension == "scenario");
+    if !is_scenario {
+        rom_test_command(args);
+        return;
+    }
+    let mut failed = 0;
+    for path in &args {
+        match Scenario::load(Path::new(path))
+            .map_err(|err| err.to_string())
+            .and_then(|scenario| scenario.run())
+        {
+            Ok(()) => println!("{}", i18n::format("test-ok", &[("path", path)])),
+            Err(err) => {
+                failed += 1;
+                println!(
+                    "{}",
+                    i18n::format("test-failed", &[("path", path), ("error", &err)])
+                );
+            }
+        }
+    }
+    println!(
+        "{}",
+        i18n::format(
+            "test-summary",
+            &[("passed", &(args.len() - failed)), ("failed", &failed)]
+        )
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+/// `chip8 test rom.ch8`: runs the ROM headlessly until it stops and
+/// exits with one of the [`testing::EXIT_PASSED`] codes.
+fn rom_test_command(args: Vec<String>) {
+    let mut mode = None;
+    let mut max_cycles = testing::DEFAULT_MAX_CYCLES;
+    let mut golden = None;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = Some(EmulatorMode::SuperChip),
+            "--xo" => mode = Some(EmulatorMode::XoChip),
+            "--mega" => mode = Some(EmulatorMode::MegaChip),
+            "--max-cycles" => match args.next().map(|value| value.parse()) {
+                Some(Ok(value)) => max_cycles = value,
+                _ => usage_error(&needs_value("--max-cycles")),
+            },
+            "--expect-screen" => match args.next() {
+                Some(path) => golden = Some(PathBuf::from(path)),
+                None => usage_error(&needs_value("--expect-screen")),
+            },
+            _ => paths.push(arg),
+        }
+    }
+    let [rom] = paths.as_slice() else {
+        usage_error(text("test-usage"));
+    };
+    // a mode on the command line wins over the ROM's metadata
+    let loaded = match mode {
+        Some(mode) => fs::read(rom)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| testing::headless(&bytes, mode))
+            .map(|cpu| (cpu, testing::INSTRUCTIONS_PER_FRAME)),
+        None => testing::headless_from_path(Path::new(rom)),
+    };
+    let (mut cpu, instructions_per_frame) = loaded.unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom, err);
+        process::exit(testing::EXIT_FAULTED);
+    });
+    let start = cpu.instructions();
+    let completion = testing::run_to_completion(&mut cpu, max_cycles, instructions_per_frame)
+        .unwrap_or_else(|fault| {
+            eprintln!("{}: {}", rom, fault);
+            process::exit(testing::EXIT_FAULTED);
+        });
+    let cycles = cpu.instructions() - start;
+    let how = match completion {
+        Completion::Halted => text("test-halted").to_string(),
+        Completion::SelfJump(address) => i18n::format(
+            "test-self-jump",
+            &[("address", &format!("{:03X}", address))],
+        ),
+        Completion::WaitingForKey => text("test-waiting-for-key").to_string(),
+        Completion::OutOfCycles => text("test-out-of-cycles").to_string(),
+    };
+    println!(
+        "{}",
+        i18n::format(
+            "test-completion",
+            &[("path", rom), ("how", &how), ("cycles", &cycles)]
+        )
+    );
+    if !completion.is_finished() {
+        process::exit(testing::EXIT_TIMED_OUT);
+    }
+    if let Some(golden) = golden {
+        if let Err(err) = check_screen(&golden, &cpu.display) {
+            println!(
+                "{}",
+                i18n::format("test-failed", &[("path", rom), ("error", &err)])
+            );
+            process::exit(testing::EXIT_MISMATCH);
+        }
+    }
+    println!("{}", i18n::format("test-ok", &[("path", rom)]));
+}
+
+/// Compares the screen with a golden PNG, or a text screenshot for any
+/// other extension.
+fn check_screen(golden: &Path, fb: &FrameBuffer) -> Result<(), String> {
+    if golden
+        .extension()
+        .is_some_and(|extension| extension == "png")
+    {
+        #[cfg(feature = "png")]
+        return testing::check_golden_png(golden, fb);
+        #[cfg(not(feature = "png"))]
+        return Err(text("test-needs-png").to_string());
+    }
+    testing::check_golden(golden, fb)
+}
+
+/// Connects to the other player for `--host` or `--join`, exiting if that
+/// fails.
+fn start_netplay(args: &Args, scheduler: &mut FrameScheduler) -> Option<NetplaySession> {
+    if args.host.is_none() && args.join.is_none() {
+        return None;
+    }
+    if scheduler.len() != 1 {
+        usage_error(text("netplay-one-rom"));
+    }
+    let machine = scheduler.machine_mut(0)?;
+    let started = match (args.host, &args.join) {
+        (Some(port), _) => {
+            eprintln!("{}", i18n::format("netplay-waiting", &[("port", &port)]));
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.subsec_nanos());
+            TcpListener::bind(("0.0.0.0", port))
+                .and_then(|listener| listener.accept())
+                .map_err(NetplayError::from)
+                .and_then(|(stream, _)| {
+                    NetplaySession::host(
+                        stream,
+                        &mut machine.cpu,
+                        machine.instructions_per_frame,
+                        DEFAULT_DELAY,
+                        seed,
+                    )
+                })
+        }
+        (None, Some(address)) => TcpStream::connect(address)
+            .map_err(NetplayError::from)
+            .and_then(|stream| NetplaySession::join(stream, &mut machine.cpu)),
+        (None, None) => return None,
+    };
+    let session = started.unwrap_or_else(|err| {
+        eprintln!("{}", i18n::format("netplay-failed", &[("error", &err)]));
+        process::exit(1);
+    });
+    machine.instructions_per_frame = session.instructions_per_frame();
+    Some(session)
+}
+
+/// Checks a ROM's run against a trace, see [`verify`], or records one.
+/// Exits with 1 on the first divergence.
+fn verify_command(args: Vec<String>) {
+    let mut mode = EmulatorMode::Chip8;
+    let mut steps = 100_000;
+    let mut record = None;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schip" => mode = EmulatorMode::SuperChip,
+            "--xo" => mode = EmulatorMode::XoChip,
+            "--mega" => mode = EmulatorMode::MegaChip,
+            "--steps" => match args.next().map(|value| value.parse()) {
+                Some(Ok(value)) => steps = value,
+                _ => usage_error(&needs_value("--steps")),
+            },
+            "--record" => match args.next() {
+                Some(path) => record = Some(path),
+                None => usage_error(&needs_value("--record")),
+            },
+            _ => paths.push(arg),
+        }
+    }
+    let (rom, trace) = match (paths.as_slice(), &record) {
+        ([rom], Some(path)) | ([rom, path], None) => (rom, path),
+        _ => usage_error(text("verify-usage")),
+    };
+    let mut cpu = CPU::new_with_mode(mode);
+    cpu.set_rng(Box::new(XorShift::new(0)));
+    if let Err(err) = cpu.load_rom_from_path(rom) {
+        eprintln!("{}: {}", rom, err);
+        process::exit(1);
+    }
+    let speed = (INSTRUCTIONS_PER_SECOND / FRAMES_PER_SECOND) as usize;
+
+    if record.is_some() {
+        let recorded = fs::File::create(trace)
+            .map(io::BufWriter::new)
+            .and_then(|out| verify::record_trace(&mut cpu, steps, speed, out));
+        match recorded {
+            Ok(steps) => println!(
+                "{}",
+                i18n::format("verify-recorded", &[("path", trace), ("steps", &steps)])
+            ),
+            Err(err) => {
+                eprintln!("{}: {}", trace, err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    let file = fs::File::open(trace).unwrap_or_else(|err| {
+        eprintln!("{}: {}", trace, err);
+        process::exit(1);
+    });
+    let mut reference = TraceReference::new(io::BufReader::new(file));
+    let result = verify::verify(&mut cpu, &mut reference, steps, speed);
+    if let Some(err) = reference.error() {
+        eprintln!(
+            "{}",
+            i18n::format("verify-bad-trace", &[("path", trace), ("error", &err)])
+        );
+        process::exit(1);
+    }
+    match result {
+        Ok(steps) => println!("{}", i18n::format("verify-ok", &[("steps", &steps)])),
+        Err(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_command(args: Vec<String>) {
+    let mut args = parse_args(args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(2);
+    });
+
+    let mut storage = data_dir().map(FileStorage::new);
+    // without ROMs, carry on with the ones from last time
+    let mut window_size = None;
+    if args.roms.is_empty() {
+        let session = load_session(&storage);
+        if session.roms.is_empty() {
+            eprintln!("{}", text("usage"));
+            process::exit(2);
+        }
+        args.roms = session.roms;
+        args.mode = args.mode.or(session.mode);
+        args.quirks = args.quirks.or(session.quirks);
+        args.autosave |= session.autosave;
+        window_size = session.window;
+    }
+    let keymap = load_keymap(args.keymap.as_deref());
+    let database = load_database();
+    let mut scheduler = FrameScheduler::new();
+    let mut views = Vec::new();
+    for rom in &args.roms {
+        let (mut machine, mut view) = load_machine(rom, args.mode, args.preset, &database)
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            });
+        if let Some(quirks) = args.quirks {
+            machine.cpu.quirks = quirks;
+        }
+        if let Some(speed) = args.speed {
+            machine.instructions_per_frame = (speed / FRAMES_PER_SECOND).max(1) as usize;
+        }
+        view.palette = args.palette.unwrap_or(view.palette);
+        if args.draw_budget.is_some() {
+            view.overlay = Some(DrawOverlay::new(args.draw_budget));
+            machine.cpu.display.set_tracking_draws(true);
+        }
+        apply_keymap(
+            &mut view.controls,
+            &keymap.for_game(&machine.name),
+            &machine.name,
+        );
+        machine.cpu.accuracy = args.accuracy;
+        machine.cpu.strictness = args.strictness;
+        machine.cpu.set_measuring_latency(args.latency);
+        machine
+            .cpu
+            .memory
+            .set_tracking_access(args.profile.is_some());
+        if let (Some(storage), true) = (&storage, machine.cpu.mode.has_super_chip()) {
+            if let Err(err) = storage::load_rpl_flags(storage, &mut machine.cpu) {
+                eprintln!(
+                    "{}",
+                    i18n::format("flags-not-loaded", &[("rom", rom), ("error", &err)])
+                );
+            }
+        }
+        if let (Some(storage), true) = (&storage, args.autosave) {
+            if let Err(err) = storage::load_resume_state(storage, &mut machine.cpu) {
+                eprintln!(
+                    "{}",
+                    i18n::format("resume-not-loaded", &[("rom", rom), ("error", &err)])
+                );
+            }
+        }
+        scheduler.add(machine);
+        views.push(view);
+    }
+
+    // recordings follow the one machine there is
+    if (args.record.is_some() || args.replay.is_some()) && scheduler.len() != 1 {
+        usage_error(text("replay-one-rom"));
+    }
+    let mut recorder = match (&args.record, scheduler.machine_mut(0)) {
+        (Some(_), Some(machine)) => {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.subsec_nanos());
+            Some(InputRecorder::start(&mut machine.cpu, seed))
+        }
+        _ => None,
+    };
+    let mut player = match (&args.replay, scheduler.machine_mut(0)) {
+        (Some(path), Some(machine)) => {
+            let started = InputRecording::load(path)
+                .and_then(|recording| InputPlayer::start(recording, &mut machine.cpu));
+            let player = started.unwrap_or_else(|err| {
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "replay-not-loaded",
+                        &[("path", &path.display()), ("error", &err)]
+                    )
+                );
+                process::exit(1);
+            });
+            if let Some(speed) = player.recording().instructions_per_frame {
+                machine.instructions_per_frame = speed;
+            }
+            Some(player)
+        }
+        _ => None,
+    };
+
+    let mut netplay = start_netplay(&args, &mut scheduler);
+
+    let title = match scheduler.machines() {
+        [machine] => format!("CHIP-8 - {}", machine.name),
+        _ => "CHIP-8 (Tab switches focus)".to_string(),
+    };
+    let (cols, rows) = scheduler.grid_size();
+    let options = WindowOptions {
+        scale_mode: ScaleMode::Stretch,
+        ..WindowOptions::default()
+    };
+    let (window_width, window_height) =
+        window_size.unwrap_or((cols * WIDTH * args.scale, rows * HEIGHT * args.scale));
+    let mut window =
+        Window::new(&title, window_width, window_height, options).unwrap_or_else(|err| {
+            eprintln!("{}", i18n::format("window-failed", &[("error", &err)]));
+            process::exit(1);
+        });
+    window.set_target_fps(FRAMES_PER_SECOND as usize);
+    let mut shown_title = title.clone();
+
+    let (buffer_width, buffer_height) = (cols * HIRES_WIDTH, rows * HIRES_HEIGHT);
+    let mut buffer = vec![Palette::default().background; buffer_width * buffer_height];
+    let mut watchers: Vec<RomWatcher> = args.roms.iter().map(RomWatcher::new).collect();
+    let mut frames = 0u32;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        frames = frames.wrapping_add(1);
+        if args.watch && frames.is_multiple_of(WATCH_INTERVAL_FRAMES) {
+            reload_changed(&mut scheduler, &mut watchers);
+        }
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            scheduler.focus_next();
+        }
+        // F5 restarts the focused game, Shift+F5 power-cycles it, but not
+        // under the other player's feet
+        if window.is_key_pressed(Key::F5, KeyRepeat::No) && netplay.is_none() {
+            let focus = scheduler.focus();
+            let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+            if let Some(machine) = scheduler.machine_mut(focus) {
+                if shift {
+                    machine.hard_reset();
+                } else {
+                    machine.soft_reset();
+                }
+            }
+        }
+        // F6 switches the focused game to the next built-in palette
+        if window.is_key_pressed(Key::F6, KeyRepeat::No) {
+            let view = &mut views[scheduler.focus()];
+            view.palette = view.palette.next_theme();
+        }
+        // F7 marks what the focused game draws each frame
+        if window.is_key_pressed(Key::F7, KeyRepeat::No) {
+            let focus = scheduler.focus();
+            let view = &mut views[focus];
+            view.overlay = match view.overlay {
+                Some(_) => None,
+                None => Some(DrawOverlay::new(args.draw_budget)),
+            };
+            view.draws = None;
+            if let Some(machine) = scheduler.machine_mut(focus) {
+                machine
+                    .cpu
+                    .display
+                    .set_tracking_draws(view.overlay.is_some());
+            }
+        }
+        if let (Some(replaying), Some(machine)) = (&mut player, scheduler.machine_mut(0)) {
+            if let Err(err) = replaying.update(&mut machine.cpu) {
+                eprintln!("{}", i18n::format("replay-stopped", &[("error", &err)]));
+                player = None;
+            }
+        } else {
+            let mut pressed = [false; 16];
+            for (host_key, key) in &views[scheduler.focus()].controls {
+                pressed[*key as usize] |= window.is_key_down(*host_key);
+            }
+            if let Some(session) = &mut netplay {
+                match session.exchange(pressed) {
+                    Ok(both) => pressed = both,
+                    Err(err) => {
+                        eprintln!("{}", i18n::format("netplay-stopped", &[("error", &err)]));
+                        netplay = None;
+                    }
+                }
+            }
+            for (key, pressed) in pressed.into_iter().enumerate() {
+                let focused = &scheduler.machines()[scheduler.focus()].cpu;
+                if focused.keypad.is_pressed(key as u8) != pressed {
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record(focused, key as u8, pressed);
+                    }
+                    scheduler.set_key(key as u8, pressed);
+                }
+            }
+        }
+
+        let failed: Vec<bool> = scheduler
+            .machines()
+            .iter()
+            .map(|m| m.error().is_some())
+            .collect();
+        scheduler.run_frame();
+        if let Some(replaying) = &player {
+            if replaying.is_finished(&scheduler.machines()[0].cpu) {
+                eprintln!("{}", text("replay-finished"));
+                player = None;
+            }
+        }
+        for index in 0..scheduler.len() {
+            let Some(machine) = scheduler.machine_mut(index) else {
+                continue;
+            };
+            if let Some(chain) = machine.cpu.take_stack_warning() {
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "deep-recursion",
+                        &[("rom", &machine.name), ("chain", &chain)]
+                    )
+                );
+            }
+            for fault in machine.cpu.take_skipped() {
+                let pc = format!("{:#05x}", fault.pc);
+                eprintln!(
+                    "{}",
+                    i18n::format(
+                        "skipped-illegal",
+                        &[("rom", &machine.name), ("error", &fault.error), ("pc", &pc)]
+                    )
+                );
+            }
+        }
+        for (index, view) in views.iter_mut().enumerate() {
+            if let Some(machine) = scheduler.machine_mut(index) {
+                view.draws = machine.cpu.display.take_draw_stats();
+            }
+        }
+        let focused = &views[scheduler.focus()];
+        let wanted = match (&focused.overlay, &focused.draws) {
+            (Some(overlay), Some(draws)) => draw_title(&title, overlay, draws),
+            _ => title.clone(),
+        };
+        if wanted != shown_title {
+            window.set_title(&wanted);
+            shown_title = wanted;
+        }
+        for (view, machine) in views.iter_mut().zip(scheduler.machines()) {
+            if let Some(score) = view.score.and_then(|score| score.read(&machine.cpu)) {
+                view.best = view.best.max(Some(score));
+            }
+        }
+        for (machine, was_failed) in scheduler.machines().iter().zip(failed) {
+            if let (Some(err), false) = (machine.error(), was_failed) {
+                eprintln!("{}: {}", machine.name, err);
+            }
+        }
+        if scheduler.machines().iter().all(|m| m.error().is_some()) {
+            save_recording(recorder.take(), &scheduler, &args);
+            save_profile(&scheduler, &args);
+            save_flags(&scheduler, &mut storage);
+            save_scores(&views, &scheduler, &mut storage);
+            process::exit(1);
+        }
+
+        render(&scheduler, &mut views, &mut buffer, cols);
+        if let Err(err) = window.update_with_buffer(&buffer, buffer_width, buffer_height) {
+            eprintln!("{}", i18n::format("draw-failed", &[("error", &err)]));
+            process::exit(1);
+        }
+    }
+
+    save_recording(recorder, &scheduler, &args);
+    save_profile(&scheduler, &args);
+    save_flags(&scheduler, &mut storage);
+    save_scores(&views, &scheduler, &mut storage);
+    save_session(&args, &scheduler, window.get_size(), &mut storage);
+    for machine in scheduler.machines() {
+        if let Some(probe) = machine.cpu.latency_probe() {
+            eprintln!("{}: {}", machine.name, probe);
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Per-ROM JSON descriptors in the format used by the CHIP-8 Archive.
+//!
+//! A descriptor sits next to the ROM with the same file stem
+//! (`pong.ch8` + `pong.json`) and carries the title, target platform and the
+//! Octo options the program was written against.
+
+use std::{
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::cpu::{EmulatorMode, Quirks};
+use crate::display::Palette;
+use crate::scores::ScoreLocation;
+use crate::speedrun::SplitDef;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomMetadata {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub options: ArchiveOptions,
+    /// Keypad keys the game uses, where the database knows them.
+    #[serde(default)]
+    pub keys: ControlHints,
+    /// Where the game keeps its score, for the leaderboard.
+    pub score: Option<ScoreLocation>,
+    /// Speedrun splits, in the order a run reaches them.
+    #[serde(default)]
+    pub splits: Vec<SplitDef>,
+}
+
+/// A game control that frontends can bind to a natural host key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The main button, e.g. fire or jump.
+    A,
+    B,
+}
+
+/// The CHIP-8 database's `keys` hints: which keypad key each control is
+/// on, e.g. `{"up": 2, "down": 8, "left": 4, "right": 6, "a": 5}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ControlHints {
+    pub up: Option<u8>,
+    pub down: Option<u8>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub a: Option<u8>,
+    pub b: Option<u8>,
+}
+
+impl ControlHints {
+    /// Every hinted control with its keypad key. Out of range keys are
+    /// dropped.
+    pub fn bindings(&self) -> Vec<(Control, u8)> {
+        [
+            (Control::Up, self.up),
+            (Control::Down, self.down),
+            (Control::Left, self.left),
+            (Control::Right, self.right),
+            (Control::A, self.a),
+            (Control::B, self.b),
+        ]
+        .into_iter()
+        .filter_map(|(control, key)| Some((control, key.filter(|key| *key < 16)?)))
+        .collect()
+    }
+}
+
+/// The subset of Octo's options this crate understands. Unknown keys are
+/// ignored so descriptors written for newer tools still load.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveOptions {
+    /// Instructions executed per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    pub fill_color: Option<String>,
+    /// Pixels lit on the second XO-CHIP plane only.
+    pub fill_color2: Option<String>,
+    /// Pixels lit on both XO-CHIP planes.
+    pub blend_color: Option<String>,
+    pub background_color: Option<String>,
+    /// 8XY6/8XYE shift VX in place.
+    pub shift_quirks: Option<bool>,
+    /// FX55/FX65 leave I unchanged.
+    pub load_store_quirks: Option<bool>,
+    /// BNNN behaves as BXNN.
+    pub jump_quirks: Option<bool>,
+    /// Sprites are clipped instead of wrapped.
+    pub clip_quirks: Option<bool>,
+    /// 8XY1/8XY2/8XY3 clear VF.
+    pub logic_quirks: Option<bool>,
+    /// DXYN waits for the vertical blank.
+    pub v_blank_quirks: Option<bool>,
+}
+
+impl ArchiveOptions {
+    /// Applies every quirk the descriptor sets, leaving the others alone.
+    pub fn apply_quirks(&self, quirks: &mut Quirks) {
+        if let Some(shift) = self.shift_quirks {
+            quirks.shift_uses_vy = !shift;
+        }
+        if let Some(load_store) = self.load_store_quirks {
+            quirks.load_store_increments_i = !load_store;
+        }
+        if let Some(jump) = self.jump_quirks {
+            quirks.jump_uses_vx = jump;
+        }
+        if let Some(clip) = self.clip_quirks {
+            quirks.wrap_sprites = !clip;
+        }
+        if let Some(logic) = self.logic_quirks {
+            quirks.logic_resets_vf = logic;
+        }
+        if let Some(v_blank) = self.v_blank_quirks {
+            quirks.display_wait = v_blank;
+        }
+    }
+
+    pub fn fill_rgb(&self) -> Option<u32> {
+        self.fill_color.as_deref().and_then(parse_color)
+    }
+
+    pub fn background_rgb(&self) -> Option<u32> {
+        self.background_color.as_deref().and_then(parse_color)
+    }
+
+    /// Takes every color the descriptor sets, leaving the others alone.
+    pub fn apply_palette(&self, palette: &mut Palette) {
+        let colors = [
+            (&mut palette.background, &self.background_color),
+            (&mut palette.foreground, &self.fill_color),
+            (&mut palette.plane2, &self.fill_color2),
+            (&mut palette.both, &self.blend_color),
+        ];
+        for (color, set) in colors {
+            if let Some(set) = set.as_deref().and_then(parse_color) {
+                *color = set;
+            }
+        }
+    }
+}
+
+/// Parses `#RRGGBB` (or `#RGB`) into `0x00RRGGBB`.
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok(),
+        3 => {
+            let short = u32::from_str_radix(hex, 16).ok()?;
+            let (r, g, b) = ((short >> 8) & 0xF, (short >> 4) & 0xF, short & 0xF);
+            Some(((r * 0x11) << 16) | ((g * 0x11) << 8) | (b * 0x11))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(err) => write!(f, "could not read descriptor: {}", err),
+            MetadataError::Parse(err) => write!(f, "invalid descriptor: {}", err),
+        }
+    }
+}
+
+impl error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MetadataError::Io(err) => Some(err),
+            MetadataError::Parse(err) => Some(err),
+        }
+    }
+}
+
+impl RomMetadata {
+    /// Maps the Archive's platform names onto an emulator mode, `None` for
+    /// platforms this crate cannot run.
+    pub fn mode(&self) -> Option<EmulatorMode> {
+        match self.platform.as_deref()? {
+            "originalChip8" | "hybridVIP" | "modernChip8" | "chip8" | "chip48" => {
+                Some(EmulatorMode::Chip8)
+            }
+            "superchip1" | "superchip" | "schip" | "schip11" => Some(EmulatorMode::SuperChip),
+            "xochip" => Some(EmulatorMode::XoChip),
+            "megachip8" => Some(EmulatorMode::MegaChip),
+            _ => None,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, MetadataError> {
+        serde_json::from_str(json).map_err(MetadataError::Parse)
+    }
+}
+
+/// Where the descriptor for `rom` would live.
+pub fn sidecar_path(rom: &Path) -> PathBuf {
+    rom.with_extension("json")
+}
+
+/// Loads the descriptor next to `rom`, `Ok(None)` when there is none.
+pub fn load_sidecar(rom: &Path) -> Result<Option<RomMetadata>, MetadataError> {
+    match fs::read_to_string(sidecar_path(rom)) {
+        Ok(json) => RomMetadata::from_json(&json).map(Some),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(MetadataError::Io(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scores::ScoreFormat;
+
+    const DESCRIPTOR: &str = r##"{
+        "title": "Octojam Title",
+        "authors": ["someone"],
+        "platform": "octo",
+        "release": "2020-10-01",
+        "options": {
+            "tickrate": 20,
+            "fillColor": "#FFCC00",
+            "backgroundColor": "#996600",
+            "blendColor": "#662200",
+            "loadStoreQuirks": true,
+            "jumpQuirks": true,
+            "screenRotation": 0
+        }
+    }"##;
+
+    #[test]
+    fn parse_descriptor() {
+        let meta = RomMetadata::from_json(DESCRIPTOR).unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Octojam Title"));
+        assert_eq!(meta.platform.as_deref(), Some("octo"));
+        assert_eq!(meta.options.tickrate, Some(20));
+        assert_eq!(meta.options.fill_rgb(), Some(0xFFCC00));
+        assert_eq!(meta.options.background_rgb(), Some(0x996600));
+        let mut palette = Palette::CLASSIC;
+        meta.options.apply_palette(&mut palette);
+        assert_eq!(palette.colors(), [0x996600, 0xFFCC00, 0xFF6600, 0x662200]);
+    }
+
+    #[test]
+    fn platform_selects_mode() {
+        let schip = RomMetadata::from_json(r#"{"platform": "superchip"}"#).unwrap();
+        let chip8 = RomMetadata::from_json(r#"{"platform": "originalChip8"}"#).unwrap();
+        let xo = RomMetadata::from_json(r#"{"platform": "xochip"}"#).unwrap();
+        let octo = RomMetadata::from_json(DESCRIPTOR).unwrap();
+
+        assert_eq!(schip.mode(), Some(EmulatorMode::SuperChip));
+        assert_eq!(xo.mode(), Some(EmulatorMode::XoChip));
+        assert_eq!(chip8.mode(), Some(EmulatorMode::Chip8));
+        assert_eq!(octo.mode(), None);
+    }
+
+    #[test]
+    fn control_hints() {
+        let meta = RomMetadata::from_json(
+            r#"{"keys": {"up": 2, "down": 8, "left": 4, "right": 6, "a": 5, "b": 99}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            meta.keys.bindings(),
+            [
+                (Control::Up, 2),
+                (Control::Down, 8),
+                (Control::Left, 4),
+                (Control::Right, 6),
+                (Control::A, 5),
+            ]
+        );
+        assert!(RomMetadata::default().keys.bindings().is_empty());
+    }
+
+    #[test]
+    fn score_location() {
+        let meta = RomMetadata::from_json(r#"{"score": {"address": 1008, "length": 3}}"#).unwrap();
+        let score = meta.score.unwrap();
+
+        assert_eq!((score.address, score.length), (0x3F0, 3));
+        assert_eq!(score.format, ScoreFormat::Digits);
+        assert_eq!(RomMetadata::default().score, None);
+
+        let meta =
+            RomMetadata::from_json(r#"{"splits": [{"name": "Boss", "when": "V3 == 1"}]}"#).unwrap();
+        assert_eq!(meta.splits[0].name, "Boss");
+    }
+
+    #[test]
+    fn options_are_optional() {
+        let meta = RomMetadata::from_json(r#"{"title": "x"}"#).unwrap();
+
+        assert_eq!(meta.options, ArchiveOptions::default());
+    }
+
+    #[test]
+    fn quirks_are_applied() {
+        let meta = RomMetadata::from_json(DESCRIPTOR).unwrap();
+        let mut quirks = Quirks::cosmac_vip();
+
+        meta.options.apply_quirks(&mut quirks);
+
+        assert!(!quirks.load_store_increments_i);
+        assert!(quirks.jump_uses_vx);
+        // not mentioned in the descriptor
+        assert!(quirks.shift_uses_vy);
+    }
+
+    #[test]
+    fn short_colors() {
+        assert_eq!(parse_color("#F0A"), Some(0xFF00AA));
+        assert_eq!(parse_color("F0A"), None);
+        assert_eq!(parse_color("#12345"), None);
+    }
+
+    #[test]
+    fn sidecar_next_to_rom() {
+        let dir = std::env::temp_dir().join("chip8_metadata_sidecar");
+        fs::create_dir_all(&dir).unwrap();
+        let rom = dir.join("game.ch8");
+        fs::write(dir.join("game.json"), DESCRIPTOR).unwrap();
+
+        let meta = load_sidecar(&rom).unwrap().unwrap();
+        let missing = load_sidecar(&dir.join("other.ch8")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(meta.options.tickrate, Some(20));
+        assert!(missing.is_none());
+    }
+}
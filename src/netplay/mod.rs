@@ -0,0 +1,408 @@
+//! Two players on one keypad from two machines, for the games that share
+//! the keypad between both players, such as Pong and Tank.
+//!
+//! Both sides run the same program in lockstep over TCP. The host sends
+//! the machine it starts from and the random seed, the way
+//! [`crate::replay`] recordings do, so the client begins in exactly the
+//! same state. After that, every frame each side sends the keys it holds,
+//! to take effect a few frames later, and waits for the other side's keys
+//! for the frame about to run. Both then run the frame with the keys of
+//! both players held, so the two machines never drift apart. The delay
+//! hides the round trip: with a latency under `delay` frames nobody
+//! waits.
+//!
+//! Messages are JSON objects, one per line.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Accuracy, SaveState, StateError, XorShift, CPU};
+use crate::database::sha1_hex;
+use crate::keypad::{KeypadState, KEY_COUNT};
+
+/// Bumped whenever the messages change.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Frames between pressing a key and the game seeing it, enough for a
+/// round trip of about 65 ms.
+pub const DEFAULT_DELAY: u64 = 4;
+
+/// How long to wait for the other side before giving up.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    /// The host's first message.
+    Welcome {
+        version: u32,
+        rom_sha1: String,
+        seed: u32,
+        accuracy: Accuracy,
+        instructions_per_frame: usize,
+        delay: u64,
+        start: Box<SaveState>,
+    },
+    /// The client's answer, once it loaded the start state.
+    Ready,
+    /// Keys held on `frame`, one bit per key.
+    Input { frame: u64, keys: u16 },
+}
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The other side hung up.
+    Disconnected,
+    /// The other side runs another version of the protocol.
+    Version {
+        ours: u32,
+        theirs: u32,
+    },
+    /// The host runs another program.
+    WrongRom {
+        expected: String,
+        actual: String,
+    },
+    State(StateError),
+    /// A message that doesn't belong where it came.
+    Unexpected(String),
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetplayError::Io(err) => write!(f, "{}", err),
+            NetplayError::Json(err) => write!(f, "invalid message: {}", err),
+            NetplayError::Disconnected => write!(f, "the other player disconnected"),
+            NetplayError::Version { ours, theirs } => write!(
+                f,
+                "the other player uses netplay version {}, this is version {}",
+                theirs, ours
+            ),
+            NetplayError::WrongRom { expected, actual } => {
+                write!(
+                    f,
+                    "the host plays ROM {} but {} is loaded",
+                    expected, actual
+                )
+            }
+            NetplayError::State(err) => write!(f, "{}", err),
+            NetplayError::Unexpected(message) => write!(f, "unexpected message: {}", message),
+        }
+    }
+}
+
+impl error::Error for NetplayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NetplayError::Io(err) => Some(err),
+            NetplayError::Json(err) => Some(err),
+            NetplayError::State(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NetplayError {
+    fn from(err: io::Error) -> Self {
+        NetplayError::Io(err)
+    }
+}
+
+impl From<StateError> for NetplayError {
+    fn from(err: StateError) -> Self {
+        NetplayError::State(err)
+    }
+}
+
+fn to_bits(keys: KeypadState) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (key, held)| bits | (*held as u16) << key)
+}
+
+fn from_bits(bits: u16) -> KeypadState {
+    let mut keys = [false; KEY_COUNT];
+    for (key, held) in keys.iter_mut().enumerate() {
+        *held = bits & 1 << key != 0;
+    }
+    keys
+}
+
+/// One side of a game in progress. Drive the CPU exactly as the other side
+/// does: [`NetplaySession::exchange`] for the keys, then one frame of
+/// [`NetplaySession::instructions_per_frame`] instructions and a timer
+/// tick, and nothing else that changes the machine, such as resets.
+pub struct NetplaySession {
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+    delay: u64,
+    instructions_per_frame: usize,
+    /// The next frame to run.
+    frame: u64,
+    /// Keys for the frames from `frame` on, as far as each side sent them.
+    local: VecDeque<u16>,
+    remote: VecDeque<u16>,
+}
+
+impl NetplaySession {
+    /// Waits for the client on `stream` to join the game `cpu` is playing,
+    /// from the state it is in now. `cpu` is reseeded with `seed` so both
+    /// sides draw the same random numbers.
+    pub fn host(
+        stream: TcpStream,
+        cpu: &mut CPU,
+        instructions_per_frame: usize,
+        delay: u64,
+        seed: u32,
+    ) -> Result<Self, NetplayError> {
+        let mut session = Self::connect(stream, instructions_per_frame, delay)?;
+        cpu.set_rng(Box::new(XorShift::new(seed)));
+        cpu.keypad.release_all();
+        session.send(&Message::Welcome {
+            version: PROTOCOL_VERSION,
+            rom_sha1: sha1_hex(cpu.rom()),
+            seed,
+            accuracy: cpu.accuracy,
+            instructions_per_frame,
+            delay,
+            start: Box::new(cpu.save_state()),
+        })?;
+        match session.receive()? {
+            Message::Ready => Ok(session),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Joins the host on `stream`, putting `cpu`, which must have the
+    /// host's ROM loaded, into the host's state.
+    pub fn join(stream: TcpStream, cpu: &mut CPU) -> Result<Self, NetplayError> {
+        let mut session = Self::connect(stream, 1, 0)?;
+        let Message::Welcome {
+            version,
+            rom_sha1,
+            seed,
+            accuracy,
+            instructions_per_frame,
+            delay,
+            start,
+        } = session.receive()?
+        else {
+            return Err(NetplayError::Unexpected("expected a welcome".to_string()));
+        };
+        if version != PROTOCOL_VERSION {
+            return Err(NetplayError::Version {
+                ours: PROTOCOL_VERSION,
+                theirs: version,
+            });
+        }
+        let actual = sha1_hex(cpu.rom());
+        if actual != rom_sha1 {
+            return Err(NetplayError::WrongRom {
+                expected: rom_sha1,
+                actual,
+            });
+        }
+        cpu.load_state(&start)?;
+        cpu.set_rng(Box::new(XorShift::new(seed)));
+        cpu.accuracy = accuracy;
+        cpu.keypad.release_all();
+        session.instructions_per_frame = instructions_per_frame;
+        session.set_delay(delay);
+        session.send(&Message::Ready)?;
+        Ok(session)
+    }
+
+    fn connect(
+        stream: TcpStream,
+        instructions_per_frame: usize,
+        delay: u64,
+    ) -> Result<Self, NetplayError> {
+        // a frame's keys are tiny and late ones stall both sides
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut session = NetplaySession {
+            reader: Box::new(BufReader::new(stream.try_clone()?)),
+            writer: Box::new(stream),
+            delay: 0,
+            instructions_per_frame,
+            frame: 0,
+            local: VecDeque::new(),
+            remote: VecDeque::new(),
+        };
+        session.set_delay(delay);
+        Ok(session)
+    }
+
+    /// Nobody holds anything for the first `delay` frames.
+    fn set_delay(&mut self, delay: u64) {
+        self.delay = delay;
+        self.local = vec![0; delay as usize].into();
+        self.remote = self.local.clone();
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    /// The host's speed, which both sides must run at.
+    pub fn instructions_per_frame(&self) -> usize {
+        self.instructions_per_frame
+    }
+
+    /// Frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Sends the keys this side holds now and returns the keys, of both
+    /// players, to hold for the frame about to run. Blocks until the other
+    /// side's keys for it arrive, up to [`TIMEOUT`].
+    pub fn exchange(&mut self, held: KeypadState) -> Result<KeypadState, NetplayError> {
+        let keys = to_bits(held);
+        self.send(&Message::Input {
+            frame: self.frame + self.delay,
+            keys,
+        })?;
+        self.local.push_back(keys);
+        while self.remote.is_empty() {
+            let expected = self.frame + self.remote.len() as u64;
+            match self.receive()? {
+                Message::Input { frame, keys } if frame == expected => self.remote.push_back(keys),
+                other => return Err(unexpected(&other)),
+            }
+        }
+        let local = self.local.pop_front().unwrap_or_default();
+        let remote = self.remote.pop_front().unwrap_or_default();
+        self.frame += 1;
+        Ok(from_bits(local | remote))
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), NetplayError> {
+        let mut line = serde_json::to_string(message).map_err(NetplayError::Json)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        Ok(self.writer.flush()?)
+    }
+
+    fn receive(&mut self) -> Result<Message, NetplayError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(NetplayError::Disconnected);
+        }
+        serde_json::from_str(&line).map_err(NetplayError::Json)
+    }
+}
+
+fn unexpected(message: &Message) -> NetplayError {
+    let text = match message {
+        Message::Welcome { .. } => "welcome".to_string(),
+        Message::Ready => "ready".to_string(),
+        Message::Input { frame, .. } => format!("input for frame {}", frame),
+    };
+    NetplayError::Unexpected(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::asm::assemble;
+
+    /// Player one scores with key 1, player two with key 2, and the draw
+    /// position is random.
+    const GAME: &str = "
+        loop:
+            LD V0, 1
+            SKNP V0
+            ADD V1, 1
+            LD V0, 2
+            SKNP V0
+            ADD V2, 1
+            RND V3, 0x3F
+            LD F, V1
+            DRW V3, V2, 5
+            JP loop
+    ";
+
+    fn cpu(source: &str) -> CPU {
+        let mut cpu = CPU::new_with_mode(crate::cpu::EmulatorMode::Chip8);
+        cpu.load_rom(&assemble(source).unwrap()).unwrap();
+        cpu
+    }
+
+    /// Runs `frames` frames, pressing `key` for the middle ones.
+    fn play(session: &mut NetplaySession, cpu: &mut CPU, key: u8, frames: u64) {
+        for frame in 0..frames {
+            let mut held = [false; KEY_COUNT];
+            held[key as usize] = (10..20).contains(&frame);
+            for (key, pressed) in session.exchange(held).unwrap().into_iter().enumerate() {
+                cpu.set_key(key as u8, pressed);
+            }
+            cpu.run_frame(session.instructions_per_frame()).unwrap();
+            cpu.tick_timers();
+        }
+    }
+
+    #[test]
+    fn both_sides_see_both_players() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (state, remote) = mpsc::channel();
+        let (done, finished) = mpsc::channel::<()>();
+        let client = thread::spawn(move || {
+            let mut cpu = cpu(GAME);
+            let stream = TcpStream::connect(address).unwrap();
+            let mut session = NetplaySession::join(stream, &mut cpu).unwrap();
+            assert_eq!(session.delay(), 3);
+            play(&mut session, &mut cpu, 2, 40);
+            state.send(cpu.save_state()).unwrap();
+            // the last frames' keys may still be on their way to the host,
+            // and hanging up on them would break its pipe
+            let _ = finished.recv();
+        });
+
+        let mut cpu = cpu(GAME);
+        cpu.run_frame(20).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut session = NetplaySession::host(stream, &mut cpu, 8, 3, 99).unwrap();
+        play(&mut session, &mut cpu, 1, 40);
+
+        let remote = remote.recv().unwrap();
+        drop(done);
+        client.join().unwrap();
+        assert_eq!(cpu.save_state(), remote);
+        assert!(cpu.registers[1] > 0 && cpu.registers[2] > 0);
+        assert_eq!(session.frame(), 40);
+    }
+
+    #[test]
+    fn joining_another_game_is_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut cpu = cpu("end:\nJP end");
+            let stream = TcpStream::connect(address).unwrap();
+            NetplaySession::join(stream, &mut cpu).err()
+        });
+
+        let mut cpu = cpu(GAME);
+        let (stream, _) = listener.accept().unwrap();
+        let hosted = NetplaySession::host(stream, &mut cpu, 8, DEFAULT_DELAY, 1);
+        assert!(matches!(
+            client.join().unwrap(),
+            Some(NetplayError::WrongRom { .. })
+        ));
+        assert!(matches!(hosted, Err(NetplayError::Disconnected)));
+    }
+}
@@ -0,0 +1,200 @@
+//! Static reference for the CHIP-8 family instruction sets, queryable by
+//! opcode or by encoding pattern (`"8XY6"`).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Chip8,
+    SuperChip,
+    XoChip,
+    MegaChip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    /// Encoding as usually written in references, e.g. `"8XY6"`.
+    pub encoding: &'static str,
+    pub mask: u16,
+    pub pattern: u16,
+    pub mnemonic: &'static str,
+    pub description: &'static str,
+    /// Variants that define the instruction.
+    pub variants: &'static [Variant],
+    /// Interpreter behaviors that change what the instruction does.
+    pub quirks: &'static [&'static str],
+}
+
+impl OpcodeInfo {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.pattern
+    }
+
+    pub fn is_available_in(&self, variant: Variant) -> bool {
+        self.variants.contains(&variant)
+    }
+}
+
+const ALL: &[Variant] = &[
+    Variant::Chip8,
+    Variant::SuperChip,
+    Variant::XoChip,
+    Variant::MegaChip,
+];
+const SCHIP: &[Variant] = &[Variant::SuperChip, Variant::XoChip, Variant::MegaChip];
+const XO: &[Variant] = &[Variant::XoChip];
+const MEGA: &[Variant] = &[Variant::MegaChip];
+
+const fn op(
+    encoding: &'static str,
+    mask: u16,
+    pattern: u16,
+    mnemonic: &'static str,
+    description: &'static str,
+    variants: &'static [Variant],
+    quirks: &'static [&'static str],
+) -> OpcodeInfo {
+    OpcodeInfo {
+        encoding,
+        mask,
+        pattern,
+        mnemonic,
+        description,
+        variants,
+        quirks,
+    }
+}
+
+/// Every known instruction. More specific encodings come first so the
+/// first match is always the right one.
+#[rustfmt::skip]
+pub static OPCODES: &[OpcodeInfo] = &[
+    op("00E0", 0xFFFF, 0x00E0, "CLS", "Clear the display.", ALL, &[]),
+    op("00EE", 0xFFFF, 0x00EE, "RET", "Return from a subroutine.", ALL, &[]),
+    op("00CN", 0xFFF0, 0x00C0, "SCD N", "Scroll the display down by N pixels.", SCHIP, &[]),
+    op("00DN", 0xFFF0, 0x00D0, "SCU N", "Scroll the display up by N pixels.", XO, &[]),
+    op("00FB", 0xFFFF, 0x00FB, "SCR", "Scroll the display right by 4 pixels.", SCHIP, &[]),
+    op("00FC", 0xFFFF, 0x00FC, "SCL", "Scroll the display left by 4 pixels.", SCHIP, &[]),
+    op("00FD", 0xFFFF, 0x00FD, "EXIT", "Exit the interpreter.", SCHIP, &[]),
+    op("00FE", 0xFFFF, 0x00FE, "LOW", "Switch to 64x32 low resolution mode.", SCHIP, &["mode switch clears screen"]),
+    op("00FF", 0xFFFF, 0x00FF, "HIGH", "Switch to 128x64 high resolution mode.", SCHIP, &["mode switch clears screen"]),
+    op("0010", 0xFFFF, 0x0010, "MEGAOFF", "Switch back to the SUPER-CHIP screen.", MEGA, &[]),
+    op("0011", 0xFFFF, 0x0011, "MEGAON", "Switch to the 256x192 indexed-color screen.", MEGA, &[]),
+    op("01NN", 0xFF00, 0x0100, "LDHI NNNNNN", "Set I to the 24-bit address in NN and the next word.", MEGA, &[]),
+    op("02NN", 0xFF00, 0x0200, "LDPAL NN", "Load NN ARGB palette entries from I, starting at entry 1.", MEGA, &[]),
+    op("03NN", 0xFF00, 0x0300, "SPRW NN", "Set the sprite width to NN (0 = 256).", MEGA, &[]),
+    op("04NN", 0xFF00, 0x0400, "SPRH NN", "Set the sprite height to NN (0 = 256).", MEGA, &[]),
+    op("05NN", 0xFF00, 0x0500, "ALPHA NN", "Set the screen alpha.", MEGA, &[]),
+    op("060N", 0xFFF0, 0x0600, "DIGISND N", "Play the digitised sound at I.", MEGA, &[]),
+    op("0700", 0xFFFF, 0x0700, "STOPSND", "Stop the digitised sound.", MEGA, &[]),
+    op("080N", 0xFFF0, 0x0800, "BMODE N", "Set the sprite blend mode.", MEGA, &[]),
+    op("09NN", 0xFF00, 0x0900, "CCOL NN", "Set the palette entry that counts as a collision.", MEGA, &[]),
+    op("0NNN", 0xF000, 0x0000, "SYS NNN", "Call a machine code routine at NNN.", &[Variant::Chip8], &[]),
+    op("1NNN", 0xF000, 0x1000, "JP NNN", "Jump to NNN.", ALL, &[]),
+    op("2NNN", 0xF000, 0x2000, "CALL NNN", "Call the subroutine at NNN.", ALL, &[]),
+    op("3XNN", 0xF000, 0x3000, "SE VX, NN", "Skip the next instruction if VX == NN.", ALL, &[]),
+    op("4XNN", 0xF000, 0x4000, "SNE VX, NN", "Skip the next instruction if VX != NN.", ALL, &[]),
+    op("5XY0", 0xF00F, 0x5000, "SE VX, VY", "Skip the next instruction if VX == VY.", ALL, &[]),
+    op("5XY2", 0xF00F, 0x5002, "SAVE VX - VY", "Store VX..VY at I without changing I.", XO, &[]),
+    op("5XY3", 0xF00F, 0x5003, "LOAD VX - VY", "Load VX..VY from I without changing I.", XO, &[]),
+    op("6XNN", 0xF000, 0x6000, "LD VX, NN", "Set VX = NN.", ALL, &[]),
+    op("7XNN", 0xF000, 0x7000, "ADD VX, NN", "Set VX = VX + NN, wrapping, VF unchanged.", ALL, &[]),
+    op("8XY0", 0xF00F, 0x8000, "LD VX, VY", "Set VX = VY.", ALL, &[]),
+    op("8XY1", 0xF00F, 0x8001, "OR VX, VY", "Set VX = VX | VY.", ALL, &["logic ops reset VF"]),
+    op("8XY2", 0xF00F, 0x8002, "AND VX, VY", "Set VX = VX & VY.", ALL, &["logic ops reset VF"]),
+    op("8XY3", 0xF00F, 0x8003, "XOR VX, VY", "Set VX = VX ^ VY.", ALL, &["logic ops reset VF"]),
+    op("8XY4", 0xF00F, 0x8004, "ADD VX, VY", "Set VX = VX + VY, VF = carry.", ALL, &[]),
+    op("8XY5", 0xF00F, 0x8005, "SUB VX, VY", "Set VX = VX - VY, VF = NOT borrow.", ALL, &[]),
+    op("8XY6", 0xF00F, 0x8006, "SHR VX {, VY}", "Shift right by one, VF = bit shifted out.", ALL, &["shift uses VY"]),
+    op("8XY7", 0xF00F, 0x8007, "SUBN VX, VY", "Set VX = VY - VX, VF = NOT borrow.", ALL, &[]),
+    op("8XYE", 0xF00F, 0x800E, "SHL VX {, VY}", "Shift left by one, VF = bit shifted out.", ALL, &["shift uses VY"]),
+    op("9XY0", 0xF00F, 0x9000, "SNE VX, VY", "Skip the next instruction if VX != VY.", ALL, &[]),
+    op("ANNN", 0xF000, 0xA000, "LD I, NNN", "Set I = NNN.", ALL, &[]),
+    op("BNNN", 0xF000, 0xB000, "JP V0, NNN", "Jump to NNN + V0 (NNN + VX with the BXNN quirk).", ALL, &["jump uses VX"]),
+    op("CXNN", 0xF000, 0xC000, "RND VX, NN", "Set VX = random byte & NN.", ALL, &[]),
+    op("DXY0", 0xF00F, 0xD000, "DRW VX, VY, 0", "Draw a 16x16 sprite at (VX, VY), VF = collision.", SCHIP, &["sprite wrapping", "display wait"]),
+    op("DXYN", 0xF000, 0xD000, "DRW VX, VY, N", "Draw an 8xN sprite from I at (VX, VY), VF = collision.", ALL, &["sprite wrapping", "display wait"]),
+    op("EX9E", 0xF0FF, 0xE09E, "SKP VX", "Skip the next instruction if key VX is pressed.", ALL, &[]),
+    op("EXA1", 0xF0FF, 0xE0A1, "SKNP VX", "Skip the next instruction if key VX is not pressed.", ALL, &[]),
+    op("F000", 0xFFFF, 0xF000, "LD I, NNNN", "Set I to the 16-bit address in the next word.", XO, &[]),
+    op("FN01", 0xF0FF, 0xF001, "PLANE N", "Select the drawing planes given by the bit mask N.", XO, &[]),
+    op("F002", 0xFFFF, 0xF002, "AUDIO", "Load the 16-byte audio pattern buffer from I.", XO, &[]),
+    op("FX07", 0xF0FF, 0xF007, "LD VX, DT", "Set VX = delay timer.", ALL, &[]),
+    op("FX0A", 0xF0FF, 0xF00A, "LD VX, K", "Wait for a key press and store it in VX.", ALL, &[]),
+    op("FX15", 0xF0FF, 0xF015, "LD DT, VX", "Set delay timer = VX.", ALL, &[]),
+    op("FX18", 0xF0FF, 0xF018, "LD ST, VX", "Set sound timer = VX.", ALL, &[]),
+    op("FX1E", 0xF0FF, 0xF01E, "ADD I, VX", "Set I = I + VX.", ALL, &[]),
+    op("FX29", 0xF0FF, 0xF029, "LD F, VX", "Point I at the small font glyph for digit VX.", ALL, &[]),
+    op("FX30", 0xF0FF, 0xF030, "LD HF, VX", "Point I at the large font glyph for digit VX.", SCHIP, &[]),
+    op("FX33", 0xF0FF, 0xF033, "LD B, VX", "Store the BCD digits of VX at I, I+1, I+2.", ALL, &[]),
+    op("FX3A", 0xF0FF, 0xF03A, "PITCH VX", "Set the audio playback pitch to VX.", XO, &[]),
+    op("FX55", 0xF0FF, 0xF055, "LD [I], VX", "Store V0..VX at I.", ALL, &["load/store increments I"]),
+    op("FX65", 0xF0FF, 0xF065, "LD VX, [I]", "Load V0..VX from I.", ALL, &["load/store increments I"]),
+    op("FX75", 0xF0FF, 0xF075, "LD R, VX", "Store V0..VX in the RPL flag registers.", SCHIP, &[]),
+    op("FX85", 0xF0FF, 0xF085, "LD VX, R", "Load V0..VX from the RPL flag registers.", SCHIP, &[]),
+];
+
+/// Finds the reference entry describing `opcode`.
+pub fn lookup(opcode: u16) -> Option<&'static OpcodeInfo> {
+    OPCODES.iter().find(|info| info.matches(opcode))
+}
+
+/// Finds an entry by its encoding pattern, case-insensitively and with an
+/// optional `0x` prefix, so both `"8XY6"` and `"0x8xy6"` work.
+pub fn lookup_encoding(encoding: &str) -> Option<&'static OpcodeInfo> {
+    let encoding = encoding
+        .strip_prefix("0x")
+        .or_else(|| encoding.strip_prefix("0X"))
+        .unwrap_or(encoding);
+    OPCODES
+        .iter()
+        .find(|info| info.encoding.eq_ignore_ascii_case(encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_by_opcode() {
+        let info = lookup(0x8126).unwrap();
+
+        assert_eq!(info.encoding, "8XY6");
+        assert!(info.quirks.contains(&"shift uses VY"));
+    }
+
+    #[test]
+    fn specific_encodings_win_over_generic_ones() {
+        assert_eq!(lookup(0x00E0).unwrap().encoding, "00E0");
+        assert_eq!(lookup(0x0A23).unwrap().encoding, "0NNN");
+        assert_eq!(lookup(0x0123).unwrap().encoding, "01NN");
+        assert_eq!(lookup(0xD120).unwrap().encoding, "DXY0");
+        assert_eq!(lookup(0xD125).unwrap().encoding, "DXYN");
+    }
+
+    #[test]
+    fn unknown_opcodes_have_no_entry() {
+        assert!(lookup(0x5121).is_none());
+        assert!(lookup(0xE0FF).is_none());
+    }
+
+    #[test]
+    fn lookup_by_encoding() {
+        assert_eq!(lookup_encoding("0x8xy6").unwrap().mnemonic, "SHR VX {, VY}");
+        assert_eq!(lookup_encoding("FX33").unwrap().pattern, 0xF033);
+        assert!(lookup_encoding("ZZZZ").is_none());
+    }
+
+    #[test]
+    fn variant_availability() {
+        assert!(lookup(0x00FF).unwrap().is_available_in(Variant::SuperChip));
+        assert!(!lookup(0x00FF).unwrap().is_available_in(Variant::Chip8));
+        assert!(lookup(0x1200).unwrap().is_available_in(Variant::Chip8));
+    }
+
+    #[test]
+    fn every_pattern_is_consistent_with_its_mask() {
+        for info in OPCODES {
+            assert_eq!(info.pattern & info.mask, info.pattern, "{}", info.encoding);
+            assert!(info.matches(info.pattern), "{}", info.encoding);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Input recordings for tool-assisted runs, regression tests of whole
+//! games and reproducible bug reports. An [`InputRecorder`] takes a
+//! snapshot of the machine, reseeds its random source and then logs every
+//! keypad change with the frame and instruction count it happened at;
+//! an [`InputPlayer`] restores the snapshot and seed and presses the same
+//! keys at the same points, so the program does exactly what it did while
+//! being recorded.
+
+use std::path::Path;
+use std::{error, fmt, fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Accuracy, SaveState, StateError, XorShift, CPU};
+use crate::database::sha1_hex;
+use crate::keypad::{KeypadState, KEY_COUNT};
+
+/// A key going down or up. Times count from the start of the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputEvent {
+    /// Frames, i.e. [`CPU::tick_timers`] calls.
+    pub frame: u64,
+    /// Instructions executed, see [`CPU::instructions`].
+    pub instructions: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Everything needed to play a recording back: which program, the machine
+/// and keys at the start, the random seed and what was pressed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// SHA-1 of the ROM, as the ROM database keys them.
+    pub rom_sha1: String,
+    pub seed: u32,
+    pub accuracy: Accuracy,
+    /// Speed the CPU was run at, for frontends to play back at the same.
+    /// `None` if the one recording didn't say.
+    #[serde(default)]
+    pub instructions_per_frame: Option<usize>,
+    pub start: SaveState,
+    /// Keys held when recording started, which save states leave out.
+    pub held: KeypadState,
+    pub events: Vec<InputEvent>,
+    /// Length of the recording in frames.
+    pub frames: u64,
+}
+
+impl InputRecording {
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(ReplayError::Json)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let json = serde_json::to_string_pretty(self).map_err(ReplayError::Json)?;
+        Ok(fs::write(path, json)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The recording was made with another program.
+    WrongRom {
+        expected: String,
+        actual: String,
+    },
+    State(StateError),
+    /// The program got to the frame of an input at a different instruction
+    /// count than when it was recorded, so it no longer behaves the same.
+    Desync {
+        frame: u64,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::Json(err) => write!(f, "invalid recording: {}", err),
+            ReplayError::WrongRom { expected, actual } => {
+                write!(f, "recorded with ROM {} but {} is loaded", expected, actual)
+            }
+            ReplayError::State(err) => write!(f, "{}", err),
+            ReplayError::Desync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "out of sync at frame {}: input was recorded after {} instructions, \
+                 playback is at {}",
+                frame, expected, actual
+            ),
+        }
+    }
+}
+
+impl error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReplayError::Io(err) => Some(err),
+            ReplayError::Json(err) => Some(err),
+            ReplayError::State(err) => Some(err),
+            ReplayError::WrongRom { .. } | ReplayError::Desync { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<StateError> for ReplayError {
+    fn from(err: StateError) -> Self {
+        ReplayError::State(err)
+    }
+}
+
+/// Where a CPU was when a recording or playback started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Origin {
+    frame: u64,
+    instructions: u64,
+}
+
+impl Origin {
+    fn of(cpu: &CPU) -> Self {
+        Origin {
+            frame: cpu.frame(),
+            instructions: cpu.instructions(),
+        }
+    }
+
+    /// How far `cpu` has come since, as (frames, instructions).
+    fn elapsed(&self, cpu: &CPU) -> (u64, u64) {
+        (
+            cpu.frame().saturating_sub(self.frame),
+            cpu.instructions().saturating_sub(self.instructions),
+        )
+    }
+}
+
+/// Records the keypad of one CPU. Tell it about every key change, at the
+/// same point [`CPU::set_key`] is called.
+#[derive(Debug, Clone)]
+pub struct InputRecorder {
+    recording: InputRecording,
+    origin: Origin,
+}
+
+impl InputRecorder {
+    /// Starts recording `cpu` as it is now, reseeding it with `seed` so
+    /// that playback draws the same random numbers.
+    pub fn start(cpu: &mut CPU, seed: u32) -> Self {
+        cpu.set_rng(Box::new(XorShift::new(seed)));
+        let mut held = [false; KEY_COUNT];
+        for (key, held) in held.iter_mut().enumerate() {
+            *held = cpu.keypad.is_pressed(key as u8);
+        }
+        InputRecorder {
+            recording: InputRecording {
+                rom_sha1: sha1_hex(cpu.rom()),
+                seed,
+                accuracy: cpu.accuracy,
+                instructions_per_frame: None,
+                start: cpu.save_state(),
+                held,
+                events: Vec::new(),
+                frames: 0,
+            },
+            origin: Origin::of(cpu),
+        }
+    }
+
+    /// Notes `key` changing on `cpu`; call it just before
+    /// [`CPU::set_key`]. Repeats of the state a key is already in are left
+    /// out.
+    pub fn record(&mut self, cpu: &CPU, key: u8, pressed: bool) {
+        if cpu.keypad.is_pressed(key) == pressed {
+            return;
+        }
+        let (frame, instructions) = self.origin.elapsed(cpu);
+        self.recording.events.push(InputEvent {
+            frame,
+            instructions,
+            key,
+            pressed,
+        });
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.recording.events
+    }
+
+    /// The recording, ending where `cpu` is now.
+    pub fn finish(mut self, cpu: &CPU) -> InputRecording {
+        self.recording.frames = self.origin.elapsed(cpu).0;
+        self.recording
+    }
+}
+
+/// Plays an [`InputRecording`] back into a CPU. Drive the CPU exactly as
+/// it was driven while recording, calling [`InputPlayer::update`] wherever
+/// keys were set then, normally once before every frame.
+#[derive(Debug, Clone)]
+pub struct InputPlayer {
+    recording: InputRecording,
+    next: usize,
+    origin: Origin,
+}
+
+impl InputPlayer {
+    /// Puts `cpu`, which must have the recorded ROM loaded, back into the
+    /// state the recording started from.
+    pub fn start(recording: InputRecording, cpu: &mut CPU) -> Result<Self, ReplayError> {
+        let actual = sha1_hex(cpu.rom());
+        if actual != recording.rom_sha1 {
+            return Err(ReplayError::WrongRom {
+                expected: recording.rom_sha1,
+                actual,
+            });
+        }
+        cpu.load_state(&recording.start)?;
+        cpu.set_rng(Box::new(XorShift::new(recording.seed)));
+        cpu.accuracy = recording.accuracy;
+        cpu.keypad.release_all();
+        for (key, held) in recording.held.iter().enumerate() {
+            cpu.keypad.set(key as u8, *held);
+        }
+        Ok(InputPlayer {
+            recording,
+            next: 0,
+            origin: Origin::of(cpu),
+        })
+    }
+
+    /// Sets the keys recorded at exactly where `cpu` is now. Fails if the
+    /// CPU went past where the next input was recorded, which means the
+    /// program or emulator no longer behaves the same; playback can't go
+    /// on after that.
+    pub fn update(&mut self, cpu: &mut CPU) -> Result<(), ReplayError> {
+        let (frame, instructions) = self.origin.elapsed(cpu);
+        while let Some(event) = self.recording.events.get(self.next) {
+            if (event.frame, event.instructions) == (frame, instructions) {
+                cpu.set_key(event.key, event.pressed);
+                self.next += 1;
+            } else if event.frame < frame || event.instructions < instructions {
+                return Err(ReplayError::Desync {
+                    frame: event.frame,
+                    expected: event.instructions,
+                    actual: instructions,
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether every input was played and `cpu` ran as many frames as
+    /// were recorded.
+    pub fn is_finished(&self, cpu: &CPU) -> bool {
+        self.next == self.recording.events.len()
+            && self.origin.elapsed(cpu).0 >= self.recording.frames
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::EmulatorMode;
+
+    /// Waits for a key, then draws a random byte's worth of pixels at the
+    /// key's position and counts frames until the next key.
+    const GAME: &str = "
+        loop:
+            LD V0, K
+            RND V1, 0xFF
+            LD I, 0x300
+            LD [I], V1
+            DRW V0, V0, 1
+            ADD V2, 1
+            JP loop
+    ";
+
+    fn cpu() -> CPU {
+        let mut cpu = CPU::new_with_mode(EmulatorMode::Chip8);
+        cpu.load_rom(&assemble(GAME).unwrap()).unwrap();
+        cpu
+    }
+
+    fn frame(cpu: &mut CPU) {
+        cpu.run_frame(10).unwrap();
+        cpu.tick_timers();
+    }
+
+    fn record() -> (InputRecording, CPU) {
+        let mut cpu = cpu();
+        frame(&mut cpu);
+        let mut recorder = InputRecorder::start(&mut cpu, 1234);
+        for (at, key, pressed) in [(2, 5, true), (3, 5, false), (6, 9, true), (6, 9, true)] {
+            while recorder.origin.elapsed(&cpu).0 < at {
+                frame(&mut cpu);
+            }
+            recorder.record(&cpu, key, pressed);
+            cpu.set_key(key, pressed);
+        }
+        frame(&mut cpu);
+        (recorder.finish(&cpu), cpu)
+    }
+
+    #[test]
+    fn playback_reproduces_the_recorded_run() {
+        let (recording, recorded) = record();
+        assert_eq!(recording.events.len(), 3);
+        assert_eq!(recording.frames, 7);
+
+        let mut cpu = cpu();
+        let mut player = InputPlayer::start(recording, &mut cpu).unwrap();
+        while !player.is_finished(&cpu) {
+            player.update(&mut cpu).unwrap();
+            frame(&mut cpu);
+        }
+        assert_eq!(cpu.save_state(), recorded.save_state());
+        assert_eq!(cpu.display, recorded.display);
+    }
+
+    #[test]
+    fn playback_notices_a_changed_program() {
+        let (mut recording, _) = record();
+        let mut changed = cpu();
+        changed.load_rom(&[0x12, 0x00]).unwrap();
+        assert!(matches!(
+            InputPlayer::start(recording.clone(), &mut changed),
+            Err(ReplayError::WrongRom { .. })
+        ));
+
+        // as if the program had taken fewer instructions to get there
+        recording.events[1].instructions -= 1;
+        let mut cpu = cpu();
+        let mut player = InputPlayer::start(recording, &mut cpu).unwrap();
+        let desync = loop {
+            if let Err(err) = player.update(&mut cpu) {
+                break err;
+            }
+            frame(&mut cpu);
+        };
+        assert!(matches!(desync, ReplayError::Desync { frame: 3, .. }));
+    }
+
+    #[test]
+    fn recordings_round_trip_through_files() {
+        let (recording, _) = record();
+        let path = std::env::temp_dir().join(format!("chip8-replay-{}.json", std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = InputRecording::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), recording);
+    }
+}
@@ -0,0 +1,251 @@
+//! Runs several independent machines in lockstep, one 60 Hz frame at a
+//! time, for frontends that show more than one game at once.
+
+use crate::cpu::{Fault, RomError, CPU};
+
+/// One CPU plus the bookkeeping the scheduler needs for it.
+pub struct Machine {
+    pub name: String,
+    pub cpu: CPU,
+    pub instructions_per_frame: usize,
+    error: Option<Fault>,
+    /// Program queued by [`Machine::reload`], swapped in before the next
+    /// frame.
+    pending_rom: Option<Vec<u8>>,
+}
+
+impl Machine {
+    pub fn new(name: impl Into<String>, cpu: CPU, instructions_per_frame: usize) -> Self {
+        Machine {
+            name: name.into(),
+            cpu,
+            instructions_per_frame,
+            error: None,
+            pending_rom: None,
+        }
+    }
+
+    /// The error that stopped this machine, if any. A failed machine is
+    /// skipped by the scheduler while the others keep running.
+    pub fn error(&self) -> Option<&Fault> {
+        self.error.as_ref()
+    }
+
+    /// Restarts the program with [`CPU::soft_reset`], reviving the machine
+    /// if it had failed.
+    pub fn soft_reset(&mut self) {
+        self.cpu.soft_reset();
+        self.error = None;
+    }
+
+    /// Like [`Machine::soft_reset`] with [`CPU::hard_reset`].
+    pub fn hard_reset(&mut self) {
+        self.cpu.hard_reset();
+        self.error = None;
+    }
+
+    /// Queues a new program image, e.g. after the file changed on disk. It
+    /// replaces the running one with [`CPU::reload_rom`] at the next frame
+    /// boundary, never between instructions, and revives a failed
+    /// machine. A ROM that doesn't fit is rejected right away.
+    pub fn reload(&mut self, rom: Vec<u8>) -> Result<(), RomError> {
+        self.cpu.check_rom(&rom)?;
+        self.pending_rom = Some(rom);
+        Ok(())
+    }
+
+    fn run_frame(&mut self) {
+        if let Some(rom) = self.pending_rom.take() {
+            if self.cpu.reload_rom(&rom).is_ok() {
+                self.error = None;
+            }
+        }
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(err) = self.cpu.run_frame(self.instructions_per_frame) {
+            self.error = Some(err);
+            return;
+        }
+        self.cpu.tick_timers();
+    }
+}
+
+/// A set of machines sharing one frame clock, with keypad input routed to
+/// whichever machine has focus.
+#[derive(Default)]
+pub struct FrameScheduler {
+    machines: Vec<Machine>,
+    focus: usize,
+}
+
+impl FrameScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a machine and returns its index.
+    pub fn add(&mut self, machine: Machine) -> usize {
+        self.machines.push(machine);
+        self.machines.len() - 1
+    }
+
+    pub fn machines(&self) -> &[Machine] {
+        &self.machines
+    }
+
+    pub fn machine_mut(&mut self, index: usize) -> Option<&mut Machine> {
+        self.machines.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    /// Moves input focus. Keys held on the previously focused machine are
+    /// released so they don't stay stuck down.
+    pub fn set_focus(&mut self, index: usize) {
+        if index >= self.machines.len() || index == self.focus {
+            return;
+        }
+        if let Some(old) = self.machines.get_mut(self.focus) {
+            old.cpu.keypad.release_all();
+        }
+        self.focus = index;
+    }
+
+    pub fn focus_next(&mut self) {
+        if !self.machines.is_empty() {
+            self.set_focus((self.focus + 1) % self.machines.len());
+        }
+    }
+
+    /// Forwards a key event to the focused machine.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(machine) = self.machines.get_mut(self.focus) {
+            machine.cpu.set_key(key, pressed);
+        }
+    }
+
+    /// Runs one frame's worth of instructions on every machine and ticks
+    /// their timers.
+    pub fn run_frame(&mut self) {
+        for machine in &mut self.machines {
+            machine.run_frame();
+        }
+    }
+
+    /// Columns and rows of the most square grid holding every machine.
+    pub fn grid_size(&self) -> (usize, usize) {
+        let n = self.machines.len().max(1);
+        let cols = (1..=n).find(|c| c * c >= n).unwrap_or(n);
+        (cols, n.div_ceil(cols))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Chip8Error;
+
+    fn counting_machine(name: &str) -> Machine {
+        let mut cpu = CPU::new();
+        // loop: ADD V0, 1; JP 0x200
+        cpu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        Machine::new(name, cpu, 10)
+    }
+
+    #[test]
+    fn every_machine_runs_its_budget() {
+        let mut scheduler = FrameScheduler::new();
+        scheduler.add(counting_machine("a"));
+        let b = scheduler.add(counting_machine("b"));
+        scheduler.machine_mut(b).unwrap().instructions_per_frame = 4;
+
+        scheduler.run_frame();
+
+        assert_eq!(scheduler.machines()[0].cpu.registers[0], 5);
+        assert_eq!(scheduler.machines()[1].cpu.registers[0], 2);
+    }
+
+    #[test]
+    fn failed_machine_does_not_stop_the_others() {
+        let mut scheduler = FrameScheduler::new();
+        let mut broken = CPU::new();
+        broken.load_rom(&[0x00, 0xEE]).unwrap();
+        scheduler.add(Machine::new("broken", broken, 10));
+        scheduler.add(counting_machine("ok"));
+
+        scheduler.run_frame();
+        scheduler.run_frame();
+
+        assert_eq!(
+            scheduler.machines()[0].error().map(|fault| &fault.error),
+            Some(&Chip8Error::StackUnderflow)
+        );
+        assert_eq!(scheduler.machines()[1].cpu.registers[0], 10);
+
+        scheduler.machine_mut(0).unwrap().soft_reset();
+        assert!(scheduler.machines()[0].error().is_none());
+    }
+
+    #[test]
+    fn reload_waits_for_the_frame_boundary() {
+        let mut scheduler = FrameScheduler::new();
+        scheduler.add(counting_machine("a"));
+        scheduler.run_frame();
+
+        let machine = scheduler.machine_mut(0).unwrap();
+        // loop: ADD V1, 1; JP 0x200
+        machine.reload(vec![0x71, 0x01, 0x12, 0x00]).unwrap();
+        assert!(machine.reload(vec![0; 0x1000]).is_err());
+        assert_eq!(machine.cpu.memory[0x200], 0x70);
+
+        scheduler.run_frame();
+
+        let cpu = &scheduler.machines()[0].cpu;
+        assert_eq!(cpu.registers[0], 0);
+        assert_eq!(cpu.registers[1], 5);
+        assert_eq!(cpu.frame(), 1);
+    }
+
+    #[test]
+    fn keys_go_to_the_focused_machine() {
+        let mut scheduler = FrameScheduler::new();
+        scheduler.add(counting_machine("a"));
+        scheduler.add(counting_machine("b"));
+
+        scheduler.set_key(0x5, true);
+        scheduler.focus_next();
+        scheduler.set_key(0x6, true);
+
+        assert_eq!(scheduler.focus(), 1);
+        let (a, b) = (&scheduler.machines()[0].cpu, &scheduler.machines()[1].cpu);
+        // switching focus released the key held on the first machine
+        assert!(!a.keypad.is_pressed(0x5));
+        assert!(b.keypad.is_pressed(0x6));
+        assert!(!b.keypad.is_pressed(0x5));
+
+        scheduler.focus_next();
+        assert_eq!(scheduler.focus(), 0);
+    }
+
+    #[test]
+    fn grid_is_roughly_square() {
+        let mut scheduler = FrameScheduler::new();
+        assert_eq!(scheduler.grid_size(), (1, 1));
+
+        for i in 0..5 {
+            scheduler.add(counting_machine(&i.to_string()));
+        }
+        assert_eq!(scheduler.grid_size(), (3, 2));
+    }
+}
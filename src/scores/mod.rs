@@ -0,0 +1,254 @@
+//! High scores. A ROM's descriptor can say where the game keeps its score,
+//! e.g. `"score": {"address": 1008, "length": 3}` for three BCD digits at
+//! 0x3F0, and a [`Leaderboard`] keeps the best ones of every game in a
+//! [`Storage`] so frontends can show them across sessions.
+
+use std::time::Duration;
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::CPU;
+use crate::storage::{Storage, StorageError};
+
+/// Storage key of the leaderboard, shared by all games.
+pub const LEADERBOARD_KEY: &str = "leaderboard.json";
+
+/// Scores kept per game.
+pub const SCORES_PER_GAME: usize = 10;
+
+/// How a game encodes its score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreFormat {
+    /// One byte per decimal digit, most significant first, as FX33 writes
+    /// them.
+    #[default]
+    Digits,
+    /// An unsigned big-endian number of up to four bytes.
+    Binary,
+}
+
+/// Where a game keeps its score, from the `score` entry of its descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ScoreLocation {
+    /// Address of the first byte. Ignored when `register` is set.
+    #[serde(default)]
+    pub address: usize,
+    /// A register holding the score instead, e.g. 14 for VE. Its value is
+    /// the score whatever `format` says.
+    pub register: Option<u8>,
+    #[serde(default)]
+    pub format: ScoreFormat,
+    /// Bytes the score takes up in memory.
+    #[serde(default = "one")]
+    pub length: usize,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl ScoreLocation {
+    /// The current score, `None` when the location lies outside the
+    /// machine or holds something that isn't a number in `format`.
+    pub fn read(&self, cpu: &CPU) -> Option<u32> {
+        if let Some(x) = self.register {
+            return cpu.registers.get(x as usize).map(|value| *value as u32);
+        }
+        let bytes = cpu
+            .memory
+            .get(self.address..self.address.checked_add(self.length)?)?;
+        match self.format {
+            ScoreFormat::Digits => bytes.iter().try_fold(0u32, |score, &digit| {
+                if digit > 9 {
+                    return None;
+                }
+                score.checked_mul(10)?.checked_add(digit as u32)
+            }),
+            ScoreFormat::Binary if bytes.len() <= 4 => Some(
+                bytes
+                    .iter()
+                    .fold(0, |score, &byte| (score << 8) | byte as u32),
+            ),
+            ScoreFormat::Binary => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub game: String,
+    pub score: u32,
+    /// Seconds since the Unix epoch, when the clock knew it.
+    pub when: Option<u64>,
+}
+
+/// The best scores of every game played, [`SCORES_PER_GAME`] each.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    /// Grouped by game, best score first within each game.
+    entries: Vec<ScoreEntry>,
+}
+
+impl Leaderboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The leaderboard saved in `storage`, empty if there is none yet.
+    pub fn load(storage: &dyn Storage) -> Result<Self, StorageError> {
+        match storage.load(LEADERBOARD_KEY)? {
+            Some(json) => serde_json::from_slice(&json)
+                .map_err(|err| StorageError::Io(io::Error::new(io::ErrorKind::InvalidData, err))),
+            None => Ok(Self::new()),
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<(), StorageError> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        storage.save(LEADERBOARD_KEY, &json)
+    }
+
+    /// Enters `score` for `game`, returning its place in the game's table,
+    /// 0 for a new best, or `None` if it didn't make the table. Scores of
+    /// zero are never entered.
+    pub fn record(&mut self, game: &str, score: u32, when: Option<Duration>) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let first = self.entries.iter().position(|e| e.game == game);
+        let start = first.unwrap_or(self.entries.len());
+        let count = self.scores(game).count();
+        // ties go after the earlier score
+        let place = self.scores(game).take_while(|e| e.score >= score).count();
+        if place >= SCORES_PER_GAME {
+            return None;
+        }
+        self.entries.insert(
+            start + place,
+            ScoreEntry {
+                game: game.to_string(),
+                score,
+                when: when.map(|when| when.as_secs()),
+            },
+        );
+        if count == SCORES_PER_GAME {
+            self.entries.remove(start + SCORES_PER_GAME);
+        }
+        Some(place)
+    }
+
+    /// The scores of `game`, best first.
+    pub fn scores<'a>(&'a self, game: &'a str) -> impl Iterator<Item = &'a ScoreEntry> + 'a {
+        self.entries.iter().filter(move |e| e.game == game)
+    }
+
+    /// The best score of each game, highest first.
+    pub fn best(&self) -> Vec<&ScoreEntry> {
+        let mut best: Vec<&ScoreEntry> = Vec::new();
+        for entry in &self.entries {
+            if best.last().is_none_or(|last| last.game != entry.game) {
+                best.push(entry);
+            }
+        }
+        best.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        best
+    }
+}
+
+/// One line per game with its best score, highest first.
+impl fmt::Display for Leaderboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let best = self.best();
+        let width = best.iter().map(|e| e.game.len()).max().unwrap_or(0);
+        for (rank, entry) in best.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>2}. {:<width$}  {:>8}",
+                rank + 1,
+                entry.game,
+                entry.score
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn scores_are_read_from_memory_or_registers() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x3F0..0x3F3].copy_from_slice(&[1, 2, 5]);
+        cpu.registers[0xE] = 42;
+        let digits = ScoreLocation {
+            address: 0x3F0,
+            register: None,
+            format: ScoreFormat::Digits,
+            length: 3,
+        };
+
+        assert_eq!(digits.read(&cpu), Some(125));
+        let binary = ScoreLocation {
+            format: ScoreFormat::Binary,
+            length: 2,
+            ..digits
+        };
+        assert_eq!(binary.read(&cpu), Some(0x0102));
+        let register = ScoreLocation {
+            register: Some(0xE),
+            ..digits
+        };
+        assert_eq!(register.read(&cpu), Some(42));
+
+        cpu.memory[0x3F1] = 10;
+        assert_eq!(digits.read(&cpu), None);
+        let outside = ScoreLocation {
+            address: 0xFFF,
+            ..digits
+        };
+        assert_eq!(outside.read(&cpu), None);
+    }
+
+    #[test]
+    fn leaderboard_keeps_the_best_scores_per_game() {
+        let mut board = Leaderboard::new();
+        for score in 1..=SCORES_PER_GAME as u32 {
+            board.record("pong", score * 10, None);
+        }
+        board.record("tetris", 7, Some(Duration::from_secs(1_700_000_000)));
+
+        assert_eq!(board.record("pong", 5, None), None);
+        assert_eq!(board.record("pong", 55, None), Some(5));
+        assert_eq!(board.record("pong", 0, None), None);
+        let pong: Vec<u32> = board.scores("pong").map(|e| e.score).collect();
+        assert_eq!(pong, [100, 90, 80, 70, 60, 55, 50, 40, 30, 20]);
+
+        let best: Vec<(&str, u32)> = board
+            .best()
+            .iter()
+            .map(|e| (e.game.as_str(), e.score))
+            .collect();
+        assert_eq!(best, [("pong", 100), ("tetris", 7)]);
+        assert_eq!(
+            board.to_string(),
+            " 1. pong         100\n 2. tetris         7\n"
+        );
+    }
+
+    #[test]
+    fn leaderboard_round_trips_through_storage() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(Leaderboard::load(&storage).unwrap(), Leaderboard::new());
+
+        let mut board = Leaderboard::new();
+        board.record("pong", 3, Some(Duration::from_secs(60)));
+        board.save(&mut storage).unwrap();
+
+        assert_eq!(Leaderboard::load(&storage).unwrap(), board);
+    }
+}
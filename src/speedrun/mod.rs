@@ -0,0 +1,209 @@
+//! Speedrun timing. A ROM's descriptor can list splits as conditions on
+//! memory, e.g. `"splits": [{"name": "Level 2", "when": "mem[0x300] >= 2"}]`,
+//! and a [`SplitTimer`] checks them every frame and notes when each one
+//! was reached. Times count emulated 60 Hz frames, so runs compare fairly
+//! whatever the host's speed or frame drops.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::cpu::CPU;
+use crate::expr::{Expr, ExprError};
+
+/// Frames per second the timer counts in.
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// One split as a descriptor gives it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SplitDef {
+    pub name: String,
+    /// An [`Expr`] that becomes non-zero when the split is reached.
+    pub when: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Split {
+    name: String,
+    condition: Expr,
+    /// Frame the split was reached on.
+    reached: Option<u64>,
+}
+
+/// Times a run through a list of splits taken in order. The clock starts
+/// with the first [`SplitTimer::update`] and stops at the last split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitTimer {
+    splits: Vec<Split>,
+    frames: u64,
+    /// Whether the current split's condition held last frame. Starts out
+    /// set, so that a first split already true at power-on, e.g. left
+    /// over in memory, has to become true again.
+    held: bool,
+}
+
+impl SplitTimer {
+    pub fn new(defs: &[SplitDef]) -> Result<Self, ExprError> {
+        let splits = defs
+            .iter()
+            .map(|def| {
+                Ok(Split {
+                    name: def.name.clone(),
+                    condition: Expr::parse(&def.when)?,
+                    reached: None,
+                })
+            })
+            .collect::<Result<_, ExprError>>()?;
+        Ok(SplitTimer {
+            splits,
+            frames: 0,
+            held: true,
+        })
+    }
+
+    /// Call once per frame, after running it. Returns the index of the
+    /// split reached this frame, if any.
+    pub fn update(&mut self, cpu: &CPU) -> Option<usize> {
+        let current = self
+            .splits
+            .iter()
+            .position(|split| split.reached.is_none())?;
+        self.frames += 1;
+        let holds = self.splits[current].condition.holds(cpu);
+        let reached = holds && !self.held;
+        self.held = holds;
+        if !reached {
+            return None;
+        }
+        self.splits[current].reached = Some(self.frames);
+        // the next condition starts fresh
+        self.held = self
+            .splits
+            .get(current + 1)
+            .is_some_and(|next| next.condition.holds(cpu));
+        Some(current)
+    }
+
+    /// Starts over for a new run.
+    pub fn reset(&mut self) {
+        for split in &mut self.splits {
+            split.reached = None;
+        }
+        self.frames = 0;
+        self.held = true;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        frames_to_time(self.frames)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.splits.iter().all(|split| split.reached.is_some())
+    }
+
+    /// Every split's name and the run time it was reached at.
+    pub fn splits(&self) -> impl Iterator<Item = (&str, Option<Duration>)> + '_ {
+        self.splits
+            .iter()
+            .map(|split| (split.name.as_str(), split.reached.map(frames_to_time)))
+    }
+}
+
+fn frames_to_time(frames: u64) -> Duration {
+    Duration::from_secs(frames / FRAMES_PER_SECOND)
+        + Duration::from_secs(1) * (frames % FRAMES_PER_SECOND) as u32 / FRAMES_PER_SECOND as u32
+}
+
+/// `m:ss.cc`, as speedrunners write times.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// The running time, then one line per split with its time or `-`.
+impl fmt::Display for SplitTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", format_time(self.elapsed()))?;
+        let width = self.splits.iter().map(|s| s.name.len()).max().unwrap_or(0);
+        for (name, time) in self.splits() {
+            let time = time.map_or_else(|| "-".to_string(), format_time);
+            writeln!(f, "{:<width$}  {:>8}", name, time)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_splits() -> SplitTimer {
+        let def = |name: &str, when: &str| SplitDef {
+            name: name.to_string(),
+            when: when.to_string(),
+        };
+        SplitTimer::new(&[
+            def("Level 2", "mem[0x300] >= 2"),
+            def("Level 3", "mem[0x300] >= 3"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn splits_are_reached_in_order() {
+        let mut cpu = CPU::new();
+        let mut timer = level_splits();
+        cpu.memory[0x300] = 1;
+
+        for _ in 0..90 {
+            assert_eq!(timer.update(&cpu), None);
+        }
+        cpu.memory[0x300] = 2;
+        assert_eq!(timer.update(&cpu), Some(0));
+        for _ in 0..29 {
+            timer.update(&cpu);
+        }
+        cpu.memory[0x300] = 3;
+        assert_eq!(timer.update(&cpu), Some(1));
+        assert!(timer.is_finished());
+        // the clock has stopped
+        assert_eq!(timer.update(&cpu), None);
+
+        let times: Vec<Option<Duration>> = timer.splits().map(|(_, time)| time).collect();
+        assert_eq!(
+            times,
+            [
+                Some(Duration::from_millis(1516) + Duration::from_nanos(666_666)),
+                Some(Duration::from_millis(2016) + Duration::from_nanos(666_666)),
+            ]
+        );
+        assert_eq!(
+            timer.to_string(),
+            "0:02.01\nLevel 2   0:01.51\nLevel 3   0:02.01\n"
+        );
+    }
+
+    #[test]
+    fn a_condition_true_from_the_start_has_to_happen_again() {
+        let mut cpu = CPU::new();
+        let mut timer = level_splits();
+        cpu.memory[0x300] = 5;
+
+        assert_eq!(timer.update(&cpu), None);
+        // e.g. a game restarting at level 1
+        cpu.memory[0x300] = 1;
+        timer.update(&cpu);
+        cpu.memory[0x300] = 2;
+        assert_eq!(timer.update(&cpu), Some(0));
+
+        timer.reset();
+        assert_eq!(timer.elapsed(), Duration::ZERO);
+        assert!(timer.splits().all(|(_, time)| time.is_none()));
+    }
+}
@@ -0,0 +1,204 @@
+//! Where data that outlives a session is kept: SUPER-CHIP RPL flags, high
+//! scores, per-ROM settings and the [`Session`] itself. Frontends pick a
+//! [`Storage`] backend, files on the desktop or [`MemoryStorage`] wherever
+//! there is no filesystem, and the helpers here key the data by ROM.
+
+mod session;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::{error, fmt, fs, io};
+
+use crate::cpu::CPU;
+use crate::fingerprint::rom_hash;
+
+pub use session::{load_resume_state, save_resume_state, Session, SESSION_KEY};
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Keys may only contain ASCII letters, digits, `.`, `-` and `_`, and
+    /// must not start with a dot.
+    InvalidKey(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::InvalidKey(key) => write!(f, "invalid storage key {:?}", key),
+            StorageError::Io(err) => write!(f, "storage error: {}", err),
+        }
+    }
+}
+
+impl error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            StorageError::Io(err) => Some(err),
+            StorageError::InvalidKey(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+/// A flat key-value store of byte blobs.
+pub trait Storage {
+    /// `None` if nothing was saved under `key`.
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    fn save(&mut self, key: &str, data: &[u8]) -> Result<(), StorageError>;
+}
+
+fn check_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+/// One file per key in a directory, created on first save.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStorage {
+    pub root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileStorage { root: root.into() }
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        check_key(key)?;
+        match fs::read(self.root.join(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&mut self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        check_key(key)?;
+        fs::create_dir_all(&self.root)?;
+        // write then rename, so a crash never leaves a truncated file
+        let path = self.root.join(key);
+        let temp = self.root.join(format!(".{}.tmp", key));
+        fs::write(&temp, data)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+}
+
+/// Keeps everything in memory. Embedded and web builds can mirror
+/// [`MemoryStorage::entries`] into whatever they persist with, such as the
+/// browser's localStorage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.entries
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        check_key(key)?;
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn save(&mut self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        check_key(key)?;
+        self.entries.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// Key for data named `name` belonging to the game `rom`, e.g.
+/// `"3f1c0a9e5b2d7788.rpl"`. `name` must itself be a valid key.
+pub fn rom_key(rom: &[u8], name: &str) -> String {
+    format!("{:016x}.{}", rom_hash(rom), name)
+}
+
+/// Saves the RPL flags of the program loaded in `cpu`.
+pub fn save_rpl_flags(storage: &mut dyn Storage, cpu: &CPU) -> Result<(), StorageError> {
+    storage.save(&rom_key(cpu.rom(), "rpl"), &cpu.rpl_flags)
+}
+
+/// Restores the RPL flags saved for the program loaded in `cpu`. Returns
+/// whether there were any; a blob of the wrong size counts as none.
+pub fn load_rpl_flags(storage: &dyn Storage, cpu: &mut CPU) -> Result<bool, StorageError> {
+    match storage.load(&rom_key(cpu.rom(), "rpl"))? {
+        Some(flags) if flags.len() == cpu.rpl_flags.len() => {
+            cpu.rpl_flags.copy_from_slice(&flags);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpl_flags_are_kept_per_rom() {
+        let mut storage = MemoryStorage::new();
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        cpu.rpl_flags[0] = 42;
+        save_rpl_flags(&mut storage, &cpu).unwrap();
+
+        let mut same = CPU::new();
+        same.load_rom(&[0x12, 0x00]).unwrap();
+        assert!(load_rpl_flags(&storage, &mut same).unwrap());
+        assert_eq!(same.rpl_flags[0], 42);
+
+        let mut other = CPU::new();
+        other.load_rom(&[0x12, 0x02]).unwrap();
+        assert!(!load_rpl_flags(&storage, &mut other).unwrap());
+        assert_eq!(other.rpl_flags[0], 0);
+    }
+
+    #[test]
+    fn keys_cannot_escape_the_store() {
+        let mut storage = MemoryStorage::new();
+        for key in ["", "../scores", "a/b", ".hidden"] {
+            assert!(matches!(
+                storage.save(key, b"x"),
+                Err(StorageError::InvalidKey(_))
+            ));
+        }
+        assert!(storage.entries().is_empty());
+    }
+
+    #[test]
+    fn file_storage_round_trips() {
+        let root = std::env::temp_dir().join(format!("chip8-storage-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root);
+
+        assert_eq!(storage.load("scores").unwrap(), None);
+        storage.save("scores", &[1, 2, 3]).unwrap();
+        assert_eq!(storage.load("scores").unwrap(), Some(vec![1, 2, 3]));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use super::{rom_key, Storage, StorageError};
+use crate::cpu::{EmulatorMode, Quirks, SaveState, CPU};
+
+/// Storage key of the [`Session`].
+pub const SESSION_KEY: &str = "session.json";
+
+/// What a frontend was running when it last exited, so it can pick up
+/// from there when started without any ROMs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Paths of the ROMs loaded, in the order they were given.
+    pub roms: Vec<String>,
+    pub mode: Option<EmulatorMode>,
+    pub quirks: Option<Quirks>,
+    /// Inner size of the window in pixels.
+    pub window: Option<(usize, usize)>,
+    /// Whether every game was saved on exit, to be resumed with
+    /// [`load_resume_state`].
+    pub autosave: bool,
+}
+
+impl Session {
+    /// The session saved in `storage`, the default if there is none yet.
+    pub fn load(storage: &dyn Storage) -> Result<Self, StorageError> {
+        match storage.load(SESSION_KEY)? {
+            Some(json) => serde_json::from_slice(&json)
+                .map_err(|err| StorageError::Io(io::Error::new(io::ErrorKind::InvalidData, err))),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<(), StorageError> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        storage.save(SESSION_KEY, &json)
+    }
+}
+
+/// Saves the whole machine, under the key of the program loaded in
+/// `cpu`, for [`load_resume_state`] to carry on from next session.
+pub fn save_resume_state(storage: &mut dyn Storage, cpu: &CPU) -> Result<(), StorageError> {
+    storage.save(&rom_key(cpu.rom(), "resume"), &cpu.save_state().to_bytes())
+}
+
+/// Puts `cpu` back the way [`save_resume_state`] left the same program.
+/// Returns whether it did; a state from an older version of the emulator,
+/// or one that no longer loads for any other reason, counts as none.
+pub fn load_resume_state(storage: &dyn Storage, cpu: &mut CPU) -> Result<bool, StorageError> {
+    let Some(bytes) = storage.load(&rom_key(cpu.rom(), "resume"))? else {
+        return Ok(false);
+    };
+    let loaded = SaveState::from_bytes(&bytes).and_then(|state| cpu.load_state(&state));
+    Ok(loaded.is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn sessions_round_trip_through_storage() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(Session::load(&storage).unwrap(), Session::default());
+
+        let session = Session {
+            roms: vec!["/games/pong.ch8".to_string()],
+            mode: Some(EmulatorMode::SuperChip),
+            quirks: Some(Quirks::cosmac_vip()),
+            window: Some((640, 320)),
+            autosave: true,
+        };
+        session.save(&mut storage).unwrap();
+
+        assert_eq!(Session::load(&storage).unwrap(), session);
+    }
+
+    #[test]
+    fn games_resume_where_they_were_left() {
+        let mut storage = MemoryStorage::new();
+        let mut cpu = CPU::new_with_mode(EmulatorMode::Chip8);
+        cpu.load_rom(&[0x60, 0x2A, 0x12, 0x02]).unwrap();
+        assert!(!load_resume_state(&storage, &mut cpu).unwrap());
+
+        cpu.run_frame(4).unwrap();
+        save_resume_state(&mut storage, &cpu).unwrap();
+        let mut resumed = CPU::new_with_mode(EmulatorMode::Chip8);
+        resumed.load_rom(&[0x60, 0x2A, 0x12, 0x02]).unwrap();
+
+        assert!(load_resume_state(&storage, &mut resumed).unwrap());
+        assert_eq!(resumed.registers[0], 0x2A);
+        assert_eq!(resumed.memory_position, cpu.memory_position);
+        // another program has nothing to resume
+        resumed.load_rom(&[0x12, 0x00]).unwrap();
+        assert!(!load_resume_state(&storage, &mut resumed).unwrap());
+    }
+}
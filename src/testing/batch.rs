@@ -0,0 +1,247 @@
+use crate::cpu::{Fault, Status, CPU};
+use crate::disasm::{disassemble, Instruction};
+
+/// Cycles `chip8 test` gives a ROM to finish, if not told otherwise.
+pub const DEFAULT_MAX_CYCLES: u64 = 10_000_000;
+
+/// Exit codes of `chip8 test` on a ROM, so CI can tell a wrong picture
+/// from a ROM that never finished or crashed. 2 is a usage error, as for
+/// every command.
+pub const EXIT_PASSED: i32 = 0;
+pub const EXIT_MISMATCH: i32 = 1;
+pub const EXIT_TIMED_OUT: i32 = 3;
+pub const EXIT_FAULTED: i32 = 4;
+
+/// How [`run_to_completion`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    /// 00FD, or 0000 with [`CPU::halt_on_zero`].
+    Halted,
+    /// Stuck on a jump to its own address, how most test ROMs stop once
+    /// their results are on screen.
+    SelfJump(usize),
+    /// FX0A, waiting for a key nobody is going to press.
+    WaitingForKey,
+    /// Still running at the cycle limit.
+    OutOfCycles,
+}
+
+impl Completion {
+    /// Whether the program came to a stop by itself.
+    pub fn is_finished(self) -> bool {
+        self != Completion::OutOfCycles
+    }
+}
+
+/// Runs `cpu` a frame at a time, ticking the timers in between, until
+/// the program stops or `max_cycles` instructions have run.
+pub fn run_to_completion(
+    cpu: &mut CPU,
+    max_cycles: u64,
+    instructions_per_frame: usize,
+) -> Result<Completion, Fault> {
+    let start = cpu.instructions();
+    loop {
+        if let Some(address) = self_jump(cpu) {
+            return Ok(Completion::SelfJump(address));
+        }
+        let ran = cpu.instructions() - start;
+        if ran >= max_cycles {
+            return Ok(Completion::OutOfCycles);
+        }
+        let budget = (max_cycles - ran).min(instructions_per_frame.max(1) as u64);
+        match cpu.run_frame(budget as usize)? {
+            Status::Halted => return Ok(Completion::Halted),
+            Status::WaitingForKey => return Ok(Completion::WaitingForKey),
+            Status::Continue => cpu.tick_timers(),
+        }
+    }
+}
+
+/// PC, if the instruction there jumps straight back to it.
+fn self_jump(cpu: &CPU) -> Option<usize> {
+    let pc = cpu.memory_position;
+    let word = cpu.memory.get(pc..pc.saturating_add(2))?;
+    match disassemble(u16::from_be_bytes([word[0], word[1]])) {
+        Instruction::Jp(address) if address as usize == pc => Some(pc),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "png")]
+pub use golden_png::{check_golden_png, write_golden_png};
+
+#[cfg(feature = "png")]
+mod golden_png {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::{self, Cursor};
+    use std::path::Path;
+
+    use crate::display::{FrameBuffer, Palette};
+
+    /// The palette index of every pixel, or the Mega-Chip color index.
+    fn pixel(fb: &FrameBuffer, x: usize, y: usize) -> u8 {
+        match fb.mega() {
+            Some(mega) => mega.index(x, y),
+            None => fb.color(x, y),
+        }
+    }
+
+    /// `fb` as a PNG in [`Palette::CLASSIC`], or the program's own colors
+    /// on the Mega-Chip screen, one image pixel per screen pixel.
+    pub fn write_golden_png(path: &Path, fb: &FrameBuffer) -> io::Result<()> {
+        let colors = Palette::CLASSIC.colors();
+        let (width, height) = (fb.width(), fb.height());
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let color = match fb.mega() {
+                    Some(mega) => mega.rgb(x, y),
+                    None => colors[fb.color(x, y) as usize],
+                };
+                rgb.extend([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+            }
+        }
+        let file = fs::File::create(path)?;
+        let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&rgb).map_err(io::Error::other)
+    }
+
+    /// `0x00RRGGBB` pixels of the PNG at `path`, with its size.
+    fn read_png(path: &Path) -> Result<(usize, usize, Vec<u32>), String> {
+        let error = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+        let bytes = fs::read(path)
+            .map_err(|err| format!("{}; run with CHIP8_BLESS=1 to record it", error(&err)))?;
+        let mut decoder = png::Decoder::new(Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|err| error(&err))?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let info = reader.next_frame(&mut buf).map_err(|err| error(&err))?;
+        let channels = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let line = &buf[y * info.line_size..][..width * channels];
+            for px in line.chunks_exact(channels) {
+                let [r, g, b] = match channels {
+                    1 | 2 => [px[0]; 3],
+                    _ => [px[0], px[1], px[2]],
+                };
+                pixels.push(u32::from_be_bytes([0, r, g, b]));
+            }
+        }
+        Ok((width, height, pixels))
+    }
+
+    /// Compares `fb` with the golden image at `path`, or writes it there
+    /// when `CHIP8_BLESS` is set, like [`crate::testing::check_golden`].
+    ///
+    /// The image may be the screen blown up by a whole factor, and in any
+    /// palette: it matches as long as each of its colors stands for one
+    /// pixel color of `fb`'s and the other way round.
+    pub fn check_golden_png(path: &Path, fb: &FrameBuffer) -> Result<(), String> {
+        if std::env::var_os("CHIP8_BLESS").is_some() {
+            return write_golden_png(path, fb)
+                .map_err(|err| format!("{}: {}", path.display(), err));
+        }
+        let (width, height, pixels) = read_png(path)?;
+        let scale = width / fb.width();
+        if scale == 0 || width != fb.width() * scale || height != fb.height() * scale {
+            return Err(format!(
+                "{} is {}x{}, not a multiple of the {}x{} screen",
+                path.display(),
+                width,
+                height,
+                fb.width(),
+                fb.height()
+            ));
+        }
+
+        let mut to_screen = HashMap::new();
+        let mut to_image = HashMap::new();
+        let mut wrong = Vec::new();
+        for y in 0..fb.height() {
+            for x in 0..fb.width() {
+                let color = pixels[y * scale * width + x * scale];
+                let actual = pixel(fb, x, y);
+                let expected = *to_screen.entry(color).or_insert(actual);
+                let image = *to_image.entry(actual).or_insert(color);
+                if expected != actual || image != color {
+                    wrong.push((x, y));
+                }
+            }
+        }
+        let Some(&(x, y)) = wrong.first() else {
+            return Ok(());
+        };
+        Err(format!(
+            "{} does not match: {} pixels differ, the first at ({}, {}); \
+             if intended, rerun with CHIP8_BLESS=1\nactual:\n{}",
+            path.display(),
+            wrong.len(),
+            x,
+            y,
+            crate::testing::screenshot(fb)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::EmulatorMode;
+    use crate::testing::{headless, INSTRUCTIONS_PER_FRAME};
+
+    fn run(source: &str, max_cycles: u64) -> (CPU, Completion) {
+        let rom = assemble(source).unwrap();
+        let mut cpu = headless(&rom, EmulatorMode::Chip8).unwrap();
+        let completion = run_to_completion(&mut cpu, max_cycles, INSTRUCTIONS_PER_FRAME).unwrap();
+        (cpu, completion)
+    }
+
+    #[test]
+    fn programs_end_by_halting_waiting_or_jumping_in_place() {
+        let (cpu, completion) = run("LD V0, 1\n end: JP end", 1000);
+        assert_eq!(completion, Completion::SelfJump(0x202));
+        assert_eq!(cpu.registers[0], 1);
+
+        // 0000 past the end of the program
+        let (_, completion) = run("CLS", 1000);
+        assert_eq!(completion, Completion::Halted);
+        let (_, completion) = run("LD V0, K", 1000);
+        assert_eq!(completion, Completion::WaitingForKey);
+    }
+
+    #[test]
+    fn busy_loops_run_out_of_cycles() {
+        let (cpu, completion) = run("top: ADD V0, 1\n JP top", 95);
+        assert_eq!(completion, Completion::OutOfCycles);
+        assert!(!completion.is_finished());
+        assert_eq!(cpu.instructions(), 95);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn golden_pngs_catch_changed_pixels() {
+        let path = std::env::temp_dir().join("chip8_batch_golden.png");
+        let rom = assemble("LD F, V0\n DRW V0, V0, 5\n end: JP end").unwrap();
+        let mut cpu = headless(&rom, EmulatorMode::Chip8).unwrap();
+        run_to_completion(&mut cpu, 1000, INSTRUCTIONS_PER_FRAME).unwrap();
+
+        write_golden_png(&path, &cpu.display).unwrap();
+        let same = check_golden_png(&path, &cpu.display);
+        cpu.display.draw_sprite(20, 20, &[0x80]);
+        let changed = check_golden_png(&path, &cpu.display);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(same, Ok(()));
+        assert!(changed
+            .unwrap_err()
+            .contains("1 pixels differ, the first at (20, 20)"));
+    }
+}
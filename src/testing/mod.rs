@@ -0,0 +1,157 @@
+//! Headless harness for checking whole programs: load a ROM, run it for a
+//! number of frames with a fixed random seed, and compare the final screen
+//! against a golden screenshot kept next to the tests.
+//!
+//! Golden screenshots are plain text, one character per pixel, so a
+//! regression shows up as a readable diff in review. The first line holds
+//! the [`screen_hash`]. When a change in output is intended, rerun the
+//! tests with `CHIP8_BLESS=1` to rewrite the golden files.
+//!
+//! [`Scenario`]s script a run instead, pressing keys and checking
+//! registers and pixels at given frames. [`run_to_completion`] runs a ROM
+//! until it stops by itself, for conformance ROMs in CI, and with the
+//! `png` feature the golden screen can be a picture, as those ROMs'
+//! documentation shows it.
+
+mod batch;
+mod scenario;
+
+use std::path::Path;
+use std::{env, fs};
+
+use crate::cpu::{EmulatorMode, Fault, Status, XorShift, CPU};
+use crate::display::FrameBuffer;
+use crate::fingerprint::Fnv;
+use crate::metadata;
+
+#[cfg(feature = "png")]
+pub use batch::{check_golden_png, write_golden_png};
+pub use batch::{
+    run_to_completion, Completion, DEFAULT_MAX_CYCLES, EXIT_FAULTED, EXIT_MISMATCH, EXIT_PASSED,
+    EXIT_TIMED_OUT,
+};
+pub use scenario::{Probe, Scenario, ScenarioError, Step};
+
+/// Roughly 700 instructions per second, as in the desktop frontend.
+pub const INSTRUCTIONS_PER_FRAME: usize = 11;
+
+/// Seed for CXNN, so that every run of a test draws the same numbers.
+pub const SEED: u32 = 0x5EED;
+
+/// A CPU in `mode` with `rom` loaded and the random source seeded.
+pub fn headless(rom: &[u8], mode: EmulatorMode) -> Result<CPU, String> {
+    let mut cpu = CPU::new_with_mode(mode);
+    cpu.set_rng(Box::new(XorShift::new(SEED)));
+    cpu.load_rom(rom).map_err(|err| err.to_string())?;
+    Ok(cpu)
+}
+
+/// Like [`headless`], taking the mode, quirks and tick rate from the ROM's
+/// sidecar metadata if it has any. Returns the instructions per frame to
+/// run it at.
+pub fn headless_from_path(path: &Path) -> Result<(CPU, usize), String> {
+    let meta = metadata::load_sidecar(path).map_err(|err| err.to_string())?;
+    let rom = fs::read(path).map_err(|err| err.to_string())?;
+    let mode = meta.as_ref().and_then(|m| m.mode()).unwrap_or_default();
+    let mut cpu = headless(&rom, mode)?;
+    let mut instructions_per_frame = INSTRUCTIONS_PER_FRAME;
+    if let Some(meta) = &meta {
+        meta.options.apply_quirks(&mut cpu.quirks);
+        if let Some(tickrate) = meta.options.tickrate {
+            instructions_per_frame = tickrate as usize;
+        }
+    }
+    Ok((cpu, instructions_per_frame))
+}
+
+/// Runs `cpu` for `frames` frames, ticking the timers after each, and
+/// returns how many ran. Stops early once the program halts; a program
+/// waiting for a key keeps its timers running as on real hardware.
+pub fn run_frames(
+    cpu: &mut CPU,
+    frames: usize,
+    instructions_per_frame: usize,
+) -> Result<usize, Fault> {
+    for frame in 0..frames {
+        if cpu.run_frame(instructions_per_frame)? == Status::Halted {
+            return Ok(frame + 1);
+        }
+        cpu.tick_timers();
+    }
+    Ok(frames)
+}
+
+/// FNV-1a over the size and color of every pixel, stable across releases.
+pub fn screen_hash(fb: &FrameBuffer) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(&(fb.width() as u16).to_be_bytes());
+    hash.write(&(fb.height() as u16).to_be_bytes());
+    for y in 0..fb.height() {
+        for x in 0..fb.width() {
+            hash.write(&[fb.color(x, y)]);
+        }
+    }
+    hash.0
+}
+
+/// The screen as text for golden files, [`FrameBuffer::to_ascii`].
+pub fn screenshot(fb: &FrameBuffer) -> String {
+    fb.to_ascii()
+}
+
+/// Compares `fb` with the golden screenshot at `path`, or writes it there
+/// when `CHIP8_BLESS` is set. The error shows both screens.
+pub fn check_golden(path: &Path, fb: &FrameBuffer) -> Result<(), String> {
+    let actual = format!("{:016x}\n{}", screen_hash(fb), screenshot(fb));
+    if env::var_os("CHIP8_BLESS").is_some() {
+        return fs::write(path, &actual).map_err(|err| format!("{}: {}", path.display(), err));
+    }
+
+    let expected = fs::read_to_string(path).map_err(|err| {
+        format!(
+            "{}: {}; run with CHIP8_BLESS=1 to record it",
+            path.display(),
+            err
+        )
+    })?;
+    if expected == actual {
+        return Ok(());
+    }
+    Err(format!(
+        "{} does not match; if intended, rerun with CHIP8_BLESS=1\n\
+         expected:\n{}\nactual:\n{}",
+        path.display(),
+        expected,
+        actual
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_shows_every_plane() {
+        let mut cpu = headless(&[], EmulatorMode::XoChip).unwrap();
+        cpu.display.select_planes(0b01);
+        cpu.display.draw_sprite(0, 0, &[0xC0]);
+        cpu.display.select_planes(0b10);
+        cpu.display.draw_sprite(1, 0, &[0xC0]);
+
+        let text = screenshot(&cpu.display);
+
+        assert!(text.starts_with("#32...."));
+        assert_eq!(text.lines().count(), 32);
+        assert!(text.lines().all(|line| line.len() == 64));
+    }
+
+    #[test]
+    fn runs_stop_when_the_program_halts() {
+        // CLS; halt
+        let mut cpu = headless(&[0x00, 0xE0, 0x00, 0x00], EmulatorMode::Chip8).unwrap();
+        cpu.delay_timer = 10;
+
+        assert_eq!(run_frames(&mut cpu, 60, INSTRUCTIONS_PER_FRAME), Ok(1));
+        assert_eq!(cpu.delay_timer, 10);
+    }
+}
@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+use super::{headless, INSTRUCTIONS_PER_FRAME};
+use crate::asm::assemble;
+use crate::config::LineError;
+use crate::cpu::EmulatorMode;
+use crate::debugger::parse_number;
+
+/// A line of a scenario file that could not be understood.
+pub type ScenarioError = LineError;
+
+/// A value a scenario can check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    Register(u8),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    Memory(usize),
+    Pixel(usize, usize),
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Probe::Register(x) => write!(f, "V{:X}", x),
+            Probe::I => write!(f, "I"),
+            Probe::Pc => write!(f, "PC"),
+            Probe::DelayTimer => write!(f, "DT"),
+            Probe::SoundTimer => write!(f, "ST"),
+            Probe::Memory(address) => write!(f, "mem {:#05x}", address),
+            Probe::Pixel(x, y) => write!(f, "pixel {} {}", x, y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Press(u8),
+    Release(u8),
+    Expect(Probe, usize),
+}
+
+/// A scripted run of one program: key presses and expectations, each at
+/// the frame it happens on. Written in a line format meant to be easy for
+/// anyone to add to:
+///
+/// ```text
+/// # comments start with a hash
+/// rom ../roms/pong.ch8      # relative to the scenario file
+/// mode schip                # chip8 (default), schip or xo
+/// ipf 11                    # instructions per frame
+/// at 60 press 5             # keys are hex digits
+/// at 70 release 5
+/// at 120 expect V3 7        # also I, PC, DT, ST and mem ADDR
+/// at 120 expect pixel 10 12 on
+/// ```
+///
+/// Instead of `rom`, the program can be written inline between `program`
+/// and `end` in the syntax of [`crate::asm`]. `at N` means after N frames
+/// have run; presses apply from that frame on and expectations are checked
+/// before it runs. Numbers are decimal or `0x` hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub rom: Vec<u8>,
+    pub mode: EmulatorMode,
+    pub instructions_per_frame: usize,
+    /// Sorted by frame; steps on the same frame keep their file order.
+    pub steps: Vec<(usize, Step)>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let text = fs::read_to_string(path).map_err(|err| ScenarioError {
+            line: 0,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parses a scenario, resolving `rom` paths against `base`.
+    pub fn parse(text: &str, base: &Path) -> Result<Self, ScenarioError> {
+        let mut rom: Option<Vec<u8>> = None;
+        let mut mode = EmulatorMode::Chip8;
+        let mut instructions_per_frame = INSTRUCTIONS_PER_FRAME;
+        let mut steps = Vec::new();
+
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+        while let Some((number, raw)) = lines.next() {
+            let error = |message: String| ScenarioError {
+                line: number,
+                message,
+            };
+            let line = raw.split('#').next().unwrap_or_default().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["rom", path] => {
+                    let path: PathBuf = base.join(path);
+                    let bytes = fs::read(&path)
+                        .map_err(|err| error(format!("{}: {}", path.display(), err)))?;
+                    rom = Some(bytes);
+                }
+                ["program"] => {
+                    let mut source = String::new();
+                    loop {
+                        match lines.next() {
+                            Some((_, line)) if line.trim() == "end" => break,
+                            Some((_, line)) => {
+                                source.push_str(line);
+                                source.push('\n');
+                            }
+                            None => return Err(error("program has no end".to_string())),
+                        }
+                    }
+                    // assembler lines count from the line after `program`
+                    let bytes = assemble(&source).map_err(|err| ScenarioError {
+                        line: number + err.line,
+                        message: err.message,
+                    })?;
+                    rom = Some(bytes);
+                }
+                ["mode", name] => {
+                    mode = match *name {
+                        "chip8" => EmulatorMode::Chip8,
+                        "schip" => EmulatorMode::SuperChip,
+                        "xo" => EmulatorMode::XoChip,
+                        "mega" => EmulatorMode::MegaChip,
+                        _ => return Err(error(format!("unknown mode {}", name))),
+                    }
+                }
+                ["ipf", count] => instructions_per_frame = number_at(count).map_err(error)?,
+                ["at", frame, action @ ..] => {
+                    let frame = number_at(frame).map_err(error)?;
+                    let step = parse_step(action).map_err(error)?;
+                    steps.push((frame, step));
+                }
+                _ => return Err(error(format!("cannot understand {:?}", line))),
+            }
+        }
+
+        let rom = rom.ok_or_else(|| ScenarioError {
+            line: 0,
+            message: "no rom or program given".to_string(),
+        })?;
+        steps.sort_by_key(|(frame, _)| *frame);
+        Ok(Scenario {
+            rom,
+            mode,
+            instructions_per_frame,
+            steps,
+        })
+    }
+
+    /// Plays the scenario. The error lists every expectation that failed,
+    /// or the fault that stopped the program.
+    pub fn run(&self) -> Result<(), String> {
+        let mut cpu = headless(&self.rom, self.mode)?;
+        let mut failures = Vec::new();
+        let mut frame = 0;
+        for (at, step) in &self.steps {
+            while frame < *at {
+                cpu.run_frame(self.instructions_per_frame)
+                    .map_err(|fault| format!("frame {}: {}", frame, fault))?;
+                cpu.tick_timers();
+                frame += 1;
+            }
+            match *step {
+                Step::Press(key) => cpu.set_key(key, true),
+                Step::Release(key) => cpu.set_key(key, false),
+                Step::Expect(probe, expected) => {
+                    let actual = match probe {
+                        Probe::Register(x) => cpu.registers[x as usize] as usize,
+                        Probe::I => cpu.i as usize,
+                        Probe::Pc => cpu.memory_position,
+                        Probe::DelayTimer => cpu.delay_timer as usize,
+                        Probe::SoundTimer => cpu.sound_timer as usize,
+                        Probe::Memory(address) => {
+                            cpu.memory.get(address).map_or(usize::MAX, |b| *b as usize)
+                        }
+                        Probe::Pixel(x, y) => cpu.display.get(x, y) as usize,
+                    };
+                    if actual != expected {
+                        failures.push(format!(
+                            "frame {}: expected {} to be {:#x}, got {:#x}",
+                            frame, probe, expected, actual
+                        ));
+                    }
+                }
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
+    }
+}
+
+/// A number as the debugger reads it, hex with `0x` or decimal.
+fn number_at(text: &str) -> Result<usize, String> {
+    parse_number(text).map_err(|err| err.0)
+}
+
+fn key_at(text: &str) -> Result<u8, String> {
+    match u8::from_str_radix(text, 16) {
+        Ok(key) if key < 16 && text.len() == 1 => Ok(key),
+        _ => Err(format!("not a key: {}", text)),
+    }
+}
+
+fn parse_step(words: &[&str]) -> Result<Step, String> {
+    let probe = |words: &[&str]| -> Result<(Probe, usize), String> {
+        match words {
+            ["pixel", x, y, state] => {
+                let lit = match *state {
+                    "on" => 1,
+                    "off" => 0,
+                    _ => return Err(format!("pixels are on or off, not {}", state)),
+                };
+                Ok((Probe::Pixel(number_at(x)?, number_at(y)?), lit))
+            }
+            ["mem", address, value] => Ok((Probe::Memory(number_at(address)?), number_at(value)?)),
+            [name, value] => {
+                let probe = match name.to_ascii_uppercase().as_str() {
+                    "I" => Probe::I,
+                    "PC" => Probe::Pc,
+                    "DT" => Probe::DelayTimer,
+                    "ST" => Probe::SoundTimer,
+                    register => match register.strip_prefix('V').map(key_at) {
+                        Some(Ok(x)) => Probe::Register(x),
+                        _ => return Err(format!("nothing called {} to check", name)),
+                    },
+                };
+                Ok((probe, number_at(value)?))
+            }
+            _ => Err(format!("cannot check {:?}", words.join(" "))),
+        }
+    };
+    match words {
+        ["press", key] => Ok(Step::Press(key_at(key)?)),
+        ["release", key] => Ok(Step::Release(key_at(key)?)),
+        ["expect", rest @ ..] => {
+            let (probe, value) = probe(rest)?;
+            Ok(Step::Expect(probe, value))
+        }
+        _ => Err(format!("unknown step {:?}", words.join(" "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = "
+        # wait for a key and show it
+        program
+            LD V0, K
+            LD F, V0
+            DRW V1, V1, 5
+            LD V3, 7
+        halt:
+            JP halt
+        end
+        at 10 expect V3 0
+        at 10 press a
+        at 12 release A
+        at 12 expect V0 0xA
+        at 12 expect v3 7
+        at 12 expect pixel 0 0 on
+        at 12 expect pixel 4 0 off
+    ";
+
+    #[test]
+    fn scenario_presses_keys_and_checks_state() {
+        let scenario = Scenario::parse(SCENARIO, Path::new(".")).unwrap();
+        assert_eq!(scenario.steps.len(), 7);
+
+        assert_eq!(scenario.run(), Ok(()));
+    }
+
+    #[test]
+    fn failures_name_the_frame_and_value() {
+        let text = SCENARIO.replace("expect v3 7", "expect v3 8");
+        let scenario = Scenario::parse(&text, Path::new(".")).unwrap();
+
+        assert_eq!(
+            scenario.run(),
+            Err("frame 12: expected V3 to be 0x8, got 0x7".to_string())
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let bad = |text: &str| Scenario::parse(text, Path::new(".")).unwrap_err().line;
+
+        assert_eq!(bad("program\nLD V0, 1\nend\nat 5 pres 1"), 4);
+        assert_eq!(bad("program\nLD V0, 1\nFROB\nend"), 3);
+        assert_eq!(bad("at 1 expect V3 1"), 0);
+    }
+}
@@ -0,0 +1,5 @@
+//! Helpers for people authoring CHIP-8 programs rather than running them.
+
+pub mod sound_composer;
+pub mod sprite_import;
+pub mod sprites;
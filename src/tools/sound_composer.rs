@@ -0,0 +1,272 @@
+//! Turns simple note lists into XO-CHIP audio data for the assembler.
+//!
+//! XO-CHIP plays a 128-bit pattern buffer at `4000 * 2^((pitch - 64) / 48)`
+//! bits per second. A note is a square wave pattern whose period, together
+//! with the pitch register, gives the wanted frequency.
+//!
+//! Scores are whitespace separated `<note>:<frames>` pairs, where a note is
+//! a name with octave (`C4`, `F#3`, `Bb5`) or `R` for a rest, and frames are
+//! 60 Hz sound timer ticks: `"C4:15 E4:15 G4:30 R:10"`.
+
+use std::{error, fmt, fmt::Write};
+
+/// Bits in the XO-CHIP audio pattern buffer.
+pub const PATTERN_BITS: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pitch {
+    Rest,
+    /// MIDI note number (60 is middle C).
+    Midi(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub pitch: Pitch,
+    /// Duration in 60 Hz frames, the value to load into the sound timer.
+    pub frames: u8,
+}
+
+/// What XO-CHIP needs to play one note: a pitch register value and the
+/// 16-byte pattern buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tone {
+    pub pitch: u8,
+    pub pattern: [u8; PATTERN_BITS / 8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposeError {
+    /// A token is not `<note>:<frames>`.
+    Syntax(String),
+    UnknownNote(String),
+    /// The frame count is zero or does not fit the sound timer.
+    BadDuration(String),
+    /// No pattern period can reach the frequency.
+    OutOfRange(String),
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComposeError::Syntax(token) => write!(f, "expected <note>:<frames>, got {:?}", token),
+            ComposeError::UnknownNote(note) => write!(f, "unknown note {:?}", note),
+            ComposeError::BadDuration(token) => write!(f, "bad duration in {:?}", token),
+            ComposeError::OutOfRange(note) => write!(f, "{} cannot be played", note),
+        }
+    }
+}
+
+impl error::Error for ComposeError {}
+
+pub fn parse_score(score: &str) -> Result<Vec<Note>, ComposeError> {
+    score
+        .split_whitespace()
+        .map(|token| {
+            let (name, frames) = token
+                .split_once(':')
+                .ok_or_else(|| ComposeError::Syntax(token.to_string()))?;
+            let frames = match frames.parse::<u8>() {
+                Ok(frames) if frames > 0 => frames,
+                _ => return Err(ComposeError::BadDuration(token.to_string())),
+            };
+            Ok(Note {
+                pitch: parse_pitch(name)?,
+                frames,
+            })
+        })
+        .collect()
+}
+
+fn parse_pitch(name: &str) -> Result<Pitch, ComposeError> {
+    if name.eq_ignore_ascii_case("r") {
+        return Ok(Pitch::Rest);
+    }
+    let unknown = || ComposeError::UnknownNote(name.to_string());
+
+    let mut chars = name.chars();
+    let semitone: i32 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => return Err(unknown()),
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+    // MIDI notes only span octaves -1 to 9
+    let octave: i32 = octave
+        .parse()
+        .ok()
+        .filter(|octave| (-1..=9).contains(octave))
+        .ok_or_else(unknown)?;
+
+    let midi = 12 * (octave + 1) + semitone + accidental;
+    u8::try_from(midi)
+        .ok()
+        .filter(|m| *m <= 127)
+        .map(Pitch::Midi)
+        .ok_or_else(unknown)
+}
+
+pub fn frequency(midi: u8) -> f64 {
+    440.0 * 2f64.powf((midi as f64 - 69.0) / 12.0)
+}
+
+/// Pattern periods tried, in order of preference. Eight bits per cycle keeps
+/// the pitch register near its default of 64 for mid-range notes.
+const PERIODS: [usize; 7] = [8, 16, 32, 64, 128, 4, 2];
+
+/// Finds a pattern and pitch register value playing `freq` Hz.
+pub fn tone_for_frequency(freq: f64) -> Option<Tone> {
+    PERIODS.iter().find_map(|&period| {
+        let rate = freq * period as f64;
+        let pitch = (64.0 + 48.0 * (rate / 4000.0).log2()).round();
+        if !(0.0..=255.0).contains(&pitch) {
+            return None;
+        }
+
+        let mut pattern = [0u8; PATTERN_BITS / 8];
+        for bit in 0..PATTERN_BITS {
+            if bit % period < period / 2 {
+                pattern[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+        Some(Tone {
+            pitch: pitch as u8,
+            pattern,
+        })
+    })
+}
+
+/// Emits one 18-byte record per note under `name`: pitch register value,
+/// sound timer frames, then the 16-byte pattern. Rests use a silent pattern.
+pub fn to_assembly(name: &str, notes: &[Note]) -> Result<String, ComposeError> {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "; {} notes, 18 bytes each: pitch, frames, pattern",
+        notes.len()
+    );
+    let _ = writeln!(out, "{}:", name);
+
+    for note in notes {
+        let (label, tone) = match note.pitch {
+            Pitch::Rest => (
+                "rest".to_string(),
+                Tone {
+                    pitch: 64,
+                    pattern: [0; PATTERN_BITS / 8],
+                },
+            ),
+            Pitch::Midi(midi) => {
+                let label = format!("midi {} ({:.1} Hz)", midi, frequency(midi));
+                let tone = tone_for_frequency(frequency(midi))
+                    .ok_or_else(|| ComposeError::OutOfRange(label.clone()))?;
+                (label, tone)
+            }
+        };
+        let pattern: Vec<String> = tone
+            .pattern
+            .iter()
+            .map(|b| format!("0x{:02X}", b))
+            .collect();
+        let _ = writeln!(out, "    ; {}, {} frames", label, note.frames);
+        let _ = writeln!(out, "    db {}, {}", tone.pitch, note.frames);
+        let _ = writeln!(out, "    db {}", pattern.join(", "));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playback_frequency(tone: &Tone, period: usize) -> f64 {
+        4000.0 * 2f64.powf((tone.pitch as f64 - 64.0) / 48.0) / period as f64
+    }
+
+    #[test]
+    fn parse_notes_and_rests() {
+        let notes = parse_score("C4:15 F#3:2  Bb5:30 R:10").unwrap();
+
+        assert_eq!(
+            notes,
+            vec![
+                Note {
+                    pitch: Pitch::Midi(60),
+                    frames: 15
+                },
+                Note {
+                    pitch: Pitch::Midi(54),
+                    frames: 2
+                },
+                Note {
+                    pitch: Pitch::Midi(82),
+                    frames: 30
+                },
+                Note {
+                    pitch: Pitch::Rest,
+                    frames: 10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_score("C4"), Err(ComposeError::Syntax("C4".into())));
+        assert_eq!(
+            parse_score("H4:1"),
+            Err(ComposeError::UnknownNote("H4".into()))
+        );
+        assert_eq!(
+            parse_score("C2147483647:1"),
+            Err(ComposeError::UnknownNote("C2147483647".into()))
+        );
+        assert_eq!(
+            parse_score("C4:0"),
+            Err(ComposeError::BadDuration("C4:0".into()))
+        );
+        assert_eq!(
+            parse_score("C4:300"),
+            Err(ComposeError::BadDuration("C4:300".into()))
+        );
+    }
+
+    #[test]
+    fn a4_plays_close_to_440_hz() {
+        let tone = tone_for_frequency(440.0).unwrap();
+
+        assert_eq!(tone.pattern[0], 0xF0);
+        let played = playback_frequency(&tone, 8);
+        // pitch resolution is a quarter semitone
+        assert!((played / 440.0).log2().abs() < 1.0 / 96.0, "{}", played);
+    }
+
+    #[test]
+    fn low_notes_use_longer_periods() {
+        let tone = tone_for_frequency(frequency(24)).unwrap();
+
+        assert_ne!(tone.pattern[0], 0xF0);
+    }
+
+    #[test]
+    fn assembly_has_one_record_per_note() {
+        let notes = parse_score("A4:4 R:2").unwrap();
+
+        let asm = to_assembly("tune", &notes).unwrap();
+
+        assert!(asm.contains("tune:\n"));
+        assert!(asm.contains("    db 64, 2\n"));
+        assert_eq!(asm.matches("    db ").count(), 4);
+    }
+}
@@ -0,0 +1,255 @@
+//! Converts monochrome images into sprite data for the assembler.
+//!
+//! Images are cut into chunks DXYN can draw directly: 8 pixels wide and at
+//! most 15 rows tall. Each chunk becomes a label followed by `db` lines.
+
+use std::fmt::Write;
+
+/// Widest sprite DXYN draws.
+pub const SPRITE_WIDTH: usize = 8;
+/// Tallest sprite DXYN draws (N is a nibble).
+pub const MAX_SPRITE_HEIGHT: usize = 15;
+
+/// A 1-bit image, row-major, `true` meaning lit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize, pixels: Vec<bool>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count mismatch");
+        Bitmap {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+}
+
+/// One drawable chunk of a larger image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    pub label: String,
+    /// Position of the chunk's top-left pixel in the source image.
+    pub x: usize,
+    pub y: usize,
+    /// One byte per row, most significant bit leftmost.
+    pub rows: Vec<u8>,
+}
+
+/// Cuts `bitmap` into 8xN sprites, left to right then top to bottom.
+///
+/// A bitmap that fits in a single sprite keeps `name` as its label, larger
+/// ones get `name_<row>_<col>` labels. Columns past the right edge of the
+/// image are left unlit.
+pub fn split_into_sprites(bitmap: &Bitmap, name: &str) -> Vec<Sprite> {
+    let cols = bitmap.width.div_ceil(SPRITE_WIDTH);
+    let rows = bitmap.height.div_ceil(MAX_SPRITE_HEIGHT);
+    let single = cols * rows == 1;
+    let mut sprites = Vec::with_capacity(cols * rows);
+
+    for row in 0..rows {
+        let y = row * MAX_SPRITE_HEIGHT;
+        let height = MAX_SPRITE_HEIGHT.min(bitmap.height - y);
+        for col in 0..cols {
+            let x = col * SPRITE_WIDTH;
+            let data = (y..y + height)
+                .map(|py| {
+                    (0..SPRITE_WIDTH).fold(0u8, |byte, bit| {
+                        byte | ((bitmap.get(x + bit, py) as u8) << (7 - bit))
+                    })
+                })
+                .collect();
+            let label = if single {
+                name.to_string()
+            } else {
+                format!("{}_{}_{}", name, row, col)
+            };
+            sprites.push(Sprite {
+                label,
+                x,
+                y,
+                rows: data,
+            });
+        }
+    }
+
+    sprites
+}
+
+/// Renders sprites as assembler source, one binary `db` per row so the
+/// shape stays visible in the listing.
+pub fn to_assembly(sprites: &[Sprite]) -> String {
+    let mut out = String::new();
+    for sprite in sprites {
+        let _ = writeln!(
+            out,
+            "; {}x{} at ({}, {})",
+            SPRITE_WIDTH,
+            sprite.rows.len(),
+            sprite.x,
+            sprite.y
+        );
+        let _ = writeln!(out, "{}:", sprite.label);
+        for row in &sprite.rows {
+            let _ = writeln!(out, "    db 0b{:08b}", row);
+        }
+    }
+    out
+}
+
+#[cfg(feature = "png")]
+mod png_import {
+    use std::{error, fmt, io::Cursor};
+
+    use super::Bitmap;
+
+    #[derive(Debug)]
+    pub enum ImportError {
+        Decode(png::DecodingError),
+        UnsupportedFormat(png::ColorType, png::BitDepth),
+    }
+
+    impl fmt::Display for ImportError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ImportError::Decode(err) => write!(f, "could not decode PNG: {}", err),
+                ImportError::UnsupportedFormat(color, depth) => {
+                    write!(f, "unsupported PNG format {:?} {:?}", color, depth)
+                }
+            }
+        }
+    }
+
+    impl error::Error for ImportError {}
+
+    impl From<png::DecodingError> for ImportError {
+        fn from(err: png::DecodingError) -> Self {
+            ImportError::Decode(err)
+        }
+    }
+
+    /// Decodes a PNG, treating opaque pixels brighter than 50% as lit.
+    /// With `invert`, dark pixels are lit instead (black-on-white art).
+    pub fn bitmap_from_png(bytes: &[u8], invert: bool) -> Result<Bitmap, ImportError> {
+        let mut decoder = png::Decoder::new(Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let info = reader.next_frame(&mut buf)?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            other => return Err(ImportError::UnsupportedFormat(other, info.bit_depth)),
+        };
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(ImportError::UnsupportedFormat(
+                info.color_type,
+                info.bit_depth,
+            ));
+        }
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let line = &buf[y * info.line_size..][..width * channels];
+            for px in line.chunks_exact(channels) {
+                let luma = match channels {
+                    1 | 2 => px[0] as u32,
+                    _ => (px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000,
+                };
+                let opaque = match channels {
+                    2 => px[1] >= 0x80,
+                    4 => px[3] >= 0x80,
+                    _ => true,
+                };
+                pixels.push(opaque && ((luma >= 0x80) != invert));
+            }
+        }
+
+        Ok(Bitmap::new(width, height, pixels))
+    }
+}
+
+#[cfg(feature = "png")]
+pub use png_import::{bitmap_from_png, ImportError};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(rows: &[&str]) -> Bitmap {
+        let width = rows[0].len();
+        let pixels = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        Bitmap::new(width, rows.len(), pixels)
+    }
+
+    #[test]
+    fn small_image_is_a_single_sprite() {
+        let bmp = bitmap(&["#..#", ".##.", "#..#"]);
+
+        let sprites = split_into_sprites(&bmp, "cross");
+
+        assert_eq!(sprites.len(), 1);
+        assert_eq!(sprites[0].label, "cross");
+        assert_eq!(sprites[0].rows, vec![0b1001_0000, 0b0110_0000, 0b1001_0000]);
+    }
+
+    #[test]
+    fn wide_and_tall_images_are_split() {
+        let bmp = Bitmap::new(12, 20, vec![true; 12 * 20]);
+
+        let sprites = split_into_sprites(&bmp, "big");
+
+        let labels: Vec<_> = sprites.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, ["big_0_0", "big_0_1", "big_1_0", "big_1_1"]);
+        assert_eq!(sprites[0].rows.len(), MAX_SPRITE_HEIGHT);
+        assert_eq!(sprites[2].rows.len(), 5);
+        assert_eq!(sprites[1].rows[0], 0b1111_0000);
+        assert_eq!((sprites[3].x, sprites[3].y), (8, 15));
+    }
+
+    #[test]
+    fn assembly_lists_one_row_per_line() {
+        let bmp = bitmap(&["##", "#."]);
+
+        let asm = to_assembly(&split_into_sprites(&bmp, "corner"));
+
+        assert_eq!(
+            asm,
+            "; 8x2 at (0, 0)\ncorner:\n    db 0b11000000\n    db 0b10000000\n"
+        );
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_pixels_become_lit_bits() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 3, 1);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0xFF, 0x00, 0xC0]).unwrap();
+        }
+
+        let bmp = bitmap_from_png(&bytes, false).unwrap();
+        assert_eq!(bmp.pixels, vec![true, false, true]);
+
+        let inverted = bitmap_from_png(&bytes, true).unwrap();
+        assert_eq!(inverted.pixels, vec![false, true, false]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Finds the sprites in a ROM or a stretch of memory and shows them as
+//! ASCII art or a [`Bitmap`], to tell bad sprite data from a bad draw
+//! routine when a game shows garbage.
+//!
+//! ```text
+//! 0x2a0: 8x5
+//! ####....
+//! #..#....
+//! ```
+
+use std::collections::BTreeSet;
+
+use super::sprite_import::{Bitmap, SPRITE_WIDTH};
+use crate::cpu::PROGRAM_START;
+use crate::disasm::{disassemble_rom, Instruction};
+
+/// Width and height of the SCHIP sprites DXY0 draws.
+pub const LARGE_SPRITE_SIZE: usize = 16;
+
+/// A run of bytes that is, or might be, drawn as a sprite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteCandidate {
+    pub address: usize,
+    /// 8, or 16 for a DXY0 sprite, whose rows take two bytes each.
+    pub width: usize,
+    pub data: Vec<u8>,
+}
+
+impl SpriteCandidate {
+    pub fn height(&self) -> usize {
+        self.data.len() / (self.width / 8)
+    }
+
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        let bytes_per_row = self.width / 8;
+        let byte = self.data[y * bytes_per_row + x / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// An `address: WxH` line, then one line per row with `#` for lit
+    /// pixels and `.` for the rest.
+    pub fn to_ascii(&self) -> String {
+        let mut out = format!("{:#05x}: {}x{}\n", self.address, self.width, self.height());
+        for y in 0..self.height() {
+            out.extend((0..self.width).map(|x| if self.is_lit(x, y) { '#' } else { '.' }));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_bitmap(&self) -> Bitmap {
+        let (width, height) = (self.width, self.height());
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.is_lit(x, y))
+            .collect();
+        Bitmap::new(width, height, pixels)
+    }
+}
+
+/// The sprites `rom` draws, going by `LD I, NNN` followed by DXYN in the
+/// listing, in address order. A guess: code reached through computed
+/// jumps, or I changed by `ADD I` in between, is missed, and only data
+/// inside the ROM itself is found.
+pub fn find_sprites(rom: &[u8]) -> Vec<SpriteCandidate> {
+    let mut found = BTreeSet::new();
+    let mut i = None;
+    for (_, instruction, _) in disassemble_rom(rom) {
+        match instruction {
+            Instruction::LdI(address) => i = Some(address as usize),
+            Instruction::LdILong(address) => i = Some(address as usize),
+            Instruction::LdIHuge(address) => i = Some(address as usize),
+            Instruction::AddI(_) | Instruction::LdFont(_) | Instruction::LdBigFont(_) => i = None,
+            Instruction::Drw { n, .. } => {
+                if let Some(address) = i {
+                    found.insert((address, n));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found
+        .into_iter()
+        .filter_map(|(address, n)| {
+            let (width, len) = match n {
+                0 => (LARGE_SPRITE_SIZE, 2 * LARGE_SPRITE_SIZE),
+                n => (SPRITE_WIDTH, n as usize),
+            };
+            let start = address.checked_sub(PROGRAM_START)?;
+            let data = rom.get(start..start + len)?;
+            Some(SpriteCandidate {
+                address,
+                width,
+                data: data.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// `memory[start..end]` cut into 8-pixel-wide sprites `height` rows tall,
+/// e.g. to look at the font or at data a program built at run time. A
+/// short last sprite is kept.
+pub fn memory_sprites(
+    memory: &[u8],
+    start: usize,
+    end: usize,
+    height: usize,
+) -> Vec<SpriteCandidate> {
+    let end = end.min(memory.len());
+    let start = start.min(end);
+    memory[start..end]
+        .chunks(height.max(1))
+        .enumerate()
+        .map(|(index, data)| SpriteCandidate {
+            address: start + index * height.max(1),
+            width: SPRITE_WIDTH,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// All of `sprites` side by side with a column of space between them, tops
+/// aligned, to save as one small image.
+pub fn sheet(sprites: &[SpriteCandidate]) -> Bitmap {
+    let width = sprites.iter().map(|sprite| sprite.width + 1).sum::<usize>();
+    let width = width.saturating_sub(1);
+    let height = sprites
+        .iter()
+        .map(SpriteCandidate::height)
+        .max()
+        .unwrap_or(0);
+    let mut pixels = vec![false; width * height];
+    let mut left = 0;
+    for sprite in sprites {
+        for y in 0..sprite.height() {
+            for x in 0..sprite.width {
+                pixels[y * width + left + x] = sprite.is_lit(x, y);
+            }
+        }
+        left += sprite.width + 1;
+    }
+    Bitmap::new(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{FONT_ADDRESS, FONT_SET, GLYPH_SIZE};
+
+    #[test]
+    fn sprites_are_found_where_the_code_draws_them() {
+        let program = "
+            LD I, box
+            DRW V0, V0, 2
+            LD F, V0
+            DRW V0, V0, 5
+            LD I, box
+            DRW V1, V1, 2
+            end:
+            JP end
+            box:
+            db 0b11110000
+            db 0b10010000
+        ";
+        let rom = crate::asm::assemble(program).unwrap();
+
+        let sprites = find_sprites(&rom);
+        assert_eq!(sprites.len(), 1);
+        assert_eq!(sprites[0].address, 0x20E);
+        assert_eq!(sprites[0].to_ascii(), "0x20e: 8x2\n####....\n#..#....\n");
+    }
+
+    #[test]
+    fn memory_regions_are_cut_into_sprites() {
+        let mut memory = vec![0; 0x200];
+        memory[FONT_ADDRESS..FONT_ADDRESS + FONT_SET.len()].copy_from_slice(&FONT_SET);
+
+        let glyphs = memory_sprites(&memory, FONT_ADDRESS, FONT_ADDRESS + 2 * GLYPH_SIZE, 5);
+        assert_eq!(glyphs.len(), 2);
+        assert_eq!(glyphs[1].address, FONT_ADDRESS + GLYPH_SIZE);
+        assert!(glyphs[1].to_ascii().ends_with("..#.....\n.###....\n"));
+
+        let image = sheet(&glyphs);
+        assert_eq!((image.width, image.height), (17, 5));
+        assert!(image.get(0, 0) && image.get(11, 0) && !image.get(8, 0));
+    }
+}
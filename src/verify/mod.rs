@@ -0,0 +1,491 @@
+//! Differential execution: a CPU runs in lockstep with a reference, either
+//! another CPU (say, configured the way a known-good release was) or a
+//! trace of states recorded from one, and the first instruction after
+//! which the two disagree is reported with everything that differs.
+//!
+//! Traces are JSON, one [`MachineState`] per line, as written by
+//! [`record_trace`]. Recording one from a release and checking it in makes
+//! a regression test for refactors of the interpreter.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Status, CPU};
+use crate::disasm::disassemble;
+
+/// What is compared after every instruction. Memory is compared by
+/// checksum, which is all a trace holds of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineState {
+    pub pc: usize,
+    pub i: u16,
+    pub registers: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// See [`memory_checksum`].
+    pub memory: u64,
+    /// Why the instruction faulted, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault: Option<String>,
+}
+
+impl MachineState {
+    pub fn of(cpu: &CPU) -> Self {
+        MachineState {
+            pc: cpu.memory_position,
+            i: cpu.i,
+            registers: cpu.registers,
+            stack: cpu.stack().to_vec(),
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            memory: memory_checksum(&cpu.memory),
+            fault: None,
+        }
+    }
+}
+
+/// 64-bit FNV-1a of `memory`, [`crate::fingerprint::rom_hash`], cheap
+/// enough to take after every instruction.
+pub fn memory_checksum(memory: &[u8]) -> u64 {
+    crate::fingerprint::rom_hash(memory)
+}
+
+/// What a CPU is checked against.
+pub trait Reference {
+    /// Runs one instruction and returns the state after it, or `None` once
+    /// there is nothing more to compare against.
+    fn advance(&mut self) -> Option<MachineState>;
+
+    /// Called once per 60 Hz frame, `instructions_per_frame` instructions
+    /// apart, after the state of the frame's last instruction was taken.
+    /// Traces already hold the timers they recorded.
+    fn tick_timers(&mut self) {}
+
+    /// The whole memory, for pointing at the first byte that differs.
+    fn memory(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl Reference for CPU {
+    fn advance(&mut self) -> Option<MachineState> {
+        let result = self.step();
+        let mut state = MachineState::of(self);
+        state.fault = result.err().map(|fault| fault.error.to_string());
+        Some(state)
+    }
+
+    fn tick_timers(&mut self) {
+        CPU::tick_timers(self);
+    }
+
+    fn memory(&self) -> Option<&[u8]> {
+        Some(&self.memory)
+    }
+}
+
+/// A recorded run, read one state per [`Reference::advance`].
+pub struct TraceReference<R> {
+    lines: io::Lines<R>,
+    /// Line number of the last state read.
+    line: usize,
+    error: Option<String>,
+}
+
+impl<R: BufRead> TraceReference<R> {
+    pub fn new(reader: R) -> Self {
+        TraceReference {
+            lines: reader.lines(),
+            line: 0,
+            error: None,
+        }
+    }
+
+    /// Why the trace ended early, if a line didn't read or parse.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl<R: BufRead> Reference for TraceReference<R> {
+    fn advance(&mut self) -> Option<MachineState> {
+        if self.error.is_some() {
+            return None;
+        }
+        let line = self.lines.next()?;
+        self.line += 1;
+        let parsed = line
+            .map_err(|err| err.to_string())
+            .and_then(|line| serde_json::from_str(&line).map_err(|err| err.to_string()));
+        match parsed {
+            Ok(state) => Some(state),
+            Err(err) => {
+                self.error = Some(format!("line {}: {}", self.line, err));
+                None
+            }
+        }
+    }
+}
+
+/// Writes the state after each of up to `steps` instructions of `cpu`,
+/// ticking the timers every `instructions_per_frame`, until it faults,
+/// halts or waits for a key. Returns the number of states written.
+pub fn record_trace(
+    cpu: &mut CPU,
+    steps: u64,
+    instructions_per_frame: usize,
+    mut out: impl Write,
+) -> io::Result<u64> {
+    let mut lockstep = Lockstep::new(instructions_per_frame);
+    for written in 0..steps {
+        let (state, status) = lockstep.step(cpu);
+        serde_json::to_writer(&mut out, &state)?;
+        writeln!(out)?;
+        if state.fault.is_some() || status != Some(Status::Continue) {
+            return Ok(written + 1);
+        }
+    }
+    Ok(steps)
+}
+
+/// One way two machines disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Pc {
+        ours: usize,
+        reference: usize,
+    },
+    I {
+        ours: u16,
+        reference: u16,
+    },
+    Register {
+        index: usize,
+        ours: u8,
+        reference: u8,
+    },
+    Stack {
+        ours: Vec<u16>,
+        reference: Vec<u16>,
+    },
+    DelayTimer {
+        ours: u8,
+        reference: u8,
+    },
+    SoundTimer {
+        ours: u8,
+        reference: u8,
+    },
+    /// `address` is the first byte that differs, when the reference has
+    /// its memory at hand.
+    Memory {
+        address: Option<usize>,
+    },
+    Fault {
+        ours: Option<String>,
+        reference: Option<String>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Pc { ours, reference } => {
+                write!(f, "PC: {:#05x}, reference {:#05x}", ours, reference)
+            }
+            Difference::I { ours, reference } => {
+                write!(f, "I: {:#05x}, reference {:#05x}", ours, reference)
+            }
+            Difference::Register {
+                index,
+                ours,
+                reference,
+            } => write!(
+                f,
+                "V{:X}: {:#04x}, reference {:#04x}",
+                index, ours, reference
+            ),
+            Difference::Stack { ours, reference } => {
+                write!(f, "stack: {:03x?}, reference {:03x?}", ours, reference)
+            }
+            Difference::DelayTimer { ours, reference } => {
+                write!(f, "delay timer: {}, reference {}", ours, reference)
+            }
+            Difference::SoundTimer { ours, reference } => {
+                write!(f, "sound timer: {}, reference {}", ours, reference)
+            }
+            Difference::Memory { address: Some(at) } => write!(f, "memory from {:#05x}", at),
+            Difference::Memory { address: None } => write!(f, "memory"),
+            Difference::Fault { ours, reference } => write!(
+                f,
+                "fault: {}, reference {}",
+                ours.as_deref().unwrap_or("none"),
+                reference.as_deref().unwrap_or("none")
+            ),
+        }
+    }
+}
+
+/// The first instruction after which the CPU and its reference disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions both ran before this one.
+    pub step: u64,
+    /// Address and opcode of the instruction that diverged.
+    pub pc: usize,
+    pub opcode: Option<u16>,
+    pub differences: Vec<Difference>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "diverged after {} instructions, at {:#05x}",
+            self.step, self.pc
+        )?;
+        if let Some(opcode) = self.opcode {
+            write!(f, ": {:04X}  {}", opcode, disassemble(opcode))?;
+        }
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `cpu` and `reference` side by side for up to `steps` instructions.
+/// Returns how many they agreed on, stopping early when the reference has
+/// no more or `cpu` faults, halts or waits for a key, or the first
+/// divergence.
+pub fn verify(
+    cpu: &mut CPU,
+    reference: &mut dyn Reference,
+    steps: u64,
+    instructions_per_frame: usize,
+) -> Result<u64, Divergence> {
+    let mut lockstep = Lockstep::new(instructions_per_frame);
+    for step in 0..steps {
+        let pc = cpu.memory_position;
+        let opcode = cpu
+            .memory
+            .get(pc..pc.saturating_add(2))
+            .map(|word| u16::from_be_bytes([word[0], word[1]]));
+        let Some(theirs) = reference.advance() else {
+            return Ok(step);
+        };
+        let (ours, status) = lockstep.step(cpu);
+        if lockstep.ticked {
+            reference.tick_timers();
+        }
+        let differences = compare(&ours, &theirs, &cpu.memory, reference.memory());
+        if !differences.is_empty() {
+            return Err(Divergence {
+                step,
+                pc,
+                opcode,
+                differences,
+            });
+        }
+        if ours.fault.is_some() || status != Some(Status::Continue) {
+            return Ok(step + 1);
+        }
+    }
+    Ok(steps)
+}
+
+/// Steps a CPU and ticks its timers once a frame.
+struct Lockstep {
+    instructions_per_frame: u64,
+    executed: u64,
+    /// Whether the last step ended a frame.
+    ticked: bool,
+}
+
+impl Lockstep {
+    fn new(instructions_per_frame: usize) -> Self {
+        Lockstep {
+            instructions_per_frame: instructions_per_frame.max(1) as u64,
+            executed: 0,
+            ticked: false,
+        }
+    }
+
+    /// The state after one instruction, before the timers tick if that
+    /// ended a frame, and its status unless it faulted.
+    fn step(&mut self, cpu: &mut CPU) -> (MachineState, Option<Status>) {
+        let result = cpu.step();
+        let mut state = MachineState::of(cpu);
+        let status = match result {
+            Ok(status) => Some(status),
+            Err(fault) => {
+                state.fault = Some(fault.error.to_string());
+                None
+            }
+        };
+        self.executed += 1;
+        self.ticked = self.executed.is_multiple_of(self.instructions_per_frame);
+        if self.ticked {
+            cpu.tick_timers();
+        }
+        (state, status)
+    }
+}
+
+fn compare(
+    ours: &MachineState,
+    theirs: &MachineState,
+    memory: &[u8],
+    reference_memory: Option<&[u8]>,
+) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    if ours.pc != theirs.pc {
+        differences.push(Difference::Pc {
+            ours: ours.pc,
+            reference: theirs.pc,
+        });
+    }
+    if ours.i != theirs.i {
+        differences.push(Difference::I {
+            ours: ours.i,
+            reference: theirs.i,
+        });
+    }
+    for (index, (ours, reference)) in ours.registers.iter().zip(theirs.registers).enumerate() {
+        if *ours != reference {
+            differences.push(Difference::Register {
+                index,
+                ours: *ours,
+                reference,
+            });
+        }
+    }
+    if ours.stack != theirs.stack {
+        differences.push(Difference::Stack {
+            ours: ours.stack.clone(),
+            reference: theirs.stack.clone(),
+        });
+    }
+    if ours.delay_timer != theirs.delay_timer {
+        differences.push(Difference::DelayTimer {
+            ours: ours.delay_timer,
+            reference: theirs.delay_timer,
+        });
+    }
+    if ours.sound_timer != theirs.sound_timer {
+        differences.push(Difference::SoundTimer {
+            ours: ours.sound_timer,
+            reference: theirs.sound_timer,
+        });
+    }
+    if ours.memory != theirs.memory {
+        let address = reference_memory.and_then(|theirs| {
+            (0..memory.len().max(theirs.len())).find(|at| memory.get(*at) != theirs.get(*at))
+        });
+        differences.push(Difference::Memory { address });
+    }
+    if ours.fault != theirs.fault {
+        differences.push(Difference::Fault {
+            ours: ours.fault.clone(),
+            reference: theirs.fault.clone(),
+        });
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::Quirks;
+
+    /// Shifts, stores and counts, so quirks show up quickly.
+    const PROGRAM: &str = "
+            LD V1, 0x81
+            LD I, 0x300
+        loop:
+            SHR V0, V1
+            LD [I], V1
+            ADD V2, 1
+            LD DT, V2
+            JP loop
+    ";
+
+    fn cpu(quirks: Quirks) -> CPU {
+        let mut cpu = CPU::new_with_quirks(quirks);
+        cpu.load_rom(&assemble(PROGRAM).unwrap()).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn a_quirk_change_is_caught_at_the_first_instruction_it_affects() {
+        let mut reference = cpu(Quirks::chip48());
+        assert_eq!(
+            verify(&mut cpu(Quirks::chip48()), &mut reference, 500, 11),
+            Ok(500)
+        );
+
+        let divergence = verify(
+            &mut cpu(Quirks::cosmac_vip()),
+            &mut cpu(Quirks::chip48()),
+            500,
+            11,
+        )
+        .unwrap_err();
+        assert_eq!((divergence.step, divergence.pc), (2, 0x204));
+        // VIP shifts VY into VX; CHIP-48 shifts VX in place
+        assert_eq!(
+            divergence.differences,
+            [
+                Difference::Register {
+                    index: 0,
+                    ours: 0x40,
+                    reference: 0
+                },
+                Difference::Register {
+                    index: 0xF,
+                    ours: 1,
+                    reference: 0
+                },
+            ]
+        );
+        assert!(divergence.to_string().contains("0x204: 8016  SHR V0, V1"));
+    }
+
+    #[test]
+    fn traces_replay_what_was_recorded() {
+        let mut trace = Vec::new();
+        let written = record_trace(&mut cpu(Quirks::chip48()), 100, 11, &mut trace);
+        assert_eq!(written.unwrap(), 100);
+        let mut reference = TraceReference::new(&trace[..]);
+        assert_eq!(
+            verify(&mut cpu(Quirks::chip48()), &mut reference, 200, 11),
+            Ok(100)
+        );
+
+        // the first store moves I under VIP quirks, where CHIP-48 kept it
+        let mut vip = cpu(Quirks {
+            load_store_increments_i: true,
+            ..Quirks::chip48()
+        });
+        let mut reference = TraceReference::new(&trace[..]);
+        let divergence = verify(&mut vip, &mut reference, 200, 11).unwrap_err();
+        assert_eq!(
+            divergence.differences,
+            [Difference::I {
+                ours: 0x302,
+                reference: 0x300
+            }]
+        );
+
+        let mut broken = TraceReference::new(&b"{\"pc\": 1}\n"[..]);
+        assert_eq!(
+            verify(&mut cpu(Quirks::chip48()), &mut broken, 10, 11),
+            Ok(0)
+        );
+        assert!(broken.error().unwrap().starts_with("line 1:"));
+    }
+}
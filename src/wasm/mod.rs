@@ -0,0 +1,177 @@
+//! A small handle for embedding the emulator in a web page.
+//!
+//! Everything crosses the boundary as plain numbers, `&[u8]` and `Vec<u8>`,
+//! which wasm-bindgen passes as `Uint8Array`, and nothing here panics on
+//! bad input: errors come back as values and a faulted machine just stops.
+
+use crate::cpu::{EmulatorMode, Status, XorShift, CPU};
+use crate::keypad::KEY_COUNT;
+
+/// Instructions per frame when the page doesn't choose, about 700 per
+/// second.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 11;
+
+/// One emulator instance. Random numbers come from seed 0 until the page
+/// calls [`WasmChip8::seed`].
+pub struct WasmChip8 {
+    cpu: CPU,
+    instructions_per_frame: usize,
+    /// Kept so that loading another ROM reuses it.
+    seed: u32,
+    error: Option<String>,
+}
+
+impl Default for WasmChip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmChip8 {
+    pub fn new() -> Self {
+        Self::new_with_mode(EmulatorMode::Chip8)
+    }
+
+    pub fn new_with_mode(mode: EmulatorMode) -> Self {
+        let mut cpu = CPU::new_with_mode(mode);
+        cpu.set_rng(Box::new(XorShift::new(0)));
+        WasmChip8 {
+            cpu,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            seed: 0,
+            error: None,
+        }
+    }
+
+    /// Seeds CXNN's random numbers, e.g. from `Math.random()`, since the
+    /// browser gives the emulator no clock to seed from.
+    pub fn seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.cpu.set_rng(Box::new(XorShift::new(seed)));
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions: usize) {
+        self.instructions_per_frame = instructions;
+    }
+
+    /// Loads a program and restarts from power-on.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        let mut cpu = CPU::new_with_mode(self.cpu.mode);
+        cpu.quirks = self.cpu.quirks;
+        cpu.load_rom(rom).map_err(|err| err.to_string())?;
+        cpu.set_rng(Box::new(XorShift::new(self.seed)));
+        self.cpu = cpu;
+        self.error = None;
+        Ok(())
+    }
+
+    /// Runs one 60 Hz frame and ticks the timers. Returns whether the
+    /// program can keep running.
+    pub fn step_frame(&mut self) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        match self.cpu.step_frame(self.instructions_per_frame) {
+            Ok(Status::Halted) => false,
+            Ok(_) => true,
+            Err(fault) => {
+                self.error = Some(fault.to_string());
+                false
+            }
+        }
+    }
+
+    /// Why the program stopped, if it faulted.
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    /// [`crate::capabilities()`] as JSON, for the page to build its UI from.
+    pub fn capabilities(&self) -> String {
+        serde_json::to_string(&crate::capabilities()).unwrap_or_default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cpu.display.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.cpu.display.height()
+    }
+
+    /// One byte per pixel, row by row, holding the palette index described
+    /// in [`crate::display::FrameBuffer::color`]. The size follows the
+    /// current resolution, so check [`WasmChip8::width`] every frame.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let fb = &self.cpu.display;
+        (0..fb.height())
+            .flat_map(|y| (0..fb.width()).map(move |x| fb.color(x, y)))
+            .collect()
+    }
+
+    /// Keys outside `0x0..=0xF` are ignored.
+    pub fn key_down(&mut self, key: u8) {
+        if (key as usize) < KEY_COUNT {
+            self.cpu.set_key(key, true);
+        }
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        if (key as usize) < KEY_COUNT {
+            self.cpu.set_key(key, false);
+        }
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.cpu.is_beeping()
+    }
+
+    /// Storage key for this ROM's RPL flags, for the page to use with
+    /// localStorage; see [`crate::storage::rom_key`].
+    pub fn rpl_key(&self) -> String {
+        crate::storage::rom_key(self.cpu.rom(), "rpl")
+    }
+
+    pub fn rpl_flags(&self) -> Vec<u8> {
+        self.cpu.rpl_flags.to_vec()
+    }
+
+    /// Ignores data of the wrong length.
+    pub fn set_rpl_flags(&mut self, flags: &[u8]) {
+        if flags.len() == self.cpu.rpl_flags.len() {
+            self.cpu.rpl_flags.copy_from_slice(flags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_after_a_frame() {
+        let mut chip8 = WasmChip8::new();
+        // LD F, V0; DRW V0, V0, 5; JP 0x204
+        chip8
+            .load_rom(&[0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04])
+            .unwrap();
+
+        assert!(chip8.step_frame());
+
+        let pixels = chip8.framebuffer();
+        assert_eq!(pixels.len(), chip8.width() * chip8.height());
+        assert_eq!(&pixels[..4], &[1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn bad_input_is_reported_not_panicked() {
+        let mut chip8 = WasmChip8::new();
+        assert!(chip8.load_rom(&[0; 0x1000]).is_err());
+
+        chip8.key_down(0x42);
+        chip8.load_rom(&[0x51, 0x21]).unwrap();
+        assert!(!chip8.step_frame());
+        assert!(chip8.error().unwrap().contains("unknown opcode 5121"));
+        assert!(!chip8.step_frame());
+    }
+}
@@ -0,0 +1,81 @@
+//! Runs every ROM in `tests/roms` headlessly and compares its final screen
+//! with `tests/golden/<name>.txt`. See `tests/roms/README.md` for the test
+//! ROMs that belong there.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cpu_emulator_chip_8::cpu::EmulatorMode;
+use cpu_emulator_chip_8::fingerprint::SELF_TEST_ROM;
+use cpu_emulator_chip_8::testing::{self, INSTRUCTIONS_PER_FRAME};
+
+/// Long enough for every suite ROM to finish drawing its results.
+const FRAMES: usize = 600;
+
+/// The ROMs `tests/roms/README.md` lists, which aren't redistributed here.
+const SUITE: [&str; 5] = [
+    "test_opcode.ch8",
+    "1-chip8-logo.ch8",
+    "2-ibm-logo.ch8",
+    "3-corax+.ch8",
+    "4-flags.ch8",
+];
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn golden(name: &str) -> PathBuf {
+    root().join("golden").join(format!("{}.txt", name))
+}
+
+fn roms() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root().join("roms")) else {
+        return Vec::new();
+    };
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("ch8") || ext.eq_ignore_ascii_case("xo8")
+            })
+        })
+        .collect();
+    roms.sort();
+    roms
+}
+
+#[test]
+fn self_test_rom_matches_golden() {
+    let mut cpu = testing::headless(&SELF_TEST_ROM, EmulatorMode::Chip8).unwrap();
+    testing::run_frames(&mut cpu, FRAMES, INSTRUCTIONS_PER_FRAME).unwrap();
+
+    testing::check_golden(&golden("self-test"), &cpu.display).unwrap();
+}
+
+#[test]
+#[ignore = "needs the suite ROMs copied into tests/roms, see its README"]
+fn suite_roms_match_golden() {
+    let missing: Vec<&str> = SUITE
+        .into_iter()
+        .filter(|name| !root().join("roms").join(name).is_file())
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "missing from tests/roms: {}",
+        missing.join(", ")
+    );
+
+    let mut failures = Vec::new();
+    for rom in roms() {
+        let name = rom.file_stem().unwrap_or_default().to_string_lossy();
+        let result = testing::headless_from_path(&rom).and_then(|(mut cpu, ipf)| {
+            testing::run_frames(&mut cpu, FRAMES, ipf).map_err(|fault| fault.to_string())?;
+            testing::check_golden(&golden(&name), &cpu.display)
+        });
+        if let Err(err) = result {
+            failures.push(format!("{}: {}", rom.display(), err));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
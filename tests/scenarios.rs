@@ -0,0 +1,30 @@
+//! Plays every `tests/scenarios/*.scenario` file; see
+//! [`cpu_emulator_chip_8::testing::Scenario`] for the format.
+
+use std::fs;
+use std::path::Path;
+
+use cpu_emulator_chip_8::testing::Scenario;
+
+#[test]
+fn scenarios_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "scenario"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenarios in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let result = Scenario::load(path)
+            .map_err(|err| err.to_string())
+            .and_then(|scenario| scenario.run());
+        if let Err(err) = result {
+            failures.push(format!("{}:\n{}", path.display(), err));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}